@@ -1,14 +1,27 @@
 use partner::Device;
 
 fn main() {
-    let devices = Device::get_all().unwrap();
+    let mut devices = Device::get_all().unwrap();
     if let Some(device) = std::env::args().nth(1) {
-        let device = devices
-            .into_iter()
-            .find(|d| d.path() == &device)
-            .unwrap_or_else(|| Device::open(device).unwrap());
-        dbg!(device);
-    } else {
-        dbg!(devices);
+        devices.retain(|d| d.path() == &device);
+        if devices.is_empty() {
+            devices.push(Device::open(device).unwrap());
+        }
+    }
+
+    for device in &devices {
+        println!("{}", device.summary());
+        for partition in device.partitions() {
+            println!(
+                "    {:<20} {:>12} {:<8} {}",
+                partition
+                    .path
+                    .as_ref()
+                    .map_or("-".into(), |p| p.display().to_string()),
+                format!("{:#.2}", partition.size()),
+                partition.fs().map_or("-".into(), |fs| fs.to_string()),
+                partition.name(),
+            );
+        }
     }
 }
@@ -0,0 +1,272 @@
+//! Telling the kernel about partitions changed behind libparted's back, with the `BLKPG` ioctl.
+//!
+//! libparted does this itself when it commits, but GPT entries rewritten afterwards, e.g. to give
+//! a partition a specific number or PARTUUID, would otherwise go unnoticed by the kernel and udev
+//! until the next reboot.
+
+use nix::libc::{c_char, c_int, c_longlong, c_void};
+use std::{
+    fs::File,
+    io::{Read, Seek, Write},
+    os::fd::AsRawFd,
+};
+
+const BLKPG_ADD_PARTITION: c_int = 1;
+const BLKPG_DEL_PARTITION: c_int = 2;
+
+/// `blkpg_ioctl_arg` from `<linux/blkpg.h>`.
+#[repr(C)]
+struct BlkpgIoctlArg {
+    op: c_int,
+    flags: c_int,
+    datalen: c_int,
+    data: *mut c_void,
+}
+
+/// `blkpg_partition` from `<linux/blkpg.h>`.
+#[repr(C)]
+struct BlkpgPartition {
+    start: c_longlong,
+    length: c_longlong,
+    pno: c_int,
+    devname: [c_char; 64],
+    volname: [c_char; 64],
+}
+
+nix::ioctl_write_ptr_bad!(blkpg, nix::request_code_none!(0x12, 105), BlkpgIoctlArg);
+
+/// A change to the kernel's view of a device's partitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    /// Forget the partition with the given number.
+    Delete(u32),
+    /// Add a partition with the given number, starting at `start` and `length` long **in bytes**.
+    Add {
+        number: u32,
+        start: u64,
+        length: u64,
+    },
+}
+
+/// A device whose partitions the kernel can be told about.
+pub(crate) trait Disk: Read + Write + Seek {
+    /// Flush what was written to the device, so that the kernel and udev read it back.
+    fn sync(&mut self) -> std::io::Result<()>;
+
+    fn update(&mut self, op: Op) -> std::io::Result<()>;
+}
+
+impl Disk for File {
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.sync_all()
+    }
+
+    fn update(&mut self, op: Op) -> std::io::Result<()> {
+        // disk images have no partitions in the kernel
+        if !std::os::unix::fs::FileTypeExt::is_block_device(&self.metadata()?.file_type()) {
+            return Ok(());
+        }
+        let (op, partition) = match op {
+            Op::Delete(number) => (BLKPG_DEL_PARTITION, (number, 0, 0)),
+            Op::Add {
+                number,
+                start,
+                length,
+            } => (BLKPG_ADD_PARTITION, (number, start, length)),
+        };
+        let mut partition = BlkpgPartition {
+            start: partition.1 as c_longlong,
+            length: partition.2 as c_longlong,
+            pno: partition.0 as c_int,
+            devname: [0; 64],
+            volname: [0; 64],
+        };
+        let arg = BlkpgIoctlArg {
+            op,
+            flags: 0,
+            datalen: size_of::<BlkpgPartition>() as c_int,
+            data: (&raw mut partition).cast(),
+        };
+        // SAFETY: the argument and the partition it points to outlive the call
+        unsafe { blkpg(self.as_raw_fd(), &arg) }?;
+        Ok(())
+    }
+}
+
+/// A partition the kernel knows by the number `old`, to be replaced with the same partition
+/// under `number`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Replacement {
+    pub old: u32,
+    pub number: u32,
+    /// **In bytes**.
+    pub start: u64,
+    /// **In bytes**.
+    pub length: u64,
+}
+
+/// Replace partitions in the kernel's view of the device, which also has udev probe them again.
+///
+/// All of them are deleted before any is added, so that numbers can be handed from one partition
+/// to another. If one can't be deleted, e.g. because it's mounted, those already deleted are added
+/// back under their old numbers.
+pub(crate) fn replace(disk: &mut impl Disk, replacements: &[Replacement]) -> std::io::Result<()> {
+    for (i, replacement) in replacements.iter().enumerate() {
+        if let Err(e) = disk.update(Op::Delete(replacement.old)) {
+            for deleted in &replacements[..i] {
+                // the error that stopped the replacement is the one worth reporting
+                let _ = disk.update(Op::Add {
+                    number: deleted.old,
+                    start: deleted.start,
+                    length: deleted.length,
+                });
+            }
+            return Err(std::io::Error::new(
+                e.kind(),
+                format!(
+                    "the kernel still has partition {} under its old number or PARTUUID until \
+                     the device is reread: {e}",
+                    replacement.old
+                ),
+            ));
+        }
+    }
+    for replacement in replacements {
+        disk.update(Op::Add {
+            number: replacement.number,
+            start: replacement.start,
+            length: replacement.length,
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{Cursor, SeekFrom};
+
+    /// Called on every kernel update of a [`FakeDisk`] with the data on the disk at that point.
+    pub(crate) type OnUpdate = Box<dyn FnMut(&mut Cursor<Vec<u8>>, Op)>;
+
+    /// What happened to a [`FakeDisk`], in order.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub(crate) enum Event {
+        Sync,
+        Update(Op),
+    }
+
+    /// A disk in memory that records syncs and kernel updates, and fails to delete the
+    /// partitions it's told are busy.
+    pub(crate) struct FakeDisk {
+        pub data: Cursor<Vec<u8>>,
+        pub events: Vec<Event>,
+        pub busy: Vec<u32>,
+        pub on_update: OnUpdate,
+    }
+
+    impl FakeDisk {
+        pub(crate) fn new(data: Cursor<Vec<u8>>) -> Self {
+            Self {
+                data,
+                events: Vec::new(),
+                busy: Vec::new(),
+                on_update: Box::new(|_, _| {}),
+            }
+        }
+    }
+
+    impl Read for FakeDisk {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.data.read(buf)
+        }
+    }
+
+    impl Write for FakeDisk {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.data.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for FakeDisk {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.data.seek(pos)
+        }
+    }
+
+    impl Disk for FakeDisk {
+        fn sync(&mut self) -> std::io::Result<()> {
+            self.events.push(Event::Sync);
+            Ok(())
+        }
+
+        fn update(&mut self, op: Op) -> std::io::Result<()> {
+            if let Op::Delete(number) = op
+                && self.busy.contains(&number)
+            {
+                return Err(std::io::Error::from_raw_os_error(nix::libc::EBUSY));
+            }
+            (self.on_update)(&mut self.data, op);
+            self.events.push(Event::Update(op));
+            Ok(())
+        }
+    }
+
+    fn replacement(old: u32, number: u32) -> Replacement {
+        Replacement {
+            old,
+            number,
+            start: old as u64 * 1024,
+            length: 1024,
+        }
+    }
+
+    #[test]
+    fn partitions_are_deleted_before_any_is_added() {
+        let mut disk = FakeDisk::new(Cursor::default());
+        // 1 takes the number 2 gives up
+        replace(&mut disk, &[replacement(1, 3), replacement(2, 1)]).expect("replacing failed");
+        assert_eq!(
+            disk.events,
+            [
+                Event::Update(Op::Delete(1)),
+                Event::Update(Op::Delete(2)),
+                Event::Update(Op::Add {
+                    number: 3,
+                    start: 1024,
+                    length: 1024
+                }),
+                Event::Update(Op::Add {
+                    number: 1,
+                    start: 2048,
+                    length: 1024
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn busy_partitions_leave_the_others_as_they_were() {
+        let mut disk = FakeDisk::new(Cursor::default());
+        disk.busy = vec![2];
+        let replacements = [replacement(1, 3), replacement(2, 4), replacement(5, 6)];
+        let error = replace(&mut disk, &replacements).expect_err("a busy partition was deleted");
+        assert_eq!(error.raw_os_error(), None);
+        assert!(error.to_string().contains("partition 2"), "{error}");
+        assert_eq!(
+            disk.events,
+            [
+                Event::Update(Op::Delete(1)),
+                Event::Update(Op::Add {
+                    number: 1,
+                    start: 1024,
+                    length: 1024
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! What the libparted partner is linked against can do, which depends on its version and how it
+//! was built.
+
+use crate::{ChangePlan, Error, FileSystem, PartitionTable, ResizeStrategy, ResizeSupport};
+use libparted_sys::{
+    _PedPartitionFlag as RawFlag, PedDiskType, PedFileSystemType, ped_disk_type_get_next,
+    ped_file_system_type_get_next, ped_get_version, ped_partition_flag_get_name,
+    ped_partition_flag_next,
+};
+use std::{
+    ffi::{CStr, c_char},
+    sync::Arc,
+};
+use strum::IntoEnumIterator;
+
+/// What the linked libparted supports, as returned by [`capabilities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// The version of libparted, e.g. `3.6`.
+    pub libparted_version: Arc<str>,
+    /// The partition table types libparted can read and write, by its names for them, e.g.
+    /// `gpt`.
+    pub disk_types: Vec<Arc<str>>,
+    /// The partition flags libparted knows, by its names for them, e.g. `esp`.
+    pub partition_flags: Vec<Arc<str>>,
+    /// The file systems libparted can detect and create partitions for.
+    pub file_systems: Vec<FileSystem>,
+    /// The file systems libparted can resize itself, with [`ResizeStrategy::Libparted`].
+    pub resizable: Vec<FileSystem>,
+}
+
+impl Capabilities {
+    pub fn supports_table(&self, table: PartitionTable) -> bool {
+        self.disk_types.iter().any(|t| **t == *table.name())
+    }
+
+    pub fn supports_flag(&self, name: &str) -> bool {
+        self.partition_flags.iter().any(|f| **f == *name)
+    }
+
+    pub fn supports_fs(&self, fs: FileSystem) -> bool {
+        self.file_systems.contains(&fs)
+    }
+
+    /// Check that libparted can make every partition table and partition in the given plan.
+    pub fn check_plan(&self, plan: &ChangePlan) -> Result<(), Error> {
+        if let Some(table) = plan.target.disk_type.as_deref()
+            && !self.disk_types.iter().any(|t| **t == *table)
+        {
+            return Err(Error::UnsupportedByLibparted(table.into()));
+        }
+        match plan
+            .target
+            .partitions
+            .iter()
+            .find_map(|p| p.fs.filter(|fs| !self.supports_fs(*fs)))
+        {
+            Some(fs) => Err(Error::UnsupportedByLibparted(fs.to_string().into())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Find out what the linked libparted supports.
+pub fn capabilities() -> Capabilities {
+    let libparted_version = unsafe { string(ped_get_version()) }.unwrap_or_default();
+
+    let mut disk_types = Vec::new();
+    let mut disk_type: *mut PedDiskType = std::ptr::null_mut();
+    loop {
+        disk_type = unsafe { ped_disk_type_get_next(disk_type) };
+        if disk_type.is_null() {
+            break;
+        }
+        disk_types.extend(unsafe { string((*disk_type).name) });
+    }
+
+    let mut fs_names = Vec::new();
+    let mut fs_type: *mut PedFileSystemType = std::ptr::null_mut();
+    loop {
+        fs_type = unsafe { ped_file_system_type_get_next(fs_type) };
+        if fs_type.is_null() {
+            break;
+        }
+        fs_names.extend(unsafe { string((*fs_type).name) });
+    }
+    let file_systems = FileSystem::iter()
+        .filter(|fs| fs_names.iter().any(|name| **name == *fs.to_string()))
+        .collect::<Vec<_>>();
+
+    // libparted returns 0 after the last flag, which isn't a valid flag, so the functions are
+    // called with plain integers
+    let (next, get_name) = unsafe {
+        (
+            std::mem::transmute::<
+                unsafe extern "C" fn(RawFlag) -> RawFlag,
+                unsafe extern "C" fn(u32) -> u32,
+            >(ped_partition_flag_next),
+            std::mem::transmute::<
+                unsafe extern "C" fn(RawFlag) -> *const c_char,
+                unsafe extern "C" fn(u32) -> *const c_char,
+            >(ped_partition_flag_get_name),
+        )
+    };
+    let mut partition_flags = Vec::new();
+    let mut flag = unsafe { next(0) };
+    while flag != 0 {
+        partition_flags.extend(unsafe { string(get_name(flag)) });
+        flag = unsafe { next(flag) };
+    }
+
+    let resizable = file_systems
+        .iter()
+        .copied()
+        .filter(|fs| fs.resize_support(ResizeStrategy::Libparted) != ResizeSupport::default())
+        .collect();
+
+    Capabilities {
+        libparted_version,
+        disk_types,
+        partition_flags,
+        file_systems,
+        resizable,
+    }
+}
+
+/// # Safety
+///
+/// `raw` must be null or point to a valid C string.
+unsafe fn string(raw: *const c_char) -> Option<Arc<str>> {
+    (!raw.is_null()).then(|| unsafe { CStr::from_ptr(raw) }.to_string_lossy().into())
+}
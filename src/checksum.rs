@@ -0,0 +1,99 @@
+//! Checksums of partition contents, for verifying clones and checking that risky operations left
+//! data intact.
+
+use sha2::{Digest as _, Sha256};
+use std::{fmt::Display, io::Read, ops::ControlFlow};
+use xxhash_rust::xxh3::Xxh3;
+
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// A hash algorithm for [`Partition::checksum`](crate::Partition::checksum).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ChecksumAlgorithm {
+    /// 64-bit XXH3, which is fast but only guards against accidental changes.
+    #[default]
+    Xxh3,
+    /// SHA-256, which is slower but also guards against deliberate changes.
+    Sha256,
+}
+
+/// The checksum of a partition's contents, as returned by
+/// [`Partition::checksum`](crate::Partition::checksum). Displays as lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Digest {
+    pub algorithm: ChecksumAlgorithm,
+    pub bytes: Vec<u8>,
+}
+
+impl Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+/// Progress of a running [`Partition::checksum`](crate::Partition::checksum).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumProgress {
+    /// The number of bytes hashed so far.
+    pub hashed: u64,
+    pub total: u64,
+}
+
+impl ChecksumProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.hashed as f64 / self.total as f64
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChecksumError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("checksum cancelled after {hashed} bytes")]
+    Cancelled { hashed: u64 },
+}
+
+enum Hasher {
+    Xxh3(Box<Xxh3>),
+    Sha256(Sha256),
+}
+
+/// Hash `total` bytes of the given reader.
+pub(crate) fn checksum(
+    mut reader: impl Read,
+    total: u64,
+    algorithm: ChecksumAlgorithm,
+    mut progress: impl FnMut(ChecksumProgress) -> ControlFlow<()>,
+) -> Result<Digest, ChecksumError> {
+    let mut hasher = match algorithm {
+        ChecksumAlgorithm::Xxh3 => Hasher::Xxh3(Box::new(Xxh3::new())),
+        ChecksumAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+    };
+    let mut buf = vec![0; CHUNK_SIZE];
+
+    let mut hashed = 0;
+    while hashed < total {
+        let chunk = &mut buf[..(total - hashed).min(CHUNK_SIZE as u64) as usize];
+        reader.read_exact(chunk)?;
+        match &mut hasher {
+            Hasher::Xxh3(hasher) => hasher.update(chunk),
+            Hasher::Sha256(hasher) => hasher.update(&*chunk),
+        }
+
+        hashed += chunk.len() as u64;
+        if progress(ChecksumProgress { hashed, total }).is_break() && hashed < total {
+            return Err(ChecksumError::Cancelled { hashed });
+        }
+    }
+
+    let bytes = match hasher {
+        Hasher::Xxh3(hasher) => hasher.digest().to_be_bytes().to_vec(),
+        Hasher::Sha256(hasher) => hasher.finalize().to_vec(),
+    };
+    Ok(Digest { algorithm, bytes })
+}
@@ -0,0 +1,467 @@
+use crate::{
+    Change, Partition, PartitionKind, ToolError, ToolOutput, Toolchain,
+    size::{self, FormatOptions},
+};
+use byte_unit::Byte;
+use std::{
+    ops::RangeInclusive,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Assumed throughput for moving partition data, to weigh moves against the other phases.
+const MOVE_BYTES_PER_SEC: f64 = 100.0 * 1024.0 * 1024.0;
+const TABLE_ESTIMATE: Duration = Duration::from_secs(1);
+const MKFS_ESTIMATE: Duration = Duration::from_secs(5);
+const VERIFY_ESTIMATE: Duration = Duration::from_secs(1);
+
+/// Options for [`Device::commit_with`](crate::Device::commit_with).
+#[derive(Debug, Clone, Default)]
+pub struct CommitOptions {
+    pub(crate) verify: bool,
+    pub(crate) mount_check: bool,
+    pub(crate) toolchain: Toolchain,
+    pub(crate) destroy_limit: Option<u64>,
+    pub(crate) destruction_ack: Option<DestructionAck>,
+}
+
+/// A token acknowledging that a commit destroys up to a given amount of existing data, as
+/// returned by [`Device::acknowledge_destruction`](crate::Device::acknowledge_destruction) or
+/// [`Transaction::acknowledge_destruction`](crate::Transaction::acknowledge_destruction).
+///
+/// The token only covers the amount it was made for, so it stops working if more destructive
+/// changes are staged before committing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DestructionAck(pub(crate) u64);
+
+impl CommitOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to re-read the partition table after committing and compare it against the
+    /// expected layout. Defaults to `false`.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Whether to also try mounting each new partition read-only, to confirm that it holds the
+    /// expected file system. Implies [`verify`](CommitOptions::verify). Defaults to `false`.
+    pub fn mount_check(mut self, mount_check: bool) -> Self {
+        self.mount_check = mount_check;
+        self.verify |= mount_check;
+        self
+    }
+
+    /// Where to find external tools such as `mkfs`. Defaults to [`Toolchain::default`].
+    pub fn toolchain(mut self, toolchain: Toolchain) -> Self {
+        self.toolchain = toolchain;
+        self
+    }
+
+    /// Refuse to commit changes that destroy more than `limit` of existing data, as measured by
+    /// [`Device::pending_bytes_destroyed`](crate::Device::pending_bytes_destroyed), unless that's
+    /// [acknowledged](CommitOptions::acknowledge_destruction). Installers can use this to make
+    /// users confirm that a plan erases their data. Defaults to no limit.
+    pub fn destroy_limit(mut self, limit: Byte) -> Self {
+        self.destroy_limit = Some(limit.as_u64());
+        self
+    }
+
+    /// Allow the commit to destroy as much data as the given token covers, even beyond the
+    /// [destroy limit](CommitOptions::destroy_limit).
+    pub fn acknowledge_destruction(mut self, ack: DestructionAck) -> Self {
+        self.destruction_ack = Some(ack);
+        self
+    }
+}
+
+/// A stage of a commit, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum CommitPhase {
+    /// Moving the file system of a [superfloppy](crate::Device::is_superfloppy) out of the way
+    /// of its new partition table.
+    #[strum(serialize = "relocating file system")]
+    Relocation,
+    /// Writing the partition table and any hybrid MBR or resized GPT entry array.
+    #[strum(serialize = "updating partition table")]
+    Table,
+    /// Copying the contents of moved partitions, which happens before the table pointing at
+    /// their new place is written.
+    #[strum(serialize = "moving data")]
+    Moves,
+    /// Creating file systems.
+    #[strum(serialize = "creating file systems")]
+    Mkfs,
+    /// Checking the committed layout, if [requested](CommitOptions::verify).
+    #[strum(serialize = "verifying")]
+    Verification,
+}
+
+/// Progress of a running commit, as reported by
+/// [`Device::commit_with_progress`](crate::Device::commit_with_progress).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitProgress {
+    pub phase: CommitPhase,
+    /// How much of the current phase is done, from 0 to 1.
+    pub phase_fraction: f64,
+    /// How much of the whole commit is done, from 0 to 1, weighted by how long each phase is
+    /// expected to take.
+    pub fraction: f64,
+    /// The estimated time until the commit is done. This is a rough guess until some of the
+    /// slower phases have run.
+    pub eta: Duration,
+    /// The change being worked on, if the current step belongs to one.
+    pub change: Option<ChangeProgress>,
+    /// The output of an external tool that just finished successfully, e.g. `mkfs`, for showing
+    /// in a log. The output of a failed tool is in the returned [`ToolError`](crate::ToolError).
+    pub output: Option<Arc<ToolOutput>>,
+}
+
+/// Progress of a single change in a commit, as reported in [`CommitProgress::change`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChangeProgress {
+    /// The position of the change in [`Device::changes`](crate::Device::changes) when the commit
+    /// started.
+    pub position: usize,
+    /// The number of changes being committed.
+    pub count: usize,
+    /// How much of the change is done, from 0 to 1.
+    pub fraction: f64,
+}
+
+/// Why a commit failed, as returned by [`Device::commit_with`](crate::Device::commit_with).
+#[derive(Debug, thiserror::Error)]
+pub enum CommitError {
+    /// The pending changes exceed the [destroy limit](CommitOptions::destroy_limit) without being
+    /// acknowledged. Nothing was written.
+    #[error(
+        "the pending changes erase {} of data, which must be acknowledged",
+        size::format(*.0, FormatOptions::new().precision(1))
+    )]
+    UnacknowledgedDestruction(Byte),
+    /// Tools the changes need aren't installed. Nothing was written.
+    #[error(transparent)]
+    MissingTools(#[from] ToolError),
+    /// A change couldn't be applied.
+    #[error("change №{position} ({change}) failed: {source}")]
+    Change {
+        /// The position of the change in [`Device::changes`](crate::Device::changes) when the
+        /// commit started.
+        position: usize,
+        change: Change,
+        #[source]
+        source: std::io::Error,
+        /// Whether earlier changes were already written to the device.
+        flushed: bool,
+    },
+    /// A part of the commit that doesn't belong to a single change failed, e.g. opening the
+    /// device or writing the partition table.
+    #[error("{phase} failed: {source}")]
+    Phase {
+        phase: CommitPhase,
+        #[source]
+        source: std::io::Error,
+        /// Whether earlier changes were already written to the device.
+        flushed: bool,
+    },
+}
+
+impl CommitError {
+    /// Whether the device was written to before the commit failed, so that its layout is
+    /// somewhere between the old and the new one.
+    pub fn flushed(&self) -> bool {
+        match self {
+            Self::Change { flushed, .. } | Self::Phase { flushed, .. } => *flushed,
+            _ => false,
+        }
+    }
+}
+
+/// How far a commit got, to say where it failed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CommitStep {
+    pub phase: CommitPhase,
+    /// The position of the change being worked on, if any.
+    pub change: Option<usize>,
+    pub flushed: bool,
+}
+
+impl CommitStep {
+    pub fn error(self, source: std::io::Error, changes: &[Change]) -> CommitError {
+        match self
+            .change
+            .and_then(|position| Some((position, changes.get(position)?)))
+        {
+            Some((position, change)) => CommitError::Change {
+                position,
+                change: change.clone(),
+                source,
+                flushed: self.flushed,
+            },
+            None => CommitError::Phase {
+                phase: self.phase,
+                source,
+                flushed: self.flushed,
+            },
+        }
+    }
+}
+
+/// Estimates how long each phase of a commit takes, extrapolating from the elapsed time once it
+/// has started.
+pub(crate) struct Estimator {
+    phases: Vec<(CommitPhase, Duration)>,
+    started: Instant,
+    changes: usize,
+}
+
+impl Estimator {
+    /// Estimate a commit with the given work. Phases with no work are skipped.
+    pub fn new(
+        changes: usize,
+        relocate_bytes: u64,
+        move_bytes: u64,
+        formats: usize,
+        verify: bool,
+    ) -> Self {
+        let mut phases = Vec::new();
+        if relocate_bytes > 0 {
+            phases.push((
+                CommitPhase::Relocation,
+                Duration::from_secs_f64(relocate_bytes as f64 / MOVE_BYTES_PER_SEC),
+            ));
+        }
+        phases.push((CommitPhase::Table, TABLE_ESTIMATE));
+        if move_bytes > 0 {
+            phases.push((
+                CommitPhase::Moves,
+                Duration::from_secs_f64(move_bytes as f64 / MOVE_BYTES_PER_SEC),
+            ));
+        }
+        if formats > 0 {
+            phases.push((CommitPhase::Mkfs, MKFS_ESTIMATE * formats as u32));
+        }
+        if verify {
+            phases.push((CommitPhase::Verification, VERIFY_ESTIMATE));
+        }
+        Self {
+            phases,
+            started: Instant::now(),
+            changes,
+        }
+    }
+
+    pub fn progress(&self, phase: CommitPhase, phase_fraction: f64) -> CommitProgress {
+        let phase_fraction = phase_fraction.clamp(0.0, 1.0);
+        let total = self
+            .phases
+            .iter()
+            .map(|(_, d)| d.as_secs_f64())
+            .sum::<f64>();
+        let done = self
+            .phases
+            .iter()
+            .take_while(|(p, _)| *p != phase)
+            .map(|(_, d)| d.as_secs_f64())
+            .sum::<f64>()
+            + self
+                .phases
+                .iter()
+                .find(|(p, _)| *p == phase)
+                .map_or(0.0, |(_, d)| d.as_secs_f64() * phase_fraction);
+        let fraction = if total == 0.0 { 1.0 } else { done / total };
+
+        // once some work is done, extrapolate from how fast it went
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let eta = if fraction > 0.0 && elapsed > 0.0 {
+            elapsed / fraction * (1.0 - fraction)
+        } else {
+            total - done
+        };
+
+        CommitProgress {
+            phase,
+            phase_fraction,
+            fraction,
+            eta: Duration::from_secs_f64(eta.max(0.0)),
+            change: None,
+            output: None,
+        }
+    }
+
+    /// Like [`progress`](Estimator::progress), while working on the change at the given position.
+    pub fn change_progress(
+        &self,
+        phase: CommitPhase,
+        phase_fraction: f64,
+        position: usize,
+        fraction: f64,
+    ) -> CommitProgress {
+        CommitProgress {
+            change: Some(ChangeProgress {
+                position,
+                count: self.changes,
+                fraction: fraction.clamp(0.0, 1.0),
+            }),
+            ..self.progress(phase, phase_fraction)
+        }
+    }
+}
+
+/// A difference between the expected and actual layout of a device after a commit.
+#[derive(Debug)]
+pub enum LayoutMismatch {
+    /// An expected partition wasn't found on the device.
+    Missing(Partition),
+    /// A partition was found on the device that wasn't expected.
+    Unexpected(Partition),
+    /// A partition was found where one was expected, but its name, file system, or bounds differ.
+    ///
+    /// New partitions may be shrunk slightly to align them, so this is only reported if the
+    /// actual bounds don't fit within the expected ones.
+    Changed {
+        expected: Box<Partition>,
+        actual: Box<Partition>,
+    },
+    /// A new partition could not be mounted as its expected file system.
+    MountFailed {
+        partition: Partition,
+        error: std::io::Error,
+    },
+}
+
+/// The result of verifying a commit, returned by
+/// [`Device::commit_with`](crate::Device::commit_with).
+#[derive(Debug, Default)]
+pub struct VerificationReport {
+    pub mismatches: Vec<LayoutMismatch>,
+}
+
+impl VerificationReport {
+    /// Whether the device's layout matches what was expected.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// A commit running on tokio's blocking thread pool, as started by
+/// [`Device::commit_async`](crate::Device::commit_async).
+#[cfg(feature = "tokio")]
+pub struct AsyncCommit {
+    /// The commit's progress reports, which end when it's done.
+    pub progress: tokio::sync::mpsc::UnboundedReceiver<CommitProgress>,
+    pub(crate) task: tokio::task::JoinHandle<SendDevice<CommitResult>>,
+}
+
+#[cfg(feature = "tokio")]
+type CommitResult = (
+    crate::Device<'static>,
+    Result<Option<VerificationReport>, CommitError>,
+);
+
+#[cfg(feature = "tokio")]
+impl AsyncCommit {
+    /// Wait for the commit to finish, getting the device back along with the result.
+    pub async fn finish(self) -> CommitResult {
+        match self.task.await {
+            Ok(result) => result.into_inner(),
+            // blocking tasks can't be cancelled, so this is a panic
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+}
+
+/// Moves a device to another thread.
+///
+/// libparted's objects aren't tied to the thread that made them; they only mustn't be used from
+/// two threads at once, which owning them rules out.
+#[cfg(feature = "tokio")]
+pub(crate) struct SendDevice<T>(pub T);
+
+#[cfg(feature = "tokio")]
+unsafe impl<T> Send for SendDevice<T> {}
+
+#[cfg(feature = "tokio")]
+impl<T> SendDevice<T> {
+    /// Take the device back. Closures have to call this rather than destructure the wrapper, so
+    /// that they capture all of it rather than just the device.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+pub(crate) fn overlaps(a: &RangeInclusive<i64>, b: &RangeInclusive<i64>) -> bool {
+    a.start() <= b.end() && b.start() <= a.end()
+}
+
+pub(crate) fn verify(
+    expected: Vec<Partition>,
+    mut actual: Vec<Partition>,
+    mount_check: bool,
+) -> VerificationReport {
+    let mut mismatches = Vec::new();
+
+    for expected in expected {
+        let Some(position) = actual
+            .iter()
+            .position(|p| overlaps(p.bounds(), expected.bounds()))
+        else {
+            mismatches.push(LayoutMismatch::Missing(expected));
+            continue;
+        };
+        let found = actual.remove(position);
+
+        let fits = expected.bounds().start() <= found.bounds().start()
+            && found.bounds().end() <= expected.bounds().end();
+        if !fits
+            || found.name() != expected.name()
+            || expected.fs().is_some_and(|fs| found.fs() != Some(fs))
+            || expected
+                .uuid()
+                .is_some_and(|uuid| found.uuid() != Some(uuid))
+        {
+            mismatches.push(LayoutMismatch::Changed {
+                expected: Box::new(expected),
+                actual: Box::new(found),
+            });
+            continue;
+        }
+
+        if mount_check
+            && expected.kind == PartitionKind::Virtual
+            && let Some(fs) = expected.fs().and_then(|fs| fs.kernel_name())
+            && let Some(path) = &found.path
+            && let Err(error) = try_mount(path, fs)
+        {
+            mismatches.push(LayoutMismatch::MountFailed {
+                partition: found,
+                error,
+            });
+        }
+    }
+
+    mismatches.extend(actual.into_iter().map(LayoutMismatch::Unexpected));
+
+    VerificationReport { mismatches }
+}
+
+fn try_mount(path: &Path, fs: &str) -> std::io::Result<()> {
+    use nix::mount::{MsFlags, mount, umount};
+
+    let target = std::env::temp_dir().join(format!("partner-verify-{}", std::process::id()));
+    std::fs::create_dir_all(&target)?;
+    let result = mount(
+        Some(path),
+        &target,
+        Some(fs),
+        MsFlags::MS_RDONLY,
+        None::<&str>,
+    )
+    .and_then(|()| umount(&target));
+    std::fs::remove_dir(&target)?;
+
+    result.map_err(Into::into)
+}
@@ -0,0 +1,142 @@
+use serde::{Serialize, Serializer};
+use std::{fmt::Display, io::Read, os::unix::fs::FileExt, path::Path};
+
+/// How much of the start of a partition is read to classify it.
+const SAMPLE_LEN: usize = 1024 * 1024;
+
+/// How many samples of a free region are read to analyze it.
+const REGION_SAMPLES: u64 = 16;
+
+const LUKS_MAGIC: &[u8] = b"LUKS\xba\xbe";
+const LVM_LABEL: &[u8] = b"LABELONE";
+const LVM_TYPE: &[u8] = b"LVM2 001";
+const MD_MAGIC: [u8; 4] = 0xa92b4efc_u32.to_le_bytes();
+
+/// A guess at what a partition without a recognized file system holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentHint {
+    /// A LUKS encrypted volume.
+    Luks,
+    /// An LVM physical volume.
+    LvmPv,
+    /// A member of a Linux software RAID array, with a version 1.1 or 1.2 superblock.
+    MdRaid,
+    /// The start of the partition is all zeroes, so it likely holds nothing.
+    Zeroed,
+    /// Something unrecognized, with the given Shannon entropy in bits per byte. Values close to 8
+    /// suggest encrypted or compressed data.
+    Data { entropy: f64 },
+}
+
+impl Display for ContentHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Luks => write!(f, "luks"),
+            Self::LvmPv => write!(f, "lvm-pv"),
+            Self::MdRaid => write!(f, "mdraid"),
+            Self::Zeroed => write!(f, "zeroed"),
+            Self::Data { entropy } => write!(f, "data ({entropy:.1} bits/byte)"),
+        }
+    }
+}
+
+impl Serialize for ContentHint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// What's in an unpartitioned region of a device, as returned by
+/// [`FreeRegion::analyze`](crate::FreeRegion::analyze).
+///
+/// This is worked out from megabyte samples spread evenly across the region, so it can miss small
+/// amounts of data.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RegionAnalysis {
+    /// Whether every sample is all zeroes, so the region most likely holds nothing.
+    pub zeroed: bool,
+    /// The Shannon entropy of the samples in bits per byte. Values close to 8 suggest encrypted
+    /// or compressed data, such as a lost LUKS volume.
+    pub entropy: f64,
+    /// Recognized signatures at the start of samples, by their offset **in bytes** from the start
+    /// of the region. These mark where a deleted partition probably started.
+    pub signatures: Vec<(u64, ContentHint)>,
+}
+
+/// Analyze `length` bytes of the device at the given path from `offset`.
+pub(crate) fn analyze(path: &Path, offset: u64, length: u64) -> std::io::Result<RegionAnalysis> {
+    let file = std::fs::File::open(path)?;
+    let sample_len = (SAMPLE_LEN as u64).min(length);
+    // samples are a whole number of megabytes apart, so they line up with any partitions aligned
+    // like the region
+    let n_samples = (length / SAMPLE_LEN as u64).clamp(1, REGION_SAMPLES);
+    let stride = (length - sample_len) / n_samples.saturating_sub(1).max(1) / SAMPLE_LEN as u64
+        * SAMPLE_LEN as u64;
+
+    let mut samples = Vec::with_capacity((n_samples * sample_len) as usize);
+    let mut signatures = Vec::new();
+    for i in 0..n_samples {
+        let at = i * stride;
+        let mut sample = vec![0; sample_len as usize];
+        file.read_exact_at(&mut sample, offset + at)?;
+        if let hint @ (ContentHint::Luks | ContentHint::LvmPv | ContentHint::MdRaid) =
+            classify(&sample)
+        {
+            signatures.push((at, hint));
+        }
+        samples.extend(sample);
+    }
+
+    Ok(RegionAnalysis {
+        zeroed: samples.iter().all(|b| *b == 0),
+        entropy: entropy(&samples),
+        signatures,
+    })
+}
+
+/// Classify the contents of the partition at the given path by its first megabyte.
+pub(crate) fn scan(path: &Path) -> std::io::Result<ContentHint> {
+    let file = std::fs::File::open(path)?;
+    let mut sample = Vec::with_capacity(SAMPLE_LEN);
+    file.take(SAMPLE_LEN as u64).read_to_end(&mut sample)?;
+
+    Ok(classify(&sample))
+}
+
+fn classify(sample: &[u8]) -> ContentHint {
+    let at = |offset: usize, magic: &[u8]| sample.get(offset..offset + magic.len()) == Some(magic);
+
+    if at(0, LUKS_MAGIC) {
+        return ContentHint::Luks;
+    }
+    // the LVM label can be in any of the first four sectors
+    if (0..4).any(|sector| at(sector * 512, LVM_LABEL) && at(sector * 512 + 24, LVM_TYPE)) {
+        return ContentHint::LvmPv;
+    }
+    if at(0, &MD_MAGIC) || at(4096, &MD_MAGIC) {
+        return ContentHint::MdRaid;
+    }
+    if sample.iter().all(|b| *b == 0) {
+        return ContentHint::Zeroed;
+    }
+
+    ContentHint::Data {
+        entropy: entropy(sample),
+    }
+}
+
+fn entropy(sample: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in sample {
+        counts[*b as usize] += 1;
+    }
+    let len = sample.len() as f64;
+    counts
+        .into_iter()
+        .filter(|c| *c > 0)
+        .map(|c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,409 @@
+//! Chunked copying of raw data between (or within) block devices.
+//!
+//! This is the engine behind every operation that moves partition contents around, so that they
+//! all share the same progress reporting, cancellation, verification and resumption.
+
+use crate::gpt::crc32;
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::ControlFlow,
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+};
+
+/// Buffers used with `O_DIRECT` must be aligned to the logical block size of the device, which is
+/// never larger than a page.
+const DIRECT_ALIGN: usize = 4096;
+
+/// Options for [`copy`].
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    block_size: usize,
+    direct: bool,
+    verify: bool,
+    resume_from: u64,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            block_size: 4 * 1024 * 1024,
+            direct: false,
+            verify: false,
+            resume_from: 0,
+        }
+    }
+}
+
+impl CopyOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The size of each chunk in bytes. Defaults to 4 MiB.
+    ///
+    /// When using [`direct`](CopyOptions::direct) IO, this must be a multiple of the devices'
+    /// sector sizes.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Whether to bypass the page cache with `O_DIRECT`. Defaults to `false`.
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Whether to read back every chunk after writing it and compare checksums. Defaults to
+    /// `false`.
+    ///
+    /// Without [`direct`](CopyOptions::direct) IO, the read-back may be served from the page
+    /// cache rather than the device.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// The number of bytes already copied by a previous, interrupted copy. Defaults to `0`.
+    ///
+    /// This is the `copied` value of [`CopyError::Cancelled`] or the last reported
+    /// [`CopyProgress`].
+    pub fn resume_from(mut self, resume_from: u64) -> Self {
+        self.resume_from = resume_from;
+        self
+    }
+}
+
+/// Progress of a running [`copy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyProgress {
+    /// The number of bytes copied so far, including those skipped by
+    /// [`resume_from`](CopyOptions::resume_from).
+    pub copied: u64,
+    pub total: u64,
+}
+
+impl CopyProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.copied as f64 / self.total as f64
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CopyError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("verification failed for the chunk at offset {offset}")]
+    ChecksumMismatch { offset: u64 },
+    #[error("copy cancelled after {copied} bytes")]
+    Cancelled { copied: u64 },
+}
+
+/// A region of a file or block device **in bytes**.
+#[derive(Debug, Clone, Copy)]
+pub struct Region<'a> {
+    pub path: &'a Path,
+    pub offset: u64,
+}
+
+/// Copy `length` bytes from `source` to `target`.
+///
+/// `progress` is called after every chunk, and the copy is cancelled if it returns
+/// [`ControlFlow::Break`]. If the regions are on the same device and overlap, the copy runs in
+/// whichever direction avoids overwriting data before it has been read, so it is safe to use for
+/// moving a partition by less than its size.
+///
+/// Returns the number of bytes copied, which is always `length` on success.
+pub fn copy(
+    source: Region,
+    target: Region,
+    length: u64,
+    options: &CopyOptions,
+    mut progress: impl FnMut(CopyProgress) -> ControlFlow<()>,
+) -> Result<u64, CopyError> {
+    let open = |path: &Path, write: bool| {
+        let mut open_options = OpenOptions::new();
+        open_options.read(true).write(write);
+        if options.direct {
+            open_options.custom_flags(nix::libc::O_DIRECT);
+        }
+        open_options.open(path)
+    };
+    let mut source_file = open(source.path, false)?;
+    let mut target_file = open(target.path, true)?;
+
+    let backwards = same_file(&source_file, &target_file)?
+        && copies_backwards(source.offset, target.offset, length);
+
+    let mut buffer = AlignedBuffer::new(options.block_size);
+    let mut readback = AlignedBuffer::new(if options.verify {
+        options.block_size
+    } else {
+        0
+    });
+
+    let mut copied = options.resume_from.min(length);
+    while copied < length {
+        let chunk = (length - copied).min(options.block_size as u64);
+        let relative = chunk_offset(copied, chunk, length, backwards);
+
+        let data = &mut buffer.slice_mut()[..chunk as usize];
+        source_file.seek(SeekFrom::Start(source.offset + relative))?;
+        source_file.read_exact(data)?;
+        target_file.seek(SeekFrom::Start(target.offset + relative))?;
+        target_file.write_all(data)?;
+
+        if options.verify {
+            target_file.flush()?;
+            let check = &mut readback.slice_mut()[..chunk as usize];
+            target_file.seek(SeekFrom::Start(target.offset + relative))?;
+            target_file.read_exact(check)?;
+            if crc32(check) != crc32(data) {
+                return Err(CopyError::ChecksumMismatch {
+                    offset: target.offset + relative,
+                });
+            }
+        }
+
+        copied += chunk;
+        if progress(CopyProgress {
+            copied,
+            total: length,
+        })
+        .is_break()
+            && copied < length
+        {
+            target_file.sync_data()?;
+            return Err(CopyError::Cancelled { copied });
+        }
+    }
+
+    target_file.sync_data()?;
+    Ok(copied)
+}
+
+/// Whether a copy within one file has to run backwards, which is only the case if the target
+/// overlaps the end of the source.
+fn copies_backwards(source_offset: u64, target_offset: u64, length: u64) -> bool {
+    target_offset > source_offset && target_offset < source_offset + length
+}
+
+/// Get the offset of the next `chunk` bytes relative to the start of the regions, once `copied`
+/// of `length` bytes are copied.
+fn chunk_offset(copied: u64, chunk: u64, length: u64, backwards: bool) -> u64 {
+    if backwards {
+        length - copied - chunk
+    } else {
+        copied
+    }
+}
+
+fn same_file(a: &File, b: &File) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (a, b) = (a.metadata()?, b.metadata()?);
+    Ok(if a.file_type().is_file() {
+        (a.dev(), a.ino()) == (b.dev(), b.ino())
+    } else {
+        a.rdev() == b.rdev()
+    })
+}
+
+/// A heap buffer whose usable region is aligned for `O_DIRECT`.
+struct AlignedBuffer {
+    storage: Vec<u8>,
+    offset: usize,
+    len: usize,
+}
+
+impl AlignedBuffer {
+    fn new(len: usize) -> Self {
+        let storage = vec![0; len + DIRECT_ALIGN];
+        let offset = storage.as_ptr().align_offset(DIRECT_ALIGN);
+        Self {
+            storage,
+            offset,
+            len,
+        }
+    }
+
+    fn slice_mut(&mut self) -> &mut [u8] {
+        &mut self.storage[self.offset..self.offset + self.len]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A file in the temporary directory, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, contents: &[u8]) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("partner-copy-{}-{name}", std::process::id()));
+            std::fs::write(&path, contents).expect("failed to write temporary file");
+            Self(path)
+        }
+
+        fn read(&self) -> Vec<u8> {
+            std::fs::read(&self.0).expect("failed to read temporary file")
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Move `length` bytes within a file of `len` bytes from `from` to `to`, in chunks of
+    /// `block_size`, and compare the result with a copy made in memory.
+    fn move_within(len: usize, from: u64, to: u64, length: u64, block_size: usize) {
+        let original = pattern(len);
+        let file = TempFile::new(&format!("{from}-{to}-{length}-{block_size}"), &original);
+        let copied = copy(
+            Region {
+                path: &file.0,
+                offset: from,
+            },
+            Region {
+                path: &file.0,
+                offset: to,
+            },
+            length,
+            &CopyOptions::new().block_size(block_size),
+            |_| ControlFlow::Continue(()),
+        )
+        .expect("copy failed");
+        assert_eq!(copied, length);
+
+        let mut expected = original.clone();
+        expected.copy_within(from as usize..(from + length) as usize, to as usize);
+        assert_eq!(file.read(), expected);
+    }
+
+    #[test]
+    fn only_a_target_overlapping_the_end_of_the_source_copies_backwards() {
+        assert!(copies_backwards(100, 150, 100));
+        assert!(copies_backwards(100, 199, 100));
+        assert!(!copies_backwards(100, 200, 100));
+        assert!(!copies_backwards(100, 100, 100));
+        assert!(!copies_backwards(150, 100, 100));
+        assert!(!copies_backwards(300, 100, 100));
+    }
+
+    #[test]
+    fn chunks_cover_the_regions_in_order() {
+        let offsets = |backwards| {
+            let (length, block_size) = (10, 4);
+            let mut offsets = Vec::new();
+            let mut copied = 0;
+            while copied < length {
+                let chunk = (length - copied).min(block_size);
+                offsets.push((chunk_offset(copied, chunk, length, backwards), chunk));
+                copied += chunk;
+            }
+            offsets
+        };
+        assert_eq!(offsets(false), [(0, 4), (4, 4), (8, 2)]);
+        assert_eq!(offsets(true), [(6, 4), (2, 4), (0, 2)]);
+    }
+
+    #[test]
+    fn moving_forwards_by_less_than_the_length_keeps_the_data() {
+        move_within(4096, 100, 300, 1000, 64);
+    }
+
+    #[test]
+    fn moving_backwards_by_less_than_the_length_keeps_the_data() {
+        move_within(4096, 300, 100, 1000, 64);
+    }
+
+    #[test]
+    fn moving_past_the_end_of_the_source_keeps_the_data() {
+        move_within(4096, 100, 2000, 1000, 64);
+    }
+
+    #[test]
+    fn copies_between_files() {
+        let source = TempFile::new("source", &pattern(1000));
+        let target = TempFile::new("target", &[0; 1000]);
+        copy(
+            Region {
+                path: &source.0,
+                offset: 200,
+            },
+            Region {
+                path: &target.0,
+                offset: 100,
+            },
+            500,
+            &CopyOptions::new().block_size(64).verify(true),
+            |_| ControlFlow::Continue(()),
+        )
+        .expect("copy failed");
+
+        let mut expected = vec![0; 1000];
+        expected[100..600].copy_from_slice(&pattern(1000)[200..700]);
+        assert_eq!(target.read(), expected);
+    }
+
+    #[test]
+    fn resuming_finishes_a_cancelled_copy() {
+        let source = TempFile::new("resume-source", &pattern(1000));
+        let target = TempFile::new("resume-target", &[0; 1000]);
+        let regions = (
+            Region {
+                path: &source.0,
+                offset: 0,
+            },
+            Region {
+                path: &target.0,
+                offset: 0,
+            },
+        );
+        let options = CopyOptions::new().block_size(100);
+
+        let Err(CopyError::Cancelled { copied }) =
+            copy(regions.0, regions.1, 1000, &options, |p| {
+                if p.copied >= 300 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+        else {
+            panic!("copy wasn't cancelled");
+        };
+        assert_eq!(copied, 300);
+        assert_eq!(target.read()[300..], [0; 700]);
+
+        let mut reported = Vec::new();
+        copy(
+            regions.0,
+            regions.1,
+            1000,
+            &options.resume_from(copied),
+            |p| {
+                reported.push(p.copied);
+                ControlFlow::Continue(())
+            },
+        )
+        .expect("resumed copy failed");
+        assert_eq!(reported, [400, 500, 600, 700, 800, 900, 1000]);
+        assert_eq!(target.read(), pattern(1000));
+    }
+}
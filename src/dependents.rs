@@ -0,0 +1,149 @@
+//! Discovery of what depends on a partition, so that frontends can say exactly what would break
+//! before it's deleted or reformatted.
+
+use std::path::{Path, PathBuf};
+
+/// Something that stops working if a partition is deleted or reformatted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dependent {
+    /// The partition is mounted at the given path.
+    Mount(PathBuf),
+    /// The partition is in use as swap.
+    Swap,
+    /// A device-mapper device built on the partition, e.g. an LVM logical volume or an open LUKS
+    /// volume.
+    Mapper { name: String, kind: MapperKind },
+    /// A software RAID array the partition is a member of.
+    Raid(String),
+    /// An `/etc/fstab` entry refers to the partition.
+    Fstab { line: usize, mount_point: String },
+    /// An `/etc/crypttab` entry refers to the partition.
+    Crypttab { line: usize, name: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapperKind {
+    Lvm,
+    Crypt,
+    Other,
+}
+
+/// An entry of a config file that refers to a block device, such as `/etc/fstab`.
+pub(crate) struct ConfigEntry {
+    /// The 1-based line number.
+    pub line: usize,
+    /// The field identifying the device, e.g. `UUID=...` or `/dev/sda1`.
+    pub spec: String,
+    /// The other field of interest: the mount point for fstab, the volume name for crypttab.
+    pub target: String,
+}
+
+/// Read the entries of an fstab-like file, where the device is in the given field and the target
+/// in the other.
+pub(crate) fn read_config(path: &Path, spec_field: usize, target_field: usize) -> Vec<ConfigEntry> {
+    let Ok(contents) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim_start().starts_with('#'))
+        .filter_map(|(i, line)| {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            Some(ConfigEntry {
+                line: i + 1,
+                spec: (*fields.get(spec_field)?).into(),
+                target: (*fields.get(target_field)?).into(),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn fstab() -> Vec<ConfigEntry> {
+    read_config(Path::new("/etc/fstab"), 0, 1)
+}
+
+pub(crate) fn crypttab() -> Vec<ConfigEntry> {
+    read_config(Path::new("/etc/crypttab"), 1, 0)
+}
+
+/// Resolve a device spec as used in fstab to the device it currently refers to.
+pub(crate) fn resolve_spec(spec: &str) -> Option<PathBuf> {
+    let link = match spec.split_once('=') {
+        Some(("UUID", uuid)) => Path::new("/dev/disk/by-uuid").join(uuid.to_lowercase()),
+        Some(("LABEL", label)) => Path::new("/dev/disk/by-label").join(label),
+        Some(("PARTUUID", uuid)) => Path::new("/dev/disk/by-partuuid").join(uuid.to_lowercase()),
+        Some(("PARTLABEL", label)) => Path::new("/dev/disk/by-partlabel").join(label),
+        _ if spec.starts_with('/') => spec.into(),
+        _ => return None,
+    };
+    link.canonicalize().ok()
+}
+
+/// Find everything that depends on the partition at the given path.
+pub(crate) fn find(path: &Path, mount_point: Option<&Path>) -> Vec<Dependent> {
+    let Ok(path) = path.canonicalize() else {
+        return Vec::new();
+    };
+    let mut dependents = Vec::new();
+
+    dependents.extend(mount_point.map(|m| Dependent::Mount(m.into())));
+
+    if let Ok(swaps) = std::fs::read_to_string("/proc/swaps")
+        && swaps
+            .lines()
+            .skip(1)
+            .filter_map(|line| line.split_whitespace().next())
+            .any(|device| Path::new(device) == path)
+    {
+        dependents.push(Dependent::Swap);
+    }
+
+    if let Some(name) = path.file_name()
+        && let Ok(holders) =
+            std::fs::read_dir(Path::new("/sys/class/block").join(name).join("holders"))
+    {
+        for holder in holders.flatten() {
+            let holder = holder.path();
+            let name = |file: &str| {
+                std::fs::read_to_string(holder.join(file))
+                    .ok()
+                    .map(|s| s.trim().to_string())
+            };
+            if let Some(dm_name) = name("dm/name") {
+                let kind = match name("dm/uuid") {
+                    Some(uuid) if uuid.starts_with("LVM-") => MapperKind::Lvm,
+                    Some(uuid) if uuid.starts_with("CRYPT-") => MapperKind::Crypt,
+                    _ => MapperKind::Other,
+                };
+                dependents.push(Dependent::Mapper {
+                    name: dm_name,
+                    kind,
+                });
+            } else if let Some(md) = holder.file_name() {
+                dependents.push(Dependent::Raid(md.to_string_lossy().into_owned()));
+            }
+        }
+    }
+
+    dependents.extend(
+        fstab()
+            .into_iter()
+            .filter(|e| resolve_spec(&e.spec).as_deref() == Some(path.as_path()))
+            .map(|e| Dependent::Fstab {
+                line: e.line,
+                mount_point: e.target,
+            }),
+    );
+    dependents.extend(
+        crypttab()
+            .into_iter()
+            .filter(|e| resolve_spec(&e.spec).as_deref() == Some(path.as_path()))
+            .map(|e| Dependent::Crypttab {
+                line: e.line,
+                name: e.target,
+            }),
+    );
+
+    dependents
+}
@@ -0,0 +1,238 @@
+//! Support for the [Discoverable Partitions Specification][dps], which lets
+//! `systemd-gpt-auto-generator` find and mount partitions by their GPT type alone.
+//!
+//! [dps]: https://uapi-group.org/specifications/specs/discoverable_partitions_specification/
+
+use crate::{
+    Device, Guid,
+    removable::{self, RemovableReport},
+};
+use std::fmt::Display;
+
+/// A CPU architecture with its own root partition type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Arch {
+    X86,
+    #[strum(serialize = "x86-64")]
+    X86_64,
+    Arm,
+    Arm64,
+    #[strum(serialize = "riscv64")]
+    RiscV64,
+}
+
+impl Arch {
+    /// The architecture this library was compiled for, if it has a root partition type.
+    pub const fn native() -> Option<Self> {
+        if cfg!(target_arch = "x86") {
+            Some(Self::X86)
+        } else if cfg!(target_arch = "x86_64") {
+            Some(Self::X86_64)
+        } else if cfg!(target_arch = "arm") {
+            Some(Self::Arm)
+        } else if cfg!(target_arch = "aarch64") {
+            Some(Self::Arm64)
+        } else if cfg!(target_arch = "riscv64") {
+            Some(Self::RiscV64)
+        } else {
+            None
+        }
+    }
+}
+
+/// What a partition is used for, as identified by its type GUID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Root(Arch),
+    Home,
+    Srv,
+    Var,
+    Tmp,
+    Swap,
+    Esp,
+    Xbootldr,
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Root(arch) => write!(f, "root ({arch})"),
+            Self::Home => write!(f, "home"),
+            Self::Srv => write!(f, "srv"),
+            Self::Var => write!(f, "var"),
+            Self::Tmp => write!(f, "tmp"),
+            Self::Swap => write!(f, "swap"),
+            Self::Esp => write!(f, "esp"),
+            Self::Xbootldr => write!(f, "xbootldr"),
+        }
+    }
+}
+
+const ROLES: [(Role, Guid); 12] = [
+    (
+        Role::Root(Arch::X86),
+        Guid::from_u128(0x44479540_F297_41B2_9AF7_D131D5F0458A),
+    ),
+    (
+        Role::Root(Arch::X86_64),
+        Guid::from_u128(0x4F68BCE3_E8CD_4DB1_96E7_FBCAF984B709),
+    ),
+    (
+        Role::Root(Arch::Arm),
+        Guid::from_u128(0x69DAD710_2CE4_4E3C_B16C_21A1D49ABED3),
+    ),
+    (
+        Role::Root(Arch::Arm64),
+        Guid::from_u128(0xB921B045_1DF0_41C3_AF44_4C6F280D3FAE),
+    ),
+    (
+        Role::Root(Arch::RiscV64),
+        Guid::from_u128(0x72EC70A6_CF74_40E6_BD49_4BDA08E8F224),
+    ),
+    (
+        Role::Home,
+        Guid::from_u128(0x933AC7E1_2EB4_4F13_B844_0E14E2AEF915),
+    ),
+    (
+        Role::Srv,
+        Guid::from_u128(0x3B8F8425_20E0_4F3B_907F_1A25A76F98E8),
+    ),
+    (
+        Role::Var,
+        Guid::from_u128(0x4D21B016_B534_45C2_A9FB_5C16E091FD2D),
+    ),
+    (
+        Role::Tmp,
+        Guid::from_u128(0x7EC6F557_3BC5_4ACA_B293_16EF5DF639D1),
+    ),
+    (
+        Role::Swap,
+        Guid::from_u128(0x0657FD6D_A4AB_43C4_84E5_0933C84B4F4F),
+    ),
+    (
+        Role::Esp,
+        Guid::from_u128(0xC12A7328_F81F_11D2_BA4B_00A0C93EC93B),
+    ),
+    (
+        Role::Xbootldr,
+        Guid::from_u128(0xBC13C2FF_59E6_4262_A352_B275FD6F7172),
+    ),
+];
+
+impl Role {
+    /// The partition type GUID for this role.
+    pub fn type_guid(&self) -> Guid {
+        ROLES
+            .iter()
+            .find(|(role, _)| role == self)
+            .map(|(_, guid)| *guid)
+            .expect("every role has a type GUID")
+    }
+
+    pub fn from_type_guid(guid: Guid) -> Option<Self> {
+        ROLES
+            .iter()
+            .find(|(_, g)| *g == guid)
+            .map(|(role, _)| *role)
+    }
+
+    /// The path the partition is mounted at when discovered, if any.
+    pub fn mount_point(&self) -> Option<&'static str> {
+        match self {
+            Self::Root(_) => Some("/"),
+            Self::Home => Some("/home"),
+            Self::Srv => Some("/srv"),
+            Self::Var => Some("/var"),
+            Self::Tmp => Some("/var/tmp"),
+            Self::Swap => None,
+            Self::Esp => Some("/efi"),
+            Self::Xbootldr => Some("/boot"),
+        }
+    }
+}
+
+/// A reason a device's layout doesn't fully conform to the specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DpsIssue {
+    /// The device doesn't have a GPT, so nothing on it can be discovered.
+    NotGpt,
+    /// There is neither an ESP nor an XBOOTLDR partition, so the root partition can't be
+    /// discovered.
+    NoBootPartition,
+    /// There is no root partition for the given architecture.
+    NoRoot(Arch),
+    /// The partition at the given index has the same role as an earlier one, so it won't be
+    /// discovered.
+    Shadowed { index: usize, role: Role },
+    /// The partition at the given index is mounted where the given role would be, but has a
+    /// different type.
+    WrongType { index: usize, expected: Role },
+}
+
+/// The result of [`analyze`].
+#[derive(Debug, Clone, Default)]
+pub struct DpsReport {
+    pub issues: Vec<DpsIssue>,
+    /// Whether the device can boot as a portable UEFI drive, if it has removable media. This
+    /// doesn't affect [compliance](DpsReport::is_compliant).
+    pub removable: Option<RemovableReport>,
+}
+
+impl DpsReport {
+    pub fn is_compliant(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check the pending layout of a device against the specification, for the given architecture.
+///
+/// Devices with removable media are also [checked](removable::check) as portable UEFI drives.
+pub fn analyze(device: &Device, arch: Arch) -> DpsReport {
+    let removable = device
+        .is_removable()
+        .then(|| removable::check(device, arch));
+    if device.disk_type.as_deref() != Some("gpt") {
+        return DpsReport {
+            issues: vec![DpsIssue::NotGpt],
+            removable,
+        };
+    }
+
+    let mut issues = Vec::new();
+    let mut seen = Vec::new();
+
+    for (index, partition) in device.partitions().enumerate() {
+        let role = partition.type_guid().and_then(Role::from_type_guid);
+
+        if let Some(role) = role {
+            // swap partitions are all used, everything else only the first of its kind
+            if role != Role::Swap && seen.contains(&role) {
+                issues.push(DpsIssue::Shadowed { index, role });
+            }
+            seen.push(role);
+        }
+
+        let mount_point = partition.mount_point.as_deref().and_then(|p| p.to_str());
+        if let Some(expected) = ROLES
+            .iter()
+            .map(|(role, _)| *role)
+            .filter(|r| !matches!(r, Role::Root(a) if *a != arch))
+            .find(|r| r.mount_point().is_some() && r.mount_point() == mount_point)
+            && role != Some(expected)
+            // the ESP is also commonly mounted at /boot when there is no XBOOTLDR partition
+            && !(expected == Role::Xbootldr && role == Some(Role::Esp))
+        {
+            issues.push(DpsIssue::WrongType { index, expected });
+        }
+    }
+
+    if !seen.contains(&Role::Esp) && !seen.contains(&Role::Xbootldr) {
+        issues.push(DpsIssue::NoBootPartition);
+    }
+    if !seen.contains(&Role::Root(arch)) {
+        issues.push(DpsIssue::NoRoot(arch));
+    }
+
+    DpsReport { issues, removable }
+}
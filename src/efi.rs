@@ -0,0 +1,110 @@
+//! Reading UEFI boot variables through `efivarfs`, to find the EFI system partition the machine
+//! booted from.
+//!
+//! Everything here returns `None` on machines that didn't boot through UEFI, or where `efivarfs`
+//! isn't mounted.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+const EFIVARS: &str = "/sys/firmware/efi/efivars";
+/// The vendor GUID of the variables defined by the UEFI specification.
+const GLOBAL_VARIABLE: &str = "8be4df61-93ca-11d2-aa0d-00e098032b8c";
+
+/// A `Boot####` load option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootEntry {
+    pub number: u16,
+    pub description: String,
+    /// The partition the entry loads from, as the PARTUUID udev links it by in
+    /// `/dev/disk/by-partuuid`.
+    pub partuuid: Option<String>,
+}
+
+impl BootEntry {
+    /// Find the partition the entry loads from among the devices on this machine.
+    pub fn partition(&self) -> Option<PathBuf> {
+        Path::new("/dev/disk/by-partuuid")
+            .join(self.partuuid.as_deref()?)
+            .canonicalize()
+            .ok()
+    }
+}
+
+/// Get the number of the boot entry the machine booted from.
+pub fn boot_current() -> Option<u16> {
+    let data = read_variable("BootCurrent")?;
+    Some(u16::from_le_bytes(data.get(..2)?.try_into().ok()?))
+}
+
+/// Read the boot entry with the given number.
+pub fn boot_entry(number: u16) -> Option<BootEntry> {
+    let data = read_variable(&format!("Boot{number:04X}"))?;
+
+    // the attributes, then the length of the device path list
+    let path_len = u16::from_le_bytes(data.get(4..6)?.try_into().ok()?) as usize;
+    let description_end = data[6..]
+        .chunks_exact(2)
+        .position(|c| c == [0, 0])
+        .map(|i| 6 + i * 2)?;
+    let description = String::from_utf16_lossy(
+        &data[6..description_end]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>(),
+    );
+    let paths = data.get(description_end + 2..description_end + 2 + path_len)?;
+
+    Some(BootEntry {
+        number,
+        description,
+        partuuid: hard_drive_partuuid(paths),
+    })
+}
+
+/// Find the partition the machine booted from, as the canonical path of its device.
+///
+/// The boot entry is only read the first time, since it can't change until the machine reboots.
+pub fn booted_partition() -> Option<PathBuf> {
+    static BOOTED: OnceLock<Option<BootEntry>> = OnceLock::new();
+    BOOTED
+        .get_or_init(|| boot_entry(boot_current()?))
+        .as_ref()?
+        .partition()
+}
+
+/// Read the data of a variable in the global namespace, without its attributes.
+fn read_variable(name: &str) -> Option<Vec<u8>> {
+    let data = std::fs::read(Path::new(EFIVARS).join(format!("{name}-{GLOBAL_VARIABLE}"))).ok()?;
+    data.get(4..).map(<[u8]>::to_vec)
+}
+
+/// Find the hard drive media node in a device path list and get the PARTUUID of the partition it
+/// refers to.
+fn hard_drive_partuuid(mut paths: &[u8]) -> Option<String> {
+    while paths.len() >= 4 {
+        let (kind, subtype) = (paths[0], paths[1]);
+        let len = u16::from_le_bytes([paths[2], paths[3]]) as usize;
+        let node = paths.get(..len).filter(|_| len >= 4)?;
+        if (kind, subtype) == (0x04, 0x01) && len >= 42 {
+            let number = u32::from_le_bytes(node[4..8].try_into().ok()?);
+            let signature = &node[24..40];
+            return match node[41] {
+                // an MBR disk signature, followed by the partition number
+                0x01 => Some(format!(
+                    "{:08x}-{number:02x}",
+                    u32::from_le_bytes(signature[..4].try_into().ok()?)
+                )),
+                0x02 => {
+                    let guid = crate::Guid::from_bytes(signature.try_into().ok()?);
+                    Some(guid.to_string().to_lowercase())
+                }
+                _ => None,
+            };
+        }
+        paths = &paths[len..];
+    }
+    None
+}
@@ -0,0 +1,91 @@
+//! The hardware partitions eMMC devices have besides their user area: two boot areas, which SoC
+//! boot ROMs load bootloaders from, and the replay-protected memory block (RPMB) used by secure
+//! firmware.
+//!
+//! The kernel exposes them as devices of their own, e.g. `/dev/mmcblk0boot0` and
+//! `/dev/mmcblk0rpmb`, which share the user area's serial number. They can't be partitioned, and
+//! writing to them by mistake can leave a board unbootable, so partner only lists them.
+
+use byte_unit::Byte;
+use std::path::{Path, PathBuf};
+
+/// Which hardware partition an [`EmmcArea`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EmmcAreaKind {
+    /// Boot area 0 or 1.
+    Boot(u8),
+    /// The replay-protected memory block, which the kernel exposes as a character device.
+    Rpmb,
+}
+
+/// A hardware partition of an eMMC device, as returned by
+/// [`Device::emmc_areas`](crate::Device::emmc_areas).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmmcArea {
+    pub path: PathBuf,
+    pub kind: EmmcAreaKind,
+    /// The size of the area, which isn't known for the RPMB.
+    pub size: Option<Byte>,
+    /// Whether the kernel has the area read-only, as it has boot areas by default.
+    pub read_only: bool,
+}
+
+/// The part of an eMMC device name after `mmcblk` and the device number, e.g. `boot0` for
+/// `mmcblk0boot0` and an empty string for the user area `mmcblk0`.
+fn suffix(path: &Path) -> Option<&str> {
+    let rest = path.file_name()?.to_str()?.strip_prefix("mmcblk")?;
+    let suffix = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+    (suffix.len() < rest.len()).then_some(suffix)
+}
+
+/// The kind of the eMMC boot area or RPMB at the given path, or `None` for any other device,
+/// including eMMC user areas.
+pub(crate) fn kind(path: &Path) -> Option<EmmcAreaKind> {
+    match suffix(path)? {
+        "rpmb" => Some(EmmcAreaKind::Rpmb),
+        suffix => suffix
+            .strip_prefix("boot")?
+            .parse()
+            .ok()
+            .map(EmmcAreaKind::Boot),
+    }
+}
+
+/// The boot areas and RPMB of the eMMC device whose user area is at the given path, or nothing
+/// if it isn't one.
+pub(crate) fn areas(path: &Path) -> Vec<EmmcArea> {
+    if suffix(path) != Some("") {
+        return Vec::new();
+    }
+    [
+        EmmcAreaKind::Boot(0),
+        EmmcAreaKind::Boot(1),
+        EmmcAreaKind::Rpmb,
+    ]
+    .into_iter()
+    .filter_map(|kind| {
+        let suffix = match kind {
+            EmmcAreaKind::Boot(n) => format!("boot{n}"),
+            EmmcAreaKind::Rpmb => "rpmb".into(),
+        };
+        let mut name = path.file_name()?.to_owned();
+        name.push(suffix);
+        let path = path.with_file_name(name);
+        if !path.exists() {
+            return None;
+        }
+        // sysfs sizes are in 512-byte sectors regardless of the device's sector size
+        let size =
+            std::fs::read_to_string(Path::new("/sys/block").join(path.file_name()?).join("size"))
+                .ok()
+                .and_then(|size| size.trim().parse::<u64>().ok())
+                .map(|sectors| Byte::from_u64(sectors * 512));
+        Some(EmmcArea {
+            read_only: crate::power::is_read_only(&path),
+            path,
+            kind,
+            size,
+        })
+    })
+    .collect()
+}
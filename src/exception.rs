@@ -0,0 +1,137 @@
+//! Rust callbacks for libparted's exceptions, which it raises to report problems and ask how to
+//! proceed, e.g. when the backup GPT isn't at the end of the disk.
+
+use libparted_sys::{
+    _PedExceptionOption as RawOption, _PedExceptionType as RawKind, PedException,
+    ped_exception_set_handler,
+};
+use std::{
+    ffi::CStr,
+    panic::AssertUnwindSafe,
+    sync::{Mutex, PoisonError},
+};
+
+type Handler = Box<dyn FnMut(Exception) -> Resolution + Send>;
+
+static HANDLER: Mutex<Option<Handler>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum ExceptionKind {
+    Information,
+    Warning,
+    Error,
+    Fatal,
+    Bug,
+    NoFeature,
+}
+
+/// An answer to an [`Exception`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum Resolution {
+    /// Don't answer, letting the operation that raised the exception decide what to do. This
+    /// usually means failing.
+    Unhandled,
+    Fix,
+    Yes,
+    No,
+    Ok,
+    Retry,
+    Ignore,
+    Cancel,
+}
+
+impl Resolution {
+    fn raw(self) -> RawOption {
+        match self {
+            Self::Unhandled => RawOption::PED_EXCEPTION_UNHANDLED,
+            Self::Fix => RawOption::PED_EXCEPTION_FIX,
+            Self::Yes => RawOption::PED_EXCEPTION_YES,
+            Self::No => RawOption::PED_EXCEPTION_NO,
+            Self::Ok => RawOption::PED_EXCEPTION_OK,
+            Self::Retry => RawOption::PED_EXCEPTION_RETRY,
+            Self::Ignore => RawOption::PED_EXCEPTION_IGNORE,
+            Self::Cancel => RawOption::PED_EXCEPTION_CANCEL,
+        }
+    }
+}
+
+/// A problem reported by libparted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exception {
+    pub kind: ExceptionKind,
+    pub message: String,
+    /// The resolutions libparted accepts for this exception. Any other answer is treated as
+    /// [`Resolution::Unhandled`].
+    pub options: Vec<Resolution>,
+}
+
+/// # Safety
+///
+/// `raw` must point to a valid exception.
+unsafe fn read(raw: *const PedException) -> Exception {
+    let raw = unsafe { &*raw };
+    let kind = match raw.type_ {
+        RawKind::PED_EXCEPTION_INFORMATION => ExceptionKind::Information,
+        RawKind::PED_EXCEPTION_WARNING => ExceptionKind::Warning,
+        RawKind::PED_EXCEPTION_ERROR => ExceptionKind::Error,
+        RawKind::PED_EXCEPTION_FATAL => ExceptionKind::Fatal,
+        RawKind::PED_EXCEPTION_NO_FEATURE => ExceptionKind::NoFeature,
+        _ => ExceptionKind::Bug,
+    };
+    let message = if raw.message.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(raw.message) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    // the options are a bitmask of several variants, so reading them as the enum isn't sound
+    let bits = unsafe { std::ptr::addr_of!(raw.options).cast::<u32>().read() };
+    let options = [
+        Resolution::Fix,
+        Resolution::Yes,
+        Resolution::No,
+        Resolution::Ok,
+        Resolution::Retry,
+        Resolution::Ignore,
+        Resolution::Cancel,
+    ]
+    .into_iter()
+    .filter(|r| bits & r.raw() as u32 != 0)
+    .collect();
+
+    Exception {
+        kind,
+        message,
+        options,
+    }
+}
+
+unsafe extern "C" fn dispatch(raw: *mut PedException) -> RawOption {
+    let exception = unsafe { read(raw) };
+    let options = exception.options.clone();
+
+    // a handler that triggers another exception can't be called again while it's still running
+    let Ok(mut handler) = HANDLER.try_lock() else {
+        return RawOption::PED_EXCEPTION_UNHANDLED;
+    };
+    let Some(handler) = handler.as_mut() else {
+        return RawOption::PED_EXCEPTION_UNHANDLED;
+    };
+    // unwinding into C is undefined behavior
+    match std::panic::catch_unwind(AssertUnwindSafe(|| handler(exception))) {
+        Ok(resolution) if options.contains(&resolution) => resolution.raw(),
+        _ => RawOption::PED_EXCEPTION_UNHANDLED,
+    }
+}
+
+pub(crate) fn set_handler(handler: Handler) {
+    *HANDLER.lock().unwrap_or_else(PoisonError::into_inner) = Some(handler);
+    unsafe { ped_exception_set_handler(Some(dispatch)) };
+}
+
+pub(crate) fn clear_handler() {
+    unsafe { ped_exception_set_handler(None) };
+    *HANDLER.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
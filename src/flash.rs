@@ -0,0 +1,149 @@
+//! Writing installer and live images (ISOs) to devices, as when making a bootable USB stick.
+
+use crate::{
+    Device, FileSystem,
+    copy::{self, CopyError, CopyOptions, CopyProgress, Region},
+    dps::Arch,
+    removable::{self, RemovableReport},
+};
+use std::{ops::ControlFlow, path::Path, sync::Arc};
+
+/// Options for [`write_image`].
+#[derive(Debug, Clone, Default)]
+pub struct FlashOptions {
+    verify: bool,
+    direct: bool,
+    persistence: Option<(FileSystem, Arc<str>)>,
+    uefi: Option<Arch>,
+}
+
+impl FlashOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to read back and compare the written data. Defaults to `false`.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Whether to bypass the page cache while writing. Defaults to `false`.
+    pub fn direct(mut self, direct: bool) -> Self {
+        self.direct = direct;
+        self
+    }
+
+    /// Create a partition with the given file system and label in the space after the image,
+    /// e.g. for a live system's persistent storage. Defaults to none.
+    ///
+    /// This only works with images that carry a partition table (hybrid ISOs), since the new
+    /// partition is added to it.
+    pub fn persistence(mut self, fs: FileSystem, label: impl Into<Arc<str>>) -> Self {
+        self.persistence = Some((fs, label.into()));
+        self
+    }
+
+    /// Check that the written device can boot as a portable UEFI drive on machines of the given
+    /// architecture, failing with [`FlashError::NotUefiBootable`] if it can't. Defaults to no
+    /// check.
+    ///
+    /// The image has been written by the time this fails. [`removable::check`] also gives
+    /// advisories for firmware the device may not boot on.
+    pub fn uefi(mut self, arch: Arch) -> Self {
+        self.uefi = Some(arch);
+        self
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FlashError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Copy(#[from] CopyError),
+    #[error(transparent)]
+    Partition(#[from] crate::Error),
+    #[error(transparent)]
+    Commit(#[from] crate::CommitError),
+    #[error("partition №{0} of the device is in use")]
+    InUse(usize),
+    #[error("the image is {image} bytes, but the device only has {device}")]
+    TooLarge { image: u64, device: u64 },
+    #[error("the written device can't boot as a portable UEFI drive")]
+    NotUefiBootable(RemovableReport),
+}
+
+/// Write the image at the given path to the start of the device, replacing everything on it.
+///
+/// `progress` is called as with [`copy`](crate::copy::copy). The device is reopened afterwards,
+/// since its partition table is now the image's.
+pub fn write_image(
+    device: Device,
+    image: impl AsRef<Path>,
+    options: &FlashOptions,
+    progress: impl FnMut(CopyProgress) -> ControlFlow<()>,
+) -> Result<Device<'static>, FlashError> {
+    if let Some(index) = device.partitions().position(|p| p.mounted()) {
+        return Err(FlashError::InUse(index));
+    }
+    let image = image.as_ref();
+    let image_len = std::fs::metadata(image)?.len();
+    let device_len = device.size().as_u64();
+    if image_len > device_len {
+        return Err(FlashError::TooLarge {
+            image: image_len,
+            device: device_len,
+        });
+    }
+
+    let path = device.path_owned();
+    let sector_size = device.sector_size();
+    device.close()?;
+
+    copy::copy(
+        Region {
+            path: image,
+            offset: 0,
+        },
+        Region {
+            path: &path,
+            offset: 0,
+        },
+        image_len,
+        &CopyOptions::new()
+            .verify(options.verify)
+            .direct(options.direct),
+        progress,
+    )?;
+
+    let mut device = Device::open(&path)?;
+    device.sync()?;
+
+    if let Some((fs, label)) = &options.persistence {
+        // start at the next MiB boundary after the image
+        let align = (1024 * 1024 / sector_size) as i64;
+        let start = image_len
+            .div_ceil(sector_size)
+            .next_multiple_of(align as u64) as i64;
+        let end = device.raw.length() as i64 - 1 - align;
+        // msdos partitions have no names
+        let name = if device.disk_type.as_deref() == Some("gpt") {
+            label.clone()
+        } else {
+            "".into()
+        };
+        device.new_partition(name, Some(*fs), start..=end)?;
+        device.stage_format(start..=end, *fs, label.clone());
+        device.commit()?;
+    }
+
+    if let Some(arch) = options.uefi {
+        let report = removable::check(&device, arch);
+        if !report.is_bootable() {
+            return Err(FlashError::NotUefiBootable(report));
+        }
+    }
+
+    Ok(device)
+}
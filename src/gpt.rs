@@ -0,0 +1,817 @@
+//! A minimal pure-Rust GPT reader, used where libparted refuses to open a disk.
+
+use crate::{Guid, blkpg};
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    ops::RangeInclusive,
+};
+
+const SIGNATURE: &[u8; 8] = b"EFI PART";
+const MIN_HEADER_SIZE: usize = 92;
+const MIN_ENTRY_SIZE: usize = 128;
+/// The largest entry array read, far more than the 16 KiB tools create, so a corrupt header
+/// can't make reading allocate without bound.
+const MAX_ENTRIES_SIZE: usize = 4 * 1024 * 1024;
+/// The UTF-16 code units of the partition name field.
+const NAME_UNITS: usize = 36;
+/// Marks a note after the terminator of a partition name, so leftover bytes aren't mistaken for
+/// one. This is a private use character, which no tool puts in names.
+const NOTE_MARKER: u16 = 0xE000;
+
+/// A GPT header along with its partition entry array, both kept in their on-disk form.
+#[derive(Clone)]
+pub(crate) struct Table {
+    header: Vec<u8>,
+    entries: Vec<u8>,
+    entry_size: usize,
+}
+
+/// A used entry in a GPT partition entry array.
+pub(crate) struct Entry {
+    /// The 1-based partition number.
+    pub number: u32,
+    pub type_guid: Guid,
+    /// The partition's own GUID, its PARTUUID.
+    pub uuid: Guid,
+    pub bounds: (u64, u64),
+    /// The attribute flags, whose top 16 bits are for the partition type to define.
+    pub attributes: u64,
+    pub name: String,
+    /// A note kept in the name field after the name's terminator.
+    pub note: Option<String>,
+}
+
+fn invalid(message: &'static str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(std::array::from_fn(|i| bytes[offset + i]))
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(std::array::from_fn(|i| bytes[offset + i]))
+}
+
+/// The CRC-32 variant used by GPT (IEEE 802.3, reflected).
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+/// The partition name field of an entry, as UTF-16 code units.
+fn name_units(entry: &[u8]) -> [u16; NAME_UNITS] {
+    std::array::from_fn(|i| u16::from_le_bytes([entry[56 + 2 * i], entry[57 + 2 * i]]))
+}
+
+/// An edit of a used GPT entry that libparted can't make, so it's made after libparted commits.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum EntryEdit {
+    /// Move the entry to the given 1-based number.
+    Number(u32),
+    Uuid(Guid),
+}
+
+/// Edit the entries of the partitions starting within the given bounds **in sectors**, which may
+/// have been aligned as for [`move_entry`](Table::move_entry), and write both GPTs of a device of
+/// the given length **in sectors**.
+///
+/// The edited partitions are then replaced in the kernel's view of the device, so that they have
+/// their new numbers and udev their new PARTUUIDs before anything looks up their paths.
+pub(crate) fn rewrite_entries(
+    disk: &mut impl blkpg::Disk,
+    sector_size: u64,
+    length: u64,
+    edits: &[(RangeInclusive<i64>, EntryEdit)],
+) -> std::io::Result<()> {
+    let mut table = Table::read_at(disk, sector_size, 1)?;
+    let mut edited = Vec::<(&RangeInclusive<i64>, u32)>::new();
+    for (bounds, _) in edits {
+        if !edited.iter().any(|(b, _)| *b == bounds) {
+            edited.push((bounds, table.number_at(bounds)?));
+        }
+    }
+    for (bounds, edit) in edits {
+        match edit {
+            EntryEdit::Number(number) => table.move_entry(bounds, *number)?,
+            EntryEdit::Uuid(uuid) => table.set_uuid(bounds, *uuid)?,
+        }
+    }
+    table.write_primary(disk, sector_size)?;
+    table.write_backup(disk, sector_size, length)?;
+    disk.sync()?;
+
+    let replacements = edited
+        .into_iter()
+        .map(|(bounds, old)| {
+            let number = table.number_at(bounds)?;
+            let entry = table
+                .entries()
+                .find(|e| e.number == number)
+                .expect("the number is of a used entry");
+            Ok(blkpg::Replacement {
+                old,
+                number,
+                start: entry.bounds.0 * sector_size,
+                length: (entry.bounds.1 - entry.bounds.0 + 1) * sector_size,
+            })
+        })
+        .collect::<std::io::Result<Vec<_>>>()?;
+    blkpg::replace(disk, &replacements)
+}
+
+impl Table {
+    /// Read the backup GPT from the last sector of a device of the given length **in sectors**.
+    pub(crate) fn read_backup(
+        file: &mut (impl Read + Seek),
+        sector_size: u64,
+        length: u64,
+    ) -> std::io::Result<Self> {
+        let lba = length
+            .checked_sub(1)
+            .ok_or_else(|| invalid("the device is empty"))?;
+        Self::read_at(file, sector_size, lba)
+    }
+
+    /// Read and verify the GPT header at the given LBA, along with its partition entry array.
+    pub(crate) fn read_at(
+        file: &mut (impl Read + Seek),
+        sector_size: u64,
+        lba: u64,
+    ) -> std::io::Result<Self> {
+        let mut sector = vec![0; sector_size as usize];
+        file.seek(SeekFrom::Start(lba * sector_size))?;
+        file.read_exact(&mut sector)?;
+
+        if &sector[..SIGNATURE.len()] != SIGNATURE {
+            return Err(invalid("missing GPT header signature"));
+        }
+
+        let header_size = u32_at(&sector, 12) as usize;
+        if !(MIN_HEADER_SIZE..=sector.len()).contains(&header_size) {
+            return Err(invalid("invalid GPT header size"));
+        }
+        let mut header = sector[..header_size].to_vec();
+        let header_crc = u32_at(&header, 16);
+        header[16..20].fill(0);
+        if crc32(&header) != header_crc {
+            return Err(invalid("GPT header checksum mismatch"));
+        }
+        header[16..20].copy_from_slice(&header_crc.to_le_bytes());
+
+        let entry_size = u32_at(&header, 84) as usize;
+        if entry_size < MIN_ENTRY_SIZE || !entry_size.is_power_of_two() {
+            return Err(invalid("invalid GPT entry size"));
+        }
+        let entries_len = (u32_at(&header, 80) as usize)
+            .checked_mul(entry_size)
+            .filter(|len| *len <= MAX_ENTRIES_SIZE)
+            .ok_or_else(|| invalid("invalid GPT entry count"))?;
+        let entries_start = u64_at(&header, 72)
+            .checked_mul(sector_size)
+            .ok_or_else(|| invalid("invalid GPT entry array location"))?;
+        let device_len = file.seek(SeekFrom::End(0))?;
+        if entries_start
+            .checked_add(entries_len as u64)
+            .is_none_or(|end| end > device_len)
+        {
+            return Err(invalid("GPT entry array is past the end of the device"));
+        }
+        let mut entries = vec![0; entries_len];
+        file.seek(SeekFrom::Start(entries_start))?;
+        file.read_exact(&mut entries)?;
+        if crc32(&entries) != u32_at(&header, 88) {
+            return Err(invalid("GPT entry array checksum mismatch"));
+        }
+
+        Ok(Self {
+            header,
+            entries,
+            entry_size,
+        })
+    }
+
+    /// The used entries of the partition entry array.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = Entry> {
+        self.entries
+            .chunks_exact(self.entry_size)
+            .zip(1..)
+            .filter(|(entry, _)| entry[..16].iter().any(|b| *b != 0))
+            .map(|(entry, number)| {
+                let units = name_units(entry);
+                let name = units
+                    .iter()
+                    .copied()
+                    .take_while(|c| *c != 0)
+                    .collect::<Vec<_>>();
+                let note = units
+                    .get(name.len() + 1..)
+                    .and_then(|rest| rest.split_first())
+                    .filter(|(marker, _)| **marker == NOTE_MARKER)
+                    .map(|(_, note)| {
+                        let note = note.iter().copied().take_while(|c| *c != 0);
+                        String::from_utf16_lossy(&note.collect::<Vec<_>>())
+                    });
+                Entry {
+                    number,
+                    type_guid: Guid::from_bytes(std::array::from_fn(|i| entry[i])),
+                    uuid: Guid::from_bytes(std::array::from_fn(|i| entry[16 + i])),
+                    bounds: (u64_at(entry, 32), u64_at(entry, 40)),
+                    attributes: u64_at(entry, 48),
+                    name: String::from_utf16_lossy(&name),
+                    note,
+                }
+            })
+    }
+
+    /// The number of entries in the partition entry array, used or not.
+    pub(crate) fn entry_capacity(&self) -> u32 {
+        u32_at(&self.header, 80)
+    }
+
+    /// The number of sectors taken up by the partition entry array.
+    fn entry_sectors(&self, sector_size: u64) -> u64 {
+        (self.entries.len() as u64).div_ceil(sector_size)
+    }
+
+    /// Change the number of entries in the partition entry array, moving the usable area of a
+    /// device of the given length **in sectors** to fit.
+    ///
+    /// Fails if a used entry would be dropped or a partition would overlap the entry array.
+    pub(crate) fn set_entry_capacity(
+        &mut self,
+        capacity: u32,
+        sector_size: u64,
+        length: u64,
+    ) -> std::io::Result<()> {
+        let len = capacity as usize * self.entry_size;
+        if self.entries[len.min(self.entries.len())..]
+            .iter()
+            .any(|b| *b != 0)
+        {
+            return Err(invalid("a used GPT entry would be dropped"));
+        }
+        self.entries.resize(len, 0);
+
+        let entry_sectors = self.entry_sectors(sector_size);
+        let first_usable = 2 + entry_sectors;
+        let last_usable = length - 2 - entry_sectors;
+        if self
+            .entries()
+            .any(|e| e.bounds.0 < first_usable || e.bounds.1 > last_usable)
+        {
+            return Err(invalid("a partition would overlap the GPT entry array"));
+        }
+
+        self.header[40..48].copy_from_slice(&first_usable.to_le_bytes());
+        self.header[48..56].copy_from_slice(&last_usable.to_le_bytes());
+        self.header[80..84].copy_from_slice(&capacity.to_le_bytes());
+        self.header[88..92].copy_from_slice(&crc32(&self.entries).to_le_bytes());
+        Ok(())
+    }
+
+    /// Move the entry of the partition starting within the given bounds **in sectors** to the
+    /// given 1-based entry number.
+    ///
+    /// libparted may move the start of a new partition to align it, so the bounds are those that
+    /// were requested rather than the exact ones.
+    pub(crate) fn move_entry(
+        &mut self,
+        bounds: &RangeInclusive<i64>,
+        number: u32,
+    ) -> std::io::Result<()> {
+        let from = self.number_at(bounds)?;
+        if from == number {
+            return Ok(());
+        }
+        if number == 0 || number > self.entry_capacity() {
+            return Err(invalid("GPT entry number out of range"));
+        }
+        if self.entries().any(|e| e.number == number) {
+            return Err(invalid("GPT entry number already taken"));
+        }
+
+        let entry = |n: u32| (n as usize - 1) * self.entry_size;
+        let (from, to) = (entry(from), entry(number));
+        let moved = self.entries[from..from + self.entry_size].to_vec();
+        self.entries[to..to + self.entry_size].copy_from_slice(&moved);
+        self.entries[from..from + self.entry_size].fill(0);
+        self.header[88..92].copy_from_slice(&crc32(&self.entries).to_le_bytes());
+        Ok(())
+    }
+
+    /// Keep a note in the name field of the entry with the given 1-based number, after the name,
+    /// or remove the note if it's `None`.
+    ///
+    /// Fails if the note doesn't fit in the space the name leaves.
+    pub(crate) fn set_note(&mut self, number: u32, note: Option<&str>) -> std::io::Result<()> {
+        let entry = self.entry_mut(number)?;
+        let mut units = name_units(entry);
+        let name_len = units.iter().take_while(|c| **c != 0).count();
+
+        let rest = &mut units[(name_len + 1).min(NAME_UNITS)..];
+        rest.fill(0);
+        if let Some(note) = note {
+            let note = std::iter::once(NOTE_MARKER)
+                .chain(note.encode_utf16())
+                .collect::<Vec<_>>();
+            if note.len() > rest.len() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "the note doesn't fit after the partition's name",
+                ));
+            }
+            rest[..note.len()].copy_from_slice(&note);
+        }
+
+        for (bytes, unit) in entry[56..128].chunks_exact_mut(2).zip(units) {
+            bytes.copy_from_slice(&unit.to_le_bytes());
+        }
+        self.header[88..92].copy_from_slice(&crc32(&self.entries).to_le_bytes());
+        Ok(())
+    }
+
+    /// Replace the attribute flags of the entry with the given 1-based number.
+    pub(crate) fn set_attributes(&mut self, number: u32, attributes: u64) -> std::io::Result<()> {
+        self.entry_mut(number)?[48..56].copy_from_slice(&attributes.to_le_bytes());
+        self.header[88..92].copy_from_slice(&crc32(&self.entries).to_le_bytes());
+        Ok(())
+    }
+
+    /// Replace the partition GUID of the entry of the partition with the given bounds, which may
+    /// have been aligned as for [`move_entry`](Table::move_entry).
+    pub(crate) fn set_uuid(
+        &mut self,
+        bounds: &RangeInclusive<i64>,
+        uuid: Guid,
+    ) -> std::io::Result<()> {
+        let number = self.number_at(bounds)?;
+        self.entry_mut(number)?[16..32].copy_from_slice(&uuid.to_bytes());
+        self.header[88..92].copy_from_slice(&crc32(&self.entries).to_le_bytes());
+        Ok(())
+    }
+
+    /// The number of the used entry starting within the given bounds.
+    fn number_at(&self, bounds: &RangeInclusive<i64>) -> std::io::Result<u32> {
+        self.entries()
+            .find(|e| (*bounds.start() as u64..=*bounds.end() as u64).contains(&e.bounds.0))
+            .map(|e| e.number)
+            .ok_or_else(|| invalid("the partition's GPT entry wasn't found"))
+    }
+
+    /// The used entry with the given 1-based number.
+    fn entry_mut(&mut self, number: u32) -> std::io::Result<&mut [u8]> {
+        let offset = number
+            .checked_sub(1)
+            .map(|n| n as usize * self.entry_size)
+            .filter(|offset| *offset < self.entries.len())
+            .ok_or_else(|| invalid("GPT entry number out of range"))?;
+        let entry = &mut self.entries[offset..offset + self.entry_size];
+        if entry[..16].iter().all(|b| *b == 0) {
+            return Err(invalid("GPT entry is unused"));
+        }
+        Ok(entry)
+    }
+
+    /// Write this table as the primary GPT, with the header at LBA 1 and the entry array
+    /// immediately following it.
+    pub(crate) fn write_primary(
+        &self,
+        file: &mut (impl Write + Seek),
+        sector_size: u64,
+    ) -> std::io::Result<()> {
+        let backup_lba = u64_at(&self.header, 24).max(u64_at(&self.header, 32));
+        self.write(file, sector_size, 1, backup_lba, 2)
+    }
+
+    /// Write this table as the backup GPT of a device of the given length **in sectors**, with
+    /// the header in the last sector and the entry array immediately preceding it.
+    pub(crate) fn write_backup(
+        &self,
+        file: &mut (impl Write + Seek),
+        sector_size: u64,
+        length: u64,
+    ) -> std::io::Result<()> {
+        let header_lba = length - 1;
+        let entries_lba = header_lba - self.entry_sectors(sector_size);
+        self.write(file, sector_size, header_lba, 1, entries_lba)
+    }
+
+    fn write(
+        &self,
+        file: &mut (impl Write + Seek),
+        sector_size: u64,
+        header_lba: u64,
+        alternate_lba: u64,
+        entries_lba: u64,
+    ) -> std::io::Result<()> {
+        let mut sector = vec![0; sector_size as usize];
+        sector[..self.header.len()].copy_from_slice(&self.header);
+
+        sector[24..32].copy_from_slice(&header_lba.to_le_bytes());
+        sector[32..40].copy_from_slice(&alternate_lba.to_le_bytes());
+        sector[72..80].copy_from_slice(&entries_lba.to_le_bytes());
+        sector[16..20].fill(0);
+        let crc = crc32(&sector[..self.header.len()]);
+        sector[16..20].copy_from_slice(&crc.to_le_bytes());
+
+        file.seek(SeekFrom::Start(entries_lba * sector_size))?;
+        file.write_all(&self.entries)?;
+        file.seek(SeekFrom::Start(header_lba * sector_size))?;
+        file.write_all(&sector)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blkpg::{
+        Op,
+        tests::{Event, FakeDisk},
+    };
+    use std::io::Cursor;
+
+    const SECTOR_SIZE: u64 = 512;
+    /// The length of the test device **in sectors**.
+    const LENGTH: u64 = 200;
+    const CAPACITY: u32 = 128;
+    /// The sectors taken up by an entry array of [`CAPACITY`] entries.
+    const ENTRY_SECTORS: u64 = CAPACITY as u64 * MIN_ENTRY_SIZE as u64 / SECTOR_SIZE;
+    const LINUX: Guid = Guid::from_u128(0x0FC63DAF_8483_4772_8E79_3D69D8477DE4);
+
+    fn entry(uuid: u128, bounds: (u64, u64), attributes: u64, name: &str) -> [u8; MIN_ENTRY_SIZE] {
+        let mut entry = [0; MIN_ENTRY_SIZE];
+        entry[..16].copy_from_slice(&LINUX.to_bytes());
+        entry[16..32].copy_from_slice(&Guid::from_u128(uuid).to_bytes());
+        entry[32..40].copy_from_slice(&bounds.0.to_le_bytes());
+        entry[40..48].copy_from_slice(&bounds.1.to_le_bytes());
+        entry[48..56].copy_from_slice(&attributes.to_le_bytes());
+        for (bytes, unit) in entry[56..].chunks_exact_mut(2).zip(name.encode_utf16()) {
+            bytes.copy_from_slice(&unit.to_le_bytes());
+        }
+        entry
+    }
+
+    /// Build a device with a primary GPT holding the given entries, numbered from 1.
+    fn device(entries: &[[u8; MIN_ENTRY_SIZE]]) -> Cursor<Vec<u8>> {
+        let mut array = vec![0; CAPACITY as usize * MIN_ENTRY_SIZE];
+        for (chunk, entry) in array.chunks_exact_mut(MIN_ENTRY_SIZE).zip(entries) {
+            chunk.copy_from_slice(entry);
+        }
+
+        let mut header = vec![0; MIN_HEADER_SIZE];
+        header[..8].copy_from_slice(SIGNATURE);
+        header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&(MIN_HEADER_SIZE as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&1u64.to_le_bytes());
+        header[32..40].copy_from_slice(&(LENGTH - 1).to_le_bytes());
+        header[40..48].copy_from_slice(&(2 + ENTRY_SECTORS).to_le_bytes());
+        header[48..56].copy_from_slice(&(LENGTH - 2 - ENTRY_SECTORS).to_le_bytes());
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&CAPACITY.to_le_bytes());
+        header[84..88].copy_from_slice(&(MIN_ENTRY_SIZE as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&crc32(&array).to_le_bytes());
+        let crc = crc32(&header);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+
+        let mut device = vec![0; (LENGTH * SECTOR_SIZE) as usize];
+        let header_start = SECTOR_SIZE as usize;
+        device[header_start..header_start + header.len()].copy_from_slice(&header);
+        let array_start = 2 * SECTOR_SIZE as usize;
+        device[array_start..array_start + array.len()].copy_from_slice(&array);
+        Cursor::new(device)
+    }
+
+    fn two_partitions() -> Cursor<Vec<u8>> {
+        device(&[
+            entry(1, (40, 79), 0, "root"),
+            entry(2, (80, 159), 1 << 63, "home"),
+        ])
+    }
+
+    fn read_primary(device: &mut Cursor<Vec<u8>>) -> std::io::Result<Table> {
+        Table::read_at(device, SECTOR_SIZE, 1)
+    }
+
+    /// The number, PARTUUID, bounds, attributes and name of an entry.
+    type Summary = (u32, u128, (u64, u64), u64, String);
+
+    fn summary(table: &Table) -> Vec<Summary> {
+        table
+            .entries()
+            .map(|e| {
+                assert_eq!(e.type_guid, LINUX);
+                (e.number, e.uuid.as_u128(), e.bounds, e.attributes, e.name)
+            })
+            .collect()
+    }
+
+    fn error(result: std::io::Result<Table>) -> String {
+        match result {
+            Ok(_) => panic!("the table was read"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn crc32_matches_the_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn reads_the_used_entries() {
+        let table = read_primary(&mut two_partitions()).expect("failed to read the table");
+        assert_eq!(table.entry_capacity(), CAPACITY);
+        assert_eq!(
+            summary(&table),
+            [
+                (1, 1, (40, 79), 0, "root".into()),
+                (2, 2, (80, 159), 1 << 63, "home".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_entries_keep_their_numbers() {
+        let table = read_primary(&mut device(&[
+            [0; MIN_ENTRY_SIZE],
+            entry(2, (80, 159), 0, "home"),
+        ]))
+        .expect("failed to read the table");
+        let numbers: Vec<_> = table.entries().map(|e| e.number).collect();
+        assert_eq!(numbers, [2]);
+    }
+
+    #[test]
+    fn a_backup_written_from_the_primary_reads_back_the_same() {
+        let mut device = two_partitions();
+        let table = read_primary(&mut device).expect("failed to read the table");
+        table
+            .write_backup(&mut device, SECTOR_SIZE, LENGTH)
+            .expect("failed to write the backup");
+        let backup =
+            Table::read_backup(&mut device, SECTOR_SIZE, LENGTH).expect("failed to read backup");
+        assert_eq!(summary(&backup), summary(&table));
+
+        // and the primary can be restored from the backup
+        device.get_mut()[SECTOR_SIZE as usize..].fill(0);
+        backup
+            .write_backup(&mut device, SECTOR_SIZE, LENGTH)
+            .expect("failed to write the backup");
+        backup
+            .write_primary(&mut device, SECTOR_SIZE)
+            .expect("failed to write the primary");
+        let primary = read_primary(&mut device).expect("failed to read the restored table");
+        assert_eq!(summary(&primary), summary(&table));
+    }
+
+    #[test]
+    fn a_missing_signature_is_rejected() {
+        let mut device = two_partitions();
+        device.get_mut()[SECTOR_SIZE as usize] = 0;
+        assert_eq!(
+            error(read_primary(&mut device)),
+            "missing GPT header signature"
+        );
+    }
+
+    #[test]
+    fn a_corrupt_header_is_rejected() {
+        let mut device = two_partitions();
+        // the first usable LBA
+        device.get_mut()[SECTOR_SIZE as usize + 40] ^= 1;
+        assert_eq!(
+            error(read_primary(&mut device)),
+            "GPT header checksum mismatch"
+        );
+    }
+
+    #[test]
+    fn a_corrupt_entry_array_is_rejected() {
+        let mut device = two_partitions();
+        device.get_mut()[2 * SECTOR_SIZE as usize + 32] ^= 1;
+        assert_eq!(
+            error(read_primary(&mut device)),
+            "GPT entry array checksum mismatch"
+        );
+    }
+
+    #[test]
+    fn an_entry_array_past_the_end_of_the_device_is_rejected() {
+        let mut device = two_partitions();
+        device.get_mut().truncate(20 * SECTOR_SIZE as usize);
+        assert_eq!(
+            error(read_primary(&mut device)),
+            "GPT entry array is past the end of the device"
+        );
+    }
+
+    #[test]
+    fn a_huge_entry_count_is_rejected_before_reading() {
+        let mut device = two_partitions();
+        let header = &mut device.get_mut()[SECTOR_SIZE as usize..][..MIN_HEADER_SIZE];
+        header[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        header[16..20].fill(0);
+        let crc = crc32(header);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(error(read_primary(&mut device)), "invalid GPT entry count");
+    }
+
+    #[test]
+    fn an_empty_device_has_no_backup() {
+        let result = Table::read_backup(&mut Cursor::new(Vec::new()), SECTOR_SIZE, 0);
+        assert_eq!(error(result), "the device is empty");
+    }
+
+    fn notes(table: &Table) -> Vec<(String, Option<String>)> {
+        table.entries().map(|e| (e.name, e.note)).collect()
+    }
+
+    #[test]
+    fn notes_are_kept_after_the_name() {
+        let mut device = two_partitions();
+        let mut table = read_primary(&mut device).expect("failed to read the table");
+        table
+            .set_note(2, Some("backup target"))
+            .expect("failed to set the note");
+        table
+            .write_primary(&mut device, SECTOR_SIZE)
+            .expect("failed to write the table");
+
+        let table = read_primary(&mut device).expect("failed to read the table back");
+        assert_eq!(
+            notes(&table),
+            [
+                ("root".into(), None),
+                ("home".into(), Some("backup target".into()))
+            ]
+        );
+    }
+
+    #[test]
+    fn removing_a_note_leaves_the_name() {
+        let mut table = read_primary(&mut two_partitions()).expect("failed to read the table");
+        table
+            .set_note(1, Some("keep"))
+            .expect("failed to set the note");
+        table.set_note(1, None).expect("failed to remove the note");
+        assert_eq!(notes(&table)[0], ("root".into(), None));
+    }
+
+    #[test]
+    fn leftover_bytes_after_a_name_are_not_a_note() {
+        let mut leftover = entry(1, (40, 79), 0, "root");
+        // "y" after the terminator, as left behind by renaming "rootxy" to "root"
+        leftover[66] = b'y';
+        let table = read_primary(&mut device(&[leftover])).expect("failed to read the table");
+        assert_eq!(notes(&table), [("root".into(), None)]);
+    }
+
+    #[test]
+    fn a_note_has_to_fit_after_the_name() {
+        let mut table = read_primary(&mut two_partitions()).expect("failed to read the table");
+        // 36 units, less the name, its terminator and the note marker
+        let longest = "n".repeat(NAME_UNITS - "root".len() - 2);
+        table
+            .set_note(1, Some(&longest))
+            .expect("failed to set the longest note");
+        assert_eq!(notes(&table)[0], ("root".into(), Some(longest.clone())));
+
+        let error = table
+            .set_note(1, Some(&(longest + "n")))
+            .expect_err("a note too long was set");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn a_full_name_leaves_no_room_for_a_note() {
+        let name = "n".repeat(NAME_UNITS);
+        let mut table = read_primary(&mut device(&[entry(1, (40, 79), 0, &name)]))
+            .expect("failed to read the table");
+        assert!(table.set_note(1, Some("a")).is_err());
+        table.set_note(1, None).expect("failed to remove no note");
+        assert_eq!(notes(&table), [(name, None)]);
+    }
+
+    #[test]
+    fn notes_can_only_be_set_on_used_entries() {
+        let mut table = read_primary(&mut two_partitions()).expect("failed to read the table");
+        for number in [0, 3, CAPACITY + 1] {
+            assert!(table.set_note(number, Some("note")).is_err());
+        }
+    }
+
+    #[test]
+    fn rewritten_entries_are_on_disk_before_the_kernel_is_told() {
+        let mut disk = FakeDisk::new(two_partitions());
+        disk.on_update = Box::new(|data, op| {
+            let table = read_primary(data).expect("failed to read the table");
+            let numbers: Vec<_> = table
+                .entries()
+                .map(|e| (e.number, e.uuid.as_u128()))
+                .collect();
+            assert_eq!(
+                numbers,
+                [(1, 2), (5, 1)],
+                "the kernel was told about {op:?} too early"
+            );
+        });
+        let edits = [
+            // libparted aligned the start of the partition
+            (30..=79, EntryEdit::Number(5)),
+            // and the second partition takes the number the first gives up
+            (80..=159, EntryEdit::Number(1)),
+        ];
+        rewrite_entries(&mut disk, SECTOR_SIZE, LENGTH, &edits).expect("failed to rewrite");
+
+        let add = |number, (first, last): (u64, u64)| {
+            Event::Update(Op::Add {
+                number,
+                start: first * SECTOR_SIZE,
+                length: (last - first + 1) * SECTOR_SIZE,
+            })
+        };
+        assert_eq!(
+            disk.events,
+            [
+                Event::Sync,
+                Event::Update(Op::Delete(1)),
+                Event::Update(Op::Delete(2)),
+                add(5, (40, 79)),
+                add(1, (80, 159)),
+            ]
+        );
+        let backup = Table::read_backup(&mut disk.data, SECTOR_SIZE, LENGTH)
+            .expect("failed to read the backup");
+        let numbers: Vec<_> = backup.entries().map(|e| e.number).collect();
+        assert_eq!(numbers, [1, 5]);
+    }
+
+    #[test]
+    fn failed_edits_leave_the_disk_and_kernel_alone() {
+        let mut disk = FakeDisk::new(two_partitions());
+        let before = disk.data.get_ref().clone();
+        let edits = [
+            (40..=79, EntryEdit::Number(5)),
+            (40..=79, EntryEdit::Number(2)),
+        ];
+        let error = rewrite_entries(&mut disk, SECTOR_SIZE, LENGTH, &edits)
+            .expect_err("an entry was moved to a used number");
+        assert_eq!(error.to_string(), "GPT entry number already taken");
+        assert_eq!(disk.data.get_ref(), &before);
+        assert!(disk.events.is_empty());
+    }
+
+    #[test]
+    fn partitions_with_new_partuuids_are_replaced_in_the_kernel() {
+        let mut disk = FakeDisk::new(two_partitions());
+        disk.on_update = Box::new(|data, op| {
+            let table = read_primary(data).expect("failed to read the table");
+            let uuids: Vec<_> = table
+                .entries()
+                .map(|e| (e.number, e.uuid.as_u128()))
+                .collect();
+            assert_eq!(
+                uuids,
+                [(2, 9), (5, 1)],
+                "the kernel was told about {op:?} too early"
+            );
+        });
+        let edits = [
+            (80..=159, EntryEdit::Uuid(Guid::from_u128(9))),
+            (40..=79, EntryEdit::Number(5)),
+        ];
+        rewrite_entries(&mut disk, SECTOR_SIZE, LENGTH, &edits).expect("failed to rewrite");
+
+        // the partition keeps its number, but udev only probes it again once it's added back
+        assert_eq!(
+            disk.events[..3],
+            [
+                Event::Sync,
+                Event::Update(Op::Delete(2)),
+                Event::Update(Op::Delete(1)),
+            ]
+        );
+        assert_eq!(
+            disk.events[3],
+            Event::Update(Op::Add {
+                number: 2,
+                start: 80 * SECTOR_SIZE,
+                length: 80 * SECTOR_SIZE,
+            })
+        );
+        let backup = Table::read_backup(&mut disk.data, SECTOR_SIZE, LENGTH)
+            .expect("failed to read the backup");
+        let uuids: Vec<_> = backup.entries().map(|e| e.uuid.as_u128()).collect();
+        assert_eq!(uuids, [9, 1]);
+    }
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt::Display, str::FromStr};
+
+/// A GUID as stored in a GPT, e.g. a partition type or partition UUID.
+///
+/// GPT stores the first three fields of a GUID little-endian, so the byte order differs from the
+/// textual form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Guid([u8; 16]);
+
+impl Guid {
+    /// Create a GUID from its textual form read as a single big-endian number, e.g.
+    /// `0xC12A7328_F81F_11D2_BA4B_00A0C93EC93B`.
+    pub const fn from_u128(value: u128) -> Self {
+        let b = value.to_be_bytes();
+        Self([
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9], b[10], b[11], b[12], b[13],
+            b[14], b[15],
+        ])
+    }
+
+    pub const fn as_u128(&self) -> u128 {
+        let b = self.0;
+        u128::from_be_bytes([
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9], b[10], b[11], b[12], b[13],
+            b[14], b[15],
+        ])
+    }
+
+    /// Create a GUID from its on-disk form.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    /// Get the on-disk form of the GUID.
+    pub const fn to_bytes(self) -> [u8; 16] {
+        self.0
+    }
+
+    pub const fn is_nil(&self) -> bool {
+        self.as_u128() == 0
+    }
+
+    /// Create a random (version 4) GUID, e.g. to give a cloned partition a PARTUUID of its own.
+    pub fn random() -> std::io::Result<Self> {
+        let mut bytes = [0; 16];
+        std::io::Read::read_exact(&mut std::fs::File::open("/dev/urandom")?, &mut bytes)?;
+        // the version is the top nibble of the third group, and the variant the top two bits of
+        // the fourth
+        let value = u128::from_be_bytes(bytes) & !(0xF << 76 | 0b11 << 62) | 4 << 76 | 0b10 << 62;
+        Ok(Self::from_u128(value))
+    }
+}
+
+impl Display for Guid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let v = self.as_u128();
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:04X}-{:012X}",
+            v >> 96,
+            (v >> 80) & 0xFFFF,
+            (v >> 64) & 0xFFFF,
+            (v >> 48) & 0xFFFF,
+            v & 0xFFFF_FFFF_FFFF
+        )
+    }
+}
+
+impl Serialize for Guid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Guid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid GUID")]
+pub struct ParseGuidError;
+
+impl FromStr for Guid {
+    type Err = ParseGuidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let groups = s.split('-').map(str::len).collect::<Vec<_>>();
+        if groups != [8, 4, 4, 4, 12] {
+            return Err(ParseGuidError);
+        }
+        u128::from_str_radix(&s.replace('-', ""), 16)
+            .map(Self::from_u128)
+            .map_err(|_| ParseGuidError)
+    }
+}
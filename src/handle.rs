@@ -0,0 +1,78 @@
+//! Read-only handles to open devices, for refreshing what's known about them from another thread
+//! while the [`Device`] itself is busy.
+
+use crate::{Device, IoStats, stats, usage};
+use byte_unit::Byte;
+use std::{fs::File, os::unix::fs::FileExt, path::Path, sync::Arc};
+
+/// A read-only handle to a device, as returned by [`Device::try_clone_handle`].
+///
+/// Unlike a [`Device`], a handle doesn't hold on to libparted, so it can be sent to and shared
+/// between threads. It knows the partitions the device had on disk when the handle was made.
+#[derive(Debug, Clone)]
+pub struct DeviceHandle {
+    path: Arc<Path>,
+    file: Arc<File>,
+    partitions: Vec<Arc<Path>>,
+}
+
+/// The state of a partition that changes without partner's involvement, as read by
+/// [`DeviceHandle::status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionStatus {
+    pub path: Arc<Path>,
+    pub mount_point: Option<Arc<Path>>,
+    /// The space used by the partition's file system, like
+    /// [`Partition::used_space`](crate::Partition::used_space).
+    pub used: Option<Byte>,
+}
+
+impl DeviceHandle {
+    pub(crate) fn new(device: &Device) -> std::io::Result<Self> {
+        Ok(Self {
+            path: device.path_owned(),
+            file: Arc::new(File::open(device.path())?),
+            partitions: device
+                .partitions
+                .iter()
+                .filter_map(|p| p.path.clone())
+                .collect(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read from the device at the given byte offset, without affecting any other handle.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        self.file.read_at(buf, offset)
+    }
+
+    /// Find out where the device's partitions are mounted now and how much of them their file
+    /// systems use.
+    ///
+    /// Pass the result to [`Device::update_status`] to bring the device up to date.
+    pub fn status(&self) -> std::io::Result<Vec<PartitionStatus>> {
+        let mounts = Device::get_mounts()?;
+        Ok(self
+            .partitions
+            .iter()
+            .map(|path| {
+                let mount_point: Option<Arc<Path>> = mounts
+                    .get(path.as_ref())
+                    .map(|m| Arc::from(m.dest.as_ref()));
+                PartitionStatus {
+                    path: path.clone(),
+                    used: usage::used_bytes(path, mount_point.as_deref()).map(Byte::from_u64),
+                    mount_point,
+                }
+            })
+            .collect())
+    }
+
+    /// Get the device's IO counters, like [`Device::io_stats`].
+    pub fn io_stats(&self) -> std::io::Result<IoStats> {
+        stats::read(&self.path)
+    }
+}
@@ -0,0 +1,91 @@
+use std::ops::{Deref, RangeBounds};
+
+/// The staged changes of a [`Device`](crate::Device), in the order they're applied, and the
+/// undone ones that can be redone, most recently undone last.
+///
+/// This only keeps the order of the changes. Reverting what a change did when it's
+/// [unstaged](History::unstage), and doing it again when it's [restaged](History::restage), is
+/// up to the device.
+pub(crate) struct History<C, R> {
+    staged: Vec<C>,
+    undone: Vec<R>,
+}
+
+impl<C, R> History<C, R> {
+    pub fn new(staged: Vec<C>) -> Self {
+        Self {
+            staged,
+            undone: Vec::new(),
+        }
+    }
+
+    /// Stage a new change, which makes the undone ones impossible to redo.
+    pub fn stage(&mut self, change: C) {
+        self.undone.clear();
+        self.staged.push(change);
+    }
+
+    /// Take the last staged change off the queue to undo it. Once it's reverted, how to redo it
+    /// is handed back with [`push_undone`](History::push_undone).
+    pub fn unstage(&mut self) -> Option<C> {
+        self.staged.pop()
+    }
+
+    pub fn push_undone(&mut self, redo: R) {
+        self.undone.push(redo);
+    }
+
+    /// Take the most recently undone change to redo it. Once it's done again, it's put back on
+    /// the queue with [`restage`](History::restage).
+    pub fn take_undone(&mut self) -> Option<R> {
+        self.undone.pop()
+    }
+
+    /// Put a redone change back on the queue, keeping the rest of the undone ones.
+    pub fn restage(&mut self, change: C) {
+        self.staged.push(change);
+    }
+
+    /// Get the number of undone changes that can be redone.
+    pub fn n_undone(&self) -> usize {
+        self.undone.len()
+    }
+
+    pub fn clear_undone(&mut self) {
+        self.undone.clear();
+    }
+
+    /// Drop all staged and undone changes.
+    pub fn clear(&mut self) {
+        self.staged.clear();
+        self.undone.clear();
+    }
+
+    /// Drop the staged changes after the first `len`, without undoing them.
+    pub fn truncate(&mut self, len: usize) {
+        self.staged.truncate(len);
+    }
+
+    /// Move the staged change at position `from` to position `to`.
+    pub fn reorder(&mut self, from: usize, to: usize) {
+        let change = self.staged.remove(from);
+        self.staged.insert(to, change);
+    }
+
+    pub fn remove(&mut self, index: usize) -> C {
+        self.staged.remove(index)
+    }
+
+    /// Take the staged changes in the given range off the queue, e.g. to apply them.
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> std::vec::Drain<'_, C> {
+        self.staged.drain(range)
+    }
+}
+
+impl<C, R> Deref for History<C, R> {
+    type Target = [C];
+
+    fn deref(&self) -> &[C] {
+        &self.staged
+    }
+}
@@ -0,0 +1,40 @@
+//! Stable identifiers of devices, which unlike kernel names such as `/dev/sda` don't change
+//! across reboots.
+
+use std::path::Path;
+
+/// Get the serial number of the device at the given path, as udev reports it, or else as its
+/// driver does in sysfs.
+pub(crate) fn serial(path: &Path) -> Option<String> {
+    udev_property(path, "ID_SERIAL_SHORT").or_else(|| sysfs_attribute(path, "device/serial"))
+}
+
+/// Get the World Wide Name of the device at the given path, as udev reports it, or else as its
+/// driver does in sysfs.
+pub(crate) fn wwn(path: &Path) -> Option<String> {
+    udev_property(path, "ID_WWN_WITH_EXTENSION")
+        .or_else(|| udev_property(path, "ID_WWN"))
+        .or_else(|| sysfs_attribute(path, "wwid"))
+        .or_else(|| sysfs_attribute(path, "device/wwid"))
+}
+
+fn sysfs_attribute(path: &Path, attribute: &str) -> Option<String> {
+    let value = std::fs::read_to_string(
+        Path::new("/sys/block")
+            .join(path.file_name()?)
+            .join(attribute),
+    )
+    .ok()?;
+    Some(value.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Look up a property in udev's database entry for the device at the given path.
+fn udev_property(path: &Path, key: &str) -> Option<String> {
+    let dev = sysfs_attribute(path, "dev")?;
+    let data = std::fs::read_to_string(Path::new("/run/udev/data").join(format!("b{dev}"))).ok()?;
+    data.lines()
+        .filter_map(|line| line.strip_prefix("E:")?.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value.to_string())
+        .filter(|v| !v.is_empty())
+}
@@ -0,0 +1,26 @@
+//! Creation of disk image files, for testing and for building images without a real device.
+
+use crate::{Device, PartitionTable};
+use byte_unit::Byte;
+use std::{io, path::Path};
+
+/// Create a sparse image file of the given size with an empty partition table, and open it.
+///
+/// The file is truncated if it already exists.
+pub fn create(
+    path: impl AsRef<Path>,
+    size: Byte,
+    table: PartitionTable,
+) -> io::Result<Device<'static>> {
+    let path = path.as_ref();
+    std::fs::File::create(path)?.set_len(size.as_u64())?;
+
+    {
+        let mut raw = libparted::Device::new(path)?;
+        let disk_type = libparted::DiskType::get(table.name())
+            .ok_or_else(|| io::Error::other(format!("libparted doesn't support {table}")))?;
+        libparted::Disk::new_fresh(&mut raw, disk_type)?.commit()?;
+    }
+
+    Device::open(path)
+}
@@ -0,0 +1,113 @@
+//! Detection of config file entries that pending changes would leave pointing at nothing.
+
+use crate::dependents::{self, ConfigEntry};
+use std::path::{Path, PathBuf};
+
+/// Why a config entry will become stale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum StaleReason {
+    /// The partition the entry refers to will be deleted.
+    Deleted,
+    /// The partition the entry refers to will get a new file system, so the file system UUID or
+    /// label the entry refers to will be gone.
+    Reformatted,
+    /// The partition the entry refers to will get a new PARTUUID, so the one the entry refers to
+    /// will be gone.
+    NewPartuuid,
+}
+
+/// An `/etc/fstab` or `/etc/crypttab` entry that the pending changes will break.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleEntry {
+    pub file: PathBuf,
+    /// The 1-based line number.
+    pub line: usize,
+    /// The field identifying the device, e.g. `UUID=...`.
+    pub spec: String,
+    pub reason: StaleReason,
+}
+
+/// The result of [`Device::config_impact`](crate::Device::config_impact).
+#[derive(Debug, Clone, Default)]
+pub struct ConfigImpact {
+    pub stale: Vec<StaleEntry>,
+}
+
+impl ConfigImpact {
+    pub fn is_empty(&self) -> bool {
+        self.stale.is_empty()
+    }
+
+    /// Get the contents of the given config file with its stale entries commented out, so that
+    /// the system still boots after committing. The file itself is left untouched.
+    pub fn patch(&self, file: impl AsRef<Path>) -> std::io::Result<String> {
+        let file = file.as_ref();
+        let contents = std::fs::read_to_string(file)?;
+        let mut patched = contents
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                match self
+                    .stale
+                    .iter()
+                    .find(|e| e.file == file && e.line == i + 1)
+                {
+                    Some(entry) => format!("# partner: {}: {line}", entry.reason),
+                    None => line.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if contents.ends_with('\n') {
+            patched.push('\n');
+        }
+        Ok(patched)
+    }
+}
+
+/// Find the config entries referring to any of the given partitions, which are about to be
+/// deleted, reformatted or given a new PARTUUID.
+pub(crate) fn analyze(affected: &[(PathBuf, StaleReason)]) -> ConfigImpact {
+    let affected = affected
+        .iter()
+        .filter_map(|(path, reason)| Some((path.canonicalize().ok()?, *reason)))
+        .collect::<Vec<_>>();
+
+    let mut stale = stale_entries(Path::new("/etc/fstab"), dependents::fstab(), &affected);
+    stale.extend(stale_entries(
+        Path::new("/etc/crypttab"),
+        dependents::crypttab(),
+        &affected,
+    ));
+    ConfigImpact { stale }
+}
+
+fn stale_entries(
+    file: &Path,
+    entries: Vec<ConfigEntry>,
+    affected: &[(PathBuf, StaleReason)],
+) -> Vec<StaleEntry> {
+    entries
+        .into_iter()
+        .filter_map(|entry| {
+            let device = dependents::resolve_spec(&entry.spec)?;
+            let (_, reason) = affected.iter().find(|(path, _)| *path == device)?;
+            // a reformatted partition keeps its path, partition UUID and partition label, and one
+            // with a new partition UUID keeps everything else
+            let stale = match reason {
+                StaleReason::Deleted => true,
+                StaleReason::Reformatted => {
+                    entry.spec.starts_with("UUID=") || entry.spec.starts_with("LABEL=")
+                }
+                StaleReason::NewPartuuid => entry.spec.starts_with("PARTUUID="),
+            };
+            stale.then(|| StaleEntry {
+                file: file.into(),
+                line: entry.line,
+                spec: entry.spec,
+                reason: *reason,
+            })
+        })
+        .collect()
+}
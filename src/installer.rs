@@ -0,0 +1,148 @@
+//! Export of [`LayoutSpec`]s for unattended installers: Anaconda kickstart files and Debian
+//! preseed partman recipes.
+//!
+//! Both formats identify partitions by where they're mounted, so partitions without a
+//! [role](crate::dps::Role) that implies a mount point are left out with a comment explaining why.
+
+use crate::{FileSystem, LayoutSpec, PartitionSpec, dps::Role};
+
+const MIB: u64 = 1024 * 1024;
+const MB: u64 = 1000 * 1000;
+
+fn mount_point(partition: &PartitionSpec) -> Option<&'static str> {
+    match partition.role? {
+        Role::Esp => Some("/boot/efi"),
+        role => role.mount_point(),
+    }
+}
+
+fn describe(partition: &PartitionSpec) -> String {
+    partition
+        .label
+        .as_ref()
+        .map(|l| format!("partition {l:?}"))
+        .unwrap_or_else(|| "unlabeled partition".into())
+}
+
+/// Render the layout as kickstart `reqpart` and `part` commands.
+///
+/// The ESP is left to `reqpart`, which creates whatever the platform needs to boot.
+pub fn kickstart(spec: &LayoutSpec) -> String {
+    let mut lines = Vec::new();
+
+    if spec.partitions.iter().any(|p| p.role == Some(Role::Esp)) {
+        lines.push("reqpart".to_string());
+    }
+
+    for partition in &spec.partitions {
+        if partition.role == Some(Role::Esp) {
+            continue;
+        }
+
+        let target = if partition.role == Some(Role::Swap) {
+            "swap"
+        } else if let Some(mount_point) = mount_point(partition) {
+            mount_point
+        } else {
+            lines.push(format!("# skipped {}: no mount point", describe(partition)));
+            continue;
+        };
+
+        let mut line = format!("part {target}");
+        if let Some(fs) = partition.fs.and_then(kickstart_fs)
+            && partition.role != Some(Role::Swap)
+        {
+            line += &format!(" --fstype={fs}");
+        }
+        let min = partition.size_min.map_or(1, |s| (s.as_u64() / MIB).max(1));
+        line += &format!(" --size={min}");
+        if partition.size_max != partition.size_min && partition.weight > 0 {
+            line += " --grow";
+            if let Some(max) = partition.size_max {
+                line += &format!(" --maxsize={}", max.as_u64() / MIB);
+            }
+        }
+        if let Some(label) = &partition.label {
+            line += &format!(" --label={label}");
+        }
+        lines.push(line);
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn kickstart_fs(fs: FileSystem) -> Option<&'static str> {
+    match fs {
+        FileSystem::Btrfs => Some("btrfs"),
+        FileSystem::Ext2 => Some("ext2"),
+        FileSystem::Ext4 => Some("ext4"),
+        FileSystem::Fat16 | FileSystem::Fat32 => Some("vfat"),
+        FileSystem::Xfs => Some("xfs"),
+        _ => None,
+    }
+}
+
+/// Render the layout as preseed directives selecting a partman expert recipe named `partner`.
+pub fn preseed(spec: &LayoutSpec) -> String {
+    let mut lines = vec![
+        "d-i partman-auto/method string regular".to_string(),
+        "d-i partman-auto/choose_recipe select partner".to_string(),
+        "d-i partman-auto/expert_recipe string \\".to_string(),
+        "    partner :: \\".to_string(),
+    ];
+    let mut skipped = Vec::new();
+
+    for partition in &spec.partitions {
+        let min = partition.size_min.map_or(1, |s| (s.as_u64() / MB).max(1));
+        let max = match partition.size_max {
+            Some(max) => (max.as_u64() / MB).to_string(),
+            None => "-1".into(),
+        };
+        let priority = (partition.weight as u64).max(min);
+
+        let method = match partition.role {
+            Some(Role::Esp) => "method{ efi } format{ }".to_string(),
+            Some(Role::Swap) => "method{ swap } format{ }".to_string(),
+            _ => {
+                let Some(mount_point) = mount_point(partition) else {
+                    skipped.push(format!("# skipped {}: no mount point", describe(partition)));
+                    continue;
+                };
+                let fs = partition.fs.and_then(preseed_fs).unwrap_or("ext4");
+                format!(
+                    "method{{ format }} format{{ }} use_filesystem{{ }} filesystem{{ {fs} }} \
+                     mountpoint{{ {mount_point} }}"
+                )
+            }
+        };
+
+        lines.push(format!("        {min} {priority} {max} free \\"));
+        lines.push(format!("            {method} . \\"));
+    }
+
+    // the recipe continues onto the line after the last backslash
+    lines.push(String::new());
+    lines.extend([
+        "d-i partman-partitioning/confirm_write_new_label boolean true".to_string(),
+        "d-i partman/choose_partition select finish".to_string(),
+        "d-i partman/confirm boolean true".to_string(),
+        "d-i partman/confirm_nooverwrite boolean true".to_string(),
+    ]);
+    lines.extend(skipped);
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn preseed_fs(fs: FileSystem) -> Option<&'static str> {
+    match fs {
+        FileSystem::Btrfs => Some("btrfs"),
+        FileSystem::Ext2 => Some("ext2"),
+        FileSystem::Ext4 => Some("ext4"),
+        FileSystem::Fat16 => Some("fat16"),
+        FileSystem::Fat32 => Some("fat32"),
+        FileSystem::Jfs => Some("jfs"),
+        FileSystem::Xfs => Some("xfs"),
+        _ => None,
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{Device, FileSystem, dps::Role};
+use byte_unit::Byte;
+
+/// A declarative description of a partition layout, independent of any particular device.
+///
+/// Sizes follow the same model as `systemd-repart`: each partition gets at least its minimum
+/// size, and any remaining space is distributed according to the partitions' weights without
+/// exceeding their maximums.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LayoutSpec {
+    pub partitions: Vec<PartitionSpec>,
+    /// Where the first partition starts.
+    pub first_start: FirstPartitionStart,
+    /// Space to leave unpartitioned at the end of the device, e.g. as over-provisioning for an
+    /// SSD.
+    pub reserve_end: Option<Reserve>,
+}
+
+/// An amount of space to leave unpartitioned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reserve {
+    Bytes(Byte),
+    /// A percentage of the device's size, from 0 to 100.
+    Percent(u8),
+}
+
+impl Reserve {
+    /// Get the amount of space to reserve on a device of the given size.
+    pub fn resolve(self, device_size: Byte) -> Byte {
+        match self {
+            Self::Bytes(bytes) => bytes.min(device_size),
+            Self::Percent(percent) => {
+                Byte::from_u64(device_size.as_u64() / 100 * percent.min(100) as u64)
+            }
+        }
+    }
+}
+
+/// Where the first partition on a device may start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FirstPartitionStart {
+    /// 1 MiB into the device, which keeps partitions aligned to any physical sector or erase
+    /// block size. This is what current partitioning tools default to.
+    #[default]
+    OneMib,
+    /// Sector 63, the start of the second track in the geometry DOS assumed, for systems that
+    /// still expect it.
+    Legacy,
+    /// The given sector.
+    Sector(u64),
+}
+
+impl FirstPartitionStart {
+    /// Get the first sector a partition may start at on a device with the given sector size.
+    pub fn sector(self, sector_size: u64) -> u64 {
+        match self {
+            Self::OneMib => (1024 * 1024u64).div_ceil(sector_size),
+            Self::Legacy => 63,
+            Self::Sector(sector) => sector,
+        }
+    }
+}
+
+/// A single partition in a [`LayoutSpec`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartitionSpec {
+    pub label: Option<String>,
+    /// The role of the partition, which determines its type. `None` means generic Linux data.
+    pub role: Option<Role>,
+    pub fs: Option<FileSystem>,
+    pub size_min: Option<Byte>,
+    pub size_max: Option<Byte>,
+    /// The share of the remaining space this partition gets, relative to the others.
+    pub weight: u32,
+}
+
+impl Default for PartitionSpec {
+    fn default() -> Self {
+        Self {
+            label: None,
+            role: None,
+            fs: None,
+            size_min: None,
+            size_max: None,
+            weight: 1000,
+        }
+    }
+}
+
+impl LayoutSpec {
+    /// Describe the pending layout of a device, with every partition fixed at its current size.
+    pub fn from_device(device: &Device) -> Self {
+        Self {
+            partitions: device
+                .partitions()
+                .map(|p| PartitionSpec {
+                    label: (!p.name().is_empty()).then(|| p.name().to_string()),
+                    role: p.type_guid().and_then(Role::from_type_guid),
+                    fs: p.fs(),
+                    size_min: Some(p.size()),
+                    size_max: Some(p.size()),
+                    weight: 0,
+                })
+                .collect(),
+            first_start: device.first_partition_start(),
+            reserve_end: None,
+        }
+    }
+
+    /// Get the space available to the partitions on a device of the given size and sector size,
+    /// after the space before the [first partition](LayoutSpec::first_start) and the
+    /// [reserved space](LayoutSpec::reserve_end).
+    pub fn usable_size(&self, device_size: Byte, sector_size: u64) -> Byte {
+        let reserved = self
+            .reserve_end
+            .map_or(Byte::from_u64(0), |r| r.resolve(device_size));
+        let before = self.first_start.sector(sector_size) * sector_size;
+        Byte::from_u64(
+            device_size
+                .as_u64()
+                .saturating_sub(reserved.as_u64())
+                .saturating_sub(before),
+        )
+    }
+
+    /// Replace the layout's swap partitions according to the given strategy. A new swap
+    /// partition goes at the end.
+    pub fn set_swap(&mut self, strategy: SwapStrategy) {
+        self.partitions.retain(|p| p.role != Some(Role::Swap));
+        if let SwapStrategy::Partition(size) = strategy {
+            self.partitions.push(PartitionSpec {
+                label: Some("swap".into()),
+                role: Some(Role::Swap),
+                fs: Some(FileSystem::LinuxSwap),
+                size_min: Some(size),
+                size_max: Some(size),
+                weight: 0,
+            });
+        }
+    }
+}
+
+/// How a layout provides swap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapStrategy {
+    /// A swap partition of the given size.
+    Partition(Byte),
+    /// No swap partition, e.g. because the system swaps to zram or a swap file. See
+    /// [`SwapSetup`](crate::SwapSetup).
+    None,
+}
+
+/// Whether the given device is a fixed SSD whose pending layout leaves no unpartitioned space at
+/// its end.
+///
+/// SSDs use free space to spread out writes, so many consider it good practice to leave some
+/// unpartitioned on consumer drives, which come with little spare area of their own.
+pub fn lacks_spare_area(device: &Device) -> bool {
+    // anything smaller is just alignment slack, or the backup GPT
+    const MIN_SPARE: u64 = 1024 * 1024;
+
+    let spare = match device.partitions().count() {
+        0 => return false,
+        n => device.gap_after(n - 1),
+    };
+    !device.is_rotational() && !device.is_removable() && spare.as_u64() < MIN_SPARE
+}
@@ -5,13 +5,94 @@
 //! This library uses [libparted] under the hood, and is intended to be simpler and more
 //! convenient, with built-in support for undoing changes and owned types for partitions and disks.
 
+mod blkpg;
+mod capabilities;
+mod checksum;
+mod commit;
+mod content;
+pub mod copy;
+mod dependents;
+pub mod dps;
+pub mod efi;
+mod emmc;
+mod exception;
+pub mod flash;
+mod gpt;
+mod guid;
+mod handle;
+mod history;
+mod ident;
+pub mod image;
+mod impact;
+pub mod installer;
+mod layout;
+mod links;
+mod manager;
+mod mbr;
+mod mkfs;
+mod naming;
+mod notes;
+pub mod oneshot;
+pub mod ops;
 mod partition;
+mod partition_io;
+mod pending;
+mod plan;
+mod planned;
+mod power;
+pub mod removable;
+pub mod repart;
+pub mod report;
+mod resize;
+pub mod size;
+pub mod slots;
+mod snapshot;
+mod space;
+mod stats;
+mod steps;
+mod summary;
+mod swap;
+mod timer;
+mod tool;
+mod transaction;
+mod usage;
+mod view;
+mod wipe;
 
-use either::Either;
+pub use capabilities::{Capabilities, capabilities};
+pub use checksum::{ChecksumAlgorithm, ChecksumError, ChecksumProgress, Digest};
+pub use commit::*;
+pub use content::{ContentHint, RegionAnalysis};
+pub use dependents::{Dependent, MapperKind};
+pub use emmc::{EmmcArea, EmmcAreaKind};
+pub use exception::{Exception, ExceptionKind, Resolution};
+pub use guid::*;
+pub use handle::{DeviceHandle, PartitionStatus};
+pub use impact::{ConfigImpact, StaleEntry, StaleReason};
+pub use layout::*;
+pub use links::StableNames;
+pub use manager::{DeviceEvent, DeviceId, DeviceManager};
+pub use mbr::{HybridEntry, MAX_HYBRID_ENTRIES};
+pub use naming::*;
+pub use notes::NoteStore;
 pub use partition::*;
+pub use partition_io::{PartitionIo, ReadWriteMode};
+pub use pending::*;
+pub use plan::{ChangePlan, DeviceTarget, PartitionKey, PartitionRef};
+pub use planned::PlannedDevice;
+pub use resize::{FsCapabilities, ResizeStrategy, ResizeSupport};
+pub use snapshot::*;
+pub use stats::IoStats;
+pub use steps::{Plan, PlanMismatch, PlanStep};
+pub use summary::{DeviceHealth, DeviceSummary};
+pub use swap::{SwapAdvisory, SwapSetup, SwapTarget};
+pub use tool::{ToolError, ToolOutput, Toolchain};
+pub use transaction::{DeviceOutcome, Transaction};
+pub use view::{FreeRegion, LayoutRow, PartitionView, ProjectedPartition, ProjectedStatus};
+pub use wipe::{WipeMethod, WipeProgress};
 
 use byte_unit::Byte;
-use libparted::Geometry;
+use history::History;
 use proc_mounts::MountInfo;
 use std::{
     collections::HashMap,
@@ -29,8 +110,21 @@ type RawDevice<'a> = libparted::Device<'a>;
 pub struct Device<'a> {
     model: Arc<str>,
     path: Arc<Path>,
+    alias: Option<Arc<Path>>,
     partitions: Vec<Partition>,
-    changes: Vec<InnerChange>,
+    changes: History<InnerChange, Redo>,
+    disk_type: Option<Arc<str>>,
+    degraded: bool,
+    hybrid_mbr: (Vec<HybridEntry>, Vec<Vec<HybridEntry>>),
+    gpt_entry_capacity: (Option<u32>, Vec<u32>),
+    safety_level: SafetyLevel,
+    confirmed: Option<usize>,
+    limits: ChangeLimits,
+    first_partition_start: FirstPartitionStart,
+    /// The alignment of new partitions **in bytes**, if not libparted's.
+    alignment: Option<u64>,
+    /// The bounds of the partitions created by the last commit.
+    created: Vec<RangeInclusive<i64>>,
     raw: RawDevice<'a>,
 }
 
@@ -39,8 +133,12 @@ impl Debug for Device<'_> {
         f.debug_struct("Device")
             .field("model", &self.model)
             .field("path", &self.path)
+            .field("alias", &self.alias)
             .field("size", &self.size())
             .field("partitions", &self.partitions().collect::<Vec<_>>())
+            .field("degraded", &self.degraded)
+            .field("hybrid_mbr", &self.hybrid_mbr())
+            .field("gpt_entry_capacity", &self.gpt_entry_capacity())
             .finish()
     }
 }
@@ -51,6 +149,167 @@ pub enum Error {
     OverlapsExisting(usize),
     #[error("given bounds are out of device bounds")]
     OutOfBounds,
+    /// The given bounds aren't within a single free region. Holds the free region they overlap
+    /// most, which is likely the one that was meant, if they overlap any.
+    #[error("given bounds aren't within a single free region")]
+    NotFree(Option<FreeRegion>),
+    #[error("invalid partition name: {0}")]
+    InvalidName(#[from] NameError),
+    #[error("operation is not supported by this partition table")]
+    UnsupportedByTable,
+    #[error("a hybrid MBR can hold at most {max} entries", max = MAX_HYBRID_ENTRIES)]
+    TooManyHybridEntries,
+    #[error("partition №{0} is in use")]
+    InUse(usize),
+    #[error("partition №{0} is in use, so changing it must be confirmed")]
+    NeedsConfirmation(usize),
+    #[error("partition №{index} is protected by its note \"{note}\"")]
+    Protected { index: usize, note: Arc<str> },
+    #[error("unknown naming scheme placeholder `{{{0}}}`")]
+    UnknownPlaceholder(Arc<str>),
+    #[error("the device is read-only")]
+    ReadOnly,
+    #[error("eMMC boot areas and RPMBs can't be partitioned")]
+    EmmcArea,
+    #[error("{0} partition tables can't be edited, only replaced")]
+    UnsupportedLabel(Arc<str>),
+    #[error("the device has no partition table")]
+    NoTable,
+    #[error("the partition table can't be converted with changes pending")]
+    PendingChanges,
+    #[error("partition №{0} overlaps where the GPT would be stored")]
+    NoRoomForGpt(usize),
+    #[error("staging this change would exceed the device's change limits")]
+    LimitExceeded,
+    #[error("the GPT entry array must have room for all {0} partitions")]
+    GptEntriesInUse(usize),
+    #[error("the plan was made for a different layout than the device has")]
+    StalePlan,
+    #[error("the plan is for {0}, which isn't this device")]
+    PlanTargetMismatch(DeviceTarget),
+    #[error("no device matches the plan's target, {0}")]
+    PlanTargetNotFound(DeviceTarget),
+    #[error("several devices match the plan's target: {}", .0.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join(", "))]
+    AmbiguousPlanTarget(Vec<PathBuf>),
+    #[error("the file system on the device is unknown, so it can't be preserved")]
+    UnknownFileSystem,
+    #[error("the linked libparted doesn't support {0}")]
+    UnsupportedByLibparted(Arc<str>),
+    #[error("{0} file systems can't be resized that way")]
+    ResizeUnsupported(FileSystem),
+    #[error("partitions can't be aligned to {0}, which isn't a multiple of the sector size")]
+    InvalidAlignment(Byte),
+    #[error("partition number {0} is out of range for the partition table")]
+    InvalidPartitionNumber(u32),
+    #[error("partition number {0} is already taken")]
+    PartitionNumberTaken(u32),
+    #[error("an MBR holds at most 4 primary and extended partitions")]
+    TooManyPrimary,
+    #[error("the partition table already has an extended partition")]
+    ExtendedExists,
+    #[error("the extended partition still holds logical partitions")]
+    ExtendedNotEmpty,
+    #[error("logical partitions must lie within the extended partition")]
+    OutsideExtended,
+    #[error("the nil GUID marks unused GPT entries, so no partition can have it")]
+    NilUuid,
+    #[error("partition №{0} already has that PARTUUID")]
+    DuplicateUuid(usize),
+    #[error("the change can't be moved past change №{0}, which depends on their order")]
+    ChangeDependency(usize),
+    #[error(transparent)]
+    PlanMismatch(#[from] PlanMismatch),
+    #[error("step {step} of the plan failed: {source}")]
+    PlanStep { step: usize, source: Box<Error> },
+}
+
+/// How a [`Device`] treats destructive operations on partitions that are in use: mounted, or the
+/// [EFI system partition the machine booted from](Partition::is_booted_esp).
+///
+/// System partitions are always in use, so this also covers them. So do partitions with a
+/// [note](Partition::note), which are rejected with [`Error::Protected`] rather than
+/// [`Error::InUse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SafetyLevel {
+    /// Reject such operations with [`Error::InUse`].
+    #[default]
+    Safe,
+    /// Reject such operations with [`Error::NeedsConfirmation`] unless they have been
+    /// [confirmed](Device::confirm).
+    Confirm,
+    /// Allow such operations.
+    Force,
+}
+
+/// A type of partition table.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartitionTable {
+    #[strum(serialize = "GPT")]
+    Gpt,
+    #[strum(serialize = "MBR")]
+    Msdos,
+}
+
+/// What to do with the file system of a [superfloppy](Device::is_superfloppy) when giving it a
+/// partition table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuperfloppyConversion {
+    /// Discard the file system, leaving an empty partition table.
+    Wipe,
+    /// Shrink the file system and move it into a partition spanning the new table.
+    Preserve,
+}
+
+/// The partition table on a device, as returned by [`Device::label`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiskLabel {
+    Gpt,
+    Msdos,
+    /// A partition table partner can read but not edit, by libparted's name for it, e.g. `sun`.
+    Other(Arc<str>),
+}
+
+impl DiskLabel {
+    /// The type of the partition table, if partner can edit it.
+    pub fn table(&self) -> Option<PartitionTable> {
+        match self {
+            Self::Gpt => Some(PartitionTable::Gpt),
+            Self::Msdos => Some(PartitionTable::Msdos),
+            Self::Other(_) => None,
+        }
+    }
+}
+
+impl From<PartitionTable> for DiskLabel {
+    fn from(value: PartitionTable) -> Self {
+        match value {
+            PartitionTable::Gpt => Self::Gpt,
+            PartitionTable::Msdos => Self::Msdos,
+        }
+    }
+}
+
+impl std::fmt::Display for DiskLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gpt => write!(f, "{}", PartitionTable::Gpt),
+            Self::Msdos => write!(f, "{}", PartitionTable::Msdos),
+            Self::Other(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+impl PartitionTable {
+    /// The name libparted uses for this type of table.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::Gpt => "gpt",
+            Self::Msdos => "msdos",
+        }
+    }
 }
 
 impl<'a> Device<'a> {
@@ -62,40 +321,158 @@ impl<'a> Device<'a> {
     }
 
     /// Open a device from the given block device path.
+    ///
+    /// If libparted can't read the partition table but a valid backup GPT is present, the device
+    /// is opened in [degraded](Device::is_degraded) mode. A device without a partition table is
+    /// opened without partitions, and its [label](Device::label) is `None`.
+    ///
+    /// Symlinks such as `/dev/disk/by-id/...` are resolved, with the given path kept as the
+    /// device's [alias](Device::alias).
+    ///
+    /// Fails with [`ErrorKind::Unsupported`](std::io::ErrorKind::Unsupported) if the device's
+    /// logical sector size isn't a power of two, e.g. SAS drives formatted with 520 or 528 byte
+    /// sectors for use behind a RAID controller.
     pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
-        Self::from_libparted(RawDevice::new(path)?, &Self::get_mounts()?)
+        let mounts = Self::get_mounts()?;
+        let path = path.as_ref();
+        let canonical = path.canonicalize()?;
+        let raw = RawDevice::new(&canonical)?;
+        check_sector_size(&raw)?;
+        let mut device = Self::from_libparted(raw, &mounts)
+            .or_else(|e| Self::recover(&canonical, &mounts, e))?;
+        device.alias = (canonical != path).then(|| path.into());
+        Ok(device)
     }
 
     /// Get all devices on the system.
     ///
     /// This isn't necessarily all of the available devices (for instance, this ignores loopback
     /// devices). [`open`](Device::open) can be used to open a specific device if you're looking
-    /// for one not returned by this.
+    /// for one not returned by this. Devices with unsupported sector sizes are skipped, as are
+    /// eMMC boot areas, which are listed by their device's [`emmc_areas`](Device::emmc_areas).
     pub fn get_all() -> std::io::Result<Vec<Self>> {
         let mounts = Self::get_mounts()?;
 
         RawDevice::devices(true)
-            .map(|d| Device::from_libparted(d, &mounts))
+            .filter(|d| check_sector_size(d).is_ok() && emmc::kind(d.path()).is_none())
+            .map(|d| {
+                let path = d.path().to_owned();
+                Device::from_libparted(d, &mounts).or_else(|e| Self::recover(&path, &mounts, e))
+            })
             .collect()
     }
 
-    fn from_libparted(
-        mut value: RawDevice<'a>,
+    /// Answer the exceptions libparted raises with the given handler, instead of printing them to
+    /// stderr.
+    ///
+    /// libparted only has one handler per process, so this replaces any previously set handler
+    /// and applies to all devices.
+    pub fn set_exception_handler(handler: impl FnMut(Exception) -> Resolution + Send + 'static) {
+        exception::set_handler(Box::new(handler));
+    }
+
+    /// Go back to libparted's default exception handling.
+    pub fn clear_exception_handler() {
+        exception::clear_handler();
+    }
+
+    /// Try to open the device at the given path from its backup GPT, returning the original
+    /// error if that fails too.
+    fn recover(
+        path: &Path,
+        mounts: &HashMap<PathBuf, MountInfo>,
+        error: std::io::Error,
+    ) -> std::io::Result<Self> {
+        RawDevice::new(path)
+            .and_then(|raw| Self::from_backup_gpt(raw, mounts))
+            .map_err(|_| error)
+    }
+
+    fn from_backup_gpt(
+        value: RawDevice<'a>,
         mounts: &HashMap<PathBuf, MountInfo>,
     ) -> std::io::Result<Self> {
         let sector_size = value.sector_size();
-        let partitions = libparted::Disk::new(&mut value)?
-            .parts()
-            .filter_map(|p| {
-                let mount = mounts.get(p.get_path()?);
-                Some(Partition::from_libparted(p, sector_size, mount))
+        let table = gpt::Table::read_backup(
+            &mut std::fs::File::open(value.path())?,
+            sector_size,
+            value.length(),
+        )?;
+
+        let partitions = table
+            .entries()
+            .map(|entry| {
+                let path = partition_path(value.path(), entry.number);
+                let path: Option<Arc<Path>> = path.exists().then(|| path.into());
+                let mount = path.as_ref().and_then(|p| mounts.get(&**p));
+                let mut partition = Partition::recovered(
+                    path,
+                    mount,
+                    entry.number,
+                    entry.name.into(),
+                    entry.bounds.0 as i64..=entry.bounds.1 as i64,
+                    entry.type_guid,
+                    entry.uuid,
+                    value.path().into(),
+                    sector_size,
+                );
+                partition.note = entry.note.map(Arc::from);
+                partition
             })
-            .collect::<Vec<_>>();
+            .collect();
+        let hybrid_mbr = mbr::read_hybrid(&mut std::fs::File::open(value.path())?)?;
+        let gpt_entry_capacity = table.entry_capacity();
+
+        Ok(Self {
+            model: value.model().into(),
+            path: value.path().into(),
+            alias: None,
+            partitions,
+            changes: History::new(vec![InnerChange::RepairGpt(table)]),
+            disk_type: Some("gpt".into()),
+            degraded: true,
+            hybrid_mbr: (hybrid_mbr, Vec::new()),
+            gpt_entry_capacity: (Some(gpt_entry_capacity), Vec::new()),
+            safety_level: SafetyLevel::default(),
+            confirmed: None,
+            limits: ChangeLimits::default(),
+            first_partition_start: FirstPartitionStart::default(),
+            alignment: None,
+            created: Vec::new(),
+            raw: value,
+        })
+    }
+
+    fn from_libparted(
+        mut value: RawDevice<'a>,
+        mounts: &HashMap<PathBuf, MountInfo>,
+    ) -> std::io::Result<Self> {
+        let (disk_type, partitions) = read_partitions(&mut value, mounts)?;
+        let (hybrid_mbr, gpt_entry_capacity) = if disk_type.as_deref() == Some("gpt") {
+            let mut file = std::fs::File::open(value.path())?;
+            let capacity = gpt::Table::read_at(&mut file, value.sector_size(), 1)
+                .ok()
+                .map(|t| t.entry_capacity());
+            (mbr::read_hybrid(&mut file)?, capacity)
+        } else {
+            (Vec::new(), None)
+        };
         Ok(Self {
             model: value.model().into(),
             path: value.path().into(),
+            alias: None,
             partitions,
-            changes: Vec::new(),
+            changes: History::new(Vec::new()),
+            disk_type,
+            degraded: false,
+            hybrid_mbr: (hybrid_mbr, Vec::new()),
+            gpt_entry_capacity: (gpt_entry_capacity, Vec::new()),
+            safety_level: SafetyLevel::default(),
+            confirmed: None,
+            limits: ChangeLimits::default(),
+            first_partition_start: FirstPartitionStart::default(),
+            alignment: None,
+            created: Vec::new(),
             raw: value,
         })
     }
@@ -112,6 +489,20 @@ impl<'a> Device<'a> {
         self.path.clone()
     }
 
+    /// The path the device was [opened](Device::open) with, if it was a symlink to the device's
+    /// actual path, e.g. `/dev/disk/by-id/...`.
+    ///
+    /// Unlike kernel names such as `/dev/sda`, these identify the same device across reboots.
+    pub fn alias(&self) -> Option<&Path> {
+        self.alias.as_deref()
+    }
+
+    /// The path to show users: the [alias](Device::alias) if there is one, otherwise the actual
+    /// path.
+    pub fn display_path(&self) -> &Path {
+        self.alias().unwrap_or(self.path())
+    }
+
     pub fn size(&self) -> Byte {
         Byte::from_u64(self.raw.length() * self.raw.sector_size())
     }
@@ -126,357 +517,4069 @@ impl<'a> Device<'a> {
     ///
     /// [`partitions`](Device::partitions) produces only partitions, leaving the caller to infer
     /// unused sectors based on gaps in partition bounds. This function does that work for you.
-    #[allow(clippy::unwrap_used, reason = "panic statically impossible")]
-    pub fn partitions_with_empty(&self) -> Vec<Either<&Partition, RangeInclusive<i64>>> {
-        fn as_left<T, U>(either: &Either<T, U>) -> Option<&T> {
-            match either {
-                Either::Left(l) => Some(l),
-                Either::Right(_) => None,
-            }
-        }
-
-        let mut partitions = self.partitions().map(Either::Left).collect::<Vec<_>>();
-        if !partitions.is_empty() {
-            let mut i = 0;
-            if *as_left(&partitions[0]).unwrap().bounds().start() > 1 {
-                partitions.insert(
-                    0,
-                    Either::Right(1..=as_left(&partitions[0]).unwrap().bounds().start() - 1),
-                );
-                i += 1;
-            }
-            while i < partitions.len() - 1 {
-                let left = *as_left(&partitions[i]).unwrap().bounds().end();
-                let right = *as_left(&partitions[i + 1]).unwrap().bounds().start();
-                assert!(right > left, "overlapping partitions");
-                if right - left > 1 {
-                    partitions.insert(i + 1, Either::Right(left + 1..=right - 1));
-                    i += 1;
-                }
+    ///
+    /// Free space within an extended partition is separate from the space around it, since only
+    /// logical partitions can be created there.
+    pub fn partitions_with_empty(&self) -> Vec<LayoutRow> {
+        let free = |bounds: RangeInclusive<i64>| {
+            LayoutRow::Free(FreeRegion::new(
+                self.path_owned(),
+                &bounds,
+                self.sector_size(),
+            ))
+        };
 
-                i += 1;
-            }
-            let end = *partitions.last().and_then(as_left).unwrap().bounds().end();
-            if Byte::from_u64(end as u64 * self.sector_size()) < self.size() {
-                partitions.push(Either::Right(
-                    end..=(self.size().as_u64() / self.sector_size()) as i64,
-                ));
+        let partitions = self.partitions().collect::<Vec<_>>();
+        space::layout(
+            &self.usable_sectors(),
+            partitions.iter().map(|p| (p.bounds(), p.partition_type())),
+        )
+        .into_iter()
+        .map(|slot| match slot {
+            space::Slot::Free(bounds) => free(bounds),
+            space::Slot::Partition(index) => {
+                LayoutRow::Partition(Box::new(PartitionView::new(index, partitions[index])))
             }
-        }
-
-        partitions
+        })
+        .collect()
     }
 
-    pub fn sector_size(&self) -> u64 {
-        self.raw.sector_size()
-    }
+    /// Get the layout the device will have after committing, along with the partitions that
+    /// committing removes, ordered by their start sector. Nothing is read from the disk.
+    ///
+    /// Unlike [`partitions`](Device::partitions), this tells new, modified and removed partitions
+    /// apart, so the pending changes don't have to be worked out from the change queue.
+    pub fn preview(&self) -> Vec<ProjectedPartition> {
+        let projected = |index: usize, partition: &Partition, status| ProjectedPartition {
+            view: PartitionView::new(index, partition),
+            status,
+            on_disk: (status != ProjectedStatus::New).then(|| partition.on_disk_geometry()),
+        };
 
-    fn partitions_enum(&self) -> impl Iterator<Item = (usize, &Partition)> {
-        self.partitions
-            .iter()
+        // a new table removes everything that was on disk, which the first one holds
+        let replaced = self.changes.iter().find_map(|c| match c {
+            InnerChange::NewTable {
+                previous_partitions,
+                ..
+            } => Some(previous_partitions),
+            _ => None,
+        });
+        let mut preview = replaced
+            .into_iter()
+            .flatten()
+            .filter(|p| p.kind != PartitionKind::Virtual)
             .enumerate()
-            .filter(|(_, p)| p.kind != PartitionKind::Hidden)
-    }
+            .map(|(index, p)| projected(index, p, ProjectedStatus::Removed))
+            .collect::<Vec<_>>();
 
-    /// Get the number of pending changes.
-    pub fn n_changes(&self) -> usize {
-        self.changes.len()
+        preview.extend(self.partitions.iter().enumerate().map(|(i, partition)| {
+            let status = match partition.kind {
+                PartitionKind::Virtual => ProjectedStatus::New,
+                PartitionKind::Hidden => ProjectedStatus::Removed,
+                PartitionKind::Real if partition.is_modified() => ProjectedStatus::Modified,
+                PartitionKind::Real => ProjectedStatus::Unchanged,
+            };
+            projected(self.get_public_index(i), partition, status)
+        }));
+        // removed partitions come before new ones in their place
+        preview.sort_by_key(|p| {
+            (
+                p.view.geometry.start_sector,
+                p.status != ProjectedStatus::Removed,
+            )
+        });
+        preview
     }
 
-    pub fn change_partition_name(&mut self, partition: usize, new: Arc<str>) {
-        self.partitions[partition].name.1.push(new.clone());
-        self.changes.push(InnerChange::Name { partition, new });
+    /// Get an overview of the device with its pending changes, e.g. for an inventory of a
+    /// machine's disks.
+    pub fn summary(&self) -> DeviceSummary {
+        let allocated = self
+            .partitions()
+            .map(|p| p.geometry().size_byte)
+            .sum::<u64>();
+        let usable = self.usable_sectors();
+        let usable_bytes = (usable.end() - usable.start() + 1).max(0) as u64 * self.sector_size();
+        DeviceSummary {
+            path: self.path().to_path_buf(),
+            model: self.model().into(),
+            size: self.size().as_u64(),
+            disk_type: self.disk_type.as_deref().map(Into::into),
+            partitions: self.partitions().count(),
+            allocated,
+            allocated_fraction: if usable_bytes == 0 {
+                0.0
+            } else {
+                (allocated as f64 / usable_bytes as f64).min(1.0)
+            },
+            largest_free: self
+                .free_regions()
+                .into_iter()
+                .map(|region| region.geometry)
+                .max_by_key(|geometry| geometry.length_sectors),
+            health: DeviceHealth {
+                degraded: self.degraded,
+                read_only: self.is_read_only(),
+                misaligned: self.misaligned_partitions().len(),
+            },
+        }
     }
 
-    /// Create a new partition with the given name, (optionally) filesystem, and bounds **in
-    /// sectors**.
-    pub fn new_partition(
-        &mut self,
-        name: Arc<str>,
-        fs: Option<FileSystem>,
-        bounds: impl RangeBounds<i64>,
-    ) -> Result<(), Error> {
-        let bounds = match bounds.start_bound() {
-            Bound::Included(b) => *b,
-            Bound::Excluded(b) => b + 1,
-            Bound::Unbounded => 0,
-        }..=match bounds.end_bound() {
-            Bound::Included(b) => *b,
-            Bound::Excluded(b) => b - 1,
-            Bound::Unbounded => self.raw.length() as i64,
-        };
-
-        let index = {
-            let mut iter = self.partitions_enum().peekable();
-            let mut out = 0;
-
-            while let Some((i, p)) = iter.next() {
-                if p.bounds().end() < bounds.start()
-                    && iter
-                        .peek()
-                        .is_some_and(|(_, p)| p.bounds().start() > bounds.end())
-                {
-                    out = i;
-                    break;
-                } else if p.bounds().end() <= bounds.start() {
-                    return Err(Error::OverlapsExisting(i));
-                } else if iter
-                    .peek()
-                    .is_some_and(|(_, p)| p.bounds().start() <= bounds.end())
-                {
-                    return Err(Error::OverlapsExisting(i + 1));
-                }
-            }
+    /// Get the ranges of unused sectors that new partitions can be created in.
+    pub fn free_regions(&self) -> Vec<FreeRegion> {
+        self.partitions_with_empty()
+            .into_iter()
+            .filter_map(|row| match row {
+                LayoutRow::Free(region) => Some(region),
+                LayoutRow::Partition(_) => None,
+            })
+            .collect()
+    }
 
-            out
+    /// Get the sectors that partitions can occupy, which excludes the partition table and the
+    /// sectors before the [first partition start](Device::first_partition_start).
+    ///
+    /// On GPT disks, the table leaves the sectors from the first to the last usable LBA, between
+    /// the primary and backup headers and entry arrays. Otherwise only the first sector is
+    /// reserved, for the MBR.
+    pub fn usable_sectors(&self) -> RangeInclusive<i64> {
+        let table = match self.disk_type.as_deref() {
+            // libparted always creates 128 entries
+            Some("gpt") => self.gpt_usable_sectors(self.gpt_entry_capacity().unwrap_or(128)),
+            _ => 1..=self.raw.length() as i64 - 1,
         };
+        let first = self.first_partition_start.sector(self.sector_size()) as i64;
+        (*table.start()).max(first)..=*table.end()
+    }
 
-        self.partitions.insert(
-            index,
-            Partition::new(name.clone(), bounds.clone(), fs, self.raw.sector_size()),
-        );
-
-        self.changes.push(InnerChange::NewPartition {
-            name,
-            fs,
-            bounds,
-            index,
-        });
-
-        Ok(())
+    /// Get the usable sectors of a GPT disk whose entry array has the given capacity.
+    fn gpt_usable_sectors(&self, capacity: u32) -> RangeInclusive<i64> {
+        // entries are practically always 128 bytes; the commit checks against the real size
+        let entry_sectors = (capacity as u64 * 128).div_ceil(self.sector_size()) as i64;
+        2 + entry_sectors..=self.raw.length() as i64 - 2 - entry_sectors
     }
 
-    /// Remove the partition at the given index.
+    /// Get the free space between the partition at the given index and the one before it, or the
+    /// start of the device if it's the first.
     ///
     /// # Panics
     ///
     /// Panics if the index is out of bounds.
-    pub fn remove_partition(&mut self, index: usize) {
-        let index = self
-            .partitions_enum()
+    pub fn gap_before(&self, index: usize) -> Byte {
+        let partition = self
+            .partitions()
             .nth(index)
-            .expect("partition index out of bounds")
-            .0;
-        let removed = if self.partitions[index].kind == PartitionKind::Virtual {
-            Some(self.partitions.remove(index))
-        } else {
-            self.partitions[index].kind = PartitionKind::Hidden;
-            None
-        };
-
-        self.changes
-            .push(InnerChange::RemovePartition { index, removed });
+            .expect("partition index out of bounds");
+        self.gap_before_bounds(partition.bounds())
     }
 
-    /// Change the bounds of the partition at the given index.
+    /// Get the free space between the partition at the given index and the one after it, or the
+    /// end of the device if it's the last.
     ///
     /// # Panics
     ///
     /// Panics if the index is out of bounds.
-    pub fn resize_partition(
-        &mut self,
-        index: usize,
-        new_bounds: impl RangeBounds<i64>,
-    ) -> Result<(), Error> {
-        let bounds = match new_bounds.start_bound() {
-            Bound::Included(b) => *b,
-            Bound::Excluded(b) => b + 1,
-            Bound::Unbounded => 0,
-        }..=match new_bounds.end_bound() {
-            Bound::Included(b) => *b,
-            Bound::Excluded(b) => b - 1,
-            Bound::Unbounded => self.raw.length() as i64,
-        };
-
-        let index = self
-            .partitions_enum()
+    pub fn gap_after(&self, index: usize) -> Byte {
+        let partition = self
+            .partitions()
             .nth(index)
-            .expect("partition index out of bounds")
-            .0;
+            .expect("partition index out of bounds");
+        self.gap_after_bounds(partition.bounds())
+    }
 
-        if *bounds.start() < 0 || *bounds.end() > self.raw.length() as i64 {
-            Err(Error::OutOfBounds)
-        } else if index != 0 && self.partitions[index - 1].bounds().end() > bounds.start() {
-            Err(Error::OverlapsExisting(index - 1))
-        } else if self.partitions[index + 1].bounds().start() < bounds.end() {
-            Err(Error::OverlapsExisting(index + 1))
-        } else {
-            self.partitions[index].bounds.1.push(bounds.clone());
-            self.changes
-                .push(InnerChange::ResizePartition { index, bounds });
-            Ok(())
-        }
+    /// Like [`gap_before`](Device::gap_before), but for a partition with the given bounds that
+    /// may not exist yet.
+    pub fn gap_before_bounds(&self, bounds: &RangeInclusive<i64>) -> Byte {
+        let partitions = self.partitions().map(|p| (p.bounds(), p.partition_type()));
+        let gap = space::gap_before(&self.usable_sectors(), partitions, bounds);
+        Byte::from_u64(gap * self.sector_size())
     }
 
-    #[allow(clippy::unwrap_used, reason = "a failure here would be a logic bug")]
-    fn get_public_index(&self, index: usize) -> usize {
-        self.partitions_enum().position(|p| p.0 == index).unwrap()
+    /// Like [`gap_after`](Device::gap_after), but for a partition with the given bounds that may
+    /// not exist yet.
+    pub fn gap_after_bounds(&self, bounds: &RangeInclusive<i64>) -> Byte {
+        let partitions = self.partitions().map(|p| (p.bounds(), p.partition_type()));
+        let gap = space::gap_after(&self.usable_sectors(), partitions, bounds);
+        Byte::from_u64(gap * self.sector_size())
     }
 
-    /// Undo the last change.
-    pub fn undo_change(&mut self) -> Option<Change> {
-        match self.changes.pop() {
-            Some(InnerChange::Name { partition, new }) => {
-                self.partitions[partition].name.1.pop();
-                Some(Change::Name { partition, new })
-            }
-            Some(InnerChange::NewPartition { index, .. }) => {
-                assert!(
-                    self.partitions[index].kind == PartitionKind::Virtual,
-                    "undo tried to remove a real partition"
-                );
-                self.remove_partition(index);
-                Some(Change::RemovePartition {
-                    index: self.get_public_index(index),
-                })
-            }
-            #[allow(clippy::unwrap_used, reason = "a failure here would be a logic bug")]
-            Some(InnerChange::RemovePartition { index, removed }) => {
-                if let Some(removed) = removed {
-                    self.partitions.insert(index, removed);
-                } else {
-                    assert!(
-                        self.partitions[index].kind == PartitionKind::Hidden,
-                        "undo tried to set a virtual partition to real"
-                    );
-                    self.partitions[index].kind = PartitionKind::Real;
-                }
-                Some(Change::RemovePartition {
-                    index: self.get_public_index(index),
-                })
-            }
-            Some(InnerChange::ResizePartition { index, bounds }) => {
-                self.partitions[index].bounds.1.pop();
-                Some(Change::ResizePartition {
-                    index: self.get_public_index(index),
-                    bounds,
-                })
-            }
-            None => None,
-        }
+    /// The logical sector size of the device, which all bounds are measured in.
+    pub fn sector_size(&self) -> u64 {
+        self.raw.sector_size()
     }
 
-    pub fn undo_all_changes(&mut self) {
-        self.changes.clear();
+    /// The physical sector size of the device, which may be larger than the logical one on
+    /// drives that emulate 512 byte sectors.
+    pub fn physical_sector_size(&self) -> u64 {
+        self.raw.phys_sector_size()
+    }
 
-        for partition in &mut self.partitions {
-            partition.undo_all_changes();
-        }
+    /// Whether the device uses 4096 byte logical sectors (4K native), so that partition tables
+    /// from 512 byte devices can't be copied to it as-is.
+    pub fn is_4k_native(&self) -> bool {
+        self.sector_size() == 4096
+    }
 
-        self.partitions.retain(|p| p.kind != PartitionKind::Virtual);
-        self.partitions
-            .iter_mut()
-            .filter(|p| p.kind == PartitionKind::Hidden)
-            .for_each(|p| p.kind = PartitionKind::Real);
+    /// Take a snapshot of the pending layout of the device.
+    pub fn snapshot(&self) -> DeviceSnapshot {
+        DeviceSnapshot::new(self)
     }
 
-    /// Commit all changes to the device.
+    /// Take a snapshot of the layout of the device as it is on disk, ignoring pending changes.
     ///
-    /// This is blocking and will likely take a while.
-    pub fn commit(&mut self) -> std::io::Result<()> {
-        let mut disk = libparted::Disk::new(&mut self.raw)?;
+    /// [Diffing](DeviceSnapshot::diff) this against [`snapshot`](Device::snapshot) gives what
+    /// committing would change.
+    pub fn on_disk_snapshot(&self) -> DeviceSnapshot {
+        DeviceSnapshot::on_disk(self)
+    }
 
-        for change in self.changes.drain(..) {
-            change.apply(&mut disk)?;
+    /// The type of the device's partition table as named by libparted, e.g. `gpt`, `msdos`, or
+    /// `sun`.
+    pub fn disk_type(&self) -> Option<&str> {
+        self.disk_type.as_deref()
+    }
+
+    /// The device's partition table, including a pending new one, or `None` if it has none: it's
+    /// blank, or a [superfloppy](Device::is_superfloppy).
+    pub fn label(&self) -> Option<DiskLabel> {
+        match self.disk_type.as_deref()? {
+            "gpt" => Some(DiskLabel::Gpt),
+            "msdos" => Some(DiskLabel::Msdos),
+            "loop" => None,
+            other => Some(DiskLabel::Other(other.into())),
         }
+    }
 
-        disk.commit()
+    /// Whether partner can edit the device's partitions. Only GPT and MBR partition tables can be
+    /// edited; others, such as Sun, BSD, and Mac labels, or libparted's `loop` label for file
+    /// systems spanning the whole device, are shown as they are but can only be replaced.
+    pub fn is_editable(&self) -> bool {
+        matches!(self.disk_type.as_deref(), Some("gpt" | "msdos")) && !self.is_emmc_area()
     }
-}
 
-enum InnerChange {
-    Name {
-        partition: usize,
-        new: Arc<str>,
-    },
-    NewPartition {
-        name: Arc<str>,
-        fs: Option<FileSystem>,
-        bounds: RangeInclusive<i64>,
-        index: usize,
-    },
-    RemovePartition {
-        index: usize,
-        removed: Option<Partition>,
-    },
-    ResizePartition {
-        index: usize,
-        bounds: RangeInclusive<i64>,
-    },
-}
+    /// Whether the device has a file system directly on it rather than a partition table, as is
+    /// common for floppies and some USB sticks. The file system is shown as a single partition
+    /// spanning the device.
+    ///
+    /// Use [`convert_superfloppy`](Device::convert_superfloppy) to give it a partition table.
+    pub fn is_superfloppy(&self) -> bool {
+        self.disk_type.as_deref() == Some("loop")
+    }
 
-/// A change to a device returned by [`Device::undo_change`].
-pub enum Change {
-    Name {
-        partition: usize,
-        new: Arc<str>,
-    },
-    NewPartition {
-        name: Arc<str>,
-        fs: Option<FileSystem>,
-        bounds: RangeInclusive<i64>,
-    },
-    RemovePartition {
-        index: usize,
-    },
-    ResizePartition {
-        index: usize,
-        bounds: RangeInclusive<i64>,
-    },
-}
+    /// The boot areas and RPMB of an eMMC device, which the kernel exposes as devices of their own
+    /// next to it. This is empty for other devices.
+    pub fn emmc_areas(&self) -> Vec<EmmcArea> {
+        emmc::areas(&self.path)
+    }
 
-impl InnerChange {
-    fn apply(self, disk: &mut libparted::Disk) -> std::io::Result<()> {
-        match self {
-            #[allow(
-                clippy::unwrap_used,
-                reason = "a panic here would be an internal logic bug"
-            )]
-            Self::Name { partition, new } => disk
-                .parts()
-                .nth(partition + 1)
-                .unwrap()
-                .set_name(new.as_ref()),
-            Self::NewPartition {
-                name, fs, bounds, ..
-            } => {
-                let mut part = libparted::Partition::new(
-                    disk,
-                    libparted::PartitionType::PED_PARTITION_NORMAL,
-                    fs.map(Into::into).as_ref(),
-                    *bounds.start(),
+    /// Whether the device is an eMMC boot area or RPMB rather than a device that can be
+    /// partitioned, because it was [opened](Device::open) by its path. No changes can be staged
+    /// on such devices, and [plans](ChangePlan) never target them.
+    pub fn is_emmc_area(&self) -> bool {
+        emmc::kind(&self.path).is_some()
+    }
+
+    /// Whether the device was opened from its backup GPT because the primary one is corrupt.
+    ///
+    /// A degraded device starts out with a pending change that rewrites the primary GPT from the
+    /// backup. Its partitions' file systems are unknown until that change is committed.
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    fn partitions_enum(&self) -> impl Iterator<Item = (usize, &Partition)> {
+        self.partitions
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.kind != PartitionKind::Hidden)
+    }
+
+    pub fn safety_level(&self) -> SafetyLevel {
+        self.safety_level
+    }
+
+    pub fn set_safety_level(&mut self, level: SafetyLevel) {
+        self.safety_level = level;
+    }
+
+    /// Allow the next destructive operation on the partition at the given index, even though it is
+    /// in use.
+    ///
+    /// This is only necessary with [`SafetyLevel::Confirm`]. The confirmation is used up by the
+    /// next operation on the partition, whether or not it succeeds, and replaced by any later
+    /// confirmation.
+    pub fn confirm(&mut self, index: usize) -> &mut Self {
+        self.confirmed = Some(index);
+        self
+    }
+
+    /// Check whether a destructive operation may be performed on the partition at the given public
+    /// index, using up any confirmation for it.
+    fn check_safety(&mut self, index: usize) -> Result<(), Error> {
+        let confirmed = self.confirmed.take_if(|i| *i == index).is_some();
+        let partition = self.partitions().nth(index);
+        let in_use = partition.is_some_and(|p| p.mounted() || p.is_booted_esp());
+        let note = partition.and_then(|p| p.note.clone());
+
+        match self.safety_level {
+            _ if !in_use && note.is_none() => Ok(()),
+            SafetyLevel::Safe => Err(match note {
+                Some(note) if !in_use => Error::Protected { index, note },
+                _ => Error::InUse(index),
+            }),
+            SafetyLevel::Confirm if !confirmed => Err(Error::NeedsConfirmation(index)),
+            SafetyLevel::Confirm | SafetyLevel::Force => Ok(()),
+        }
+    }
+
+    /// Get the number of pending changes.
+    pub fn n_changes(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Get the pending changes, in the order they are applied on commit.
+    ///
+    /// Partition indices are those the partitions have now. A removed partition gets the index it
+    /// would have if the removal were undone.
+    pub fn changes(&self) -> Vec<Change> {
+        let size = |bounds: &RangeInclusive<i64>| {
+            Byte::from_u64(Geometry::new(bounds, self.sector_size()).size_byte)
+        };
+        self.changes
+            .iter()
+            .enumerate()
+            .map(|(position, change)| {
+                let public = |index| self.get_public_index(self.index_now(position, index).0);
+                let path = |index| match self.index_now(position, index) {
+                    (_, Some(gone)) => gone.path.clone(),
+                    (index, None) => self.partitions[index].path.clone(),
+                };
+                match change {
+                    InnerChange::Name { partition, new, .. } => Change::Name {
+                        partition: public(*partition),
+                        path: path(*partition),
+                        new: new.clone(),
+                    },
+                    InnerChange::NewPartition {
+                        name,
+                        fs,
+                        bounds,
+                        partition_type,
+                        ..
+                    } => Change::NewPartition {
+                        name: name.clone(),
+                        fs: *fs,
+                        bounds: bounds.clone(),
+                        size: size(bounds),
+                        partition_type: *partition_type,
+                    },
+                    InnerChange::RemovePartition { index, removed, .. } => {
+                        Change::RemovePartition {
+                            index: public(*index),
+                            path: removed
+                                .as_ref()
+                                .map_or_else(|| path(*index), |p| p.path.clone()),
+                        }
+                    }
+                    InnerChange::ResizePartition {
+                        index,
+                        from,
+                        bounds,
+                        ..
+                    } => Change::ResizePartition {
+                        index: public(*index),
+                        path: path(*index),
+                        bounds: bounds.clone(),
+                        old_size: size(from),
+                        new_size: size(bounds),
+                    },
+                    InnerChange::MovePartition { index, to, .. } => Change::MovePartition {
+                        index: public(*index),
+                        path: path(*index),
+                        bounds: to.clone(),
+                    },
+                    InnerChange::SetBootable { index, .. } => Change::SetBootable {
+                        index: public(*index),
+                        path: path(*index),
+                    },
+                    InnerChange::SetFlag {
+                        index, flag, state, ..
+                    } => Change::SetFlag {
+                        index: public(*index),
+                        path: path(*index),
+                        flag: *flag,
+                        state: *state,
+                    },
+                    InnerChange::SetUuid { index, uuid, .. } => Change::SetUuid {
+                        index: public(*index),
+                        path: path(*index),
+                        uuid: *uuid,
+                    },
+                    InnerChange::RepairGpt(_) => Change::RepairGpt,
+                    InnerChange::HybridMbr => Change::HybridMbr,
+                    InnerChange::GptEntryCapacity => Change::GptEntryCapacity,
+                    InnerChange::NewTable { table, .. } => Change::NewTable { table: *table },
+                    InnerChange::Format { bounds, fs, label } => Change::Format {
+                        bounds: bounds.clone(),
+                        fs: *fs,
+                        label: label.clone(),
+                    },
+                    InnerChange::RelocateFs { fs, to, .. } => Change::RelocateFs {
+                        fs: *fs,
+                        bounds: to.clone(),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Follow the partition at `index` when the change at `position` was staged through the
+    /// changes staged after it, giving the index it has now.
+    ///
+    /// Partitions that are gone since, i.e. new ones that were removed again or those replaced
+    /// along with the table, are returned along with the index they would have if that were
+    /// undone.
+    fn index_now(&self, position: usize, mut index: usize) -> (usize, Option<&Partition>) {
+        let mut gone = None;
+        for change in &self.changes[position + 1..] {
+            match change {
+                InnerChange::NewPartition { index: new, .. } if *new <= index => index += 1,
+                InnerChange::RemovePartition {
+                    index: removed_at,
+                    removed: Some(removed),
+                    ..
+                } if gone.is_none() && *removed_at == index => gone = Some(&**removed),
+                InnerChange::RemovePartition {
+                    index: removed_at,
+                    removed: Some(_),
+                    ..
+                } if *removed_at < index => index -= 1,
+                InnerChange::NewTable {
+                    previous_partitions,
+                    ..
+                } if gone.is_none() => return (index, previous_partitions.get(index)),
+                _ => {}
+            }
+        }
+        (index, gone)
+    }
+
+    /// Move the pending change at position `from` to position `to`, e.g. to apply quick renames
+    /// before a slow resize.
+    ///
+    /// Fails if the change would be moved past one whose result depends on their order, such as
+    /// a resize of a neighbouring partition or anything that adds or removes partitions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either position is out of bounds.
+    pub fn move_change(&mut self, from: usize, to: usize) -> Result<(), Error> {
+        assert!(
+            from < self.changes.len() && to < self.changes.len(),
+            "change position out of bounds"
+        );
+
+        if let Some(position) = (from.min(to)..=from.max(to))
+            .filter(|i| *i != from)
+            .find(|i| self.changes[from].depends_on_order(&self.changes[*i]))
+        {
+            return Err(Error::ChangeDependency(position));
+        }
+
+        self.changes.reorder(from, to);
+        Ok(())
+    }
+
+    /// Get the number of pending changes of each type.
+    pub fn pending_counts(&self) -> PendingCounts {
+        let mut counts = PendingCounts::default();
+        for change in self.changes.iter() {
+            match change {
+                InnerChange::Name { .. } => counts.renames += 1,
+                InnerChange::NewPartition { .. } => counts.new_partitions += 1,
+                InnerChange::RemovePartition { .. } => counts.removals += 1,
+                InnerChange::ResizePartition { .. } => counts.resizes += 1,
+                InnerChange::MovePartition { .. } | InnerChange::RelocateFs { .. } => {
+                    counts.moves += 1
+                }
+                InnerChange::SetBootable { .. }
+                | InnerChange::SetFlag { .. }
+                | InnerChange::SetUuid { .. }
+                | InnerChange::RepairGpt(_)
+                | InnerChange::HybridMbr
+                | InnerChange::GptEntryCapacity
+                | InnerChange::NewTable { .. }
+                | InnerChange::Format { .. } => counts.other += 1,
+            }
+        }
+        counts
+    }
+
+    /// Get the amount of partition data whose position on the device changes with the pending
+    /// resizes.
+    ///
+    /// partner doesn't move partition contents itself, so this is how much a file system aware
+    /// tool would have to move to preserve them.
+    pub fn pending_bytes_to_move(&self) -> Byte {
+        Byte::from_u64(self.partitions().map(|p| p.bytes_to_move(p.bounds())).sum())
+    }
+
+    /// Get the amount of existing data the pending changes destroy: the sizes of the partitions on
+    /// disk that are removed, or replaced along with the partition table.
+    ///
+    /// Use [`CommitOptions::destroy_limit`] to have commits that destroy more than a given amount
+    /// acknowledged first.
+    pub fn pending_bytes_destroyed(&self) -> Byte {
+        let preserved = self
+            .changes
+            .iter()
+            .filter_map(|c| match c {
+                InnerChange::RelocateFs { from, .. } => Some(from),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // the first new table holds the partitions that are on disk now
+        let on_disk = self.changes.iter().find_map(|c| match c {
+            InnerChange::NewTable {
+                previous_partitions,
+                ..
+            } => Some(previous_partitions),
+            _ => None,
+        });
+        let size = |p: &Partition| Geometry::new(&p.bounds.0, self.sector_size()).size_byte;
+        Byte::from_u64(match on_disk {
+            Some(partitions) => partitions
+                .iter()
+                .filter(|p| p.kind != PartitionKind::Virtual && !preserved.contains(&&p.bounds.0))
+                .map(size)
+                .sum(),
+            None => self
+                .partitions
+                .iter()
+                .filter(|p| p.kind == PartitionKind::Hidden)
+                .map(size)
+                .sum(),
+        })
+    }
+
+    /// Get a token acknowledging that committing the pending changes destroys as much data as
+    /// [`pending_bytes_destroyed`](Device::pending_bytes_destroyed) says, to pass to
+    /// [`CommitOptions::acknowledge_destruction`] once the user has confirmed it.
+    pub fn acknowledge_destruction(&self) -> DestructionAck {
+        DestructionAck(self.pending_bytes_destroyed().as_u64())
+    }
+
+    /// Check the pending changes against the [destroy limit](CommitOptions::destroy_limit).
+    pub(crate) fn check_destruction(&self, options: &CommitOptions) -> Result<(), CommitError> {
+        let destroyed = self.pending_bytes_destroyed();
+        match options.destroy_limit {
+            Some(limit)
+                if destroyed.as_u64() > limit
+                    && options
+                        .destruction_ack
+                        .is_none_or(|ack| ack.0 < destroyed.as_u64()) =>
+            {
+                Err(CommitError::UnacknowledgedDestruction(destroyed))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Find the `/etc/fstab` and `/etc/crypttab` entries that the pending changes will break,
+    /// because they refer to a partition that will be deleted, reformatted or given a new
+    /// PARTUUID.
+    ///
+    /// Use [`ConfigImpact::patch`] to get versions of those files with the stale entries
+    /// commented out.
+    pub fn config_impact(&self) -> ConfigImpact {
+        let formatted = self
+            .changes
+            .iter()
+            .filter_map(|c| match c {
+                InnerChange::Format { bounds, .. } => Some(bounds),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let replaced = self.changes.iter().flat_map(|c| match c {
+            InnerChange::NewTable {
+                previous_partitions,
+                ..
+            } => previous_partitions.as_slice(),
+            _ => &[],
+        });
+
+        let affected = self
+            .partitions
+            .iter()
+            .filter(|p| p.kind == PartitionKind::Hidden)
+            .chain(replaced.filter(|p| p.kind != PartitionKind::Virtual))
+            .filter_map(|p| {
+                // a partition recreated in the same place keeps its path
+                let reason = if formatted.contains(&&p.bounds.0) {
+                    StaleReason::Reformatted
+                } else {
+                    StaleReason::Deleted
+                };
+                Some((p.path.as_deref()?.to_path_buf(), reason))
+            })
+            .chain(
+                self.partitions
+                    .iter()
+                    .filter(|p| p.kind == PartitionKind::Real && p.uuid() != p.uuid.0)
+                    .filter_map(|p| {
+                        Some((p.path.as_deref()?.to_path_buf(), StaleReason::NewPartuuid))
+                    }),
+            )
+            .collect::<Vec<_>>();
+
+        impact::analyze(&affected)
+    }
+
+    pub fn first_partition_start(&self) -> FirstPartitionStart {
+        self.first_partition_start
+    }
+
+    /// Set where the first partition may start. This limits where new partitions can be created,
+    /// and the [free regions](Device::free_regions) reported, but leaves partitions that already
+    /// start earlier alone.
+    pub fn set_first_partition_start(&mut self, start: FirstPartitionStart) {
+        self.first_partition_start = start;
+    }
+
+    /// The alignment new partitions are given, if one was [set](Device::set_alignment).
+    pub fn alignment(&self) -> Option<Byte> {
+        self.alignment.map(Byte::from_u64)
+    }
+
+    /// Align new partitions to the given size, e.g. the chunk size of a hardware RAID stripe or
+    /// an SSD's erase block, rather than to libparted's optimal alignment for the device. `None`
+    /// goes back to libparted's.
+    ///
+    /// The alignment must be a multiple of the sector size, which is checked when partitions are
+    /// created.
+    pub fn set_alignment(&mut self, alignment: Option<Byte>) {
+        self.alignment = alignment.map(|a| a.as_u64());
+    }
+
+    pub fn limits(&self) -> ChangeLimits {
+        self.limits
+    }
+
+    /// Set the limits on pending changes. Changes that are already staged are kept even if they
+    /// exceed the new limits.
+    pub fn set_limits(&mut self, limits: ChangeLimits) {
+        self.limits = limits;
+    }
+
+    /// Check that the partition table is one partner can edit.
+    fn check_editable(&self) -> Result<(), Error> {
+        if self.is_emmc_area() {
+            return Err(Error::EmmcArea);
+        }
+        match self.disk_type.as_deref() {
+            None => Err(Error::NoTable),
+            Some(label) if !self.is_editable() => Err(Error::UnsupportedLabel(label.into())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that `n` more changes can be staged, with `bytes_to_move` more data being moved, and
+    /// that the device isn't read-only or an eMMC boot area.
+    fn check_limits(&self, n: usize, bytes_to_move: u64) -> Result<(), Error> {
+        if self.is_emmc_area() {
+            return Err(Error::EmmcArea);
+        }
+        if self.is_read_only() {
+            return Err(Error::ReadOnly);
+        }
+
+        let too_many = self
+            .limits
+            .max_changes
+            .is_some_and(|max| self.changes.len() + n > max);
+        let too_much = self.limits.max_bytes_to_move.is_some_and(|max| {
+            self.pending_bytes_to_move().as_u64() + bytes_to_move > max.as_u64()
+        });
+
+        if too_many || too_much {
+            Err(Error::LimitExceeded)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Rename the partition at the given index.
+    ///
+    /// The name is validated with [`validate_name`]; use [`sanitize_name`] first if it comes from
+    /// an untrusted source.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn change_partition_name(&mut self, partition: usize, new: Arc<str>) -> Result<(), Error> {
+        self.check_editable()?;
+        validate_name(&new)?;
+        self.check_limits(1, 0)?;
+        let partition =
+            partition::internal_index(self.partitions.iter().map(|p| p.kind), partition)
+                .expect("partition index out of bounds");
+        self.partitions[partition].name.1.push(new.clone());
+        self.push_change(InnerChange::Name {
+            partition,
+            target: ChangeTarget::of(&self.partitions[partition]),
+            new,
+        });
+        Ok(())
+    }
+
+    /// Rename every partition according to the given scheme.
+    ///
+    /// Each rename is staged as a separate change, skipping partitions that already have the
+    /// right name. Nothing is staged if any of the names are invalid.
+    pub fn apply_naming_scheme(&mut self, scheme: &NamingScheme) -> Result<(), Error> {
+        self.check_editable()?;
+        let renames = self
+            .partitions_enum()
+            .enumerate()
+            .map(|(n, (i, p))| {
+                let name = scheme.render(n + 1, p).map_err(Error::UnknownPlaceholder)?;
+                validate_name(&name)?;
+                Ok((i, p.name() != name, name))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        self.check_limits(renames.iter().filter(|(_, changed, _)| *changed).count(), 0)?;
+
+        for (partition, _, new) in renames.into_iter().filter(|(_, changed, _)| *changed) {
+            let new = Arc::<str>::from(new);
+            self.partitions[partition].name.1.push(new.clone());
+            self.push_change(InnerChange::Name {
+                partition,
+                target: ChangeTarget::of(&self.partitions[partition]),
+                new,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Create a new partition with the given name, (optionally) filesystem, and bounds **in
+    /// sectors**.
+    ///
+    /// Unbounded ends extend to the first or last [usable sector](Device::usable_sectors), and
+    /// bounds reaching into the partition table are clamped to them. Fails with
+    /// [`Error::NotFree`] if the bounds don't fit in one of the
+    /// [free regions](Device::free_regions).
+    ///
+    /// The partition is aligned to the device's [alignment](Device::set_alignment) if it has one,
+    /// shrinking it to the aligned sectors within the bounds.
+    ///
+    /// On MBR disks, the partition is a logical one if it's within the
+    /// [extended partition](Device::new_extended_partition), and a primary one otherwise, which
+    /// fails with [`Error::TooManyPrimary`] if there are four already.
+    pub fn new_partition(
+        &mut self,
+        name: Arc<str>,
+        fs: Option<FileSystem>,
+        bounds: impl RangeBounds<i64>,
+    ) -> Result<(), Error> {
+        self.stage_new_partition(name, fs, bounds, None, self.alignment, false)
+    }
+
+    /// Like [`new_partition`](Device::new_partition), but aligned to the given size instead of the
+    /// device's [alignment](Device::set_alignment).
+    ///
+    /// Fails with [`Error::InvalidAlignment`] if the alignment isn't a multiple of the sector
+    /// size.
+    pub fn new_partition_aligned(
+        &mut self,
+        name: Arc<str>,
+        fs: Option<FileSystem>,
+        bounds: impl RangeBounds<i64>,
+        alignment: Byte,
+    ) -> Result<(), Error> {
+        self.stage_new_partition(name, fs, bounds, None, Some(alignment.as_u64()), false)
+    }
+
+    /// Like [`new_partition`](Device::new_partition), but with the given GPT partition number
+    /// rather than the first free one, e.g. to follow a convention like using entry 128 for a
+    /// BIOS boot partition.
+    ///
+    /// Fails with [`Error::PartitionNumberTaken`] if another partition has or will have that
+    /// number, and with [`Error::UnsupportedByTable`] on anything but GPT.
+    pub fn new_partition_with_number(
+        &mut self,
+        name: Arc<str>,
+        fs: Option<FileSystem>,
+        bounds: impl RangeBounds<i64>,
+        number: u32,
+    ) -> Result<(), Error> {
+        self.check_partition_number(number)?;
+        self.stage_new_partition(name, fs, bounds, Some(number), self.alignment, false)
+    }
+
+    /// Create an extended partition with the given bounds **in sectors** on an MBR disk, to hold
+    /// logical partitions beyond the four primary ones an MBR has room for. Logical partitions
+    /// are created in it with [`new_partition`](Device::new_partition), and it can be grown
+    /// with [`resize_partition`](Device::resize_partition) to make room for more.
+    ///
+    /// The bounds are treated as by [`new_partition`](Device::new_partition). Fails with
+    /// [`Error::UnsupportedByTable`] on anything but MBR, with [`Error::ExtendedExists`] if there
+    /// is one already, and with [`Error::TooManyPrimary`] if there are four primary partitions.
+    pub fn new_extended_partition(&mut self, bounds: impl RangeBounds<i64>) -> Result<(), Error> {
+        self.stage_new_partition("".into(), None, bounds, None, self.alignment, true)
+    }
+
+    /// Get the type a new partition with the given bounds would have on an MBR disk, which is
+    /// logical within the extended partition, and check that the table has room for it.
+    fn new_partition_type(
+        &self,
+        bounds: &RangeInclusive<i64>,
+        extended: bool,
+    ) -> Result<PartitionType, Error> {
+        if self.disk_type.as_deref() != Some("msdos") {
+            return if extended {
+                Err(Error::UnsupportedByTable)
+            } else {
+                Ok(PartitionType::Primary)
+            };
+        }
+        let container = self
+            .partitions()
+            .find(|p| p.partition_type() == PartitionType::Extended);
+        if let Some(container) = container {
+            if extended {
+                return Err(Error::ExtendedExists);
+            }
+            // free regions end at the extended partition's bounds, so a start within it means the
+            // partition is entirely within it
+            if container.bounds().contains(bounds.start()) {
+                return Ok(PartitionType::Logical);
+            }
+        }
+        let primary = self
+            .partitions()
+            .filter(|p| p.partition_type() != PartitionType::Logical)
+            .count();
+        if primary >= 4 {
+            return Err(Error::TooManyPrimary);
+        }
+        Ok(if extended {
+            PartitionType::Extended
+        } else {
+            PartitionType::Primary
+        })
+    }
+
+    /// Check that a new partition can have the given GPT partition number.
+    fn check_partition_number(&self, number: u32) -> Result<(), Error> {
+        let Some(capacity) = self.gpt_entry_capacity() else {
+            return Err(Error::UnsupportedByTable);
+        };
+        if number == 0 || number > capacity {
+            return Err(Error::InvalidPartitionNumber(number));
+        }
+        if self.partition_number_taken(number) {
+            return Err(Error::PartitionNumberTaken(number));
+        }
+        Ok(())
+    }
+
+    /// Whether a partition on disk that isn't being removed, or a pending new partition, has the
+    /// given GPT partition number.
+    fn partition_number_taken(&self, number: u32) -> bool {
+        let requested = self.changes.iter().any(
+            |c| matches!(c, InnerChange::NewPartition { number: Some(n), .. } if *n == number),
+        );
+        // a new table frees every number
+        if self
+            .changes
+            .iter()
+            .any(|c| matches!(c, InnerChange::NewTable { .. }))
+        {
+            return requested;
+        }
+
+        let on_disk = std::fs::File::open(&self.path)
+            .and_then(|mut file| gpt::Table::read_at(&mut file, self.sector_size(), 1))
+            .map(|table| {
+                table.entries().any(|e| {
+                    e.number == number
+                        && !self.partitions.iter().any(|p| {
+                            p.kind == PartitionKind::Hidden
+                                && *p.bounds.0.start() as u64 == e.bounds.0
+                        })
+                })
+            })
+            .unwrap_or(false);
+        requested || on_disk
+    }
+
+    fn stage_new_partition(
+        &mut self,
+        name: Arc<str>,
+        fs: Option<FileSystem>,
+        bounds: impl RangeBounds<i64>,
+        number: Option<u32>,
+        alignment: Option<u64>,
+        extended: bool,
+    ) -> Result<(), Error> {
+        self.check_editable()?;
+        validate_name(&name)?;
+        self.check_limits(1, 0)?;
+
+        // the table's own sectors can't be partitioned, so bounds reaching into them are clamped
+        let usable = self.usable_sectors();
+        let start = match bounds.start_bound() {
+            Bound::Included(b) => *b,
+            Bound::Excluded(b) => b + 1,
+            Bound::Unbounded => *usable.start(),
+        };
+        let end = match bounds.end_bound() {
+            Bound::Included(b) => *b,
+            Bound::Excluded(b) => b - 1,
+            Bound::Unbounded => *usable.end(),
+        };
+        if start < 0 || end >= self.raw.length() as i64 {
+            return Err(Error::OutOfBounds);
+        }
+        let (start, end) = (start.max(*usable.start()), end.min(*usable.end()));
+        let bounds = match alignment {
+            Some(alignment) => {
+                let sector_size = self.sector_size();
+                if alignment == 0 || alignment % sector_size != 0 {
+                    return Err(Error::InvalidAlignment(Byte::from_u64(alignment)));
+                }
+                let align = (alignment / sector_size) as i64;
+                // both are past the start of the device, which was checked above
+                (start as u64).div_ceil(align as u64) as i64 * align..=(end + 1) / align * align - 1
+            }
+            None => start..=end,
+        };
+        if bounds.is_empty() {
+            return Err(Error::OutOfBounds);
+        }
+
+        let free = self.free_regions();
+        if !free
+            .iter()
+            .any(|r| r.bounds().contains(bounds.start()) && r.bounds().contains(bounds.end()))
+        {
+            let overlap = |r: &&FreeRegion| {
+                (r.bounds().end().min(bounds.end()) - r.bounds().start().max(bounds.start()) + 1)
+                    .max(0)
+            };
+            let nearest = free
+                .iter()
+                .filter(|r| overlap(r) > 0)
+                .max_by_key(overlap)
+                .cloned();
+            return Err(Error::NotFree(nearest));
+        }
+        let partition_type = self.new_partition_type(&bounds, extended)?;
+
+        // removed partitions are kept in place, and may overlap the new one
+        let index = self
+            .partitions
+            .iter()
+            .position(|p| p.bounds().start() > bounds.start())
+            .unwrap_or(self.partitions.len());
+
+        let mut partition = Partition::new(
+            name.clone(),
+            bounds.clone(),
+            fs,
+            self.path_owned(),
+            self.raw.sector_size(),
+        );
+        partition.partition_type = partition_type;
+        self.partitions.insert(index, partition);
+
+        self.push_change(InnerChange::NewPartition {
+            name,
+            fs,
+            bounds,
+            index,
+            number,
+            exact: alignment.is_some(),
+            partition_type,
+        });
+
+        Ok(())
+    }
+
+    /// Remove the partition at the given index.
+    ///
+    /// Fails if the partition is in use, depending on the [safety level](SafetyLevel).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn remove_partition(&mut self, index: usize) -> Result<(), Error> {
+        self.check_editable()?;
+        self.check_limits(1, 0)?;
+        self.check_safety(index)?;
+
+        let index = self
+            .partitions_enum()
+            .nth(index)
+            .expect("partition index out of bounds")
+            .0;
+        if self.partitions[index].partition_type() == PartitionType::Extended
+            && self
+                .partitions()
+                .any(|p| p.partition_type() == PartitionType::Logical)
+        {
+            return Err(Error::ExtendedNotEmpty);
+        }
+        let target = ChangeTarget::of(&self.partitions[index]);
+        let removed = if self.partitions[index].kind == PartitionKind::Virtual {
+            Some(Box::new(self.partitions.remove(index)))
+        } else {
+            self.partitions[index].kind = PartitionKind::Hidden;
+            None
+        };
+
+        self.push_change(InnerChange::RemovePartition {
+            index,
+            target,
+            removed,
+        });
+        Ok(())
+    }
+
+    /// Change the bounds of the partition at the given index, resizing its file system with its
+    /// own tools if it has any and the start stays put, or else with libparted.
+    ///
+    /// Fails if the partition is in use, depending on the [safety level](SafetyLevel), or with
+    /// [`Error::ResizeUnsupported`] if its file system can't be resized that way.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn resize_partition(
+        &mut self,
+        index: usize,
+        new_bounds: impl RangeBounds<i64>,
+    ) -> Result<(), Error> {
+        let partition = self
+            .partitions()
+            .nth(index)
+            .expect("partition index out of bounds");
+        let from = partition.bounds();
+        let resized = (new_bounds.start_bound(), new_bounds.end_bound());
+        let moves_start = space::resize_bounds(&self.usable_sectors(), from, resized)
+            .is_some_and(|bounds| bounds.start() != from.start());
+        let strategy = ResizeStrategy::preferred(partition.fs(), moves_start);
+        self.resize_partition_with(index, new_bounds, strategy)
+    }
+
+    /// Change the bounds of the partition at the given index, resizing its file system with the
+    /// given strategy.
+    ///
+    /// Fails if the partition is in use, depending on the [safety level](SafetyLevel), or with
+    /// [`Error::ResizeUnsupported`] if its file system can't be resized that way according to
+    /// [`FileSystem::resize_support`]. Fails with [`Error::OutsideExtended`] if a logical
+    /// partition would leave the extended partition, or the extended partition would no longer
+    /// hold all logical ones.
+    ///
+    /// Unbounded ends reach the ends of the [usable sectors](Device::usable_sectors), and bounds
+    /// reaching past them fail with [`Error::OutOfBounds`]. A partition that starts before them
+    /// can keep its start.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn resize_partition_with(
+        &mut self,
+        index: usize,
+        new_bounds: impl RangeBounds<i64>,
+        strategy: ResizeStrategy,
+    ) -> Result<(), Error> {
+        self.check_editable()?;
+        self.check_safety(index)?;
+
+        let (index, partition) = self
+            .partitions_enum()
+            .nth(index)
+            .expect("partition index out of bounds");
+        let Some(bounds) =
+            space::resize_bounds(&self.usable_sectors(), partition.bounds(), new_bounds)
+        else {
+            return Err(Error::OutOfBounds);
+        };
+
+        self.check_overlap(index, &bounds)?;
+        let partition = &self.partitions[index];
+        let from = partition.bounds().clone();
+        let fs = partition.fs();
+        if let Some(fs) = fs {
+            let support = fs.resize_support(strategy);
+            let (old_len, new_len) = (from.end() - from.start(), bounds.end() - bounds.start());
+            if (strategy == ResizeStrategy::External && bounds.start() != from.start())
+                || (new_len > old_len && !support.grow)
+                || (new_len < old_len && !support.shrink)
+            {
+                return Err(Error::ResizeUnsupported(fs));
+            }
+        }
+        let extra = partition.bytes_to_move(&bounds) - partition.bytes_to_move(&from);
+        self.check_limits(1, extra)?;
+
+        let target = ChangeTarget::of(&self.partitions[index]);
+        self.partitions[index].bounds.1.push(bounds.clone());
+        self.push_change(InnerChange::ResizePartition {
+            index,
+            target,
+            from,
+            bounds,
+            strategy,
+            fs,
+        });
+        Ok(())
+    }
+
+    /// Check that the partition at the given internal index can have the given bounds without
+    /// overlapping any other, except that logical partitions must be within the extended one.
+    fn check_overlap(&self, index: usize, bounds: &RangeInclusive<i64>) -> Result<(), Error> {
+        let this = self.partitions[index].partition_type();
+        for (i, other) in self.partitions_enum().filter(|(i, _)| *i != index) {
+            let nested = match (this, other.partition_type()) {
+                (PartitionType::Logical, PartitionType::Extended) => Some((other.bounds(), bounds)),
+                (PartitionType::Extended, PartitionType::Logical) => Some((bounds, other.bounds())),
+                _ => None,
+            };
+            match nested {
+                Some((outer, inner))
+                    if !outer.contains(inner.start()) || !outer.contains(inner.end()) =>
+                {
+                    return Err(Error::OutsideExtended);
+                }
+                Some(_) => {}
+                None if commit::overlaps(other.bounds(), bounds) => {
+                    return Err(Error::OverlapsExisting(self.get_public_index(i)));
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the indices of partitions whose start isn't aligned to the device's physical sectors,
+    /// e.g. those created by old tools at sector 63. Writes to them are split across physical
+    /// sectors, which is slow on SSDs and 4K drives.
+    pub fn misaligned_partitions(&self) -> Vec<usize> {
+        let align = self.physical_sector_size().max(4096);
+        self.partitions()
+            .enumerate()
+            .filter(|(_, p)| p.kind == PartitionKind::Real)
+            .filter(|(_, p)| {
+                !(*p.bounds().start() as u64 * self.sector_size()).is_multiple_of(align)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Move the partition at the given index to the nearest 1 MiB boundary, keeping its size.
+    /// The partition's contents are moved along with it when committing.
+    ///
+    /// Fails if the partition is in use, depending on the [safety level](SafetyLevel), or if it
+    /// doesn't fit at either neighbouring boundary, within the
+    /// [usable sectors](Device::usable_sectors) and clear of the other partitions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn realign_partition(&mut self, index: usize) -> Result<(), Error> {
+        self.check_editable()?;
+        self.check_safety(index)?;
+
+        let (internal, partition) = self
+            .partitions_enum()
+            .nth(index)
+            .expect("partition index out of bounds");
+        let from = partition.bounds().clone();
+        let align = (1024 * 1024 / self.sector_size()) as i64;
+        let down = from.start() - from.start().rem_euclid(align);
+        let mut candidates = [down, down + align];
+        candidates.sort_by_key(|start| (start - from.start()).abs());
+
+        let usable = self.usable_sectors();
+        let place = |start: i64| {
+            let to = start..=start + (from.end() - from.start());
+            if !usable.contains(to.start()) || !usable.contains(to.end()) {
+                return Err(Error::OutOfBounds);
+            }
+            self.check_overlap(internal, &to)?;
+            Ok(to)
+        };
+        let to = place(candidates[0]).or_else(|_| place(candidates[1]))?;
+        if to == from {
+            return Ok(());
+        }
+
+        let extra = partition.bytes_to_move(&to) - partition.bytes_to_move(&from);
+        self.check_limits(1, extra)?;
+
+        let target = ChangeTarget::of(&self.partitions[internal]);
+        self.partitions[internal].bounds.1.push(to.clone());
+        self.push_change(InnerChange::MovePartition {
+            index: internal,
+            target,
+            from,
+            to,
+        });
+        Ok(())
+    }
+
+    /// Set the legacy MBR boot flag on the partition at the given index, clearing it from all
+    /// other partitions.
+    ///
+    /// Only msdos partition tables have this flag; on GPT, the closest equivalent is the ESP type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set_bootable(&mut self, index: usize) -> Result<(), Error> {
+        self.check_editable()?;
+        if self.disk_type.as_deref() != Some("msdos") {
+            return Err(Error::UnsupportedByTable);
+        }
+        self.check_limits(1, 0)?;
+
+        let index = self
+            .partitions_enum()
+            .nth(index)
+            .expect("partition index out of bounds")
+            .0;
+
+        let cleared = self
+            .partitions_enum()
+            .filter(|(i, p)| *i != index && p.is_bootable())
+            .map(|(i, p)| (i, ChangeTarget::of(p)))
+            .collect::<Vec<_>>();
+        for (i, _) in &cleared {
+            self.partitions[*i].bootable.1.push(false);
+        }
+        let target = ChangeTarget::of(&self.partitions[index]);
+        self.partitions[index].bootable.1.push(true);
+
+        self.push_change(InnerChange::SetBootable {
+            index,
+            target,
+            cleared,
+        });
+        Ok(())
+    }
+
+    /// Set or clear a flag on the partition at the given index, e.g. to mark an EFI system
+    /// partition.
+    ///
+    /// Setting the [boot flag](PartitionFlag::Boot) clears it from all other partitions, as
+    /// [`set_bootable`](Device::set_bootable) does. On GPT, the ESP, LVM, RAID, swap and Microsoft
+    /// reserved flags are stored as the partition's type, so setting one clears the others.
+    ///
+    /// Fails with [`Error::UnsupportedByTable`] if the partition table has no such flag. Nothing
+    /// is staged if the flag is already in the given state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set_partition_flag(
+        &mut self,
+        index: usize,
+        flag: PartitionFlag,
+        state: bool,
+    ) -> Result<(), Error> {
+        self.check_editable()?;
+        let disk_type = self.disk_type.as_deref().unwrap_or_default();
+        if !flag.supported_by(disk_type) {
+            return Err(Error::UnsupportedByTable);
+        }
+        if flag == PartitionFlag::Boot && state {
+            return self.set_bootable(index);
+        }
+        self.check_limits(1, 0)?;
+
+        let (index, partition) = self
+            .partitions_enum()
+            .nth(index)
+            .expect("partition index out of bounds");
+        let flags = partition.flags();
+        if flags.contains(&flag) == state {
+            return Ok(());
+        }
+        let cleared = if disk_type == "gpt" && state && flag.is_gpt_type() {
+            flags.into_iter().filter(|f| f.is_gpt_type()).collect()
+        } else {
+            Vec::new()
+        };
+        let target = ChangeTarget::of(partition);
+
+        let partition = &mut self.partitions[index];
+        for flag in &cleared {
+            partition.push_flag(*flag, false);
+        }
+        partition.push_flag(flag, state);
+        self.push_change(InnerChange::SetFlag {
+            index,
+            target,
+            flag,
+            state,
+            cleared,
+        });
+        Ok(())
+    }
+
+    /// Set the GPT partition GUID (PARTUUID) of the partition at the given index, e.g. to a
+    /// [random](Guid::random) one so that a cloned partition can be told apart from the original.
+    ///
+    /// Entries in `/etc/fstab` and `/etc/crypttab` that refer to the old PARTUUID are reported by
+    /// [`config_impact`](Device::config_impact), but kernel command lines with `root=PARTUUID=`
+    /// aren't, and have to be updated separately.
+    ///
+    /// Fails with [`Error::UnsupportedByTable`] on tables other than GPT, and with
+    /// [`Error::DuplicateUuid`] if another partition on the device has the GUID. Nothing is
+    /// staged if the partition already has it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set_partition_uuid(&mut self, index: usize, uuid: Guid) -> Result<(), Error> {
+        self.check_editable()?;
+        if self.disk_type.as_deref() != Some("gpt") {
+            return Err(Error::UnsupportedByTable);
+        }
+        if uuid.is_nil() {
+            return Err(Error::NilUuid);
+        }
+        self.check_limits(1, 0)?;
+
+        let (index, partition) = self
+            .partitions_enum()
+            .nth(index)
+            .expect("partition index out of bounds");
+        if partition.uuid() == Some(uuid) {
+            return Ok(());
+        }
+        if let Some(other) = self.partitions().position(|p| p.uuid() == Some(uuid)) {
+            return Err(Error::DuplicateUuid(other));
+        }
+        let target = ChangeTarget::of(partition);
+
+        self.partitions[index].uuid.1.push(uuid);
+        self.push_change(InnerChange::SetUuid {
+            index,
+            target,
+            uuid,
+        });
+        Ok(())
+    }
+
+    /// Get the hybrid MBR entries of a GPT disk.
+    ///
+    /// This is empty for disks with a purely protective MBR.
+    pub fn hybrid_mbr(&self) -> &[HybridEntry] {
+        self.hybrid_mbr.1.last().unwrap_or(&self.hybrid_mbr.0)
+    }
+
+    /// Add an entry to the hybrid MBR, turning a protective MBR into a hybrid one if necessary.
+    pub fn add_hybrid_entry(&mut self, entry: HybridEntry) -> Result<(), Error> {
+        if self.hybrid_mbr().len() >= MAX_HYBRID_ENTRIES {
+            return Err(Error::TooManyHybridEntries);
+        }
+        let mut entries = self.hybrid_mbr().to_vec();
+        entries.push(entry);
+        self.stage_hybrid_mbr(entries)
+    }
+
+    /// Replace the hybrid MBR entry at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn update_hybrid_entry(&mut self, index: usize, entry: HybridEntry) -> Result<(), Error> {
+        let mut entries = self.hybrid_mbr().to_vec();
+        entries[index] = entry;
+        self.stage_hybrid_mbr(entries)
+    }
+
+    /// Remove the hybrid MBR entry at the given index. Removing the last entry leaves a purely
+    /// protective MBR.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn remove_hybrid_entry(&mut self, index: usize) -> Result<(), Error> {
+        let mut entries = self.hybrid_mbr().to_vec();
+        entries.remove(index);
+        self.stage_hybrid_mbr(entries)
+    }
+
+    fn stage_hybrid_mbr(&mut self, mut entries: Vec<HybridEntry>) -> Result<(), Error> {
+        if self.disk_type.as_deref() != Some("gpt") {
+            return Err(Error::UnsupportedByTable);
+        }
+        // MBR entries address sectors with 32 bits
+        let max = (self.raw.length() - 1).min(u32::MAX as u64) as i64;
+        if entries
+            .iter()
+            .any(|e| *e.bounds.start() < 1 || *e.bounds.end() > max || e.bounds.is_empty())
+        {
+            return Err(Error::OutOfBounds);
+        }
+
+        self.check_limits(1, 0)?;
+
+        entries.sort_by_key(|e| *e.bounds.start());
+        self.hybrid_mbr.1.push(entries);
+        self.push_change(InnerChange::HybridMbr);
+        Ok(())
+    }
+
+    /// Get the number of entries in the GPT partition entry array, used or not.
+    ///
+    /// This is `None` for non-GPT disks, or if the primary GPT couldn't be read directly.
+    pub fn gpt_entry_capacity(&self) -> Option<u32> {
+        self.gpt_entry_capacity
+            .1
+            .last()
+            .copied()
+            .or(self.gpt_entry_capacity.0)
+    }
+
+    /// Change the number of entries in the GPT partition entry array, like `sgdisk
+    /// --resize-table`. The usable area of the device shrinks or grows to fit the array.
+    ///
+    /// Most tools expect at least 128 entries, which is also the minimum that the UEFI
+    /// specification allows.
+    pub fn resize_gpt_entry_array(&mut self, capacity: u32) -> Result<(), Error> {
+        if self.gpt_entry_capacity().is_none() {
+            return Err(Error::UnsupportedByTable);
+        }
+        let count = self.partitions().count();
+        if (capacity as usize) < count {
+            return Err(Error::GptEntriesInUse(count));
+        }
+
+        let usable = self.gpt_usable_sectors(capacity);
+        if let Some(i) = self.partitions().position(|p| {
+            !usable.contains(p.bounds().start()) || !usable.contains(p.bounds().end())
+        }) {
+            return Err(Error::OverlapsExisting(i));
+        }
+        self.check_limits(1, 0)?;
+
+        self.gpt_entry_capacity.1.push(capacity);
+        self.push_change(InnerChange::GptEntryCapacity);
+        Ok(())
+    }
+
+    /// Stage a new change, which makes the undone ones impossible to redo.
+    fn push_change(&mut self, change: InnerChange) {
+        self.changes.stage(change);
+    }
+
+    /// Undo the changes staged after the first `n_changes`, so that something that fails partway
+    /// through staging leaves none of its changes staged or redoable.
+    fn roll_back_to(&mut self, n_changes: usize) {
+        if self.changes.len() > n_changes {
+            while self.changes.len() > n_changes {
+                self.undo_change();
+            }
+            self.changes.clear_undone();
+        }
+    }
+
+    /// Replace the partition table with an empty one of the given type, discarding all partitions
+    /// and any hybrid MBR.
+    fn stage_new_table(&mut self, table: PartitionTable) {
+        let change = self.replace_table(table);
+        self.push_change(change);
+    }
+
+    /// Replace the partition table the way [`stage_new_table`](Device::stage_new_table) does,
+    /// returning the change to stage.
+    fn replace_table(&mut self, table: PartitionTable) -> InnerChange {
+        let previous_type = self.disk_type.replace(table.name().into());
+        let previous_partitions = std::mem::take(&mut self.partitions);
+        let previous_hybrid_mbr = std::mem::take(&mut self.hybrid_mbr);
+        let previous_gpt_entry_capacity = std::mem::replace(
+            &mut self.gpt_entry_capacity,
+            // libparted always creates 128 entries
+            ((table == PartitionTable::Gpt).then_some(128), Vec::new()),
+        );
+
+        InnerChange::NewTable {
+            table,
+            previous_type,
+            previous_partitions,
+            previous_hybrid_mbr,
+            previous_gpt_entry_capacity,
+        }
+    }
+
+    /// Give the device a new, empty partition table of the given type, as `parted mklabel` does,
+    /// e.g. to start using a brand-new drive.
+    ///
+    /// Any partition table the device has, or a [superfloppy](Device::is_superfloppy)'s file
+    /// system, is replaced, and its partitions are discarded. Like other changes, this can be
+    /// undone until it's committed.
+    ///
+    /// Fails with [`Error::UnsupportedByTable`] for labels other than GPT and MBR, and if any
+    /// partition is in use, depending on the [safety level](SafetyLevel).
+    pub fn create_label(&mut self, label: DiskLabel) -> Result<(), Error> {
+        let table = label.table().ok_or(Error::UnsupportedByTable)?;
+        for index in 0..self.partitions().count() {
+            self.check_safety(index)?;
+        }
+        self.check_limits(1, 0)?;
+        self.stage_new_table(table);
+        Ok(())
+    }
+
+    /// Convert the device's MBR partition table to the given type, keeping every partition where
+    /// it is, as `gdisk` does when it loads an MBR disk. Only conversion to GPT is supported.
+    ///
+    /// This stages a new table and a new partition in place of each partition, with the same
+    /// bounds, number, file system and flags, so the data on them is left untouched. Extended
+    /// partitions are dropped, since GPT has no need for them, and logical partitions become
+    /// ordinary ones. The boot flag has no GPT equivalent and is dropped too.
+    ///
+    /// GPT stores its header and entry array at both the start and the end of the device, so the
+    /// partitions must leave room for them: usually 34 sectors at the start and 33 at the end.
+    ///
+    /// Fails with [`Error::UnsupportedByTable`] unless the device has an MBR and the label is GPT,
+    /// with [`Error::PendingChanges`] if there are pending changes, with
+    /// [`Error::NoRoomForGpt`] if a partition is where the GPT would be stored, and if any
+    /// partition is in use, depending on the [safety level](SafetyLevel).
+    pub fn convert_label(&mut self, label: DiskLabel) -> Result<(), Error> {
+        self.check_editable()?;
+        if self.disk_type.as_deref() != Some("msdos") || label.table() != Some(PartitionTable::Gpt)
+        {
+            return Err(Error::UnsupportedByTable);
+        }
+        if !self.changes.is_empty() {
+            return Err(Error::PendingChanges);
+        }
+
+        // libparted always creates 128 entries
+        let usable = self.gpt_usable_sectors(128);
+        let mut kept = Vec::new();
+        for (index, partition) in self.partitions().enumerate() {
+            if partition.partition_type() == PartitionType::Extended {
+                continue;
+            }
+            if !usable.contains(partition.bounds().start())
+                || !usable.contains(partition.bounds().end())
+            {
+                return Err(Error::NoRoomForGpt(index));
+            }
+            let flags = partition
+                .flags()
+                .into_iter()
+                .filter(|f| f.supported_by("gpt"))
+                .collect::<Vec<_>>();
+            kept.push((
+                partition.bounds().clone(),
+                partition.fs(),
+                partition.number.filter(|n| *n <= 128),
+                flags,
+            ));
+        }
+        for index in 0..self.partitions().count() {
+            self.check_safety(index)?;
+        }
+        let n_flags = kept.iter().map(|(.., flags)| flags.len()).sum::<usize>();
+        self.check_limits(1 + kept.len() + n_flags, 0)?;
+
+        self.stage_new_table(PartitionTable::Gpt);
+        for (bounds, fs, number, flags) in kept {
+            let index = self.partitions.len();
+            self.partitions.push(Partition::new(
+                "".into(),
+                bounds.clone(),
+                fs,
+                self.path_owned(),
+                self.raw.sector_size(),
+            ));
+            self.push_change(InnerChange::NewPartition {
+                name: "".into(),
+                fs,
+                bounds,
+                index,
+                number,
+                exact: true,
+                partition_type: PartitionType::Primary,
+            });
+            for flag in flags {
+                self.set_partition_flag(index, flag, true)
+                    .expect("GPT supports every flag but the boot flag");
+            }
+        }
+        Ok(())
+    }
+
+    /// Wipe the device and format it as a single partition spanning the whole device, with the
+    /// given file system and label, as is usual for USB sticks and memory cards.
+    ///
+    /// An MBR partition table is used for compatibility, unless the device is too large for one.
+    /// The file system is created after the partition table is committed, using the usual
+    /// `mkfs` tools, which must be installed.
+    ///
+    /// Fails if any partition is in use, depending on the [safety level](SafetyLevel).
+    pub fn quick_format(&mut self, fs: FileSystem, label: &str) -> Result<(), Error> {
+        for index in 0..self.partitions().count() {
+            self.check_safety(index)?;
+        }
+        self.check_limits(3, 0)?;
+
+        let length = self.raw.length();
+        let table = if length > u32::MAX as u64 {
+            PartitionTable::Gpt
+        } else {
+            PartitionTable::Msdos
+        };
+        let name = match table {
+            PartitionTable::Gpt => label,
+            PartitionTable::Msdos => "",
+        };
+        validate_name(name)?;
+
+        let n_changes = self.changes.len();
+        self.stage_new_table(table);
+
+        // start where the first partition may, and leave 1 MiB at the end for alignment and the
+        // backup GPT
+        let margin = (1024 * 1024 / self.sector_size()) as i64;
+        let bounds = *self.usable_sectors().start()..=length as i64 - 1 - margin;
+        if let Err(e) = self.new_partition(name.into(), Some(fs), bounds.clone()) {
+            self.roll_back_to(n_changes);
+            return Err(e);
+        }
+        self.stage_format(bounds, fs, label.into());
+        Ok(())
+    }
+
+    /// Give a [superfloppy](Device::is_superfloppy) a partition table of the given type.
+    ///
+    /// With [`SuperfloppyConversion::Preserve`], the file system is shrunk to make room for the
+    /// table and moved into a new partition that spans the rest of the device. This needs a file
+    /// system partner can shrink; ext4 and NTFS are shrunk with their own tools, which must be
+    /// installed, and copied into place, while FAT is shrunk and moved by libparted.
+    ///
+    /// Fails with [`Error::UnsupportedByTable`] if the device isn't a superfloppy, and if the file
+    /// system is in use, depending on the [safety level](SafetyLevel).
+    pub fn convert_superfloppy(
+        &mut self,
+        table: PartitionTable,
+        conversion: SuperfloppyConversion,
+    ) -> Result<(), Error> {
+        let Some(partition) = self.partitions().next().filter(|_| self.is_superfloppy()) else {
+            return Err(Error::UnsupportedByTable);
+        };
+        let from = partition.bounds().clone();
+        let fs = partition.fs();
+        self.check_safety(0)?;
+
+        if conversion == SuperfloppyConversion::Wipe {
+            self.check_limits(1, 0)?;
+            self.stage_new_table(table);
+            return Ok(());
+        }
+
+        let fs = fs.ok_or(Error::UnknownFileSystem)?;
+        let strategy = [ResizeStrategy::Libparted, ResizeStrategy::External]
+            .into_iter()
+            .find(|s| fs.resize_support(*s).shrink)
+            .ok_or(Error::ResizeUnsupported(fs))?;
+        self.check_limits(3, Geometry::new(&from, self.sector_size()).size_byte)?;
+
+        let n_changes = self.changes.len();
+        self.stage_new_table(table);
+        // leave at least 1 MiB at the end for the backup GPT, and end on a 1 MiB boundary so that
+        // libparted doesn't shrink the partition below the file system to align it
+        let mib = (1024 * 1024 / self.sector_size()) as i64;
+        let end = (self.raw.length() as i64 - mib) / mib * mib - 1;
+        let to = *self.usable_sectors().start()..=end;
+        if let Err(e) = self.new_partition("".into(), Some(fs), to.clone()) {
+            self.roll_back_to(n_changes);
+            return Err(e);
+        }
+        self.push_change(InnerChange::RelocateFs {
+            fs,
+            from,
+            to,
+            strategy,
+        });
+        Ok(())
+    }
+
+    /// Create a file system on the new partition at the given bounds after committing.
+    pub(crate) fn stage_format(
+        &mut self,
+        bounds: RangeInclusive<i64>,
+        fs: FileSystem,
+        label: Arc<str>,
+    ) {
+        self.push_change(InnerChange::Format { bounds, fs, label });
+    }
+
+    /// Get the external tools that committing the pending changes runs, such as `mkfs.ext4` or
+    /// `resize2fs`.
+    ///
+    /// Use [`Toolchain::check`] to find out whether they are available before committing.
+    pub fn required_tools(&self) -> Vec<&'static str> {
+        // changes before a new table are discarded when committing
+        let start = self
+            .changes
+            .iter()
+            .rposition(|c| matches!(c, InnerChange::NewTable { .. }))
+            .unwrap_or(0);
+        let mut tools = self.changes[start..]
+            .iter()
+            .flat_map(|c| match c {
+                InnerChange::Format { fs, .. } => vec![mkfs::program(*fs)],
+                InnerChange::ResizePartition {
+                    index,
+                    strategy: ResizeStrategy::External,
+                    ..
+                } => self.partitions[*index]
+                    .fs()
+                    .map(|fs| resize::programs(fs).to_vec())
+                    .unwrap_or_default(),
+                InnerChange::RelocateFs {
+                    fs,
+                    strategy: ResizeStrategy::External,
+                    ..
+                } => resize::programs(*fs).to_vec(),
+                _ => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        tools.sort_unstable();
+        tools.dedup();
+        tools
+    }
+
+    /// Get a reference to the partition at the given index that stays valid when it's renumbered
+    /// or the device gets another name on the next boot, or `None` if the partition isn't on disk
+    /// yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn partition_ref(&self, index: usize) -> Option<PartitionRef> {
+        let partition = self
+            .partitions()
+            .nth(index)
+            .expect("partition index out of bounds");
+        let key = match (partition.uuid.0, partition.number) {
+            (Some(uuid), _) => PartitionKey::Uuid(uuid),
+            (None, Some(number)) => PartitionKey::Number(number),
+            (None, None) => return None,
+        };
+        Some(PartitionRef {
+            disk: DeviceTarget::of(self),
+            partition: key,
+        })
+    }
+
+    /// Find the index of the partition the given reference refers to, if it's on this device.
+    pub fn find_partition(&self, reference: &PartitionRef) -> Option<usize> {
+        if !reference.disk.matches(self) {
+            return None;
+        }
+        self.partitions().position(|p| match reference.partition {
+            PartitionKey::Uuid(uuid) => p.uuid.0 == Some(uuid),
+            PartitionKey::Number(number) => p.number == Some(number),
+        })
+    }
+
+    /// Find the index of the partition with the given [number](Partition::number) on disk, e.g. 3
+    /// for `/dev/sda3`. Numbers mostly stay the same as other partitions are added and removed,
+    /// so they can be kept to address a partition across changes where indices can't.
+    ///
+    /// Partitions with a pending removal aren't found.
+    pub fn partition_by_number(&self, number: u32) -> Option<usize> {
+        self.partitions().position(|p| p.number == Some(number))
+    }
+
+    /// Record the pending layout as a plan that can be reviewed and applied later.
+    pub fn plan(&self) -> ChangePlan {
+        ChangePlan {
+            device: DeviceTarget::of(self),
+            source: DeviceSnapshot::on_disk(self).content_hash(),
+            target: self.snapshot(),
+        }
+    }
+
+    /// Stage the changes that bring the device to the layout of the given plan.
+    ///
+    /// Fails with [`Error::PlanTargetMismatch`] if the plan is for another device, as identified by
+    /// its [target](ChangePlan::device). Use [`ChangePlan::find_device`] to find the right one.
+    ///
+    /// Fails with [`Error::StalePlan`] if the on-disk layout of the device is not the one the plan
+    /// was made for, unless `force` is set, and always if the device has a different sector size
+    /// or length. Changes to the file system or type of an existing partition are not staged,
+    /// since partner can't make them.
+    ///
+    /// Fails with [`Error::UnsupportedByLibparted`] if the linked libparted can't make the plan's
+    /// partition table or file systems, as found by [`Capabilities::check_plan`].
+    ///
+    /// If staging any of the changes fails, none of them are left staged.
+    pub fn apply_plan(&mut self, plan: &ChangePlan, force: bool) -> Result<(), Error> {
+        if !plan.device.matches(self) {
+            return Err(Error::PlanTargetMismatch(plan.device.clone()));
+        }
+        if !force && DeviceSnapshot::on_disk(self).content_hash() != plan.source {
+            return Err(Error::StalePlan);
+        }
+        capabilities().check_plan(plan)?;
+        if plan.target.sector_size != self.sector_size() || plan.target.length != self.raw.length()
+        {
+            return Err(Error::StalePlan);
+        }
+
+        let n_changes = self.changes.len();
+        let result = self.stage_plan(plan);
+        if result.is_err() {
+            self.roll_back_to(n_changes);
+        }
+        result
+    }
+
+    /// Stage the changes of [`apply_plan`](Device::apply_plan), which rolls them back if any
+    /// fails.
+    fn stage_plan(&mut self, plan: &ChangePlan) -> Result<(), Error> {
+        let deltas = self.snapshot().diff(&plan.target);
+        let position = |device: &Self, start: i64| {
+            device
+                .partitions()
+                .position(|p| *p.bounds().start() == start)
+        };
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for delta in deltas {
+            match delta {
+                LayoutDelta::DiskType { after, .. } => {
+                    let table = match after.as_deref() {
+                        Some("gpt") => PartitionTable::Gpt,
+                        Some("msdos") => PartitionTable::Msdos,
+                        _ => return Err(Error::UnsupportedByTable),
+                    };
+                    for index in 0..self.partitions().count() {
+                        self.check_safety(index)?;
+                    }
+                    self.check_limits(1, 0)?;
+                    self.stage_new_table(table);
+                    // everything is recreated on the new table
+                    added = plan.target.partitions.clone();
+                    break;
+                }
+                LayoutDelta::Removed(before) => {
+                    if let Some(index) = position(self, before.start) {
+                        self.remove_partition(index)?;
+                    }
+                }
+                LayoutDelta::Changed { before, after } => changed.push((before, after)),
+                LayoutDelta::Added(after) => added.push(after),
+            }
+        }
+
+        for (before, after) in changed {
+            let Some(index) = position(self, before.start) else {
+                continue;
+            };
+            if (before.start, before.end) != (after.start, after.end) {
+                self.resize_partition(index, after.start..=after.end)?;
+            }
+            if before.name != after.name {
+                self.change_partition_name(index, after.name.as_str().into())?;
+            }
+            if after.bootable && !before.bootable {
+                self.set_bootable(index)?;
+            }
+        }
+
+        for after in added {
+            self.new_partition(
+                after.name.as_str().into(),
+                after.fs,
+                after.start..=after.end,
+            )?;
+            if after.bootable
+                && let Some(index) = position(self, after.start)
+            {
+                self.set_bootable(index)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Record the pending changes as the steps that staged them, so that they can be saved and
+    /// staged again later with [`import_plan`](Device::import_plan), e.g. on another machine with
+    /// an identical device.
+    ///
+    /// The automatic GPT repair of a [degraded](Device::is_degraded) device isn't recorded, since
+    /// it's staged whenever such a device is opened.
+    pub fn export_plan(&self) -> Plan {
+        // a new table takes the hybrid MBR and GPT entry array histories of the table it replaces
+        let histories = |position: usize| {
+            self.changes[position..]
+                .iter()
+                .find_map(|c| match c {
+                    InnerChange::NewTable {
+                        previous_hybrid_mbr,
+                        previous_gpt_entry_capacity,
+                        ..
+                    } => Some((&previous_hybrid_mbr.1, &previous_gpt_entry_capacity.1)),
+                    _ => None,
+                })
+                .unwrap_or((&self.hybrid_mbr.1, &self.gpt_entry_capacity.1))
+        };
+
+        let mut steps = Vec::new();
+        let (mut hybrid_mbrs, mut capacities) = (0, 0);
+        let mut changes = self.changes.iter().enumerate().peekable();
+        while let Some((position, change)) = changes.next() {
+            let step = match change {
+                InnerChange::Name { target, new, .. } => PlanStep::Rename {
+                    partition: target.start,
+                    name: new.to_string(),
+                },
+                InnerChange::NewPartition {
+                    name,
+                    fs,
+                    bounds,
+                    number,
+                    exact,
+                    partition_type,
+                    ..
+                } => PlanStep::NewPartition {
+                    name: name.to_string(),
+                    fs: *fs,
+                    start: *bounds.start(),
+                    end: *bounds.end(),
+                    number: *number,
+                    exact: *exact,
+                    partition_type: *partition_type,
+                },
+                InnerChange::RemovePartition { target, .. } => PlanStep::RemovePartition {
+                    partition: target.start,
+                },
+                InnerChange::ResizePartition {
+                    target,
+                    bounds,
+                    strategy,
+                    ..
+                } => PlanStep::ResizePartition {
+                    partition: target.start,
+                    start: *bounds.start(),
+                    end: *bounds.end(),
+                    strategy: *strategy,
+                },
+                InnerChange::MovePartition { target, to, .. } => PlanStep::MovePartition {
+                    partition: target.start,
+                    to: *to.start(),
+                },
+                InnerChange::SetBootable { target, .. } => PlanStep::SetBootable {
+                    partition: target.start,
+                },
+                InnerChange::SetFlag {
+                    target,
+                    flag,
+                    state,
+                    ..
+                } => PlanStep::SetFlag {
+                    partition: target.start,
+                    flag: *flag,
+                    state: *state,
+                },
+                InnerChange::SetUuid { target, uuid, .. } => PlanStep::SetUuid {
+                    partition: target.start,
+                    uuid: *uuid,
+                },
+                InnerChange::RepairGpt(_) => continue,
+                InnerChange::HybridMbr => {
+                    hybrid_mbrs += 1;
+                    PlanStep::HybridMbr {
+                        entries: histories(position)
+                            .0
+                            .get(hybrid_mbrs - 1)
+                            .cloned()
+                            .unwrap_or_default(),
+                    }
+                }
+                InnerChange::GptEntryCapacity => {
+                    capacities += 1;
+                    let Some(capacity) = histories(position).1.get(capacities - 1) else {
+                        continue;
+                    };
+                    PlanStep::GptEntryCapacity {
+                        capacity: *capacity,
+                    }
+                }
+                InnerChange::NewTable { table, .. } => {
+                    (hybrid_mbrs, capacities) = (0, 0);
+                    // a superfloppy conversion stages its new partition and the relocation right
+                    // after the table
+                    if matches!(
+                        self.changes.get(position + 2),
+                        Some(InnerChange::RelocateFs { .. })
+                    ) {
+                        changes.nth(1);
+                        PlanStep::ConvertSuperfloppy { table: *table }
+                    } else {
+                        PlanStep::NewTable { table: *table }
+                    }
+                }
+                InnerChange::Format { bounds, fs, label } => PlanStep::Format {
+                    start: *bounds.start(),
+                    end: *bounds.end(),
+                    fs: *fs,
+                    label: label.to_string(),
+                },
+                InnerChange::RelocateFs { .. } => continue,
+            };
+            steps.push(step);
+        }
+
+        Plan {
+            source: DeviceSnapshot::on_disk(self),
+            steps,
+        }
+    }
+
+    /// Stage the steps of a plan [exported](Device::export_plan) from this or an identical device.
+    ///
+    /// Fails with [`Error::PlanMismatch`] if the device's sector size, length, partition table type
+    /// or on-disk partition bounds differ from those the plan was made against, or if a step
+    /// refers to a partition the device doesn't have. A step that can't be staged fails with
+    /// [`Error::PlanStep`]. Either way, none of the plan's steps are left staged.
+    pub fn import_plan(&mut self, plan: &Plan) -> Result<(), Error> {
+        let disk = DeviceSnapshot::on_disk(self);
+        let source = &plan.source;
+        if source.sector_size != disk.sector_size {
+            return Err(PlanMismatch::SectorSize {
+                plan: source.sector_size,
+                device: disk.sector_size,
+            }
+            .into());
+        }
+        if source.length != disk.length {
+            return Err(PlanMismatch::Length {
+                plan: source.length,
+                device: disk.length,
+            }
+            .into());
+        }
+        if source.disk_type != disk.disk_type {
+            return Err(PlanMismatch::DiskType {
+                plan: source.disk_type.clone(),
+                device: disk.disk_type,
+            }
+            .into());
+        }
+        let bounds = |snapshot: &DeviceSnapshot| {
+            snapshot
+                .partitions
+                .iter()
+                .map(|p| p.start..=p.end)
+                .collect::<Vec<_>>()
+        };
+        let (expected, actual) = (bounds(source), bounds(&disk));
+        if let Some(missing) = expected.iter().find(|b| !actual.contains(b)) {
+            return Err(PlanMismatch::MissingPartition(missing.clone()).into());
+        }
+        if let Some(unexpected) = actual.iter().find(|b| !expected.contains(b)) {
+            return Err(PlanMismatch::UnexpectedPartition(unexpected.clone()).into());
+        }
+
+        let n_changes = self.changes.len();
+        let result = plan
+            .steps
+            .iter()
+            .enumerate()
+            .try_for_each(|(step, s)| self.import_step(step, s));
+        if result.is_err() {
+            self.roll_back_to(n_changes);
+        }
+        result
+    }
+
+    fn import_step(&mut self, step: usize, plan_step: &PlanStep) -> Result<(), Error> {
+        let find = |device: &Self, start: i64| {
+            device
+                .partitions()
+                .position(|p| *p.bounds().start() == start)
+                .ok_or(PlanMismatch::NoPartition { step, start })
+        };
+        let failed = |source| Error::PlanStep {
+            step,
+            source: Box::new(source),
+        };
+
+        match plan_step {
+            PlanStep::Rename { partition, name } => {
+                let index = find(self, *partition)?;
+                self.change_partition_name(index, name.as_str().into())
+            }
+            PlanStep::NewPartition {
+                name,
+                fs,
+                start,
+                end,
+                number,
+                exact,
+                partition_type,
+            } => {
+                if let Some(number) = number {
+                    self.check_partition_number(*number).map_err(failed)?;
+                }
+                // the bounds were aligned when the partition was planned, so aligning them to a
+                // sector keeps them as they are
+                let alignment = exact.then(|| self.sector_size());
+                self.stage_new_partition(
+                    name.as_str().into(),
+                    *fs,
+                    *start..=*end,
+                    *number,
+                    alignment,
+                    *partition_type == PartitionType::Extended,
+                )
+            }
+            PlanStep::RemovePartition { partition } => {
+                let index = find(self, *partition)?;
+                self.remove_partition(index)
+            }
+            PlanStep::ResizePartition {
+                partition,
+                start,
+                end,
+                strategy,
+            } => {
+                let index = find(self, *partition)?;
+                self.resize_partition_with(index, *start..=*end, *strategy)
+            }
+            PlanStep::MovePartition { partition, to } => {
+                let index = find(self, *partition)?;
+                let n_changes = self.changes.len();
+                self.realign_partition(index).map_err(failed)?;
+                let actual = match self.changes.last() {
+                    Some(InnerChange::MovePartition { to: moved, .. })
+                        if self.changes.len() > n_changes =>
+                    {
+                        *moved.start()
+                    }
+                    _ => *partition,
+                };
+                if actual != *to {
+                    return Err(PlanMismatch::MoveTarget {
+                        step,
+                        expected: *to,
+                        actual,
+                    }
+                    .into());
+                }
+                Ok(())
+            }
+            PlanStep::SetBootable { partition } => {
+                let index = find(self, *partition)?;
+                self.set_bootable(index)
+            }
+            PlanStep::SetFlag {
+                partition,
+                flag,
+                state,
+            } => {
+                let index = find(self, *partition)?;
+                self.set_partition_flag(index, *flag, *state)
+            }
+            PlanStep::SetUuid { partition, uuid } => {
+                let index = find(self, *partition)?;
+                self.set_partition_uuid(index, *uuid)
+            }
+            PlanStep::HybridMbr { entries } => self.stage_hybrid_mbr(entries.clone()),
+            PlanStep::GptEntryCapacity { capacity } => self.resize_gpt_entry_array(*capacity),
+            PlanStep::NewTable { table } => {
+                for index in 0..self.partitions().count() {
+                    self.check_safety(index).map_err(failed)?;
+                }
+                self.check_limits(1, 0).map_err(failed)?;
+                self.stage_new_table(*table);
+                Ok(())
+            }
+            PlanStep::ConvertSuperfloppy { table } => {
+                self.convert_superfloppy(*table, SuperfloppyConversion::Preserve)
+            }
+            PlanStep::Format {
+                start,
+                end,
+                fs,
+                label,
+            } => {
+                self.check_limits(1, 0).map_err(failed)?;
+                self.stage_format(*start..=*end, *fs, label.as_str().into());
+                Ok(())
+            }
+        }
+        .map_err(|e| match e {
+            e @ (Error::PlanMismatch(_) | Error::PlanStep { .. }) => e,
+            e => failed(e),
+        })
+    }
+
+    /// Get the index the partition at the given index in `self.partitions` has in
+    /// [`partitions`](Device::partitions). A removed partition gets the index it would have if the
+    /// removal were undone.
+    fn get_public_index(&self, index: usize) -> usize {
+        partition::public_index(self.partitions.iter().map(|p| p.kind), index)
+    }
+
+    /// Undo the last change, returning it as [`changes`](Device::changes) described it.
+    pub fn undo_change(&mut self) -> Option<Change> {
+        // described before anything is undone, so the indices are still the ones it was shown with
+        let undone = self.changes().pop()?;
+        let mut change = self.changes.unstage()?;
+        let redo = match &mut change {
+            InnerChange::Name { partition, .. } => {
+                self.partitions[*partition].name.1.pop();
+                Redo::Change(change)
+            }
+            InnerChange::NewPartition { index, .. } => {
+                assert!(
+                    self.partitions[*index].kind == PartitionKind::Virtual,
+                    "undo tried to remove a real partition"
+                );
+                self.partitions.remove(*index);
+                Redo::Change(change)
+            }
+            InnerChange::RemovePartition { index, removed, .. } => {
+                if let Some(removed) = removed.take() {
+                    self.partitions.insert(*index, *removed);
+                } else {
+                    assert!(
+                        self.partitions[*index].kind == PartitionKind::Hidden,
+                        "undo tried to set a virtual partition to real"
+                    );
+                    self.partitions[*index].kind = PartitionKind::Real;
+                }
+                Redo::Change(change)
+            }
+            InnerChange::ResizePartition { index, .. }
+            | InnerChange::MovePartition { index, .. } => {
+                self.partitions[*index].bounds.1.pop();
+                Redo::Change(change)
+            }
+            InnerChange::SetBootable { index, cleared, .. } => {
+                self.partitions[*index].bootable.1.pop();
+                for (i, _) in cleared {
+                    self.partitions[*i].bootable.1.pop();
+                }
+                Redo::Change(change)
+            }
+            InnerChange::SetFlag {
+                index,
+                flag,
+                cleared,
+                ..
+            } => {
+                self.partitions[*index].pop_flag(*flag);
+                for flag in cleared {
+                    self.partitions[*index].pop_flag(*flag);
+                }
+                Redo::Change(change)
+            }
+            InnerChange::SetUuid { index, .. } => {
+                self.partitions[*index].uuid.1.pop();
+                Redo::Change(change)
+            }
+            InnerChange::RepairGpt(_)
+            | InnerChange::Format { .. }
+            | InnerChange::RelocateFs { .. } => Redo::Change(change),
+            InnerChange::HybridMbr => Redo::HybridMbr(self.hybrid_mbr.1.pop().unwrap_or_default()),
+            InnerChange::GptEntryCapacity => {
+                Redo::GptEntryCapacity(self.gpt_entry_capacity.1.pop())
+            }
+            InnerChange::NewTable {
+                table,
+                previous_type,
+                previous_partitions,
+                previous_hybrid_mbr,
+                previous_gpt_entry_capacity,
+            } => {
+                self.disk_type = previous_type.take();
+                self.partitions = std::mem::take(previous_partitions);
+                self.hybrid_mbr = std::mem::take(previous_hybrid_mbr);
+                self.gpt_entry_capacity = std::mem::take(previous_gpt_entry_capacity);
+                Redo::NewTable(*table)
+            }
+        };
+        self.changes.push_undone(redo);
+        Some(undone)
+    }
+
+    /// Get the number of undone changes that can be [redone](Device::redo_change).
+    pub fn n_redoable(&self) -> usize {
+        self.changes.n_undone()
+    }
+
+    /// Redo the last undone change.
+    ///
+    /// Undone changes can be redone until a new change is staged or the device is committed.
+    pub fn redo_change(&mut self) -> Option<Change> {
+        let redo = self.changes.take_undone()?;
+        self.reapply(redo);
+        self.changes().pop()
+    }
+
+    fn reapply(&mut self, redo: Redo) {
+        let change = match redo {
+            Redo::NewTable(table) => self.replace_table(table),
+            Redo::HybridMbr(entries) => {
+                self.hybrid_mbr.1.push(entries);
+                InnerChange::HybridMbr
+            }
+            Redo::GptEntryCapacity(capacity) => {
+                self.gpt_entry_capacity.1.extend(capacity);
+                InnerChange::GptEntryCapacity
+            }
+            Redo::Change(mut change) => {
+                match &mut change {
+                    InnerChange::Name { partition, new, .. } => {
+                        self.partitions[*partition].name.1.push(new.clone());
+                    }
+                    InnerChange::NewPartition {
+                        name,
+                        fs,
+                        bounds,
+                        index,
+                        partition_type,
+                        ..
+                    } => {
+                        let mut partition = Partition::new(
+                            name.clone(),
+                            bounds.clone(),
+                            *fs,
+                            self.path_owned(),
+                            self.sector_size(),
+                        );
+                        partition.partition_type = *partition_type;
+                        self.partitions.insert(*index, partition);
+                    }
+                    InnerChange::ResizePartition { index, bounds, .. }
+                    | InnerChange::MovePartition {
+                        index, to: bounds, ..
+                    } => {
+                        self.partitions[*index].bounds.1.push(bounds.clone());
+                    }
+                    InnerChange::RemovePartition { index, removed, .. } => {
+                        if self.partitions[*index].kind == PartitionKind::Virtual {
+                            *removed = Some(Box::new(self.partitions.remove(*index)));
+                        } else {
+                            self.partitions[*index].kind = PartitionKind::Hidden;
+                        }
+                    }
+                    InnerChange::SetBootable { index, cleared, .. } => {
+                        for (i, _) in cleared.iter() {
+                            self.partitions[*i].bootable.1.push(false);
+                        }
+                        self.partitions[*index].bootable.1.push(true);
+                    }
+                    InnerChange::SetFlag {
+                        index,
+                        flag,
+                        state,
+                        cleared,
+                        ..
+                    } => {
+                        for flag in cleared.iter() {
+                            self.partitions[*index].push_flag(*flag, false);
+                        }
+                        self.partitions[*index].push_flag(*flag, *state);
+                    }
+                    InnerChange::SetUuid { index, uuid, .. } => {
+                        self.partitions[*index].uuid.1.push(*uuid);
+                    }
+                    _ => {}
+                }
+                change
+            }
+        };
+        self.changes.restage(change);
+    }
+
+    /// Undo all pending changes at once. Unlike those undone one at a time, these can't be
+    /// [redone](Device::redo_change).
+    pub fn undo_all_changes(&mut self) {
+        // a new table replaces the device's state, so the first one holds the original state
+        if let Some(position) = self
+            .changes
+            .iter()
+            .position(|c| matches!(c, InnerChange::NewTable { .. }))
+        {
+            self.changes.truncate(position + 1);
+            self.undo_change();
+        }
+        self.changes.clear();
+        self.hybrid_mbr.1.clear();
+        self.gpt_entry_capacity.1.clear();
+
+        for partition in &mut self.partitions {
+            partition.undo_all_changes();
+        }
+
+        self.partitions.retain(|p| p.kind != PartitionKind::Virtual);
+        self.partitions
+            .iter_mut()
+            .filter(|p| p.kind == PartitionKind::Hidden)
+            .for_each(|p| p.kind = PartitionKind::Real);
+    }
+
+    /// Commit all changes to the device.
+    ///
+    /// This is blocking and will likely take a while.
+    ///
+    /// libparted replaces the MBR of GPT disks with a protective one, so any
+    /// [hybrid MBR](Device::hybrid_mbr) is written back afterwards.
+    pub fn commit(&mut self) -> Result<(), CommitError> {
+        self.commit_with(&CommitOptions::default()).map(|_| ())
+    }
+
+    /// Commit all changes to the device with the given options.
+    ///
+    /// If [verification](CommitOptions::verify) is enabled, the partition table is re-read
+    /// afterwards and compared against the layout that was expected before committing.
+    ///
+    /// If the commit fails, the error says which change or phase failed, and whether the device
+    /// was already written to.
+    pub fn commit_with(
+        &mut self,
+        options: &CommitOptions,
+    ) -> Result<Option<VerificationReport>, CommitError> {
+        self.commit_with_progress(options, |_| {})
+    }
+
+    /// Like [`commit_with`](Device::commit_with), but reports progress through each
+    /// [phase](CommitPhase) of the commit and each [change](CommitProgress::change) in it, with an
+    /// estimate of how long the rest will take.
+    ///
+    /// File systems resized by libparted report their progress through its timer API, so the
+    /// slowest changes don't stall the progress bar.
+    pub fn commit_with_progress(
+        &mut self,
+        options: &CommitOptions,
+        mut progress: impl FnMut(CommitProgress),
+    ) -> Result<Option<VerificationReport>, CommitError> {
+        self.check_destruction(options)?;
+        options.toolchain.check(&self.required_tools())?;
+        let changes = self.changes();
+        // the repair is always the first change, and happens before anything else
+        let step = std::cell::Cell::new(CommitStep {
+            phase: CommitPhase::Table,
+            change: matches!(self.changes.first(), Some(InnerChange::RepairGpt(_))).then_some(0),
+            flushed: false,
+        });
+        self.apply_changes(options, &step, |p| {
+            step.set(CommitStep {
+                phase: p.phase,
+                // a finished change is no longer to blame
+                change: p.change.filter(|c| c.fraction < 1.0).map(|c| c.position),
+                ..step.get()
+            });
+            progress(p);
+        })
+        .map_err(|e| step.get().error(e, &changes))
+    }
+
+    /// Do the work of [`commit_with_progress`](Device::commit_with_progress), keeping track of
+    /// whether anything was written to the device in `step`.
+    fn apply_changes(
+        &mut self,
+        options: &CommitOptions,
+        step: &std::cell::Cell<CommitStep>,
+        mut progress: impl FnMut(CommitProgress),
+    ) -> std::io::Result<Option<VerificationReport>> {
+        let flushed = || {
+            step.set(CommitStep {
+                flushed: true,
+                ..step.get()
+            })
+        };
+        self.changes.clear_undone();
+        self.created.clear();
+
+        let expected = options
+            .verify
+            .then(|| self.partitions().cloned().collect::<Vec<_>>());
+        // changes are reported by their position before any of them are taken off the queue
+        let n_changes = self.changes.len();
+        let mut offset = 0;
+
+        // the repair has to happen before libparted can open the disk, and is always the first
+        // change since it's only ever staged when the device is opened
+        if let Some(InnerChange::RepairGpt(table)) = self.changes.first() {
+            let mut file = std::fs::OpenOptions::new().write(true).open(&self.path)?;
+            table.write_primary(&mut file, self.sector_size())?;
+            file.sync_all()?;
+            step.set(CommitStep {
+                change: None,
+                flushed: true,
+                ..step.get()
+            });
+            self.changes.remove(0);
+            self.degraded = false;
+            offset += 1;
+        }
+
+        // changes before a new table only affect the table it replaces
+        let new_table = self
+            .changes
+            .iter()
+            .rposition(|c| matches!(c, InnerChange::NewTable { .. }));
+        let offset = offset + new_table.map_or(0, |position| position + 1);
+        let table = new_table.map(|position| {
+            let Some(InnerChange::NewTable { table, .. }) =
+                self.changes.drain(..=position).next_back()
+            else {
+                unreachable!("position is of a new table");
+            };
+            table
+        });
+
+        let sector_size = self.sector_size();
+        let move_bytes = self
+            .changes
+            .iter()
+            .filter_map(|c| match c {
+                InnerChange::MovePartition { from, .. } => {
+                    Some((from.end() - from.start() + 1) as u64 * sector_size)
+                }
+                _ => None,
+            })
+            .sum::<u64>();
+        let n_formats = self
+            .changes
+            .iter()
+            .filter(|c| matches!(c, InnerChange::Format { .. }))
+            .count();
+        let relocation = self.changes.iter().enumerate().find_map(|(i, c)| match c {
+            InnerChange::RelocateFs {
+                fs,
+                from,
+                to,
+                strategy,
+            } => Some((offset + i, *fs, from.clone(), to.clone(), *strategy)),
+            _ => None,
+        });
+        let relocate_bytes = relocation
+            .as_ref()
+            .map_or(0, |(.., to, _)| Geometry::new(to, sector_size).size_byte);
+        let estimator = commit::Estimator::new(
+            n_changes,
+            relocate_bytes,
+            move_bytes,
+            n_formats,
+            options.verify,
+        );
+
+        // a superfloppy's file system starts where the new table goes, so it has to be moved out
+        // of the way first
+        if let Some((position, fs, from, to, strategy)) = relocation {
+            let phase = CommitPhase::Relocation;
+            progress(estimator.change_progress(phase, 0.0, position, 0.0));
+            flushed();
+            if strategy == ResizeStrategy::Libparted {
+                resize::resize_with_libparted(&self.path, fs, &from, &to, |f| {
+                    progress(estimator.change_progress(phase, f, position, f));
+                })?;
+            } else {
+                for output in resize::resize(&options.toolchain, &self.path, fs, relocate_bytes)? {
+                    progress(CommitProgress {
+                        output: Some(Arc::new(output)),
+                        ..estimator.change_progress(phase, 0.0, position, 0.0)
+                    });
+                }
+                let region = |bounds: &RangeInclusive<i64>| copy::Region {
+                    path: &self.path,
+                    offset: *bounds.start() as u64 * sector_size,
+                };
+                copy::copy(
+                    region(&from),
+                    region(&to),
+                    relocate_bytes,
+                    &copy::CopyOptions::new(),
+                    |p| {
+                        let f = p.fraction();
+                        progress(estimator.change_progress(phase, f, position, f));
+                        std::ops::ControlFlow::Continue(())
+                    },
+                )
+                .map_err(|e| match e {
+                    copy::CopyError::Io(e) => e,
+                    e => std::io::Error::other(e),
+                })?;
+            }
+            progress(estimator.change_progress(phase, 1.0, position, 1.0));
+        }
+
+        // read from the staged partitions before libparted borrows the device
+        let numbered = self
+            .changes
+            .iter()
+            .filter_map(|c| match c {
+                // skip partitions that were removed again
+                InnerChange::NewPartition {
+                    bounds,
+                    number: Some(number),
+                    ..
+                } if self.partitions().any(|p| p.bounds() == bounds) => {
+                    Some((bounds.clone(), *number))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let created = self
+            .changes
+            .iter()
+            .filter_map(|c| match c {
+                InnerChange::NewPartition { bounds, .. }
+                    if self.partitions().any(|p| p.bounds() == bounds) =>
+                {
+                    Some(bounds.clone())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let uuids = self
+            .partitions()
+            .filter(|p| !p.uuid.1.is_empty())
+            .filter_map(|p| Some((p.bounds().clone(), p.uuid()?)))
+            .collect::<Vec<_>>();
+
+        let mut disk = if let Some(table) = table {
+            let disk_type = libparted::DiskType::get(table.name()).ok_or_else(|| {
+                std::io::Error::other(format!("libparted doesn't support {table}"))
+            })?;
+            libparted::Disk::new_fresh(&mut self.raw, disk_type)?
+        } else {
+            libparted::Disk::new(&mut self.raw)?
+        };
+        progress(estimator.progress(CommitPhase::Table, 0.0));
+
+        // file systems have to be shrunk before their partitions, and grown after. libparted
+        // shrinks happen as the changes are applied, since they don't need the new table
+        let (shrinks, grows): (Vec<_>, Vec<_>) = self
+            .changes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match c {
+                InnerChange::ResizePartition {
+                    index,
+                    from,
+                    bounds,
+                    strategy: ResizeStrategy::External,
+                    fs: Some(fs),
+                    ..
+                } => {
+                    let size = (bounds.end() - bounds.start() + 1) as u64 * sector_size;
+                    Some((
+                        offset + i,
+                        self.partitions[*index].path.clone()?,
+                        *fs,
+                        size,
+                        bounds.end() < from.end(),
+                    ))
+                }
+                _ => None,
+            })
+            .partition(|(.., shrink)| *shrink);
+        let parted_grows = self
+            .changes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| match c {
+                InnerChange::ResizePartition {
+                    from,
+                    bounds,
+                    strategy: ResizeStrategy::Libparted,
+                    fs: Some(fs),
+                    ..
+                } if bounds.end() - bounds.start() > from.end() - from.start() => {
+                    Some((offset + i, *fs, from.clone(), bounds.clone()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let resize_fs =
+            |(position, path, fs, size, _): (usize, Arc<Path>, FileSystem, u64, bool),
+             phase_fraction: f64,
+             progress: &mut dyn FnMut(CommitProgress)| {
+                let phase = CommitPhase::Table;
+                progress(estimator.change_progress(phase, phase_fraction, position, 0.0));
+                for output in resize::resize(&options.toolchain, &path, fs, size)? {
+                    progress(CommitProgress {
+                        output: Some(Arc::new(output)),
+                        ..estimator.change_progress(phase, phase_fraction, position, 0.0)
+                    });
+                }
+                progress(estimator.change_progress(phase, phase_fraction, position, 1.0));
+                std::io::Result::Ok(())
+            };
+        for shrink in shrinks {
+            flushed();
+            resize_fs(shrink, 0.0, &mut progress)?;
+        }
+
+        let mut formats = Vec::new();
+        let mut moves = Vec::new();
+        let n_applied = self.changes.len();
+        for (i, change) in self.changes.drain(..).enumerate() {
+            let position = offset + i;
+            if let InnerChange::Format { bounds, fs, label } = change {
+                formats.push((position, bounds, fs, label));
+                continue;
+            }
+            if let InnerChange::MovePartition { from, to, .. } = &change {
+                moves.push((position, from.clone(), to.clone()));
+            }
+            let phase_fraction = i as f64 / n_applied as f64;
+            progress(estimator.change_progress(CommitPhase::Table, phase_fraction, position, 0.0));
+            change.apply(&mut disk, |f| {
+                progress(estimator.change_progress(
+                    CommitPhase::Table,
+                    phase_fraction,
+                    position,
+                    f,
+                ));
+            })?;
+            progress(estimator.change_progress(CommitPhase::Table, phase_fraction, position, 1.0));
+        }
+
+        progress(estimator.progress(CommitPhase::Table, 1.0));
+
+        // moved contents are copied before the table pointing at them is written, so a copy that
+        // fails leaves the partitions where they were on disk
+        if !moves.is_empty() {
+            flushed();
+        }
+        let mut moved = 0;
+        for (position, from, to) in moves {
+            let region = |bounds: &RangeInclusive<i64>| copy::Region {
+                path: &self.path,
+                offset: *bounds.start() as u64 * sector_size,
+            };
+            let length = (from.end() - from.start() + 1) as u64 * sector_size;
+            copy::copy(
+                region(&from),
+                region(&to),
+                length,
+                &copy::CopyOptions::new(),
+                |p| {
+                    progress(estimator.change_progress(
+                        CommitPhase::Moves,
+                        (moved + p.copied) as f64 / move_bytes as f64,
+                        position,
+                        p.fraction(),
+                    ));
+                    std::ops::ControlFlow::Continue(())
+                },
+            )
+            .map_err(|e| match e {
+                copy::CopyError::Io(e) => e,
+                e => std::io::Error::other(e),
+            })?;
+            moved += length;
+        }
+
+        disk.commit()?;
+        drop(disk);
+        flushed();
+        self.created = created;
+        for grow in grows {
+            resize_fs(grow, 1.0, &mut progress)?;
+        }
+        for (position, fs, from, to) in parted_grows {
+            resize::resize_with_libparted(&self.path, fs, &from, &to, |f| {
+                progress(estimator.change_progress(CommitPhase::Table, 1.0, position, f));
+            })?;
+        }
+
+        let hybrid_mbr = self.hybrid_mbr().to_vec();
+        if !hybrid_mbr.is_empty() || !self.hybrid_mbr.1.is_empty() {
+            let mut file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.path)?;
+            mbr::write_hybrid(&mut file, &hybrid_mbr, self.raw.length())?;
+            file.sync_all()?;
+        }
+        self.hybrid_mbr = (hybrid_mbr, Vec::new());
+
+        let gpt_entry_capacity = self.gpt_entry_capacity();
+        if let Some(capacity) = gpt_entry_capacity
+            && !self.gpt_entry_capacity.1.is_empty()
+        {
+            let mut file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.path)?;
+            let mut table = gpt::Table::read_at(&mut file, sector_size, 1)?;
+            table.set_entry_capacity(capacity, sector_size, self.raw.length())?;
+            table.write_primary(&mut file, sector_size)?;
+            table.write_backup(&mut file, sector_size, self.raw.length())?;
+            file.sync_all()?;
+        }
+        self.gpt_entry_capacity = (gpt_entry_capacity, Vec::new());
+
+        // libparted always uses the first free entry and has no way to set PARTUUIDs, so the
+        // entries are edited afterwards
+        let edits = numbered
+            .into_iter()
+            .map(|(bounds, number)| (bounds, gpt::EntryEdit::Number(number)))
+            .chain(
+                uuids
+                    .into_iter()
+                    .map(|(bounds, uuid)| (bounds, gpt::EntryEdit::Uuid(uuid))),
+            )
+            .collect::<Vec<_>>();
+        if !edits.is_empty() {
+            let mut file = std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.path)?;
+            gpt::rewrite_entries(&mut file, sector_size, self.raw.length(), &edits)?;
+        }
+
+        if !formats.is_empty() {
+            progress(estimator.progress(CommitPhase::Mkfs, 0.0));
+            let (_, partitions) = read_partitions(&mut self.raw, &Self::get_mounts()?)?;
+            for (i, (position, bounds, fs, label)) in formats.into_iter().enumerate() {
+                let phase_fraction = |i: usize| i as f64 / n_formats as f64;
+                progress(estimator.change_progress(
+                    CommitPhase::Mkfs,
+                    phase_fraction(i),
+                    position,
+                    0.0,
+                ));
+                let path = partitions
+                    .iter()
+                    .find(|p| commit::overlaps(p.bounds(), &bounds))
+                    .and_then(|p| p.path.clone())
+                    .ok_or_else(|| std::io::Error::other("formatted partition wasn't created"))?;
+                wait_for_path(&path)?;
+                let output = mkfs::mkfs(&options.toolchain, &path, fs, &label)?;
+                progress(CommitProgress {
+                    output: Some(Arc::new(output)),
+                    ..estimator.change_progress(
+                        CommitPhase::Mkfs,
+                        phase_fraction(i + 1),
+                        position,
+                        1.0,
+                    )
+                });
+            }
+            progress(estimator.progress(CommitPhase::Mkfs, 1.0));
+        }
+
+        let Some(expected) = expected else {
+            return Ok(None);
+        };
+        progress(estimator.progress(CommitPhase::Verification, 0.0));
+        let (_, actual) = read_partitions(&mut self.raw, &Self::get_mounts()?)?;
+        let report = commit::verify(expected, actual, options.mount_check);
+        progress(estimator.progress(CommitPhase::Verification, 1.0));
+        Ok(Some(report))
+    }
+
+    /// Get the stable `/dev/disk` names of the partitions created by the last commit, in the order
+    /// they're on the device, waiting up to the given timeout for udev to create them.
+    ///
+    /// Provisioning code can use these as soon as this returns, rather than retrying until they
+    /// appear.
+    pub fn stable_names(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> std::io::Result<Vec<StableNames>> {
+        if self.created.is_empty() {
+            return Ok(Vec::new());
+        }
+        let deadline = std::time::Instant::now() + timeout;
+        let (disk_type, partitions) = read_partitions(&mut self.raw, &Self::get_mounts()?)?;
+        let gpt = disk_type.as_deref() == Some("gpt");
+        partitions
+            .iter()
+            .filter(|p| self.created.contains(p.bounds()))
+            .map(|p| {
+                let path = p
+                    .path
+                    .as_deref()
+                    .ok_or_else(|| std::io::Error::other("created partition has no device node"))?;
+                let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                links::wait(path, gpt && !p.name().is_empty(), remaining)
+            })
+            .collect()
+    }
+
+    /// Overwrite the device's [free regions](Device::free_regions), e.g. to get rid of what's left
+    /// of deleted partitions, without touching the partitions or the partition table.
+    ///
+    /// This writes to the device directly, so it fails if there are pending changes, which could
+    /// make the free regions differ from those on disk. It also fails on devices without an
+    /// editable partition table, whose data may well be in what looks like free space.
+    pub fn wipe_free_space(
+        &mut self,
+        method: WipeMethod,
+        progress: impl FnMut(WipeProgress),
+    ) -> std::io::Result<()> {
+        if !self.is_editable() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "free space can only be wiped on GPT and MBR disks",
+            ));
+        }
+        if !self.changes.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "free space can't be wiped with changes pending",
+            ));
+        }
+        let sector_size = self.sector_size();
+        let regions = self
+            .free_regions()
+            .iter()
+            .map(|region| {
+                let bounds = region.bounds();
+                (
+                    *bounds.start() as u64 * sector_size,
+                    (bounds.end() - bounds.start() + 1) as u64 * sector_size,
+                )
+            })
+            .collect::<Vec<_>>();
+        wipe::wipe(&self.path, &regions, method, progress)
+    }
+
+    /// Give the partition at the given index a note, e.g. "backup target, do not delete", or
+    /// remove its note if it's `None`.
+    ///
+    /// Notes are written right away rather than staged, and can only be given to GPT partitions
+    /// that are on disk, since they're kept by PARTUUID. Keeping them in the
+    /// [GPT name field](NoteStore::GptName) also writes the partition table, so it fails if there
+    /// are pending changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set_partition_note(
+        &mut self,
+        index: usize,
+        note: Option<&str>,
+        store: &NoteStore,
+    ) -> std::io::Result<()> {
+        let note = note.filter(|note| !note.is_empty());
+        let (index, partition) = self
+            .partitions_enum()
+            .nth(index)
+            .expect("partition index out of bounds");
+        let (Some(uuid), Some(number), PartitionKind::Real) =
+            (partition.uuid.0, partition.number, partition.kind)
+        else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "only GPT partitions on disk can have notes",
+            ));
+        };
+
+        match store {
+            NoteStore::GptName => self.edit_gpt(|table| table.set_note(number, note))?,
+            NoteStore::Sidecar(path) => notes::write_sidecar(path, uuid, note)?,
+        }
+        self.partitions[index].note = note.map(Arc::from);
+        Ok(())
+    }
+
+    /// Edit the GPT on disk in place, writing both copies, for metadata that isn't staged.
+    ///
+    /// Fails if there are pending changes, since committing them could write over the edit.
+    pub(crate) fn edit_gpt(
+        &self,
+        edit: impl FnOnce(&mut gpt::Table) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        if !self.changes.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "the partition table can't be edited in place with changes pending",
+            ));
+        }
+        let sector_size = self.sector_size();
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.path)?;
+        let mut table = gpt::Table::read_at(&mut file, sector_size, 1)?;
+        edit(&mut table)?;
+        table.write_primary(&mut file, sector_size)?;
+        table.write_backup(&mut file, sector_size, self.raw.length())?;
+        file.sync_all()
+    }
+
+    /// Read the notes of the device's partitions from a sidecar file written with
+    /// [`NoteStore::Sidecar`]. Notes in the file replace those kept in the GPT name field.
+    pub fn load_notes(&mut self, sidecar: impl AsRef<Path>) -> std::io::Result<()> {
+        let notes = notes::read_sidecar(sidecar.as_ref())?;
+        for partition in &mut self.partitions {
+            if let Some(note) = partition.uuid.0.and_then(|uuid| notes.get(&uuid)) {
+                partition.note = Some(note.as_str().into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the device's write caches, e.g. after writing to it directly with
+    /// [`copy`](crate::copy::copy).
+    pub fn sync(&mut self) -> std::io::Result<()> {
+        self.raw.open()?;
+        let result = self.raw.sync();
+        // the sync error is more useful than the close error
+        let closed = if unsafe { libparted_sys::ped_device_close(self.raw.ped_device()) } == 0 {
+            Err(std::io::Error::other("libparted couldn't close the device"))
+        } else {
+            Ok(())
+        };
+        result.and(closed)
+    }
+
+    /// Flush the device's write caches and close it.
+    ///
+    /// Dropping a device closes it too, but ignores any errors. Uncommitted changes are discarded
+    /// either way.
+    pub fn close(mut self) -> std::io::Result<()> {
+        self.sync()
+    }
+
+    /// Get a read-only handle to the device that can be used from other threads, e.g. to keep
+    /// mount and usage information fresh while this device is being edited.
+    pub fn try_clone_handle(&self) -> std::io::Result<DeviceHandle> {
+        DeviceHandle::new(self)
+    }
+
+    /// Update the mount points and used space of the device's partitions from statuses read
+    /// through a [handle](Device::try_clone_handle). Partitions without a status are left as they
+    /// are.
+    pub fn update_status(&mut self, statuses: &[PartitionStatus]) {
+        for partition in &mut self.partitions {
+            if let Some(status) = statuses
+                .iter()
+                .find(|s| partition.path.as_ref() == Some(&s.path))
+            {
+                partition.set_status(status);
+            }
+        }
+    }
+
+    /// Get the device's IO counters.
+    pub fn io_stats(&self) -> std::io::Result<IoStats> {
+        stats::read(&self.path)
+    }
+
+    /// Measure how busy the device is over the given interval, from 0 to 1, blocking for that
+    /// long.
+    ///
+    /// Committing to a busy device is slow, and the longer it takes the more there is to lose if
+    /// it's interrupted, so this is worth checking first.
+    pub fn utilization(&self, interval: std::time::Duration) -> std::io::Result<f64> {
+        let before = self.io_stats()?;
+        let start = std::time::Instant::now();
+        std::thread::sleep(interval);
+        let after = self.io_stats()?;
+        Ok(before.utilization(&after, start.elapsed()))
+    }
+
+    /// Whether the kernel has the device read-only, e.g. a CD-ROM or an SD card with its write
+    /// protect switch set. No changes can be staged on such devices.
+    pub fn is_read_only(&self) -> bool {
+        power::is_read_only(&self.path)
+    }
+
+    /// Whether the device has removable media, e.g. a USB stick or an optical drive.
+    pub fn is_removable(&self) -> bool {
+        power::is_removable(&self.path)
+    }
+
+    /// The device's serial number, if it reports one.
+    pub fn serial(&self) -> Option<String> {
+        ident::serial(&self.path)
+    }
+
+    /// The device's World Wide Name, if it has one.
+    pub fn wwn(&self) -> Option<String> {
+        ident::wwn(&self.path)
+    }
+
+    /// Whether the device is a spinning hard drive rather than an SSD.
+    pub fn is_rotational(&self) -> bool {
+        power::is_rotational(&self.path)
+    }
+
+    fn check_unmounted(&self) -> std::io::Result<()> {
+        if self.partitions.iter().any(|p| p.mounted()) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::ResourceBusy,
+                format!("{} has mounted partitions", self.path.display()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Flush the device's write caches and eject its media, so that it can be safely removed.
+    ///
+    /// Fails if any partition is mounted. Uncommitted changes are discarded.
+    pub fn eject(mut self) -> std::io::Result<()> {
+        self.check_unmounted()?;
+        self.sync()?;
+        power::eject(&self.path)
+    }
+
+    /// Flush the device's write caches and spin it down. It spins back up on the next access.
+    ///
+    /// Fails if any partition is mounted.
+    pub fn standby(&mut self) -> std::io::Result<()> {
+        self.check_unmounted()?;
+        self.sync()?;
+        power::standby(&self.path)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Device<'static> {
+    /// Commit all changes like [`commit_with_progress`](Device::commit_with_progress), but on
+    /// tokio's blocking thread pool, sending progress over a channel. The device is handed back
+    /// by [`AsyncCommit::finish`] along with the result.
+    ///
+    /// libparted isn't thread safe, so other devices mustn't be used until the commit is done.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn commit_async(self, options: CommitOptions) -> AsyncCommit {
+        let (sender, progress) = tokio::sync::mpsc::unbounded_channel();
+        let device = commit::SendDevice(self);
+        let task = tokio::task::spawn_blocking(move || {
+            let mut device = device.into_inner();
+            let result = device.commit_with_progress(&options, |p| {
+                // the receiver may have been dropped by a caller that only wants the result
+                let _ = sender.send(p);
+            });
+            commit::SendDevice((device, result))
+        });
+        AsyncCommit { progress, task }
+    }
+}
+
+/// The partition a change applies to.
+///
+/// Indices into [`Device::partitions`] shift as partitions are added and removed, and don't match
+/// partition numbers once a partition in the middle is deleted, so changes find their partition
+/// by number. Partitions that aren't on disk yet have no number, and are found by the sector
+/// they start at when the change is applied.
+#[derive(Debug, Clone)]
+struct ChangeTarget {
+    number: Option<u32>,
+    start: i64,
+}
+
+impl ChangeTarget {
+    fn of(partition: &Partition) -> Self {
+        Self {
+            number: partition.number,
+            start: *partition.bounds().start(),
+        }
+    }
+
+    /// Find the number of the partition in the table being built.
+    fn number(&self, disk: &mut libparted::Disk) -> std::io::Result<u32> {
+        self.resolve(disk.parts().map(|p| (p.geom_start(), p.num())))
+    }
+
+    /// Find the number of the partition among the starts and numbers of the parts of a table,
+    /// whose free space and metadata have negative numbers.
+    fn resolve(&self, parts: impl IntoIterator<Item = (i64, i32)>) -> std::io::Result<u32> {
+        if let Some(number) = self.number {
+            return Ok(number);
+        }
+        parts
+            .into_iter()
+            .filter(|(start, _)| *start == self.start)
+            .find_map(|(_, number)| u32::try_from(number).ok())
+            .ok_or_else(|| {
+                std::io::Error::other(format!(
+                    "no partition starts at sector {} to apply a change to",
+                    self.start
+                ))
+            })
+    }
+}
+
+enum InnerChange {
+    Name {
+        partition: usize,
+        target: ChangeTarget,
+        new: Arc<str>,
+    },
+    NewPartition {
+        name: Arc<str>,
+        fs: Option<FileSystem>,
+        bounds: RangeInclusive<i64>,
+        index: usize,
+        /// The GPT partition number to give the partition, rather than the first free one.
+        number: Option<u32>,
+        /// Whether the bounds are already aligned, so libparted mustn't align them again.
+        exact: bool,
+        partition_type: PartitionType,
+    },
+    RemovePartition {
+        index: usize,
+        target: ChangeTarget,
+        removed: Option<Box<Partition>>,
+    },
+    ResizePartition {
+        index: usize,
+        target: ChangeTarget,
+        from: RangeInclusive<i64>,
+        bounds: RangeInclusive<i64>,
+        strategy: ResizeStrategy,
+        /// The file system to resize along with the partition, if partner knows it.
+        fs: Option<FileSystem>,
+    },
+    MovePartition {
+        index: usize,
+        target: ChangeTarget,
+        from: RangeInclusive<i64>,
+        to: RangeInclusive<i64>,
+    },
+    SetBootable {
+        index: usize,
+        target: ChangeTarget,
+        cleared: Vec<(usize, ChangeTarget)>,
+    },
+    SetFlag {
+        index: usize,
+        target: ChangeTarget,
+        flag: PartitionFlag,
+        state: bool,
+        /// The GPT type flags that setting this one clears.
+        cleared: Vec<PartitionFlag>,
+    },
+    SetUuid {
+        index: usize,
+        target: ChangeTarget,
+        uuid: Guid,
+    },
+    RepairGpt(gpt::Table),
+    HybridMbr,
+    GptEntryCapacity,
+    NewTable {
+        table: PartitionTable,
+        previous_type: Option<Arc<str>>,
+        previous_partitions: Vec<Partition>,
+        previous_hybrid_mbr: (Vec<HybridEntry>, Vec<Vec<HybridEntry>>),
+        previous_gpt_entry_capacity: (Option<u32>, Vec<u32>),
+    },
+    Format {
+        bounds: RangeInclusive<i64>,
+        fs: FileSystem,
+        label: Arc<str>,
+    },
+    /// Move a superfloppy's file system from `from` into the new partition at `to`.
+    RelocateFs {
+        fs: FileSystem,
+        from: RangeInclusive<i64>,
+        to: RangeInclusive<i64>,
+        strategy: ResizeStrategy,
+    },
+}
+
+/// How to redo an undone change.
+enum Redo {
+    /// Stage the change again, redoing what it did to the partitions.
+    Change(InnerChange),
+    HybridMbr(Vec<HybridEntry>),
+    GptEntryCapacity(Option<u32>),
+    /// Stage a new table again, which takes the state it replaces afresh.
+    NewTable(PartitionTable),
+}
+
+/// A change to a device, as returned by [`Device::undo_change`] and [`Device::changes`].
+///
+/// Changes display as a short description for lists of pending operations, e.g.
+/// `Resize /dev/sda3 from 20 GiB to 32 GiB`. Partitions are described by their path, if they have
+/// one yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Name {
+        partition: usize,
+        path: Option<Arc<Path>>,
+        new: Arc<str>,
+    },
+    NewPartition {
+        name: Arc<str>,
+        fs: Option<FileSystem>,
+        bounds: RangeInclusive<i64>,
+        size: Byte,
+        partition_type: PartitionType,
+    },
+    RemovePartition {
+        index: usize,
+        path: Option<Arc<Path>>,
+    },
+    ResizePartition {
+        index: usize,
+        path: Option<Arc<Path>>,
+        bounds: RangeInclusive<i64>,
+        old_size: Byte,
+        new_size: Byte,
+    },
+    /// Move a partition and its contents to the given bounds, keeping its size.
+    MovePartition {
+        index: usize,
+        path: Option<Arc<Path>>,
+        bounds: RangeInclusive<i64>,
+    },
+    SetBootable {
+        index: usize,
+        path: Option<Arc<Path>>,
+    },
+    SetFlag {
+        index: usize,
+        path: Option<Arc<Path>>,
+        flag: PartitionFlag,
+        state: bool,
+    },
+    /// Give a partition the given PARTUUID.
+    SetUuid {
+        index: usize,
+        path: Option<Arc<Path>>,
+        uuid: Guid,
+    },
+    /// Rewrite the corrupt primary GPT of a [degraded](Device::is_degraded) device from its backup.
+    RepairGpt,
+    HybridMbr,
+    GptEntryCapacity,
+    /// Replace the partition table with an empty one.
+    NewTable {
+        table: PartitionTable,
+    },
+    /// Create a file system on the partition at the given bounds after committing.
+    Format {
+        bounds: RangeInclusive<i64>,
+        fs: FileSystem,
+        label: Arc<str>,
+    },
+    /// Shrink the file system of a [superfloppy](Device::is_superfloppy) and move it into the
+    /// new partition at the given bounds.
+    RelocateFs {
+        fs: FileSystem,
+        bounds: RangeInclusive<i64>,
+    },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let partition = |index: usize, path: &Option<Arc<Path>>| match path {
+            Some(path) => path.display().to_string(),
+            None => format!("partition {}", index + 1),
+        };
+        let format_size =
+            |bytes: Byte| size::format(bytes, size::FormatOptions::new().precision(10));
+        match self {
+            Self::Name {
+                partition: index,
+                path,
+                new,
+            } => write!(f, "Rename {} to \"{new}\"", partition(*index, path)),
+            Self::NewPartition {
+                name,
+                fs,
+                size,
+                partition_type,
+                ..
+            } => {
+                let size = format_size(*size);
+                match partition_type {
+                    PartitionType::Primary => write!(f, "Create {size} partition \"{name}\"")?,
+                    _ => write!(f, "Create {size} {partition_type} partition \"{name}\"")?,
+                }
+                match fs {
+                    Some(fs) => write!(f, " ({fs})"),
+                    None => Ok(()),
+                }
+            }
+            Self::RemovePartition { index, path } => {
+                write!(f, "Remove {}", partition(*index, path))
+            }
+            Self::ResizePartition {
+                index,
+                path,
+                old_size,
+                new_size,
+                ..
+            } => write!(
+                f,
+                "Resize {} from {} to {}",
+                partition(*index, path),
+                format_size(*old_size),
+                format_size(*new_size)
+            ),
+            Self::MovePartition {
+                index,
+                path,
+                bounds,
+            } => write!(
+                f,
+                "Move {} to sector {}",
+                partition(*index, path),
+                bounds.start()
+            ),
+            Self::SetBootable { index, path } => {
+                write!(f, "Mark {} bootable", partition(*index, path))
+            }
+            Self::SetFlag {
+                index,
+                path,
+                flag,
+                state,
+            } => write!(
+                f,
+                "{} the {flag} flag on {}",
+                if *state { "Set" } else { "Clear" },
+                partition(*index, path)
+            ),
+            Self::SetUuid { index, path, uuid } => {
+                write!(
+                    f,
+                    "Set the PARTUUID of {} to {uuid}",
+                    partition(*index, path)
+                )
+            }
+            Self::RepairGpt => write!(f, "Repair the primary GPT"),
+            Self::HybridMbr => write!(f, "Update the hybrid MBR"),
+            Self::GptEntryCapacity => write!(f, "Resize the GPT entry array"),
+            Self::NewTable { table } => write!(f, "Create a new {table} partition table"),
+            Self::Format { bounds, fs, .. } => {
+                write!(f, "Create {fs} file system at sector {}", bounds.start())
+            }
+            Self::RelocateFs { fs, bounds } => {
+                write!(f, "Move the {fs} file system to sector {}", bounds.start())
+            }
+        }
+    }
+}
+
+impl InnerChange {
+    /// Whether applying this change and `other` in a different order could give a different
+    /// result.
+    ///
+    /// Changes that touch different fields of the device's state are independent, which also
+    /// keeps their undo histories consistent when they're reordered.
+    fn depends_on_order(&self, other: &Self) -> bool {
+        match (self, other) {
+            // these replace the table or shift the indices of other partitions
+            (
+                Self::RepairGpt(_)
+                | Self::NewTable { .. }
+                | Self::NewPartition { .. }
+                | Self::RemovePartition { .. },
+                _,
+            )
+            | (
+                _,
+                Self::RepairGpt(_)
+                | Self::NewTable { .. }
+                | Self::NewPartition { .. }
+                | Self::RemovePartition { .. },
+            ) => true,
+            (Self::Name { partition: a, .. }, Self::Name { partition: b, .. })
+            | (Self::SetFlag { index: a, .. }, Self::SetFlag { index: b, .. })
+            | (Self::SetUuid { index: a, .. }, Self::SetUuid { index: b, .. }) => a == b,
+            // clearing the boot flag undoes setting it
+            (Self::SetFlag { .. }, Self::SetBootable { .. })
+            | (Self::SetBootable { .. }, Self::SetFlag { .. }) => true,
+            // setting the boot flag clears it on the other partitions
+            (Self::SetBootable { .. }, Self::SetBootable { .. })
+            | (Self::HybridMbr, Self::HybridMbr)
+            | (Self::GptEntryCapacity, Self::GptEntryCapacity) => true,
+            // space freed by one resize or move may be taken by another, and formats target bounds
+            (
+                Self::ResizePartition { .. }
+                | Self::MovePartition { .. }
+                | Self::Format { .. }
+                | Self::RelocateFs { .. },
+                Self::ResizePartition { .. }
+                | Self::MovePartition { .. }
+                | Self::Format { .. }
+                | Self::RelocateFs { .. },
+            ) => true,
+            _ => false,
+        }
+    }
+
+    /// Apply the change to the given disk, calling `progress` with how much of it is done if it
+    /// takes a while.
+    fn apply(self, disk: &mut libparted::Disk, progress: impl FnMut(f64)) -> std::io::Result<()> {
+        match self {
+            #[allow(
+                clippy::unwrap_used,
+                reason = "a panic here would be an internal logic bug"
+            )]
+            Self::Name { target, new, .. } => {
+                let number = target.number(disk)?;
+                disk.get_partition(number).unwrap().set_name(new.as_ref())
+            }
+            #[allow(
+                clippy::unwrap_used,
+                reason = "a panic here would be an internal logic bug"
+            )]
+            Self::NewPartition {
+                name,
+                fs,
+                bounds,
+                exact,
+                partition_type,
+                ..
+            } => {
+                let mut part = libparted::Partition::new(
+                    disk,
+                    partition_type.into(),
+                    fs.map(Into::into).as_ref(),
+                    *bounds.start(),
                     *bounds.end(),
                 )?;
 
-                part.set_name(name.as_ref())?;
+                // msdos partitions have no names
+                if !name.is_empty() {
+                    part.set_name(name.as_ref())?;
+                }
 
-                disk.add_partition(
-                    &mut part,
+                let geometry = libparted::Geometry::new(
                     // SAFETY: this device reference is only used once
-                    &unsafe { disk.get_device().get_optimal_aligned_constraint()? },
-                )
+                    &unsafe { disk.get_device() },
+                    *bounds.start(),
+                    bounds.end() - bounds.start() + 1,
+                )?;
+                let constraint = if exact {
+                    geometry.exact().unwrap()
+                } else {
+                    // SAFETY: this device reference is only used once
+                    unsafe { disk.get_device().get_optimal_aligned_constraint()? }
+                };
+                disk.add_partition(&mut part, &constraint)
+            }
+            Self::RemovePartition { target, .. } => {
+                let number = target.number(disk)?;
+                disk.remove_partition_by_number(number)
+            }
+            Self::ResizePartition {
+                target,
+                from,
+                bounds,
+                strategy,
+                fs,
+                ..
+            } => {
+                let number = target.number(disk)?;
+                // libparted shrinks file systems here, before the table; everything else is resized
+                // by the commit, before shrinking or after growing
+                if strategy == ResizeStrategy::Libparted
+                    && let Some(fs) = fs
+                    && bounds.end() - bounds.start() < from.end() - from.start()
+                {
+                    // SAFETY: this device reference is only used once
+                    let path = unsafe { disk.get_device() }.path().to_path_buf();
+                    resize::resize_with_libparted(&path, fs, &from, &bounds, progress)?;
+                }
+                set_partition_bounds(disk, number, &bounds)
             }
-            Self::RemovePartition { index, .. } => {
-                disk.remove_partition_by_number(index as u32 + 1)
+            Self::MovePartition { target, to, .. } => {
+                let number = target.number(disk)?;
+                // the contents are copied before libparted commits the table
+                set_partition_bounds(disk, number, &to)
+            }
+            #[allow(
+                clippy::unwrap_used,
+                reason = "a panic here would be an internal logic bug"
+            )]
+            Self::SetBootable {
+                target, cleared, ..
+            } => {
+                for (_, cleared) in cleared {
+                    let number = cleared.number(disk)?;
+                    disk.get_partition(number)
+                        .unwrap()
+                        .set_flag(libparted::PartitionFlag::PED_PARTITION_BOOT, false)?;
+                }
+                let number = target.number(disk)?;
+                disk.get_partition(number)
+                    .unwrap()
+                    .set_flag(libparted::PartitionFlag::PED_PARTITION_BOOT, true)
             }
             #[allow(
                 clippy::unwrap_used,
                 reason = "a panic here would be an internal logic bug"
             )]
-            Self::ResizePartition { index, bounds } => disk
-                .get_partition(index as u32)
-                .unwrap()
-                .get_geom()
-                .open_fs()
-                .unwrap()
-                .resize(
-                    &Geometry::new(
-                        &unsafe { disk.get_device() },
-                        *bounds.start(),
-                        bounds.end() - bounds.start(),
-                    )?,
-                    None,
-                ),
+            Self::SetFlag {
+                target,
+                flag,
+                state,
+                ..
+            } => {
+                // libparted clears the other GPT type flags itself
+                let number = target.number(disk)?;
+                disk.get_partition(number)
+                    .unwrap()
+                    .set_flag(flag.into(), state)
+            }
+            Self::RepairGpt(_) => unreachable!("GPT repair is applied before opening the disk"),
+            // the hybrid MBR, GPT entry array and PARTUUIDs are written after libparted commits,
+            // and superfloppy file systems are relocated before the new table is opened
+            Self::HybridMbr
+            | Self::GptEntryCapacity
+            | Self::SetUuid { .. }
+            | Self::RelocateFs { .. } => Ok(()),
+            Self::NewTable { .. } | Self::Format { .. } => {
+                unreachable!("new tables and formats are handled by the commit")
+            }
+        }
+    }
+}
+
+/// Set the bounds of the partition with the given number exactly, without aligning them.
+fn set_partition_bounds(
+    disk: &mut libparted::Disk,
+    number: u32,
+    bounds: &RangeInclusive<i64>,
+) -> std::io::Result<()> {
+    let geometry = libparted::Geometry::new(
+        // SAFETY: this device reference is only used once
+        &unsafe { disk.get_device() },
+        *bounds.start(),
+        bounds.end() - bounds.start() + 1,
+    )?;
+    let constraint = geometry
+        .exact()
+        .ok_or_else(|| std::io::Error::other("libparted couldn't constrain the partition"))?;
+    // `get_partition` borrows the disk for as long as the partition lives, so it's taken raw. the
+    // partition belongs to the disk, so it mustn't be destroyed when it's dropped
+    let raw = unsafe { disk.get_partition_raw(number) };
+    if raw.is_null() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("partition {number} doesn't exist"),
+        ));
+    }
+    let mut partition = std::mem::ManuallyDrop::new(libparted::Partition::from(raw));
+    disk.set_partition_geometry(&mut partition, &constraint, *bounds.start(), *bounds.end())
+}
+
+/// Read the partition table type and partitions of the given device.
+fn read_partitions(
+    raw: &mut RawDevice,
+    mounts: &HashMap<PathBuf, MountInfo>,
+) -> std::io::Result<(Option<Arc<str>>, Vec<Partition>)> {
+    let sector_size = raw.sector_size();
+    let device: Arc<Path> = raw.path().into();
+    // libparted fails to open devices without a partition table, so blank devices are told apart
+    // from unreadable ones first
+    if !has_label(raw.path())? {
+        // a corrupt primary GPT isn't recognized either, but the device can be recovered from
+        // the backup
+        let mut file = std::fs::File::open(raw.path())?;
+        if gpt::Table::read_backup(&mut file, sector_size, raw.length()).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the primary GPT is corrupt",
+            ));
+        }
+        return Ok((None, Vec::new()));
+    }
+    let disk = libparted::Disk::new(raw)?;
+    let disk_type = disk.get_disk_type_name().map(Arc::<str>::from);
+    let msdos = disk_type.as_deref() == Some("msdos");
+    // libparted doesn't expose partition type GUIDs or PARTUUIDs, or notes after the names, so
+    // they're read separately
+    let guids = if disk_type.as_deref() == Some("gpt") {
+        gpt::Table::read_at(&mut std::fs::File::open(&device)?, sector_size, 1)
+            .map(|table| {
+                table
+                    .entries()
+                    .map(|e| (e.number, (e.type_guid, e.uuid, e.note)))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default()
+    } else {
+        HashMap::new()
+    };
+    let partitions = disk
+        .parts()
+        .filter_map(|p| {
+            let mount = mounts.get(p.get_path()?);
+            let guids = guids.get(&(p.num() as u32));
+            let mut partition = Partition::from_libparted(
+                p,
+                device.clone(),
+                sector_size,
+                mount,
+                msdos,
+                guids.map(|(type_guid, ..)| *type_guid),
+                guids.map(|(_, uuid, _)| *uuid),
+            );
+            partition.note = guids.and_then(|(.., note)| note.as_deref().map(Arc::from));
+            Some(partition)
+        })
+        .collect();
+    Ok((disk_type, partitions))
+}
+
+/// Wait for udev to create the device node at the given path, which happens shortly after the
+/// kernel learns of a new partition.
+fn wait_for_path(path: &Path) -> std::io::Result<()> {
+    for _ in 0..50 {
+        if path.exists() {
+            return Ok(());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{} didn't appear", path.display()),
+    ))
+}
+
+/// Whether libparted recognizes a partition table, or a file system spanning the device, on the
+/// device at the given path.
+fn has_label(path: &Path) -> std::io::Result<bool> {
+    use libparted_sys::{ped_device_close, ped_device_get, ped_device_open, ped_disk_probe};
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    let device = unsafe { ped_device_get(path.as_ptr()) };
+    if device.is_null() {
+        return Err(std::io::Error::other("libparted couldn't get the device"));
+    }
+    // probing on its own doesn't say whether nothing was found or the device couldn't be read
+    if unsafe { ped_device_open(device) } == 0 {
+        return Err(std::io::Error::other("libparted couldn't open the device"));
+    }
+    let disk_type = unsafe { ped_disk_probe(device) };
+    unsafe { ped_device_close(device) };
+    Ok(!disk_type.is_null())
+}
+
+/// Check that the device's logical sector size is one that sector arithmetic and partition tables
+/// work with.
+fn check_sector_size(raw: &RawDevice) -> std::io::Result<()> {
+    let size = raw.sector_size();
+    if size.is_power_of_two() && (512..=65536).contains(&size) {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!(
+                "{} has {size} byte sectors, which aren't supported; reformat it with 512 or \
+                 4096 byte sectors first",
+                raw.path().display()
+            ),
+        ))
+    }
+}
+
+/// Get the path of the partition with the given number on the given device, following the
+/// kernel's naming convention (`/dev/sda1`, `/dev/nvme0n1p1`).
+fn partition_path(device: &Path, number: u32) -> PathBuf {
+    let device = device.as_os_str().to_string_lossy();
+    if device.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{device}p{number}").into()
+    } else {
+        format!("{device}{number}").into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// A GPT layout with numbering gaps: partitions 2 and 4 were deleted on disk, 3 has a staged
+    /// removal, and a new partition is staged between 1 and 3.
+    fn gappy() -> Vec<Partition> {
+        let partition = |number, start, kind| {
+            let mut partition = Partition::new(
+                "".into(),
+                start..=start + 99,
+                None,
+                Path::new("/dev/sda").into(),
+                512,
+            );
+            partition.number = number;
+            partition.kind = kind;
+            partition
+        };
+        vec![
+            partition(Some(1), 100, PartitionKind::Real),
+            partition(None, 200, PartitionKind::Virtual),
+            partition(Some(3), 300, PartitionKind::Hidden),
+            partition(Some(5), 500, PartitionKind::Real),
+        ]
+    }
+
+    /// The parts libparted has once the new partition is added, with free space between them.
+    const PARTS: [(i64, i32); 6] = [(0, -1), (100, 1), (200, -1), (200, 2), (300, 3), (500, 5)];
+
+    #[test]
+    fn change_targets_resolve_to_the_numbers_on_disk() {
+        let numbers: Vec<_> = gappy()
+            .iter()
+            .map(|p| {
+                ChangeTarget::of(p)
+                    .resolve(PARTS)
+                    .expect("no partition found")
+            })
+            .collect();
+        assert_eq!(numbers, [1, 2, 3, 5]);
+    }
+
+    #[test]
+    fn new_partitions_are_found_by_their_start_past_free_space() {
+        let target = ChangeTarget {
+            number: None,
+            start: 200,
+        };
+        assert_eq!(target.resolve(PARTS).expect("no partition found"), 2);
+        let free = [(0, -1), (200, -1)];
+        assert!(target.resolve(free).is_err());
+    }
+
+    #[test]
+    fn logical_partitions_resolve_by_number() {
+        // an extended partition 2 holding logical partitions 5 and 6, after their EBR sectors
+        let parts = [(100, 1), (300, 2), (300, -1), (302, 5), (402, -1), (404, 6)];
+        let logical = |number| ChangeTarget { number, start: 404 };
+        assert_eq!(
+            logical(Some(6)).resolve(parts).expect("no partition found"),
+            6
+        );
+        assert_eq!(logical(None).resolve(parts).expect("no partition found"), 6);
+    }
+
+    #[test]
+    fn renames_skip_removed_partitions() {
+        let partitions = gappy();
+        let kinds = || partitions.iter().map(|p| p.kind);
+        let renamed: Vec<_> = (0..3)
+            .map(|public| {
+                let internal = partition::internal_index(kinds(), public).expect("not shown");
+                ChangeTarget::of(&partitions[internal])
+                    .resolve(PARTS)
+                    .expect("no partition found")
+            })
+            .collect();
+        assert_eq!(renamed, [1, 2, 5]);
+        assert_eq!(partition::internal_index(kinds(), 3), None);
+    }
+
+    /// A GPT device of `length` sectors holding partitions with the given bounds, which exists
+    /// only in memory so that staging can be tested without libparted.
+    fn synthetic(length: u64, bounds: &[RangeInclusive<i64>]) -> Device<'static> {
+        let path = Path::new("/dev/synthetic");
+        // SAFETY: a zeroed PedDevice is a valid one with null pointers, and no libparted function
+        // is called on it
+        let mut raw: libparted_sys::PedDevice = unsafe { std::mem::zeroed() };
+        raw.model = c"Synthetic".as_ptr().cast_mut();
+        raw.path = c"/dev/synthetic".as_ptr().cast_mut();
+        raw.sector_size = 512;
+        raw.phys_sector_size = 512;
+        raw.length = length as i64;
+        let partitions = bounds
+            .iter()
+            .zip(1..)
+            .map(|(bounds, number)| {
+                let mut partition =
+                    Partition::new("".into(), bounds.clone(), None, path.into(), 512);
+                partition.number = Some(number);
+                partition.kind = PartitionKind::Real;
+                partition
+            })
+            .collect();
+        Device {
+            model: "Synthetic".into(),
+            path: path.into(),
+            alias: None,
+            partitions,
+            changes: History::new(Vec::new()),
+            disk_type: Some("gpt".into()),
+            degraded: false,
+            hybrid_mbr: (Vec::new(), Vec::new()),
+            gpt_entry_capacity: (Some(128), Vec::new()),
+            safety_level: SafetyLevel::default(),
+            confirmed: None,
+            limits: ChangeLimits::default(),
+            first_partition_start: FirstPartitionStart::default(),
+            alignment: None,
+            created: Vec::new(),
+            // SAFETY: the device is leaked, so it outlives the returned one, which never closes it
+            // as it isn't open
+            raw: unsafe { RawDevice::from_ped_device(Box::leak(Box::new(raw))) },
+        }
+    }
+
+    #[test]
+    fn changes_describe_partitions_by_their_index_now() {
+        let indices = |device: &Device| -> Vec<_> {
+            device
+                .changes()
+                .iter()
+                .filter_map(|change| match change {
+                    Change::ResizePartition { index, .. }
+                    | Change::RemovePartition { index, .. } => Some(*index),
+                    _ => None,
+                })
+                .collect()
+        };
+        let mut device = synthetic(4096, &[2148..=2197]);
+        device
+            .resize_partition_with(0, 2148..=2247, ResizeStrategy::Libparted)
+            .expect("resizing failed");
+        device
+            .new_partition("".into(), None, 2048..=2097)
+            .expect("staging a partition failed");
+        device
+            .resize_partition_with(0, 2048..=2107, ResizeStrategy::Libparted)
+            .expect("resizing failed");
+        // the first resize is of the partition the new one was staged before
+        assert_eq!(indices(&device), [1, 0]);
+
+        device.remove_partition(0).expect("removing failed");
+        assert_eq!(indices(&device), [0, 0, 0]);
+        assert_eq!(device.partitions().count(), 1);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        New(i64, i64),
+        Remove(usize),
+        Resize(usize, i64),
+        Undo,
+        Redo,
+        UndoAll,
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            (2000..4096i64, 1..200i64).prop_map(|(start, len)| Op::New(start, len)),
+            any::<usize>().prop_map(Op::Remove),
+            (any::<usize>(), 1..200i64).prop_map(|(i, len)| Op::Resize(i, len)),
+            Just(Op::Undo),
+            Just(Op::Redo),
+            Just(Op::UndoAll),
+        ]
+    }
+
+    /// Partitions in slots of 100 sectors past the first MiB of a 4096 sector device.
+    fn layout() -> impl Strategy<Value = Vec<RangeInclusive<i64>>> {
+        prop::collection::btree_set(0..19i64, 0..8).prop_map(|slots| {
+            slots
+                .into_iter()
+                .map(|slot| 2048 + slot * 100..=2048 + slot * 100 + 49)
+                .collect()
+        })
+    }
+
+    /// Run an operation, ignoring the errors of those that don't fit the layout.
+    fn run(device: &mut Device, op: Op) {
+        let n_partitions = device.partitions().count();
+        let _ = match op {
+            Op::New(start, len) => device.new_partition("".into(), None, start..start + len),
+            Op::Remove(i) if n_partitions > 0 => device.remove_partition(i % n_partitions),
+            Op::Resize(i, len) if n_partitions > 0 => {
+                let i = i % n_partitions;
+                let start = *device
+                    .partitions()
+                    .nth(i)
+                    .expect("no partition")
+                    .bounds()
+                    .start();
+                device.resize_partition_with(i, start..start + len, ResizeStrategy::Libparted)
+            }
+            Op::Remove(_) | Op::Resize(..) => Ok(()),
+            Op::Undo => {
+                device.undo_change();
+                Ok(())
+            }
+            Op::Redo => {
+                device.redo_change();
+                Ok(())
+            }
+            Op::UndoAll => {
+                device.undo_all_changes();
+                Ok(())
+            }
+        };
+    }
+
+    proptest! {
+        #[test]
+        fn staged_partitions_never_overlap(
+            layout in layout(),
+            ops in prop::collection::vec(op(), 0..64),
+        ) {
+            let mut device = synthetic(4096, &layout);
+            let usable = device.usable_sectors();
+            for op in ops {
+                run(&mut device, op);
+                let partitions: Vec<_> = device.partitions().map(|p| p.bounds().clone()).collect();
+                for (i, a) in partitions.iter().enumerate() {
+                    prop_assert!(usable.contains(a.start()) && usable.contains(a.end()));
+                    for b in &partitions[i + 1..] {
+                        prop_assert!(!commit::overlaps(a, b), "{a:?} overlaps {b:?}");
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn undo_all_restores_the_original(
+            layout in layout(),
+            ops in prop::collection::vec(op(), 0..64),
+        ) {
+            let mut device = synthetic(4096, &layout);
+            let original = device.snapshot();
+            for op in ops {
+                run(&mut device, op);
+            }
+            device.undo_all_changes();
+            prop_assert_eq!(device.snapshot(), original);
+            prop_assert_eq!(device.n_changes(), 0);
+            prop_assert!(device.redo_change().is_none());
+        }
+
+        #[test]
+        fn redo_after_undo_is_identity(
+            layout in layout(),
+            ops in prop::collection::vec(op(), 0..64),
+        ) {
+            let mut device = synthetic(4096, &layout);
+            for op in ops {
+                run(&mut device, op);
+            }
+            let snapshot = device.snapshot();
+            let changes = device.changes();
+
+            if let Some(undone) = device.undo_change() {
+                prop_assert_eq!(Some(&undone), changes.last());
+                prop_assert_eq!(device.redo_change(), Some(undone));
+            }
+            prop_assert_eq!(device.snapshot(), snapshot);
+            prop_assert_eq!(device.changes(), changes);
         }
     }
 }
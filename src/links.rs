@@ -0,0 +1,65 @@
+//! The stable names udev links partitions by in `/dev/disk`, which only appear once udev has
+//! processed the kernel's events for them, some time after a commit.
+
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The udev symlinks to a partition created by a commit, as returned by
+/// [`Device::stable_names`](crate::Device::stable_names).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StableNames {
+    /// The partition's kernel name, e.g. `/dev/sda1`.
+    pub path: Arc<Path>,
+    /// Links in `/dev/disk/by-id`, which name the partition after the device's model and serial
+    /// number, if it has any.
+    pub by_id: Vec<Arc<Path>>,
+    pub by_partuuid: Option<Arc<Path>>,
+    /// The link in `/dev/disk/by-partlabel`, for GPT partitions with a name.
+    pub by_partlabel: Option<Arc<Path>>,
+}
+
+/// Wait until udev has linked the partition at the given path, then get its links.
+///
+/// udev creates all of a partition's links for the same event, so once the PARTUUID link and, if
+/// the partition has a name, the PARTLABEL link are there, the rest are too.
+pub(crate) fn wait(path: &Path, named: bool, timeout: Duration) -> std::io::Result<StableNames> {
+    let start = Instant::now();
+    loop {
+        if let Ok(target) = path.canonicalize() {
+            let names = StableNames {
+                path: path.into(),
+                by_id: links("by-id", &target),
+                by_partuuid: links("by-partuuid", &target).into_iter().next(),
+                by_partlabel: links("by-partlabel", &target).into_iter().next(),
+            };
+            if names.by_partuuid.is_some() && (!named || names.by_partlabel.is_some()) {
+                return Ok(names);
+            }
+        }
+        if start.elapsed() >= timeout {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("udev didn't link {} in time", path.display()),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Find the symlinks in the given `/dev/disk` directory that point to the given device node.
+fn links(dir: &str, target: &Path) -> Vec<Arc<Path>> {
+    let Ok(entries) = std::fs::read_dir(Path::new("/dev/disk").join(dir)) else {
+        return Vec::new();
+    };
+    let mut links = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|link| link.canonicalize().is_ok_and(|p| p == target))
+        .map(Arc::from)
+        .collect::<Vec<_>>();
+    links.sort();
+    links
+}
@@ -0,0 +1,160 @@
+//! A registry of open devices, so that long-running frontends never have two [`Device`]s for one
+//! disk.
+
+use crate::Device;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+
+/// How many events a subscriber can fall behind by before it misses some.
+const EVENT_CAPACITY: usize = 64;
+
+/// A handle to a device owned by a [`DeviceManager`].
+///
+/// Ids are never reused, so a handle to a device that was removed stays invalid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeviceId(u64);
+
+/// Something that happened to the devices of a [`DeviceManager`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceEvent {
+    /// A device was opened, or appeared on a [refresh](DeviceManager::refresh).
+    Added { id: DeviceId, path: PathBuf },
+    /// A device disappeared on a [refresh](DeviceManager::refresh), or was
+    /// [closed](DeviceManager::close).
+    Removed { id: DeviceId, path: PathBuf },
+    /// A device was [modified](DeviceManager::modify), e.g. by staging or committing changes.
+    Changed(DeviceId),
+}
+
+/// Owns all open devices, deduplicating opens of the same disk and broadcasting changes to them.
+///
+/// libparted's handles can't be shared between threads, so the manager and its devices stay on
+/// the thread that created them. [Subscriptions](DeviceManager::subscribe) can be sent anywhere,
+/// e.g. to an async task that updates a UI.
+pub struct DeviceManager<'a> {
+    devices: Vec<(DeviceId, Device<'a>)>,
+    next_id: u64,
+    events: broadcast::Sender<DeviceEvent>,
+}
+
+impl Default for DeviceManager<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> DeviceManager<'a> {
+    /// Create a manager with no devices.
+    pub fn new() -> Self {
+        Self {
+            devices: Vec::new(),
+            next_id: 0,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Get notified of everything that happens to the manager's devices from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceEvent> {
+        self.events.subscribe()
+    }
+
+    /// Open the device at the given path, or get the handle to it if it's already open, even
+    /// through another path such as a `/dev/disk/by-id` link.
+    pub fn open(&mut self, path: impl AsRef<Path>) -> std::io::Result<DeviceId> {
+        let canonical = path.as_ref().canonicalize()?;
+        if let Some(id) = self.find(&canonical) {
+            return Ok(id);
+        }
+        let device = Device::open(path)?;
+        Ok(self.insert(device))
+    }
+
+    /// Open all devices on the system that aren't open yet, and get the handles to all of them.
+    pub fn open_all(&mut self) -> std::io::Result<Vec<DeviceId>> {
+        for device in Device::get_all()? {
+            if self.find(device.path()).is_none() {
+                self.insert(device);
+            }
+        }
+        Ok(self.ids().collect())
+    }
+
+    /// Pick up devices that were plugged in, and drop those that were unplugged, along with any
+    /// changes staged on them.
+    pub fn refresh(&mut self) -> std::io::Result<()> {
+        let present = Device::get_all()?;
+        let (kept, removed): (Vec<_>, Vec<_>) = std::mem::take(&mut self.devices)
+            .into_iter()
+            .partition(|(_, d)| present.iter().any(|p| p.path() == d.path()));
+        self.devices = kept;
+        for (id, device) in removed {
+            self.send(DeviceEvent::Removed {
+                id,
+                path: device.path().to_path_buf(),
+            });
+        }
+        for device in present {
+            if self.find(device.path()).is_none() {
+                self.insert(device);
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the device with the given handle, discarding any changes staged on it.
+    pub fn close(&mut self, id: DeviceId) -> Option<Device<'a>> {
+        let index = self.devices.iter().position(|(i, _)| *i == id)?;
+        let (_, device) = self.devices.remove(index);
+        self.send(DeviceEvent::Removed {
+            id,
+            path: device.path().to_path_buf(),
+        });
+        Some(device)
+    }
+
+    pub fn get(&self, id: DeviceId) -> Option<&Device<'a>> {
+        self.devices.iter().find(|(i, _)| *i == id).map(|(_, d)| d)
+    }
+
+    /// Do something with the device with the given handle, and tell subscribers it changed.
+    pub fn modify<R>(&mut self, id: DeviceId, f: impl FnOnce(&mut Device<'a>) -> R) -> Option<R> {
+        let device = self
+            .devices
+            .iter_mut()
+            .find(|(i, _)| *i == id)
+            .map(|(_, d)| d)?;
+        let out = f(device);
+        self.send(DeviceEvent::Changed(id));
+        Some(out)
+    }
+
+    /// Get the handles of the open devices, in the order they were opened.
+    pub fn ids(&self) -> impl Iterator<Item = DeviceId> + '_ {
+        self.devices.iter().map(|(id, _)| *id)
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = (DeviceId, &Device<'a>)> {
+        self.devices.iter().map(|(id, d)| (*id, d))
+    }
+
+    fn find(&self, path: &Path) -> Option<DeviceId> {
+        self.devices
+            .iter()
+            .find(|(_, d)| d.path() == path)
+            .map(|(id, _)| *id)
+    }
+
+    fn insert(&mut self, device: Device<'a>) -> DeviceId {
+        let id = DeviceId(self.next_id);
+        self.next_id += 1;
+        let path = device.path().to_path_buf();
+        self.devices.push((id, device));
+        self.send(DeviceEvent::Added { id, path });
+        id
+    }
+
+    fn send(&self, event: DeviceEvent) {
+        // sending only fails when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+}
@@ -0,0 +1,104 @@
+//! Reading and writing of the MBR in the first sector of a GPT disk, to preserve hybrid MBRs.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    ops::RangeInclusive,
+};
+
+const ENTRIES_OFFSET: usize = 446;
+const ENTRY_SIZE: usize = 16;
+const SIGNATURE: [u8; 2] = [0x55, 0xAA];
+const PROTECTIVE: u8 = 0xEE;
+
+/// The maximum number of hybrid entries, since one of the four MBR slots must hold the protective
+/// entry.
+pub const MAX_HYBRID_ENTRIES: usize = 3;
+
+/// An MBR partition entry mirroring part of a GPT disk.
+///
+/// Hybrid MBRs let firmware and operating systems that don't understand GPT see some of its
+/// partitions. libparted rewrites the MBR of GPT disks as purely protective on every commit, so
+/// these are restored afterwards.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HybridEntry {
+    /// The MBR partition type byte, e.g. `0x07` for NTFS or `0x0C` for FAT32.
+    pub partition_type: u8,
+    /// Whether the entry has the active/boot flag set.
+    pub bootable: bool,
+    /// The bounds of the entry **in sectors**.
+    pub bounds: RangeInclusive<i64>,
+}
+
+/// Read the non-protective entries of the MBR.
+pub(crate) fn read_hybrid(file: &mut (impl Read + Seek)) -> std::io::Result<Vec<HybridEntry>> {
+    let mut sector = [0; 512];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut sector)?;
+
+    if sector[510..] != SIGNATURE {
+        return Ok(Vec::new());
+    }
+
+    Ok(sector[ENTRIES_OFFSET..510]
+        .chunks_exact(ENTRY_SIZE)
+        .filter(|e| e[4] != 0 && e[4] != PROTECTIVE)
+        .map(|e| {
+            let start = u32::from_le_bytes([e[8], e[9], e[10], e[11]]) as i64;
+            let length = u32::from_le_bytes([e[12], e[13], e[14], e[15]]) as i64;
+            HybridEntry {
+                partition_type: e[4],
+                bootable: e[0] == 0x80,
+                bounds: start..=start + length - 1,
+            }
+        })
+        .collect())
+}
+
+/// Rewrite the MBR partition entries as the given hybrid entries, preceded by a protective entry
+/// covering everything before the first of them.
+///
+/// The boot code and disk signature are left untouched.
+pub(crate) fn write_hybrid(
+    file: &mut (impl Read + Write + Seek),
+    entries: &[HybridEntry],
+    length: u64,
+) -> std::io::Result<()> {
+    let mut sector = [0; 512];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut sector)?;
+
+    let protective_end = entries
+        .iter()
+        .map(|e| *e.bounds.start() as u64 - 1)
+        .min()
+        .unwrap_or(length - 1)
+        .min(u32::MAX as u64);
+    let protective = std::iter::once((PROTECTIVE, false, 1..=protective_end));
+    let hybrid = entries.iter().map(|e| {
+        (
+            e.partition_type,
+            e.bootable,
+            *e.bounds.start() as u64..=*e.bounds.end() as u64,
+        )
+    });
+
+    sector[ENTRIES_OFFSET..510].fill(0);
+    for (slot, (kind, bootable, bounds)) in sector[ENTRIES_OFFSET..510]
+        .chunks_exact_mut(ENTRY_SIZE)
+        .zip(protective.chain(hybrid))
+    {
+        slot[0] = if bootable { 0x80 } else { 0 };
+        // CHS addressing is long obsolete, so mark both ends as beyond its range
+        slot[1..4].copy_from_slice(&[0xFE, 0xFF, 0xFF]);
+        slot[4] = kind;
+        slot[5..8].copy_from_slice(&[0xFE, 0xFF, 0xFF]);
+        slot[8..12].copy_from_slice(&(*bounds.start() as u32).to_le_bytes());
+        slot[12..16].copy_from_slice(&((bounds.end() - bounds.start() + 1) as u32).to_le_bytes());
+    }
+    sector[510..].copy_from_slice(&SIGNATURE);
+
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&sector)?;
+    file.flush()
+}
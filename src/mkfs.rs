@@ -0,0 +1,53 @@
+//! Creation of file systems with the external `mkfs` tools, since libparted can't create them.
+
+use crate::{FileSystem, ToolError, ToolOutput, Toolchain, tool};
+use std::{path::Path, process::Command};
+
+/// The tool that creates the given file system.
+pub(crate) fn program(fs: FileSystem) -> &'static str {
+    match fs {
+        FileSystem::Btrfs => "mkfs.btrfs",
+        FileSystem::Exfat => "mkfs.exfat",
+        FileSystem::Ext2 => "mkfs.ext2",
+        FileSystem::Ext4 => "mkfs.ext4",
+        FileSystem::F2fs => "mkfs.f2fs",
+        FileSystem::Fat16 | FileSystem::Fat32 => "mkfs.fat",
+        FileSystem::Jfs => "mkfs.jfs",
+        FileSystem::LinuxSwap => "mkswap",
+        FileSystem::Ntfs => "mkfs.ntfs",
+        FileSystem::Xfs => "mkfs.xfs",
+    }
+}
+
+/// The command that creates the given file system with the given label, without prompting.
+fn command(toolchain: &Toolchain, fs: FileSystem, label: &str) -> Result<Command, ToolError> {
+    let (args, label_flag): (&[&str], &str) = match fs {
+        FileSystem::Btrfs => (&["-f"], "-L"),
+        FileSystem::Exfat => (&[], "-n"),
+        FileSystem::Ext2 => (&["-F"], "-L"),
+        FileSystem::Ext4 => (&["-F"], "-L"),
+        FileSystem::F2fs => (&["-f"], "-l"),
+        FileSystem::Fat16 => (&["-F", "16"], "-n"),
+        FileSystem::Fat32 => (&["-F", "32"], "-n"),
+        FileSystem::Jfs => (&["-q"], "-L"),
+        FileSystem::LinuxSwap => (&[], "-L"),
+        FileSystem::Ntfs => (&["-f"], "-L"),
+        FileSystem::Xfs => (&["-f"], "-L"),
+    };
+    let mut command = toolchain.command(program(fs))?;
+    command.args(args);
+    if !label.is_empty() {
+        command.args([label_flag, label]);
+    }
+    Ok(command)
+}
+
+/// Create a file system on the partition at the given path.
+pub(crate) fn mkfs(
+    toolchain: &Toolchain,
+    path: &Path,
+    fs: FileSystem,
+    label: &str,
+) -> Result<ToolOutput, ToolError> {
+    tool::run(command(toolchain, fs, label)?.arg(path))
+}
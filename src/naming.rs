@@ -0,0 +1,85 @@
+use crate::{FileSystem, Partition};
+use std::{collections::HashMap, sync::Arc};
+
+/// A template for naming all partitions of a device consistently, applied with
+/// [`Device::apply_naming_scheme`](crate::Device::apply_naming_scheme).
+///
+/// Placeholders are written as `{name}`. The following are always available, and can be
+/// overridden with [`var`](NamingScheme::var):
+///
+/// - `{index}`: the 1-based position of the partition on the device
+/// - `{host}`: the hostname of this machine
+/// - `{role}`: what the partition is used for, based on its mount point and file system (`root`,
+///   `home`, `boot`, `esp`, `swap`, or `data`)
+/// - `{fs}`: the file system of the partition, or `none`
+#[derive(Debug, Clone)]
+pub struct NamingScheme {
+    template: String,
+    vars: HashMap<String, String>,
+}
+
+impl NamingScheme {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+            vars: HashMap::new(),
+        }
+    }
+
+    /// Set the value of a placeholder.
+    pub fn var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Render the name for the given partition, failing with the name of the first unknown
+    /// placeholder.
+    pub(crate) fn render(&self, index: usize, partition: &Partition) -> Result<String, Arc<str>> {
+        let mut out = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            let name = &rest[1..end];
+            rest = &rest[end + 1..];
+
+            match (self.vars.get(name), name) {
+                (Some(value), _) => out.push_str(value),
+                (None, "index") => out.push_str(&index.to_string()),
+                (None, "host") => out.push_str(&hostname()),
+                (None, "role") => out.push_str(role(partition)),
+                (None, "fs") => out.push_str(
+                    &partition
+                        .fs()
+                        .map(|fs| fs.to_string())
+                        .unwrap_or_else(|| "none".into()),
+                ),
+                (None, _) => return Err(name.into()),
+            }
+        }
+        out.push_str(rest);
+
+        Ok(out)
+    }
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|h| h.trim().to_string())
+        .unwrap_or_else(|_| "localhost".into())
+}
+
+fn role(partition: &Partition) -> &'static str {
+    match partition.mount_point.as_deref().and_then(|p| p.to_str()) {
+        Some("/") => "root",
+        Some("/home") => "home",
+        Some("/boot") => "boot",
+        Some("/boot/efi" | "/efi") => "esp",
+        _ if partition.fs() == Some(FileSystem::LinuxSwap) => "swap",
+        _ => "data",
+    }
+}
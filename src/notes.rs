@@ -0,0 +1,118 @@
+//! Free-form notes on partitions, such as "backup target, do not delete", which make
+//! [`Device`](crate::Device) treat them as in use.
+
+use crate::Guid;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// Where [`Device::set_partition_note`](crate::Device::set_partition_note) keeps a note.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NoteStore {
+    /// In the GPT partition name field, after the name's terminator, which leaves the name as
+    /// other tools see it untouched.
+    ///
+    /// The note has to fit in the 36 UTF-16 code units of the field along with the name, and is
+    /// lost if the partition is renamed. Notes kept this way are read whenever a device is opened.
+    GptName,
+    /// In a JSON file mapping PARTUUIDs to notes, which is created if it doesn't exist. Notes
+    /// kept this way are read with [`Device::load_notes`](crate::Device::load_notes).
+    Sidecar(PathBuf),
+}
+
+/// Read the notes in a sidecar file, by PARTUUID. A missing file has no notes.
+pub(crate) fn read_sidecar(path: &Path) -> std::io::Result<HashMap<Guid, String>> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+}
+
+/// Set or remove the note of the partition with the given PARTUUID in a sidecar file.
+pub(crate) fn write_sidecar(path: &Path, uuid: Guid, note: Option<&str>) -> std::io::Result<()> {
+    let mut notes = read_sidecar(path)?;
+    match note {
+        Some(note) => notes.insert(uuid, note.into()),
+        None => notes.remove(&uuid),
+    };
+    // sorted, so that the file can be kept under version control
+    let notes = notes
+        .into_iter()
+        .map(|(uuid, note)| (uuid.to_string(), note))
+        .collect::<BTreeMap<_, _>>();
+    let json = serde_json::to_string_pretty(&notes)?;
+    std::fs::write(path, json + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A sidecar path in the temporary directory, removed when dropped.
+    struct Sidecar(PathBuf);
+
+    impl Sidecar {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("partner-notes-{}-{name}.json", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for Sidecar {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    const FIRST: Guid = Guid::from_u128(0x11111111_1111_1111_1111_111111111111);
+    const SECOND: Guid = Guid::from_u128(0x22222222_2222_2222_2222_222222222222);
+
+    #[test]
+    fn a_missing_sidecar_has_no_notes() {
+        let sidecar = Sidecar::new("missing");
+        let notes = read_sidecar(&sidecar.0).expect("failed to read the sidecar");
+        assert!(notes.is_empty());
+    }
+
+    #[test]
+    fn notes_are_set_and_removed_by_partuuid() {
+        let sidecar = Sidecar::new("set");
+        write_sidecar(&sidecar.0, SECOND, Some("second")).expect("failed to write a note");
+        write_sidecar(&sidecar.0, FIRST, Some("first")).expect("failed to write a note");
+        write_sidecar(&sidecar.0, SECOND, Some("changed")).expect("failed to change a note");
+        let notes = read_sidecar(&sidecar.0).expect("failed to read the sidecar");
+        assert_eq!(
+            notes,
+            HashMap::from([(FIRST, "first".into()), (SECOND, "changed".into())])
+        );
+
+        write_sidecar(&sidecar.0, FIRST, None).expect("failed to remove a note");
+        let notes = read_sidecar(&sidecar.0).expect("failed to read the sidecar");
+        assert_eq!(notes, HashMap::from([(SECOND, "changed".into())]));
+    }
+
+    #[test]
+    fn sidecars_are_sorted_by_partuuid() {
+        let sidecar = Sidecar::new("sorted");
+        write_sidecar(&sidecar.0, SECOND, Some("second")).expect("failed to write a note");
+        write_sidecar(&sidecar.0, FIRST, Some("first")).expect("failed to write a note");
+        let json = std::fs::read_to_string(&sidecar.0).expect("failed to read the sidecar");
+        assert_eq!(
+            json,
+            format!("{{\n  \"{FIRST}\": \"first\",\n  \"{SECOND}\": \"second\"\n}}\n")
+        );
+    }
+
+    #[test]
+    fn a_malformed_sidecar_is_an_error() {
+        let sidecar = Sidecar::new("malformed");
+        std::fs::write(&sidecar.0, "not json").expect("failed to write the sidecar");
+        assert!(read_sidecar(&sidecar.0).is_err());
+        assert!(write_sidecar(&sidecar.0, FIRST, Some("note")).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Bringing a device to a [`LayoutSpec`] on first boot, the way `systemd-repart` does, for
+//! embedded images that ship small and take the rest of the device on their first boot.
+
+use crate::{
+    CommitError, CommitOptions, Device, FileSystem, LayoutSpec, PartitionFlag, PartitionSpec,
+    PartitionTable, VerificationReport,
+    dps::Role,
+    size::{self, FormatOptions},
+};
+use byte_unit::Byte;
+use std::{ops::RangeInclusive, sync::Arc};
+
+const MIB: u64 = 1024 * 1024;
+
+/// What [`ensure_layout`] did.
+#[derive(Debug, Default)]
+pub struct EnsureReport {
+    /// The number of partitions created.
+    pub created: usize,
+    /// The number of existing partitions grown.
+    pub grown: usize,
+    /// The number of file systems created, on new or existing partitions.
+    pub formatted: usize,
+    /// The verification report of the commit, if [verification](CommitOptions::verify) was
+    /// enabled and anything was committed.
+    pub verification: Option<VerificationReport>,
+}
+
+impl EnsureReport {
+    /// Whether the device already had the layout, so nothing was done.
+    pub fn is_unchanged(&self) -> bool {
+        self.created == 0 && self.grown == 0 && self.formatted == 0
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EnsureError {
+    #[error("the device has pending changes")]
+    PendingChanges,
+    /// The device has a partition that doesn't match the layout's partition at the same position,
+    /// which is never changed or removed.
+    #[error("partition №{0} of the device doesn't match the layout")]
+    Mismatch(usize),
+    #[error("the device has more partitions than the layout")]
+    ExtraPartitions,
+    #[error(
+        "the layout needs {}, but only {} is free",
+        size::format(*.needed, FormatOptions::new()),
+        size::format(*.available, FormatOptions::new())
+    )]
+    NoSpace { needed: Byte, available: Byte },
+    #[error(transparent)]
+    Stage(#[from] crate::Error),
+    #[error(transparent)]
+    Commit(#[from] CommitError),
+}
+
+/// A partition the device already has.
+struct Existing {
+    bounds: RangeInclusive<i64>,
+    name: String,
+    fs: Option<FileSystem>,
+    /// Whether it holds nothing partner recognizes, so it can be formatted.
+    blank: bool,
+}
+
+/// A partition competing for the free space at the end of the device.
+struct Claim {
+    /// Its size **in sectors**, which starts out at its minimum.
+    size: u64,
+    max: u64,
+    weight: u32,
+}
+
+/// Bring the device to the given layout, committing whatever that takes with the given options.
+///
+/// This is idempotent, so it can run on every boot: if the device already has the layout,
+/// nothing is done. Otherwise, partitions the device lacks are created after the ones it has,
+/// file systems the layout asks for are created on partitions that have none, and the last
+/// existing partition is grown into the free space after it, according to its size limits and
+/// weight. Nothing is ever shrunk, moved or removed.
+///
+/// The device's partitions are matched to the layout's by position, and match if their labels
+/// and file systems agree where the layout gives them. Labels are ignored on MBR, which has none.
+/// New partitions get the type libparted gives their file system, except that ESPs get the
+/// [`esp`](PartitionFlag::Esp) flag. A device without a partition table is given a GPT.
+///
+/// Growing a mounted partition, such as the root file system, is subject to the device's
+/// [safety level](crate::SafetyLevel), which has to be lowered to allow it.
+pub fn ensure_layout(
+    device: &mut Device,
+    spec: &LayoutSpec,
+    options: &CommitOptions,
+) -> Result<EnsureReport, EnsureError> {
+    if device.n_changes() > 0 {
+        return Err(EnsureError::PendingChanges);
+    }
+    device.set_first_partition_start(spec.first_start);
+    if device.disk_type().is_none() {
+        device.check_limits(1, 0)?;
+        device.stage_new_table(PartitionTable::Gpt);
+    }
+
+    let sector_size = device.sector_size();
+    let align = device
+        .alignment()
+        .map_or(MIB, |a| a.as_u64())
+        .max(sector_size)
+        / sector_size;
+    let align_up = |sector: u64| sector.div_ceil(align) * align;
+    let sectors = |bytes: Option<Byte>| bytes.map(|b| b.as_u64().div_ceil(sector_size));
+
+    let existing = device
+        .partitions()
+        .map(|p| Existing {
+            bounds: p.bounds().clone(),
+            name: p.name().into(),
+            fs: p.fs(),
+            blank: p.content_hint().is_none(),
+        })
+        .collect::<Vec<_>>();
+    if existing.len() > spec.partitions.len() {
+        return Err(EnsureError::ExtraPartitions);
+    }
+    // msdos partitions have no names
+    let named = device.disk_type() != Some("msdos");
+    for (index, (partition, wanted)) in existing.iter().zip(&spec.partitions).enumerate() {
+        let label_matches = !named
+            || wanted
+                .label
+                .as_ref()
+                .is_none_or(|label| *label == partition.name);
+        let fs_matches = wanted
+            .fs
+            .is_none_or(|wanted| partition.fs.is_none_or(|fs| fs == wanted));
+        if !(label_matches && fs_matches) {
+            return Err(EnsureError::Mismatch(index));
+        }
+    }
+    let missing = &spec.partitions[existing.len()..];
+
+    // the free space starts at the last existing partition, which may grow into it
+    let usable = device.usable_sectors();
+    let reserved = spec
+        .reserve_end
+        .map_or(0, |r| r.resolve(device.size()).as_u64() / sector_size);
+    let region_end = *usable.end() as u64 - reserved;
+    let grown = existing
+        .last()
+        .map(|p| (&p.bounds, &spec.partitions[existing.len() - 1]));
+    let region_start = match grown {
+        Some((bounds, _)) => *bounds.start() as u64,
+        None => align_up(*usable.start() as u64),
+    };
+    let available = (region_end + 1).saturating_sub(region_start);
+
+    let claim = |partition: &PartitionSpec, min: u64| Claim {
+        size: min,
+        max: sectors(partition.size_max).map_or(u64::MAX, |max| max.max(min)),
+        weight: partition.weight,
+    };
+    let mut claims = Vec::new();
+    if let Some((bounds, partition)) = grown {
+        let current = (bounds.end() - bounds.start() + 1) as u64;
+        let min = sectors(partition.size_min).unwrap_or(0).max(current);
+        // the partitions after it start aligned
+        claims.push(claim(
+            partition,
+            align_up(region_start + min) - region_start,
+        ));
+    }
+    for partition in missing {
+        let min = sectors(partition.size_min).map_or(align, align_up);
+        claims.push(claim(partition, min));
+    }
+
+    let needed = claims.iter().map(|c| c.size).sum::<u64>();
+    if needed > available {
+        return Err(EnsureError::NoSpace {
+            needed: Byte::from_u64(needed * sector_size),
+            available: Byte::from_u64(available * sector_size),
+        });
+    }
+    distribute(&mut claims, available - needed, align);
+
+    let mut report = EnsureReport::default();
+    // existing partitions only get a file system if they have no content at all
+    for (partition, wanted) in existing.iter().zip(&spec.partitions) {
+        if let (Some(fs), None, true) = (wanted.fs, partition.fs, partition.blank) {
+            device.stage_format(partition.bounds.clone(), fs, label(wanted));
+            report.formatted += 1;
+        }
+    }
+
+    let mut claims = claims.into_iter();
+    let mut cursor = region_start;
+    if let Some((bounds, _)) = grown {
+        let Claim { size, .. } = claims.next().expect("the grown partition has a claim");
+        let end = region_start + size - 1;
+        // alignment slack isn't worth a resize
+        if end >= *bounds.end() as u64 + align {
+            device.resize_partition(existing.len() - 1, *bounds.start()..=end as i64)?;
+            report.grown += 1;
+        }
+        cursor = align_up(region_start + size);
+    }
+
+    for (partition, Claim { size, .. }) in missing.iter().zip(claims) {
+        let bounds = cursor as i64..=(cursor + size - 1) as i64;
+        let name = if named { label(partition) } else { "".into() };
+        device.new_partition(name, partition.fs, bounds.clone())?;
+        report.created += 1;
+        if let Some(fs) = partition.fs {
+            device.stage_format(bounds.clone(), fs, label(partition));
+            report.formatted += 1;
+        }
+        if partition.role == Some(Role::Esp) {
+            let index = device
+                .partitions()
+                .position(|p| crate::commit::overlaps(p.bounds(), &bounds));
+            if let Some(index) = index {
+                device.set_partition_flag(index, PartitionFlag::Esp, true)?;
+            }
+        }
+        cursor = align_up(cursor + size);
+    }
+
+    if device.n_changes() > 0 {
+        report.verification = device.commit_with(options)?;
+    }
+    Ok(report)
+}
+
+fn label(partition: &PartitionSpec) -> Arc<str> {
+    partition.label.as_deref().unwrap_or_default().into()
+}
+
+/// Share out `remaining` sectors among the claims by weight, in multiples of `align`, without
+/// taking any past its maximum.
+fn distribute(claims: &mut [Claim], mut remaining: u64, align: u64) {
+    loop {
+        let total_weight = claims
+            .iter()
+            .filter(|c| c.weight > 0 && c.size < c.max)
+            .map(|c| c.weight as u64)
+            .sum::<u64>();
+        if total_weight == 0 || remaining < align {
+            return;
+        }
+
+        let share = |weight: u32, remaining: u64| {
+            (remaining as u128 * weight as u128 / total_weight as u128) as u64 / align * align
+        };
+
+        // claims that would pass their maximum take only that much, and the rest is shared again
+        let available = remaining;
+        let mut capped = false;
+        for claim in claims.iter_mut().filter(|c| c.weight > 0 && c.size < c.max) {
+            if claim.size.saturating_add(share(claim.weight, available)) >= claim.max {
+                remaining -= claim.max - claim.size;
+                claim.size = claim.max;
+                capped = true;
+            }
+        }
+        if !capped {
+            for claim in claims.iter_mut().filter(|c| c.weight > 0 && c.size < c.max) {
+                claim.size += share(claim.weight, available);
+            }
+            return;
+        }
+    }
+}
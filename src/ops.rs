@@ -0,0 +1,211 @@
+//! Tracking of long-running operations, so that frontends can list, follow, and cancel them
+//! regardless of which thread they run on.
+
+use crate::copy::CopyProgress;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    ops::ControlFlow,
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OperationId(u64);
+
+impl Display for OperationId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum OperationKind {
+    Commit,
+    Copy,
+    Scan,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OperationState {
+    Running,
+    Finished,
+    Failed(Arc<str>),
+    Cancelled,
+}
+
+/// The state of an operation at the time [`OperationRegistry::snapshot`] was called.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationSnapshot {
+    pub id: OperationId,
+    pub kind: OperationKind,
+    pub description: Arc<str>,
+    /// The fraction of the operation that is complete, if known.
+    pub progress: Option<f64>,
+    pub state: OperationState,
+}
+
+struct Entry {
+    snapshot: OperationSnapshot,
+    cancelled: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    operations: BTreeMap<OperationId, Entry>,
+}
+
+/// A registry of operations, shared between the threads running them and the frontend.
+///
+/// Cloning the registry gives another handle to the same operations.
+#[derive(Clone, Default)]
+pub struct OperationRegistry {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl OperationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // the registry is never left in an inconsistent state, so a panic elsewhere doesn't matter
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Register a new running operation, returning the handle its worker uses to report on it.
+    pub fn start(&self, kind: OperationKind, description: impl Into<Arc<str>>) -> OperationHandle {
+        let mut inner = self.lock();
+        let id = OperationId(inner.next_id);
+        inner.next_id += 1;
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        inner.operations.insert(
+            id,
+            Entry {
+                snapshot: OperationSnapshot {
+                    id,
+                    kind,
+                    description: description.into(),
+                    progress: None,
+                    state: OperationState::Running,
+                },
+                cancelled: cancelled.clone(),
+            },
+        );
+
+        OperationHandle {
+            id,
+            registry: self.clone(),
+            cancelled,
+            done: false,
+        }
+    }
+
+    /// Get the state of all operations, in the order they were started.
+    pub fn snapshot(&self) -> Vec<OperationSnapshot> {
+        self.lock()
+            .operations
+            .values()
+            .map(|e| e.snapshot.clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: OperationId) -> Option<OperationSnapshot> {
+        self.lock().operations.get(&id).map(|e| e.snapshot.clone())
+    }
+
+    /// Request that a running operation stop. Returns whether the operation was running.
+    ///
+    /// Cancellation is cooperative, so the operation may keep running until its worker next checks
+    /// [`OperationHandle::is_cancelled`].
+    pub fn cancel(&self, id: OperationId) -> bool {
+        self.lock()
+            .operations
+            .get(&id)
+            .filter(|e| e.snapshot.state == OperationState::Running)
+            .inspect(|e| e.cancelled.store(true, Ordering::Relaxed))
+            .is_some()
+    }
+
+    /// Forget all operations that are no longer running.
+    pub fn clear_finished(&self) {
+        self.lock()
+            .operations
+            .retain(|_, e| e.snapshot.state == OperationState::Running);
+    }
+
+    fn update(&self, id: OperationId, f: impl FnOnce(&mut OperationSnapshot)) {
+        if let Some(entry) = self.lock().operations.get_mut(&id) {
+            f(&mut entry.snapshot);
+        }
+    }
+}
+
+/// The worker's side of an operation in an [`OperationRegistry`].
+///
+/// If the handle is dropped without calling [`finish`](OperationHandle::finish), the operation
+/// is marked as failed.
+pub struct OperationHandle {
+    id: OperationId,
+    registry: OperationRegistry,
+    cancelled: Arc<AtomicBool>,
+    done: bool,
+}
+
+impl OperationHandle {
+    pub fn id(&self) -> OperationId {
+        self.id
+    }
+
+    /// Whether cancellation of the operation has been requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Set the fraction of the operation that is complete.
+    pub fn set_progress(&self, progress: f64) {
+        self.registry
+            .update(self.id, |s| s.progress = Some(progress.clamp(0.0, 1.0)));
+    }
+
+    /// Get a progress callback for [`copy`](crate::copy::copy) that reports to this operation and
+    /// cancels the copy when the operation is cancelled.
+    pub fn copy_progress(&self) -> impl FnMut(CopyProgress) -> ControlFlow<()> + '_ {
+        |progress| {
+            self.set_progress(progress.fraction());
+            if self.is_cancelled() {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    /// Mark the operation as done, with its outcome.
+    ///
+    /// An operation that fails after cancellation was requested is considered cancelled.
+    pub fn finish<E: Display>(mut self, result: Result<(), E>) {
+        let state = match result {
+            Ok(()) => OperationState::Finished,
+            Err(_) if self.is_cancelled() => OperationState::Cancelled,
+            Err(e) => OperationState::Failed(e.to_string().into()),
+        };
+        self.registry.update(self.id, |s| s.state = state);
+        self.done = true;
+    }
+}
+
+impl Drop for OperationHandle {
+    fn drop(&mut self) {
+        if !self.done {
+            self.registry.update(self.id, |s| {
+                s.state = OperationState::Failed("operation was abandoned".into())
+            });
+        }
+    }
+}
@@ -1,18 +1,46 @@
+use crate::{
+    ChecksumAlgorithm, ChecksumError, ChecksumProgress, ContentHint, Dependent, Digest, Guid,
+    PartitionIo, PartitionStatus, ReadWriteMode, checksum, content,
+    copy::{self, CopyError, CopyOptions, Region},
+    dependents, efi, usage,
+};
 use byte_unit::Byte;
 use proc_mounts::MountInfo;
-use std::{fmt::Debug, ops::RangeInclusive, path::Path, sync::Arc};
-use strum::{Display, EnumString};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt::Debug,
+    ops::{ControlFlow, RangeInclusive},
+    path::Path,
+    sync::Arc,
+};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
 
 #[derive(Clone)]
 pub struct Partition {
     pub path: Option<Arc<Path>>,
-    // TODO
-    // pub occupied: Byte,
     pub mount_point: Option<Arc<Path>>,
     pub(crate) kind: PartitionKind,
+    /// The partition's number in the table on disk, which is `None` until it's committed.
+    pub(crate) number: Option<u32>,
+    pub(crate) partition_type: PartitionType,
     pub(crate) name: (Arc<str>, Vec<Arc<str>>),
     pub(crate) bounds: (RangeInclusive<i64>, Vec<RangeInclusive<i64>>),
     pub(crate) fs: (Option<FileSystem>, Vec<Option<FileSystem>>),
+    pub(crate) bootable: (bool, Vec<bool>),
+    /// The flags other than the boot flag, which is kept in `bootable`, and the flags set or
+    /// cleared since.
+    pub(crate) flags: (Vec<PartitionFlag>, Vec<(PartitionFlag, bool)>),
+    pub(crate) type_guid: Option<Guid>,
+    /// The GPT partition GUID, known for partitions on disk, and the GUIDs set since.
+    pub(crate) uuid: (Option<Guid>, Vec<Guid>),
+    pub(crate) content: Option<ContentHint>,
+    /// A note kept in the GPT name field or loaded from a sidecar file.
+    pub(crate) note: Option<Arc<str>>,
+    fs_label: Option<Arc<str>>,
+    used: Option<u64>,
+    booted_esp: bool,
+    /// The device the partition is on.
+    device: Arc<Path>,
     sector_size: u64,
 }
 
@@ -24,11 +52,54 @@ impl Debug for Partition {
             .field("name", &self.name())
             .field("bounds", self.bounds())
             .field("fs", &self.fs())
+            .field("fs_label", &self.fs_label)
+            .field("bootable", &self.is_bootable())
+            .field("flags", &self.flags())
+            .field("type_guid", &self.type_guid)
+            .field("uuid", &self.uuid())
+            .field("content", &self.content_hint())
+            .field("note", &self.note)
+            .field("used", &self.used)
+            .field("booted_esp", &self.booted_esp)
             .field("kind", &self.kind)
+            .field("number", &self.number)
+            .field("partition_type", &self.partition_type)
             .finish()
     }
 }
 
+/// The position and size of a region of a device, as returned by [`Partition::geometry`].
+///
+/// Ends are inclusive, so a region from sector 0 to 0 is one sector long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Geometry {
+    pub start_sector: i64,
+    pub end_sector: i64,
+    pub length_sectors: u64,
+    pub start_byte: u64,
+    /// The last byte of the region.
+    pub end_byte: u64,
+    pub size_byte: u64,
+}
+
+impl Geometry {
+    /// Get the geometry of the given bounds **in sectors** on a device with the given sector
+    /// size.
+    pub fn new(bounds: &RangeInclusive<i64>, sector_size: u64) -> Self {
+        let length_sectors = (bounds.end() - bounds.start() + 1).max(0) as u64;
+        let start_byte = *bounds.start() as u64 * sector_size;
+        let size_byte = length_sectors * sector_size;
+        Self {
+            start_sector: *bounds.start(),
+            end_sector: *bounds.end(),
+            length_sectors,
+            start_byte,
+            end_byte: (start_byte + size_byte).saturating_sub(1),
+            size_byte,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub(crate) enum PartitionKind {
     /// A partition that concretely exists
@@ -39,6 +110,30 @@ pub(crate) enum PartitionKind {
     Hidden,
 }
 
+/// Get the index the partition at `index` among partitions of the given kinds has once the hidden
+/// ones are left out. A hidden partition gets the index it would have if it were shown again.
+pub(crate) fn public_index(kinds: impl IntoIterator<Item = PartitionKind>, index: usize) -> usize {
+    kinds
+        .into_iter()
+        .take(index)
+        .filter(|kind| *kind != PartitionKind::Hidden)
+        .count()
+}
+
+/// Get the index among partitions of the given kinds of the one with the given index once the
+/// hidden ones are left out, or `None` if there are too few.
+pub(crate) fn internal_index(
+    kinds: impl IntoIterator<Item = PartitionKind>,
+    public: usize,
+) -> Option<usize> {
+    kinds
+        .into_iter()
+        .enumerate()
+        .filter(|(_, kind)| *kind != PartitionKind::Hidden)
+        .nth(public)
+        .map(|(index, _)| index)
+}
+
 impl Partition {
     pub fn name(&self) -> &str {
         self.name.1.last().unwrap_or(&self.name.0).as_ref()
@@ -53,61 +148,597 @@ impl Partition {
         self.fs.1.last().copied().unwrap_or(self.fs.0)
     }
 
+    /// The partition's number in the table on disk, e.g. 3 for `/dev/sda3`, or `None` if it isn't
+    /// on disk yet.
+    ///
+    /// Unlike its index, the number stays the same as other partitions are added and removed,
+    /// except that removing a logical partition renumbers the logical partitions after it.
+    pub fn number(&self) -> Option<u32> {
+        self.number
+    }
+
+    /// Whether the partition is a primary, extended or logical one. Only MBR tables have the
+    /// latter two, so every other partition is primary.
+    pub fn partition_type(&self) -> PartitionType {
+        self.partition_type
+    }
+
+    /// Whether the partition has the legacy MBR active/boot flag set.
+    ///
+    /// This is always `false` for partitions on non-msdos disks.
+    pub fn is_bootable(&self) -> bool {
+        self.bootable.1.last().copied().unwrap_or(self.bootable.0)
+    }
+
+    /// The flags set on the partition, including the [boot flag](Partition::is_bootable).
+    ///
+    /// This is empty for partitions from a [degraded](crate::Device::is_degraded) device's
+    /// backup GPT, whose flags aren't known.
+    pub fn flags(&self) -> Vec<PartitionFlag> {
+        let mut flags = self.flags.0.clone();
+        for (flag, state) in &self.flags.1 {
+            flags.retain(|f| f != flag);
+            if *state {
+                flags.push(*flag);
+            }
+        }
+        if self.is_bootable() {
+            flags.push(PartitionFlag::Boot);
+        }
+        flags.sort();
+        flags
+    }
+
+    /// The partition's note, e.g. "backup target, do not delete", if it has one. See
+    /// [`Device::set_partition_note`](crate::Device::set_partition_note).
+    ///
+    /// Partitions with notes are treated as in use, so the [safety level](crate::SafetyLevel)
+    /// guards them against destructive changes.
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    /// The partition's GPT partition GUID, its PARTUUID, as referred to by `root=PARTUUID=`. See
+    /// [`Device::set_partition_uuid`](crate::Device::set_partition_uuid).
+    ///
+    /// This is `None` on tables other than GPT, and for new partitions that weren't given one,
+    /// whose GUID is picked by libparted when they're committed.
+    pub fn uuid(&self) -> Option<Guid> {
+        self.uuid.1.last().copied().or(self.uuid.0)
+    }
+
+    /// The GPT partition type GUID, if the partition is on a GPT disk.
+    pub fn type_guid(&self) -> Option<Guid> {
+        self.type_guid
+    }
+
+    /// The label of the file system on the partition, as probed by udev when the device was
+    /// opened.
+    pub fn fs_label(&self) -> Option<&str> {
+        self.fs_label.as_deref()
+    }
+
+    /// What the partition appears to hold, if it has no recognized file system.
+    ///
+    /// This is determined when the device is opened, so it's `None` for new partitions.
+    pub fn content_hint(&self) -> Option<ContentHint> {
+        self.content.filter(|_| self.fs().is_none())
+    }
+
+    /// The space used by the partition's file system, which it can't be shrunk below.
+    ///
+    /// This is determined when the device is opened, for mounted file systems and unmounted
+    /// ext2/3/4 ones. It's `None` for other file systems, new partitions, and partitions with a
+    /// pending file system change.
+    pub fn used_space(&self) -> Option<Byte> {
+        self.used
+            .filter(|_| self.fs.1.is_empty())
+            .map(Byte::from_u64)
+    }
+
+    /// Find everything that would stop working if the partition were deleted or reformatted:
+    /// mounts, swap, device-mapper and RAID devices built on it, and `/etc/fstab` and
+    /// `/etc/crypttab` entries referring to it.
+    ///
+    /// This is always empty for partitions that don't exist yet.
+    pub fn dependents(&self) -> Vec<Dependent> {
+        self.path
+            .as_deref()
+            .map(|path| dependents::find(path, self.mount_point.as_deref()))
+            .unwrap_or_default()
+    }
+
     pub fn mounted(&self) -> bool {
         self.mount_point.is_some()
     }
 
+    /// Whether this is the EFI system partition the machine booted from, according to the UEFI
+    /// `BootCurrent` variable. Such partitions are treated as in use even when unmounted, since
+    /// wiping them leaves the machine unbootable.
+    pub fn is_booted_esp(&self) -> bool {
+        self.booted_esp
+    }
+
     pub fn size(&self) -> Byte {
-        let bounds = self.bounds();
-        Byte::from_u64((bounds.end() - bounds.start()) as u64 * self.sector_size)
+        Byte::from_u64(self.geometry().size_byte)
+    }
+
+    /// The position and size of the partition in both sectors and bytes.
+    pub fn geometry(&self) -> Geometry {
+        Geometry::new(self.bounds(), self.sector_size)
+    }
+
+    /// Open the partition's contents for reading, or writing too, within its
+    /// [bounds](Partition::bounds).
+    ///
+    /// The partition is opened through its device rather than its own device node, so new
+    /// partitions can be opened as soon as they're committed. Until then, they hold whatever was
+    /// there before.
+    pub fn open_io(&self, mode: ReadWriteMode) -> std::io::Result<PartitionIo> {
+        let geometry = self.geometry();
+        PartitionIo::open(&self.device, geometry.start_byte, geometry.size_byte, mode)
+    }
+
+    /// Hash the partition's contents, e.g. to check that a clone matches or that a resize left
+    /// the data intact.
+    ///
+    /// `progress` is called after every chunk, and the checksum is cancelled if it returns
+    /// [`ControlFlow::Break`].
+    pub fn checksum(
+        &self,
+        algorithm: ChecksumAlgorithm,
+        progress: impl FnMut(ChecksumProgress) -> ControlFlow<()>,
+    ) -> Result<Digest, ChecksumError> {
+        let io = self.open_io(ReadWriteMode::Read)?;
+        let len = io.len();
+        checksum::checksum(io, len, algorithm, progress)
+    }
+
+    /// Write a raw image, such as a bootloader or firmware blob, to the start of the partition,
+    /// optionally reading back every chunk to check it was written correctly.
+    ///
+    /// This is meant for small partitions without a file system, so it refuses to write over a
+    /// file system, LUKS volume, LVM physical volume or RAID member, or to a partition that's in
+    /// use. The rest of the partition after the image is left as it is. The partition has to be on
+    /// disk, without pending changes to its bounds, so that the image ends up where it will stay.
+    ///
+    /// Without `O_DIRECT`, the read-back may be served from the page cache rather than the
+    /// device, as with [`CopyOptions::verify`].
+    pub fn write_raw_image(
+        &self,
+        path: impl AsRef<Path>,
+        verify: bool,
+    ) -> Result<(), RawImageError> {
+        if self.kind != PartitionKind::Real || !self.bounds.1.is_empty() {
+            return Err(RawImageError::NotOnDisk);
+        }
+        if let Some(fs) = self.fs() {
+            return Err(RawImageError::HasFileSystem(fs));
+        }
+        if let Some(hint @ (ContentHint::Luks | ContentHint::LvmPv | ContentHint::MdRaid)) =
+            self.content_hint()
+        {
+            return Err(RawImageError::HasVolume(hint));
+        }
+        if self.mounted() || !self.dependents().is_empty() {
+            return Err(RawImageError::InUse);
+        }
+
+        let path = path.as_ref();
+        let image = std::fs::metadata(path)?.len();
+        let geometry = self.geometry();
+        if image > geometry.size_byte {
+            return Err(RawImageError::TooLarge {
+                image,
+                partition: geometry.size_byte,
+            });
+        }
+
+        copy::copy(
+            Region { path, offset: 0 },
+            Region {
+                path: &self.device,
+                offset: geometry.start_byte,
+            },
+            image,
+            &CopyOptions::new().verify(verify),
+            |_| ControlFlow::Continue(()),
+        )?;
+        Ok(())
+    }
+
+    /// The position and size of the partition on disk, before any pending changes.
+    pub(crate) fn on_disk_geometry(&self) -> Geometry {
+        Geometry::new(&self.bounds.0, self.sector_size)
+    }
+
+    /// Whether the partition has pending changes to its name, bounds, file system, flags or
+    /// PARTUUID.
+    pub(crate) fn is_modified(&self) -> bool {
+        !(self.name.1.is_empty()
+            && self.bounds.1.is_empty()
+            && self.fs.1.is_empty()
+            && self.bootable.1.is_empty()
+            && self.flags.1.is_empty()
+            && self.uuid.1.is_empty())
+    }
+
+    /// Stage setting or clearing a flag.
+    pub(crate) fn push_flag(&mut self, flag: PartitionFlag, state: bool) {
+        if flag == PartitionFlag::Boot {
+            self.bootable.1.push(state);
+        } else {
+            self.flags.1.push((flag, state));
+        }
+    }
+
+    /// Undo the last staged change to a flag.
+    pub(crate) fn pop_flag(&mut self, flag: PartitionFlag) {
+        if flag == PartitionFlag::Boot {
+            self.bootable.1.pop();
+        } else {
+            self.flags.1.pop();
+        }
+    }
+
+    /// The amount of this partition's data whose position changes if it is given the given
+    /// bounds, compared to its committed bounds.
+    pub(crate) fn bytes_to_move(&self, bounds: &RangeInclusive<i64>) -> u64 {
+        let original = &self.bounds.0;
+        if self.kind != PartitionKind::Real || bounds.start() == original.start() {
+            return 0;
+        }
+        let sectors = (bounds.end() - bounds.start()).min(original.end() - original.start()) + 1;
+        sectors as u64 * self.sector_size
     }
 
     pub(crate) fn undo_all_changes(&mut self) {
         self.name.1.clear();
         self.bounds.1.clear();
         self.fs.1.clear();
+        self.bootable.1.clear();
+        self.flags.1.clear();
+        self.uuid.1.clear();
     }
 
     pub(crate) fn from_libparted(
         value: libparted::Partition,
+        device: Arc<Path>,
         sector_size: u64,
         mount_info: Option<&MountInfo>,
+        msdos: bool,
+        type_guid: Option<Guid>,
+        uuid: Option<Guid>,
     ) -> Self {
         let path = value.get_path().map(Arc::from);
-        Self {
+        let mut partition = Self {
             path,
             mount_point: mount_info.map(|m| Arc::from(m.dest.as_ref())),
             kind: PartitionKind::Real,
+            number: u32::try_from(value.num()).ok(),
+            partition_type: match value.type_get_name() {
+                "extended" => PartitionType::Extended,
+                "logical" => PartitionType::Logical,
+                _ => PartitionType::Primary,
+            },
             name: (value.name().unwrap_or_default().into(), Vec::new()),
             bounds: (value.geom_start()..=value.geom_end(), Vec::new()),
             fs: (
-                #[allow(clippy::unwrap_used, reason = "statically impossible")]
-                value.fs_type_name().map(|name| name.parse().unwrap()),
+                // libparted knows file systems partner doesn't, which are left unknown
+                value.fs_type_name().and_then(|name| name.parse().ok()),
                 Vec::new(),
             ),
+            bootable: (
+                msdos && value.get_flag(libparted::PartitionFlag::PED_PARTITION_BOOT),
+                Vec::new(),
+            ),
+            flags: (
+                PartitionFlag::iter()
+                    .filter(|f| *f != PartitionFlag::Boot)
+                    .filter(|f| value.is_flag_available((*f).into()) && value.get_flag((*f).into()))
+                    .collect(),
+                Vec::new(),
+            ),
+            type_guid,
+            uuid: (uuid, Vec::new()),
+            content: None,
+            note: None,
+            fs_label: None,
+            used: None,
+            booted_esp: false,
+            device,
+            sector_size,
+        };
+        partition.probe();
+        partition
+    }
+
+    /// A real partition read from a backup GPT, whose file system is unknown.
+    #[allow(
+        clippy::too_many_arguments,
+        reason = "these are the fields of a backup GPT entry"
+    )]
+    pub(crate) fn recovered(
+        path: Option<Arc<Path>>,
+        mount_info: Option<&MountInfo>,
+        number: u32,
+        name: Arc<str>,
+        bounds: RangeInclusive<i64>,
+        type_guid: Guid,
+        uuid: Guid,
+        device: Arc<Path>,
+        sector_size: u64,
+    ) -> Self {
+        let mut partition = Self {
+            path,
+            mount_point: mount_info.map(|m| Arc::from(m.dest.as_ref())),
+            kind: PartitionKind::Real,
+            number: Some(number),
+            name: (name, Vec::new()),
+            bounds: (bounds, Vec::new()),
+            fs: (None, Vec::new()),
+            bootable: (false, Vec::new()),
+            flags: (Vec::new(), Vec::new()),
+            type_guid: Some(type_guid),
+            partition_type: PartitionType::Primary,
+            uuid: (Some(uuid), Vec::new()),
+            content: None,
+            note: None,
+            fs_label: None,
+            used: None,
+            booted_esp: false,
+            device,
             sector_size,
+        };
+        partition.probe();
+        partition
+    }
+
+    /// Fill in what can be learned about the partition by looking at its contents.
+    fn probe(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        self.fs_label = read_fs_label(path);
+        self.used = usage::used_bytes(path, self.mount_point.as_deref());
+        self.booted_esp = efi::booted_partition()
+            .is_some_and(|booted| path.canonicalize().is_ok_and(|p| p == booted));
+        if self.fs().is_none() {
+            self.content = content::scan(path).ok();
         }
     }
 
+    /// Replace what was learned about the partition's mount and usage with a newer status.
+    pub(crate) fn set_status(&mut self, status: &PartitionStatus) {
+        self.mount_point = status.mount_point.clone();
+        self.used = status.used.map(|used| used.as_u64());
+    }
+
     pub(crate) fn new(
         name: Arc<str>,
         bounds: RangeInclusive<i64>,
         fs: Option<FileSystem>,
+        device: Arc<Path>,
         sector_size: u64,
     ) -> Self {
         Self {
             path: None,
             mount_point: None,
             kind: PartitionKind::Virtual,
+            number: None,
+            partition_type: PartitionType::Primary,
             name: (name, Vec::new()),
             bounds: (bounds, Vec::new()),
             fs: (fs, Vec::new()),
+            bootable: (false, Vec::new()),
+            flags: (Vec::new(), Vec::new()),
+            type_guid: None,
+            uuid: (None, Vec::new()),
+            content: None,
+            note: None,
+            fs_label: None,
+            used: None,
+            booted_esp: false,
+            device,
             sector_size,
         }
     }
 }
 
-#[derive(Display, EnumString, Debug, Clone, Copy)]
+/// Find the file system label of the partition at the given path among the symlinks udev
+/// creates in `/dev/disk/by-label`.
+fn read_fs_label(path: &Path) -> Option<Arc<str>> {
+    let path = path.canonicalize().ok()?;
+    std::fs::read_dir("/dev/disk/by-label")
+        .ok()?
+        .flatten()
+        .find(|entry| entry.path().canonicalize().is_ok_and(|p| p == path))
+        .map(|entry| unescape_udev(&entry.file_name().to_string_lossy()).into())
+}
+
+/// Undo udev's `\xNN` escaping of characters that aren't allowed in symlink names.
+fn unescape_udev(name: &str) -> String {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'\\'
+            && let Some(hex) = tail.strip_prefix(b"x").and_then(|t| t.get(..2))
+            && let Ok(hex) = std::str::from_utf8(hex)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            bytes.push(byte);
+            rest = &tail[3..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The maximum length of a partition name, in UTF-16 code units.
+///
+/// GPT stores partition names as 36 UTF-16LE code units, so characters outside the basic
+/// multilingual plane take up two.
+pub const MAX_NAME_LEN: usize = 36;
+
+/// An error from [`Partition::write_raw_image`].
+#[derive(Debug, thiserror::Error)]
+pub enum RawImageError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Copy(#[from] CopyError),
+    #[error("the partition isn't on disk yet, or has pending changes to its bounds")]
+    NotOnDisk,
+    #[error("the partition holds a {0} file system")]
+    HasFileSystem(FileSystem),
+    #[error("the partition holds a {0} volume")]
+    HasVolume(ContentHint),
+    #[error("the partition is in use")]
+    InUse,
+    #[error("the image is {image} bytes, but the partition only has {partition}")]
+    TooLarge { image: u64, partition: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum NameError {
+    #[error("name is {0} UTF-16 code units long, but the maximum is {max}", max = MAX_NAME_LEN)]
+    TooLong(usize),
+    #[error("name contains a NUL character")]
+    ContainsNul,
+}
+
+/// Check that the given name can be stored in a partition table.
+pub fn validate_name(name: &str) -> Result<(), NameError> {
+    if name.contains('\0') {
+        return Err(NameError::ContainsNul);
+    }
+
+    let len = name.encode_utf16().count();
+    if len > MAX_NAME_LEN {
+        return Err(NameError::TooLong(len));
+    }
+
+    Ok(())
+}
+
+/// Make the given name valid by stripping control characters and truncating it to
+/// [`MAX_NAME_LEN`].
+///
+/// Truncation never splits a character, so the result may be shorter than the limit.
+pub fn sanitize_name(name: &str) -> String {
+    let mut len = 0;
+    name.chars()
+        .filter(|c| !c.is_control())
+        .take_while(|c| {
+            len += c.len_utf16();
+            len <= MAX_NAME_LEN
+        })
+        .collect()
+}
+
+/// Where an MBR partition is in the table, as returned by [`Partition::partition_type`].
+///
+/// An MBR holds at most four primary partitions. To have more, one of them can be an extended
+/// partition, a container for any number of logical partitions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Display, Serialize, Deserialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "kebab-case")]
+pub enum PartitionType {
+    #[default]
+    Primary,
+    Extended,
+    Logical,
+}
+
+impl From<PartitionType> for libparted::PartitionType {
+    fn from(value: PartitionType) -> Self {
+        match value {
+            PartitionType::Primary => Self::PED_PARTITION_NORMAL,
+            PartitionType::Extended => Self::PED_PARTITION_EXTENDED,
+            PartitionType::Logical => Self::PED_PARTITION_LOGICAL,
+        }
+    }
+}
+
+/// A partition flag, as returned by [`Partition::flags`] and set with
+/// [`Device::set_partition_flag`](crate::Device::set_partition_flag).
+///
+/// Displays as the name `parted` uses for the flag, e.g. `esp` or `msftres`.
+#[derive(
+    Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
+#[strum(serialize_all = "lowercase")]
+pub enum PartitionFlag {
+    /// The legacy MBR active flag. See [`Partition::is_bootable`].
+    Boot,
+    /// An EFI system partition.
+    Esp,
+    /// An LVM physical volume.
+    Lvm,
+    /// A member of a software RAID array.
+    Raid,
+    /// Swap space.
+    Swap,
+    /// A partition operating systems shouldn't mount or show.
+    Hidden,
+    /// A Microsoft reserved partition.
+    #[strum(serialize = "msftres")]
+    MsftReserved,
+}
+
+impl PartitionFlag {
+    /// Whether partitions on the given type of partition table, as named by libparted, can have
+    /// this flag.
+    pub fn supported_by(&self, disk_type: &str) -> bool {
+        match disk_type {
+            "msdos" => *self != Self::MsftReserved,
+            // libparted treats the GPT boot flag as the ESP type
+            "gpt" => *self != Self::Boot,
+            _ => false,
+        }
+    }
+
+    /// Whether the flag is stored as the partition's type on GPT, so setting it replaces the
+    /// others that are.
+    pub(crate) fn is_gpt_type(&self) -> bool {
+        matches!(
+            self,
+            Self::Esp | Self::Lvm | Self::Raid | Self::Swap | Self::MsftReserved
+        )
+    }
+}
+
+impl Serialize for PartitionFlag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for PartitionFlag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<PartitionFlag> for libparted::PartitionFlag {
+    fn from(value: PartitionFlag) -> Self {
+        match value {
+            PartitionFlag::Boot => Self::PED_PARTITION_BOOT,
+            PartitionFlag::Esp => Self::PED_PARTITION_ESP,
+            PartitionFlag::Lvm => Self::PED_PARTITION_LVM,
+            PartitionFlag::Raid => Self::PED_PARTITION_RAID,
+            PartitionFlag::Swap => Self::PED_PARTITION_SWAP,
+            PartitionFlag::Hidden => Self::PED_PARTITION_HIDDEN,
+            PartitionFlag::MsftReserved => Self::PED_PARTITION_MSFT_RESERVED,
+        }
+    }
+}
+
+#[derive(Display, EnumString, EnumIter, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[strum(serialize_all = "kebab-case")]
 pub enum FileSystem {
     Btrfs,
@@ -124,9 +755,163 @@ pub enum FileSystem {
     Xfs,
 }
 
+impl FileSystem {
+    /// The name the kernel uses for this file system when mounting, if it can be mounted.
+    pub(crate) fn kernel_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Btrfs => Some("btrfs"),
+            Self::Exfat => Some("exfat"),
+            Self::Ext2 => Some("ext2"),
+            Self::Ext4 => Some("ext4"),
+            Self::F2fs => Some("f2fs"),
+            Self::Fat16 | Self::Fat32 => Some("vfat"),
+            Self::Jfs => Some("jfs"),
+            Self::LinuxSwap => None,
+            Self::Ntfs => Some("ntfs3"),
+            Self::Xfs => Some("xfs"),
+        }
+    }
+}
+
+impl Serialize for FileSystem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileSystem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
 impl From<FileSystem> for libparted::FileSystemType<'_> {
     fn from(value: FileSystem) -> Self {
         #[allow(clippy::unwrap_used, reason = "statically impossible")]
         Self::get(&value.to_string()).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{PartitionKind, internal_index, public_index};
+    use proptest::prelude::*;
+
+    #[test]
+    fn hidden_partitions_are_left_out_before_the_index() {
+        use PartitionKind::*;
+        let kinds = [Real, Hidden, Virtual, Hidden, Real];
+        let public: Vec<_> = (0..kinds.len()).map(|i| public_index(kinds, i)).collect();
+        assert_eq!(public, [0, 1, 1, 2, 2]);
+        let internal: Vec<_> = (0..4).map(|i| internal_index(kinds, i)).collect();
+        assert_eq!(internal, [Some(0), Some(2), Some(4), None]);
+    }
+
+    /// A stand-in for a staged change that adds or removes partitions, as undo sees it.
+    #[derive(Debug)]
+    enum Edit {
+        New(usize),
+        /// A removal, with the partition taken out of the list if it was a virtual one.
+        Remove(usize, Option<(u32, PartitionKind)>),
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        New(usize),
+        Remove(usize),
+        Undo,
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            any::<usize>().prop_map(Op::New),
+            any::<usize>().prop_map(Op::Remove),
+            Just(Op::Undo),
+        ]
+    }
+
+    /// Get the IDs of the partitions that aren't hidden, in order.
+    fn shown(partitions: &[(u32, PartitionKind)]) -> Vec<u32> {
+        partitions
+            .iter()
+            .filter(|(_, kind)| *kind != PartitionKind::Hidden)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    proptest! {
+        /// Stages and undoes new partitions and removals the way the device does, checking that
+        /// an undone change is described with the index its partition has before and after.
+        #[test]
+        fn undone_changes_keep_their_public_index(
+            n_real in 0..8u32,
+            ops in prop::collection::vec(op(), 0..64),
+        ) {
+            let mut partitions: Vec<_> = (0..n_real).map(|id| (id, PartitionKind::Real)).collect();
+            let mut next_id = n_real;
+            let mut edits = Vec::new();
+            for op in ops {
+                match op {
+                    Op::New(position) => {
+                        let index = position % (partitions.len() + 1);
+                        partitions.insert(index, (next_id, PartitionKind::Virtual));
+                        next_id += 1;
+                        edits.push(Edit::New(index));
+                    }
+                    Op::Remove(position) => {
+                        let shown: Vec<_> = (0..partitions.len())
+                            .filter(|i| partitions[*i].1 != PartitionKind::Hidden)
+                            .collect();
+                        if shown.is_empty() {
+                            continue;
+                        }
+                        let index = shown[position % shown.len()];
+                        let removed = match partitions[index].1 {
+                            PartitionKind::Virtual => Some(partitions.remove(index)),
+                            _ => {
+                                partitions[index].1 = PartitionKind::Hidden;
+                                None
+                            }
+                        };
+                        edits.push(Edit::Remove(index, removed));
+                    }
+                    Op::Undo => {
+                        let Some(edit) = edits.pop() else {
+                            continue;
+                        };
+                        let kinds = || partitions.iter().map(|(_, kind)| *kind);
+                        match edit {
+                            Edit::New(index) => {
+                                let public = public_index(kinds(), index);
+                                let id = partitions[index].0;
+                                prop_assert_eq!(shown(&partitions).get(public).copied(), Some(id));
+                                prop_assert_eq!(partitions[index].1, PartitionKind::Virtual);
+                                partitions.remove(index);
+                            }
+                            Edit::Remove(index, removed) => {
+                                let public = public_index(kinds(), index);
+                                let id = match removed {
+                                    Some(partition) => {
+                                        partitions.insert(index, partition);
+                                        partition.0
+                                    }
+                                    None => {
+                                        prop_assert_eq!(
+                                            partitions[index].1,
+                                            PartitionKind::Hidden
+                                        );
+                                        partitions[index].1 = PartitionKind::Real;
+                                        partitions[index].0
+                                    }
+                                };
+                                prop_assert_eq!(shown(&partitions).get(public).copied(), Some(id));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
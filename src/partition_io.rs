@@ -0,0 +1,113 @@
+//! Standard IO on a partition's contents, bounded to its extent on the device.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+/// How [`Partition::open_io`](crate::Partition::open_io) opens a partition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadWriteMode {
+    #[default]
+    Read,
+    ReadWrite,
+}
+
+/// A partition's contents, opened with [`Partition::open_io`](crate::Partition::open_io).
+///
+/// Positions are relative to the start of the partition. Reads stop at its end, and writes past
+/// it write nothing, so nothing outside the partition can be touched.
+#[derive(Debug)]
+pub struct PartitionIo {
+    file: File,
+    /// The offset of the partition on the device **in bytes**.
+    start: u64,
+    len: u64,
+    position: u64,
+}
+
+impl PartitionIo {
+    pub(crate) fn open(
+        device: &Path,
+        start: u64,
+        len: u64,
+        mode: ReadWriteMode,
+    ) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(mode == ReadWriteMode::ReadWrite)
+            .open(device)?;
+        Ok(Self {
+            file,
+            start,
+            len,
+            position: 0,
+        })
+    }
+
+    /// The size of the partition in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Flush the written data to the device.
+    pub fn sync(&self) -> std::io::Result<()> {
+        self.file.sync_data()
+    }
+
+    /// The part of a buffer of the given length that fits in the partition from the current
+    /// position.
+    fn remaining(&self, buf_len: usize) -> usize {
+        self.len.saturating_sub(self.position).min(buf_len as u64) as usize
+    }
+}
+
+impl Read for PartitionIo {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.remaining(buf.len());
+        let read = self
+            .file
+            .read_at(&mut buf[..len], self.start + self.position)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for PartitionIo {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.remaining(buf.len());
+        let written = self
+            .file
+            .write_at(&buf[..len], self.start + self.position)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for PartitionIo {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        let Some(position) = position else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek to a negative position",
+            ));
+        };
+        self.position = position;
+        Ok(position)
+    }
+}
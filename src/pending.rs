@@ -0,0 +1,33 @@
+use byte_unit::Byte;
+
+/// Limits on the pending changes of a [`Device`](crate::Device), for programmatic users that
+/// generate changes and want them bounded.
+///
+/// Staging a change that would exceed a limit fails with
+/// [`Error::LimitExceeded`](crate::Error::LimitExceeded). No limits are set by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChangeLimits {
+    /// The maximum number of pending changes.
+    pub max_changes: Option<usize>,
+    /// The maximum amount of data that the pending resizes move.
+    pub max_bytes_to_move: Option<Byte>,
+}
+
+/// The number of pending changes of each type, returned by
+/// [`Device::pending_counts`](crate::Device::pending_counts).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PendingCounts {
+    pub renames: usize,
+    pub new_partitions: usize,
+    pub removals: usize,
+    pub resizes: usize,
+    pub moves: usize,
+    /// Boot flag, hybrid MBR, GPT repair, GPT entry array, new table, and format changes.
+    pub other: usize,
+}
+
+impl PendingCounts {
+    pub fn total(&self) -> usize {
+        self.renames + self.new_partitions + self.removals + self.resizes + self.moves + self.other
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{Device, DeviceSnapshot, Error, Guid, snapshot::Fnv1a};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+};
+
+/// A layout to bring a device to, together with the layout it was planned against, as returned by
+/// [`Device::plan`](crate::Device::plan).
+///
+/// Plans are applied with [`Device::apply_plan`](crate::Device::apply_plan), which refuses to
+/// apply a plan to a device whose on-disk layout differs from the one the plan was made for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ChangePlan {
+    /// The device the plan is for.
+    pub device: DeviceTarget,
+    /// The [content hash](DeviceSnapshot::content_hash) of the on-disk layout of the device the
+    /// plan was made for.
+    pub source: u64,
+    /// The layout after applying the plan.
+    pub target: DeviceSnapshot,
+}
+
+impl ChangePlan {
+    /// Get a hash of the plan that is stable across runs, e.g. for approving a specific plan.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Find the device the plan is for among the given ones.
+    ///
+    /// Fails with [`Error::PlanTargetNotFound`] if none match, and with
+    /// [`Error::AmbiguousPlanTarget`] listing the candidates if several do, e.g. USB enclosures
+    /// that all report the same serial number.
+    pub fn find_device<'d, 'a>(
+        &self,
+        devices: &'d mut [Device<'a>],
+    ) -> Result<&'d mut Device<'a>, Error> {
+        let matching = devices
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| self.device.matches(d))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        match matching[..] {
+            [] => Err(Error::PlanTargetNotFound(self.device.clone())),
+            [i] => Ok(&mut devices[i]),
+            _ => Err(Error::AmbiguousPlanTarget(
+                matching
+                    .iter()
+                    .map(|i| devices[*i].path().to_path_buf())
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// How a [`ChangePlan`] identifies the device it's for.
+///
+/// Kernel names like `/dev/sda` can change between boots, so plans made with
+/// [`Device::plan`](crate::Device::plan) use the device's WWN or serial number where it has one.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeviceTarget {
+    Path(PathBuf),
+    Serial(String),
+    /// The device's World Wide Name.
+    Wwn(String),
+}
+
+impl DeviceTarget {
+    /// Identify the given device as stably as possible.
+    pub fn of(device: &Device) -> Self {
+        if let Some(wwn) = device.wwn() {
+            Self::Wwn(wwn)
+        } else if let Some(serial) = device.serial() {
+            Self::Serial(serial)
+        } else {
+            Self::Path(device.path().to_path_buf())
+        }
+    }
+
+    pub fn matches(&self, device: &Device) -> bool {
+        // eMMC boot areas share their device's serial number, so they'd match its plans too
+        if device.is_emmc_area() {
+            return false;
+        }
+        match self {
+            // the device may have been opened through a symlink to it
+            Self::Path(path) => {
+                device.path() == path
+                    || device.alias() == Some(path)
+                    || path.canonicalize().is_ok_and(|p| p == device.path())
+            }
+            Self::Serial(serial) => device.serial().as_ref() == Some(serial),
+            Self::Wwn(wwn) => device.wwn().is_some_and(|w| w.eq_ignore_ascii_case(wwn)),
+        }
+    }
+}
+
+impl Display for DeviceTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Serial(serial) => write!(f, "serial {serial}"),
+            Self::Wwn(wwn) => write!(f, "WWN {wwn}"),
+        }
+    }
+}
+
+/// A reference to a partition that stays valid across renumbering and reboots, for systems
+/// outside partner to keep track of partitions by, as returned by
+/// [`Device::partition_ref`](crate::Device::partition_ref).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PartitionRef {
+    pub disk: DeviceTarget,
+    pub partition: PartitionKey,
+}
+
+/// How a [`PartitionRef`] identifies a partition on its disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartitionKey {
+    /// The GPT partition GUID, which survives the partition being renumbered.
+    Uuid(Guid),
+    /// The partition number, for MBR disks, whose partitions have no GUID.
+    Number(u32),
+}
+
+impl Display for PartitionRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.partition {
+            PartitionKey::Uuid(uuid) => write!(f, "partition {uuid} on {}", self.disk),
+            PartitionKey::Number(number) => write!(f, "partition {number} on {}", self.disk),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::{
+    ChangePlan, DeviceSnapshot, DeviceTarget, Error, FileSystem, PartitionSnapshot,
+    commit::overlaps, validate_name,
+};
+use std::ops::RangeInclusive;
+
+/// A device that exists only as a [`DeviceSnapshot`], for planning layouts without access to the
+/// device itself.
+///
+/// This supports the subset of [`Device`](crate::Device)'s changes that only touch the partition
+/// table. The resulting [plan](PlannedDevice::plan) can then be applied on the machine the
+/// snapshot was taken on with [`Device::apply_plan`](crate::Device::apply_plan).
+#[derive(Debug, Clone)]
+pub struct PlannedDevice {
+    source: DeviceSnapshot,
+    layout: DeviceSnapshot,
+    /// The layouts before each staged change, for undoing.
+    history: Vec<DeviceSnapshot>,
+}
+
+impl From<DeviceSnapshot> for PlannedDevice {
+    fn from(value: DeviceSnapshot) -> Self {
+        Self::new(value)
+    }
+}
+
+impl PlannedDevice {
+    /// Start planning from the on-disk layout in the given snapshot.
+    pub fn new(snapshot: DeviceSnapshot) -> Self {
+        Self {
+            layout: snapshot.clone(),
+            source: snapshot,
+            history: Vec::new(),
+        }
+    }
+
+    /// The layout with all staged changes.
+    pub fn snapshot(&self) -> &DeviceSnapshot {
+        &self.layout
+    }
+
+    /// The partitions with all staged changes, ordered by their start sector.
+    pub fn partitions(&self) -> &[PartitionSnapshot] {
+        &self.layout.partitions
+    }
+
+    pub fn n_changes(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Record the staged layout as a plan for the device the snapshot was taken of.
+    pub fn plan(&self) -> ChangePlan {
+        ChangePlan {
+            device: DeviceTarget::Path(self.source.path.clone()),
+            source: self.source.content_hash(),
+            target: self.layout.clone(),
+        }
+    }
+
+    /// Stage a change to the layout, keeping the previous one if it fails.
+    fn stage(
+        &mut self,
+        change: impl FnOnce(&mut DeviceSnapshot) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut layout = self.layout.clone();
+        change(&mut layout)?;
+        self.history
+            .push(std::mem::replace(&mut self.layout, layout));
+        Ok(())
+    }
+
+    /// Check that the given bounds fit on the device without overlapping any partition other
+    /// than the one at `except`.
+    fn check_bounds(
+        layout: &DeviceSnapshot,
+        bounds: &RangeInclusive<i64>,
+        except: Option<usize>,
+    ) -> Result<(), Error> {
+        if *bounds.start() < 0
+            || bounds.start() > bounds.end()
+            || *bounds.end() >= layout.length as i64
+        {
+            return Err(Error::OutOfBounds);
+        }
+        match layout
+            .partitions
+            .iter()
+            .enumerate()
+            .find(|(i, p)| Some(*i) != except && overlaps(&(p.start..=p.end), bounds))
+        {
+            Some((i, _)) => Err(Error::OverlapsExisting(i)),
+            None => Ok(()),
+        }
+    }
+
+    /// Create a new partition with the given name, (optionally) file system, and bounds **in
+    /// sectors**.
+    pub fn new_partition(
+        &mut self,
+        name: &str,
+        fs: Option<FileSystem>,
+        bounds: RangeInclusive<i64>,
+    ) -> Result<(), Error> {
+        validate_name(name)?;
+        self.stage(|layout| {
+            Self::check_bounds(layout, &bounds, None)?;
+            layout.partitions.push(PartitionSnapshot {
+                name: name.into(),
+                start: *bounds.start(),
+                end: *bounds.end(),
+                fs,
+                bootable: false,
+                type_guid: None,
+            });
+            layout.partitions.sort_by_key(|p| p.start);
+            Ok(())
+        })
+    }
+
+    /// Remove the partition at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn remove_partition(&mut self, index: usize) -> Result<(), Error> {
+        self.stage(|layout| {
+            layout.partitions.remove(index);
+            Ok(())
+        })
+    }
+
+    /// Change the bounds of the partition at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn resize_partition(
+        &mut self,
+        index: usize,
+        bounds: RangeInclusive<i64>,
+    ) -> Result<(), Error> {
+        self.stage(|layout| {
+            Self::check_bounds(layout, &bounds, Some(index))?;
+            let partition = &mut layout.partitions[index];
+            partition.start = *bounds.start();
+            partition.end = *bounds.end();
+            Ok(())
+        })
+    }
+
+    /// Rename the partition at the given index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn change_partition_name(&mut self, index: usize, name: &str) -> Result<(), Error> {
+        validate_name(name)?;
+        self.stage(|layout| {
+            layout.partitions[index].name = name.into();
+            Ok(())
+        })
+    }
+
+    /// Set the legacy MBR boot flag on the partition at the given index, clearing it from all
+    /// other partitions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of bounds.
+    pub fn set_bootable(&mut self, index: usize) -> Result<(), Error> {
+        assert!(
+            index < self.layout.partitions.len(),
+            "partition index out of bounds"
+        );
+        self.stage(|layout| {
+            if layout.disk_type.as_deref() != Some("msdos") {
+                return Err(Error::UnsupportedByTable);
+            }
+            for (i, partition) in layout.partitions.iter_mut().enumerate() {
+                partition.bootable = i == index;
+            }
+            Ok(())
+        })
+    }
+
+    /// Undo the last staged change, returning whether there was one.
+    pub fn undo_change(&mut self) -> bool {
+        match self.history.pop() {
+            Some(layout) => {
+                self.layout = layout;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn undo_all_changes(&mut self) {
+        self.layout = self.source.clone();
+        self.history.clear();
+    }
+}
@@ -0,0 +1,126 @@
+//! Ejecting and spinning down devices, using CD-ROM and SCSI ioctls.
+//!
+//! USB mass storage and SATA devices accept SCSI commands through the kernel's translation
+//! layers, so `START STOP UNIT` covers most removable media that aren't optical drives.
+
+use nix::libc::{c_int, c_uchar, c_uint, c_ushort, c_void};
+use std::{fs::File, os::fd::AsRawFd, path::Path};
+
+const SG_INTERFACE_ID: c_int = b'S' as c_int;
+const SG_DXFER_NONE: c_int = -1;
+const TIMEOUT_MS: c_uint = 30_000;
+
+const ALLOW_MEDIUM_REMOVAL: u8 = 0x1E;
+const START_STOP_UNIT: u8 = 0x1B;
+/// The `LOEJ` bit of `START STOP UNIT`, which ejects the medium when `START` is clear.
+const LOAD_EJECT: u8 = 0x02;
+
+/// `sg_io_hdr` from `<scsi/sg.h>`.
+#[repr(C)]
+struct SgIoHdr {
+    interface_id: c_int,
+    dxfer_direction: c_int,
+    cmd_len: c_uchar,
+    mx_sb_len: c_uchar,
+    iovec_count: c_ushort,
+    dxfer_len: c_uint,
+    dxferp: *mut c_void,
+    cmdp: *const c_uchar,
+    sbp: *mut c_uchar,
+    timeout: c_uint,
+    flags: c_uint,
+    pack_id: c_int,
+    usr_ptr: *mut c_void,
+    status: c_uchar,
+    masked_status: c_uchar,
+    msg_status: c_uchar,
+    sb_len_wr: c_uchar,
+    host_status: c_ushort,
+    driver_status: c_ushort,
+    resid: c_int,
+    duration: c_uint,
+    info: c_uint,
+}
+
+nix::ioctl_readwrite_bad!(sg_io, 0x2285, SgIoHdr);
+nix::ioctl_none_bad!(cdrom_eject, 0x5309);
+
+/// Send a SCSI command that transfers no data.
+fn scsi_command(file: &File, cdb: [u8; 6]) -> std::io::Result<()> {
+    let mut sense = [0u8; 32];
+    let mut header = SgIoHdr {
+        interface_id: SG_INTERFACE_ID,
+        dxfer_direction: SG_DXFER_NONE,
+        cmd_len: cdb.len() as c_uchar,
+        mx_sb_len: sense.len() as c_uchar,
+        iovec_count: 0,
+        dxfer_len: 0,
+        dxferp: std::ptr::null_mut(),
+        cmdp: cdb.as_ptr(),
+        sbp: sense.as_mut_ptr(),
+        timeout: TIMEOUT_MS,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    };
+    // SAFETY: the header and the buffers it points to outlive the call
+    unsafe { sg_io(file.as_raw_fd(), &mut header) }?;
+
+    if header.status != 0 || header.host_status != 0 || header.driver_status != 0 {
+        return Err(std::io::Error::other(format!(
+            "SCSI command {:#04x} failed with status {:#04x}",
+            cdb[0], header.status
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn eject(path: &Path) -> std::io::Result<()> {
+    let file = File::open(path)?;
+
+    // SAFETY: CDROMEJECT takes no argument
+    if unsafe { cdrom_eject(file.as_raw_fd()) }.is_ok() {
+        return Ok(());
+    }
+
+    scsi_command(&file, [ALLOW_MEDIUM_REMOVAL, 0, 0, 0, 0, 0])?;
+    scsi_command(&file, [START_STOP_UNIT, 0, 0, 0, LOAD_EJECT, 0])
+}
+
+pub(crate) fn standby(path: &Path) -> std::io::Result<()> {
+    scsi_command(&File::open(path)?, [START_STOP_UNIT, 0, 0, 0, 0, 0])
+}
+
+/// Read a boolean sysfs attribute of the device at the given path, relative to its
+/// `/sys/block` directory.
+fn sysfs_flag(path: &Path, attribute: &str) -> Option<bool> {
+    let name = path.file_name()?;
+    let value = std::fs::read_to_string(Path::new("/sys/block").join(name).join(attribute)).ok()?;
+    Some(value.trim() == "1")
+}
+
+/// Whether the kernel considers the device at the given path removable.
+pub(crate) fn is_removable(path: &Path) -> bool {
+    sysfs_flag(path, "removable").unwrap_or(false)
+}
+
+/// Whether the kernel has the device at the given path read-only, e.g. an SD card with its write
+/// protect switch set, or a CD-ROM.
+pub(crate) fn is_read_only(path: &Path) -> bool {
+    sysfs_flag(path, "ro").unwrap_or(false)
+}
+
+/// Whether the kernel considers the device at the given path rotational, i.e. a hard drive.
+/// Devices the kernel doesn't know about are assumed to be.
+pub(crate) fn is_rotational(path: &Path) -> bool {
+    sysfs_flag(path, "queue/rotational").unwrap_or(true)
+}
@@ -0,0 +1,184 @@
+//! Checks for devices meant to boot on any UEFI machine, such as portable installs and rescue
+//! drives.
+//!
+//! Firmware has no boot entry for such a device, so it falls back to loading
+//! `\EFI\BOOT\BOOT<arch>.EFI` from an EFI system partition on it. That only works if the firmware
+//! can find and read the partition, which the UEFI specification only guarantees for FAT on GPT
+//! and MBR disks.
+
+use crate::{Device, FileSystem, PartitionFlag, dps::Arch};
+use byte_unit::Byte;
+use std::sync::Arc;
+
+/// The smallest FAT32 file system: it needs at least 65525 clusters, which is about 33 MiB with
+/// the smallest clusters.
+const MIN_FAT32_SIZE: u64 = 33 * 1024 * 1024;
+/// The free space needed for a fallback loader, which is enough for shim and GRUB or any of the
+/// smaller boot managers, but not for a unified kernel image.
+const MIN_LOADER_SPACE: u64 = 16 * 1024 * 1024;
+
+/// The path firmware loads on removable media for the given architecture, as the UEFI
+/// specification names it.
+pub fn fallback_path(arch: Arch) -> &'static str {
+    match arch {
+        Arch::X86 => r"\EFI\BOOT\BOOTIA32.EFI",
+        Arch::X86_64 => r"\EFI\BOOT\BOOTX64.EFI",
+        Arch::Arm => r"\EFI\BOOT\BOOTARM.EFI",
+        Arch::Arm64 => r"\EFI\BOOT\BOOTAA64.EFI",
+        Arch::RiscV64 => r"\EFI\BOOT\BOOTRISCV64.EFI",
+    }
+}
+
+/// A reason a device can't boot as a portable UEFI drive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemovableIssue {
+    /// The device has no partition table, or one firmware can't read.
+    UnsupportedTable(Option<Arc<str>>),
+    /// There is neither an ESP nor a FAT partition firmware could use as one.
+    NoEsp,
+    /// The ESP at the given index doesn't have a FAT file system, so firmware can't read it.
+    NotFat {
+        index: usize,
+        fs: Option<FileSystem>,
+    },
+    /// The FAT32 ESP at the given index is too small to hold a FAT32 file system.
+    TooSmall { index: usize, size: Byte },
+    /// The ESP at the given index is mounted and has no fallback loader for the architecture.
+    NoFallbackLoader { index: usize, arch: Arch },
+    /// The ESP at the given index has no fallback loader, and too little free space to add one.
+    NoRoomForLoader { index: usize, free: Byte },
+}
+
+/// A reason a device may not boot on typical firmware, although it meets the UEFI
+/// specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemovableAdvisory {
+    /// The ESP at the given index is FAT16, which the specification allows on removable media,
+    /// but some firmware only reads FAT32.
+    NotFat32 { index: usize },
+    /// The FAT partition at the given index isn't typed as an ESP. Most firmware boots from it
+    /// anyway, but some only looks at ESPs.
+    UntypedEsp { index: usize },
+    /// There are several ESPs, and firmware differs in which one it loads from. Holds the index
+    /// of the ones after the first.
+    SeveralEsps(Vec<usize>),
+    /// The device has 4096 byte sectors, which a lot of firmware can't boot from.
+    FourKNative,
+}
+
+/// The result of [`check`].
+#[derive(Debug, Clone, Default)]
+pub struct RemovableReport {
+    pub issues: Vec<RemovableIssue>,
+    pub advisories: Vec<RemovableAdvisory>,
+}
+
+impl RemovableReport {
+    /// Whether the device meets the specification for booting as a portable UEFI drive.
+    pub fn is_bootable(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Check the pending layout of a device intended to boot on any UEFI machine of the given
+/// architecture.
+///
+/// The fallback loader is only looked for if the ESP is mounted, and its free space is only
+/// known for mounted file systems.
+pub fn check(device: &Device, arch: Arch) -> RemovableReport {
+    let mut report = RemovableReport::default();
+    if device.is_4k_native() {
+        report.advisories.push(RemovableAdvisory::FourKNative);
+    }
+    // superfloppies show up as "loop", and are read like any other FAT medium
+    if !matches!(device.disk_type(), Some("gpt" | "msdos" | "loop")) {
+        report
+            .issues
+            .push(RemovableIssue::UnsupportedTable(device.disk_type.clone()));
+        return report;
+    }
+
+    let esps = device
+        .partitions()
+        .enumerate()
+        .filter(|(_, p)| p.flags().contains(&PartitionFlag::Esp))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let index = match esps.split_first() {
+        Some((first, rest)) => {
+            if !rest.is_empty() {
+                report
+                    .advisories
+                    .push(RemovableAdvisory::SeveralEsps(rest.to_vec()));
+            }
+            *first
+        }
+        None => {
+            let Some(index) = device
+                .partitions()
+                .position(|p| matches!(p.fs(), Some(FileSystem::Fat16 | FileSystem::Fat32)))
+            else {
+                report.issues.push(RemovableIssue::NoEsp);
+                return report;
+            };
+            // a superfloppy has no partition type to set
+            if device.disk_type() != Some("loop") {
+                report
+                    .advisories
+                    .push(RemovableAdvisory::UntypedEsp { index });
+            }
+            index
+        }
+    };
+
+    let esp = device
+        .partitions()
+        .nth(index)
+        .expect("the ESP was found among the partitions");
+    match esp.fs() {
+        Some(FileSystem::Fat32) => {
+            if esp.size().as_u64() < MIN_FAT32_SIZE {
+                report.issues.push(RemovableIssue::TooSmall {
+                    index,
+                    size: esp.size(),
+                });
+            }
+        }
+        Some(FileSystem::Fat16) => report
+            .advisories
+            .push(RemovableAdvisory::NotFat32 { index }),
+        fs => {
+            report.issues.push(RemovableIssue::NotFat { index, fs });
+            return report;
+        }
+    }
+
+    // vfat looks names up case-insensitively, so the path can be checked as the spec spells it
+    let has_loader = esp.mount_point.as_deref().map(|mount_point| {
+        mount_point
+            .join(
+                fallback_path(arch)
+                    .trim_start_matches('\\')
+                    .replace('\\', "/"),
+            )
+            .is_file()
+    });
+    let free = esp
+        .used_space()
+        .map(|used| esp.size().as_u64().saturating_sub(used.as_u64()));
+    match (has_loader, free) {
+        (Some(true), _) => {}
+        (_, Some(free)) if free < MIN_LOADER_SPACE => {
+            report.issues.push(RemovableIssue::NoRoomForLoader {
+                index,
+                free: Byte::from_u64(free),
+            });
+        }
+        (Some(false), _) => report
+            .issues
+            .push(RemovableIssue::NoFallbackLoader { index, arch }),
+        (None, _) => {}
+    }
+
+    report
+}
@@ -0,0 +1,227 @@
+//! Conversion between [`LayoutSpec`]s and `systemd-repart` partition definition files.
+//!
+//! Only the `Type=`, `Label=`, `Format=`, `SizeMinBytes=`, `SizeMaxBytes=` and `Weight=` keys of
+//! the `[Partition]` section are supported; anything else is ignored on import.
+
+use crate::{
+    FileSystem, FirstPartitionStart, LayoutSpec, PartitionSpec,
+    dps::{Arch, Role},
+};
+use byte_unit::Byte;
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RepartError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{file}: unsupported partition type `{value}`")]
+    UnknownType { file: String, value: String },
+    #[error("{file}: unsupported format `{value}`")]
+    UnknownFormat { file: String, value: String },
+    #[error("{file}: invalid value `{value}` for {key}")]
+    InvalidValue {
+        file: String,
+        key: String,
+        value: String,
+    },
+}
+
+fn type_name(role: Option<Role>) -> String {
+    match role {
+        Some(Role::Root(arch)) => format!("root-{arch}"),
+        Some(role) => role.to_string(),
+        None => "linux-generic".into(),
+    }
+}
+
+fn parse_type(value: &str) -> Option<Option<Role>> {
+    let role = match value {
+        "linux-generic" => return Some(None),
+        "root" => Role::Root(Arch::native()?),
+        "home" => Role::Home,
+        "srv" => Role::Srv,
+        "var" => Role::Var,
+        "tmp" => Role::Tmp,
+        "swap" => Role::Swap,
+        "esp" => Role::Esp,
+        "xbootldr" => Role::Xbootldr,
+        _ => {
+            let arch = match value.strip_prefix("root-")? {
+                "x86" => Arch::X86,
+                "x86-64" => Arch::X86_64,
+                "arm" => Arch::Arm,
+                "arm64" => Arch::Arm64,
+                "riscv64" => Arch::RiscV64,
+                _ => return None,
+            };
+            Role::Root(arch)
+        }
+    };
+    Some(Some(role))
+}
+
+fn format_name(fs: FileSystem) -> Option<&'static str> {
+    match fs {
+        FileSystem::Btrfs => Some("btrfs"),
+        FileSystem::Ext4 => Some("ext4"),
+        FileSystem::Fat16 | FileSystem::Fat32 => Some("vfat"),
+        FileSystem::LinuxSwap => Some("swap"),
+        FileSystem::Xfs => Some("xfs"),
+        _ => None,
+    }
+}
+
+fn parse_format(value: &str) -> Option<FileSystem> {
+    match value {
+        "btrfs" => Some(FileSystem::Btrfs),
+        "ext4" => Some(FileSystem::Ext4),
+        "vfat" => Some(FileSystem::Fat32),
+        "swap" => Some(FileSystem::LinuxSwap),
+        "xfs" => Some(FileSystem::Xfs),
+        _ => None,
+    }
+}
+
+/// Parse a size as accepted by `systemd-repart`, with an optional base-1024 suffix.
+fn parse_size(value: &str) -> Option<Byte> {
+    let (number, multiplier) = match value.as_bytes().last()? {
+        b'K' => (&value[..value.len() - 1], 1 << 10),
+        b'M' => (&value[..value.len() - 1], 1 << 20),
+        b'G' => (&value[..value.len() - 1], 1 << 30),
+        b'T' => (&value[..value.len() - 1], 1 << 40),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(multiplier)
+        .map(Byte::from_u64)
+}
+
+/// Render a layout as `systemd-repart` definition files, returned as pairs of file name and
+/// contents.
+///
+/// File names are prefixed with the partition's position, since `systemd-repart` orders
+/// partitions by file name. Formats it doesn't support are left out.
+pub fn export(spec: &LayoutSpec) -> Vec<(String, String)> {
+    spec.partitions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let type_name = type_name(p.role);
+            let mut lines = vec!["[Partition]".to_string(), format!("Type={type_name}")];
+            lines.extend(p.label.as_ref().map(|label| format!("Label={label}")));
+            lines.extend(
+                p.fs.and_then(format_name)
+                    .map(|format| format!("Format={format}")),
+            );
+            lines.extend(
+                p.size_min
+                    .map(|min| format!("SizeMinBytes={}", min.as_u64())),
+            );
+            lines.extend(
+                p.size_max
+                    .map(|max| format!("SizeMaxBytes={}", max.as_u64())),
+            );
+            lines.push(format!("Weight={}", p.weight));
+            lines.push(String::new());
+
+            (format!("{:02}-{type_name}.conf", i * 10), lines.join("\n"))
+        })
+        .collect()
+}
+
+/// Parse a layout from `systemd-repart` definition files, given as pairs of file name and
+/// contents. Partitions are ordered by file name, as `systemd-repart` does.
+pub fn import(
+    files: impl IntoIterator<Item = (String, String)>,
+) -> Result<LayoutSpec, RepartError> {
+    let mut files = files.into_iter().collect::<Vec<_>>();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let partitions = files
+        .into_iter()
+        .map(|(file, contents)| {
+            let mut spec = PartitionSpec::default();
+            let mut in_partition = false;
+
+            for line in contents.lines().map(str::trim) {
+                if line.starts_with(['#', ';']) {
+                    continue;
+                }
+                if line.starts_with('[') {
+                    in_partition = line == "[Partition]";
+                    continue;
+                }
+                let Some((key, value)) = line.split_once('=') else {
+                    continue;
+                };
+                if !in_partition {
+                    continue;
+                }
+                let (key, value) = (key.trim(), value.trim());
+                let invalid = || RepartError::InvalidValue {
+                    file: file.clone(),
+                    key: key.into(),
+                    value: value.into(),
+                };
+
+                match key {
+                    "Type" => {
+                        spec.role = parse_type(value).ok_or_else(|| RepartError::UnknownType {
+                            file: file.clone(),
+                            value: value.into(),
+                        })?;
+                    }
+                    "Label" => spec.label = Some(value.into()),
+                    "Format" => {
+                        spec.fs = Some(parse_format(value).ok_or_else(|| {
+                            RepartError::UnknownFormat {
+                                file: file.clone(),
+                                value: value.into(),
+                            }
+                        })?);
+                    }
+                    "SizeMinBytes" => spec.size_min = Some(parse_size(value).ok_or_else(invalid)?),
+                    "SizeMaxBytes" => spec.size_max = Some(parse_size(value).ok_or_else(invalid)?),
+                    "Weight" => spec.weight = value.parse().map_err(|_| invalid())?,
+                    _ => {}
+                }
+            }
+
+            Ok(spec)
+        })
+        .collect::<Result<_, RepartError>>()?;
+
+    Ok(LayoutSpec {
+        partitions,
+        first_start: FirstPartitionStart::default(),
+        reserve_end: None,
+    })
+}
+
+/// Read all `*.conf` files in a directory with [`import`].
+pub fn import_dir(path: impl AsRef<Path>) -> Result<LayoutSpec, RepartError> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "conf")
+            && let Some(name) = path.file_name()
+        {
+            files.push((
+                name.to_string_lossy().into_owned(),
+                std::fs::read_to_string(&path)?,
+            ));
+        }
+    }
+
+    import(files)
+}
+
+/// Write the files produced by [`export`] into a directory, which must exist.
+pub fn export_dir(spec: &LayoutSpec, path: impl AsRef<Path>) -> std::io::Result<()> {
+    for (name, contents) in export(spec) {
+        std::fs::write(path.as_ref().join(name), contents)?;
+    }
+    Ok(())
+}
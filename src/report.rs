@@ -0,0 +1,354 @@
+//! Shareable reports of device layouts and their pending changes, e.g. for attaching to a change
+//! review before anyone approves repartitioning a machine.
+
+use crate::{
+    Change, Device, DeviceSummary, FreeRegion, Geometry, ProjectedPartition, ProjectedStatus,
+    size::{self, FormatOptions},
+};
+use byte_unit::Byte;
+
+const SVG_WIDTH: f64 = 800.0;
+const SVG_MARGIN: f64 = 70.0;
+const BAR_HEIGHT: f64 = 28.0;
+const DEVICE_HEIGHT: f64 = 110.0;
+/// Partitions narrower than this many pixels aren't labeled, since the label wouldn't fit.
+const MIN_LABEL_WIDTH: f64 = 60.0;
+
+/// What [`render`] produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::Display)]
+#[strum(serialize_all = "kebab-case")]
+pub enum Format {
+    /// A picture with a bar per device for its current layout and one for the layout after
+    /// committing.
+    Svg,
+    /// A standalone page with the pictures, a table of partitions, and the pending changes of
+    /// each device.
+    Html,
+    /// The tables and pending changes, for pasting into a ticket.
+    Markdown,
+}
+
+/// What a report shows of a device.
+struct DeviceReport {
+    summary: DeviceSummary,
+    partitions: Vec<ProjectedPartition>,
+    free: Vec<FreeRegion>,
+    changes: Vec<Change>,
+}
+
+impl DeviceReport {
+    fn new(device: &Device) -> Self {
+        Self {
+            summary: device.summary(),
+            partitions: device.preview(),
+            free: device.free_regions(),
+            changes: device.changes(),
+        }
+    }
+
+    /// The partitions on disk now, with what committing does to them.
+    fn current(&self) -> impl Iterator<Item = (&ProjectedPartition, &Geometry)> {
+        self.partitions
+            .iter()
+            .filter_map(|p| Some((p, p.on_disk.as_ref()?)))
+    }
+
+    /// The partitions there will be after committing.
+    fn after(&self) -> impl Iterator<Item = &ProjectedPartition> {
+        self.partitions
+            .iter()
+            .filter(|p| p.status != ProjectedStatus::Removed)
+    }
+
+    /// The rows of the report's table: every partition, including removed ones, and the free
+    /// regions there will be after committing, in the order they're on the device.
+    fn rows(&self) -> Vec<Row<'_>> {
+        let mut rows = self
+            .partitions
+            .iter()
+            .map(Row::Partition)
+            .chain(self.free.iter().map(Row::Free))
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|row| match row {
+            Row::Partition(p) => p.view.geometry.start_sector,
+            Row::Free(f) => f.geometry.start_sector,
+        });
+        rows
+    }
+}
+
+enum Row<'a> {
+    Partition(&'a ProjectedPartition),
+    Free(&'a FreeRegion),
+}
+
+impl Row<'_> {
+    /// The cells of the row: device node, name, file system, start sector, size and status.
+    fn cells(&self) -> [String; 6] {
+        match self {
+            Self::Partition(p) => [
+                node(p),
+                p.view.name.clone(),
+                p.view.fs.map(|fs| fs.to_string()).unwrap_or_default(),
+                p.view.geometry.start_sector.to_string(),
+                size(&p.view.geometry),
+                status(p),
+            ],
+            Self::Free(f) => [
+                String::new(),
+                String::new(),
+                String::new(),
+                f.geometry.start_sector.to_string(),
+                size(&f.geometry),
+                "free".into(),
+            ],
+        }
+    }
+}
+
+/// The partition's device node, which new partitions don't have yet.
+fn node(partition: &ProjectedPartition) -> String {
+    partition
+        .view
+        .path
+        .as_deref()
+        .map_or_else(|| "(new)".into(), |path| path.display().to_string())
+}
+
+fn size(geometry: &Geometry) -> String {
+    size::format(Byte::from_u64(geometry.size_byte), FormatOptions::new())
+}
+
+fn status(partition: &ProjectedPartition) -> String {
+    match (partition.status, &partition.on_disk) {
+        (ProjectedStatus::Unchanged, _) => "unchanged".into(),
+        (ProjectedStatus::New, _) => "new".into(),
+        (ProjectedStatus::Removed, _) => "removed".into(),
+        (ProjectedStatus::Modified, Some(on_disk)) if *on_disk != partition.view.geometry => {
+            format!(
+                "modified (was {} at sector {})",
+                size(on_disk),
+                on_disk.start_sector
+            )
+        }
+        (ProjectedStatus::Modified, _) => "modified".into(),
+    }
+}
+
+fn color(status: ProjectedStatus) -> &'static str {
+    match status {
+        ProjectedStatus::Unchanged => "#7f9fbf",
+        ProjectedStatus::New => "#6fbf6f",
+        ProjectedStatus::Modified => "#e0a040",
+        ProjectedStatus::Removed => "#d06060",
+    }
+}
+
+/// Escape text for SVG and HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escape text for a Markdown table cell.
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Render a report of the given devices' layouts and pending changes.
+///
+/// The report shows the devices as they are when this is called, so it should be made after
+/// staging the changes to review and before committing them.
+pub fn render<'d, 'a: 'd>(
+    devices: impl IntoIterator<Item = &'d Device<'a>>,
+    format: Format,
+) -> String {
+    let reports = devices
+        .into_iter()
+        .map(DeviceReport::new)
+        .collect::<Vec<_>>();
+    match format {
+        Format::Svg => svg(&reports),
+        Format::Html => html(&reports),
+        Format::Markdown => markdown(&reports),
+    }
+}
+
+fn svg(reports: &[DeviceReport]) -> String {
+    let height = DEVICE_HEIGHT * reports.len() as f64;
+    let mut lines = vec![format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{SVG_WIDTH}" height="{height}" font-family="sans-serif" font-size="12">"#
+    )];
+    for (i, report) in reports.iter().enumerate() {
+        lines.push(format!(
+            r#"<g transform="translate(0 {})">"#,
+            DEVICE_HEIGHT * i as f64
+        ));
+        lines.extend(svg_device(report));
+        lines.push("</g>".into());
+    }
+    lines.push("</svg>".into());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// The elements picturing a device, `DEVICE_HEIGHT` high.
+fn svg_device(report: &DeviceReport) -> Vec<String> {
+    let device_size = report.summary.size.max(1) as f64;
+    let scale = (SVG_WIDTH - SVG_MARGIN - 10.0) / device_size;
+    let rect = |geometry: &Geometry, y: f64, fill: &str, title: &str, label: &str| {
+        let x = SVG_MARGIN + geometry.start_byte as f64 * scale;
+        let width = (geometry.size_byte as f64 * scale).max(1.0);
+        let mut element = format!(
+            r##"<rect x="{x:.1}" y="{y}" width="{width:.1}" height="{BAR_HEIGHT}" fill="{fill}" stroke="#333"><title>{}</title></rect>"##,
+            escape(title)
+        );
+        if width >= MIN_LABEL_WIDTH && !label.is_empty() {
+            element += &format!(
+                r#"<text x="{:.1}" y="{}">{}</text>"#,
+                x + 4.0,
+                y + BAR_HEIGHT / 2.0 + 4.0,
+                escape(label)
+            );
+        }
+        element
+    };
+    let title =
+        |p: &ProjectedPartition| format!("{} {} {}", node(p), size(&p.view.geometry), status(p));
+
+    let now_y = 30.0;
+    let after_y = now_y + BAR_HEIGHT + 8.0;
+    let mut lines = vec![
+        format!(
+            r#"<text x="0" y="16" font-weight="bold">{}</text>"#,
+            escape(&report.summary.to_string())
+        ),
+        format!(
+            r#"<text x="0" y="{}">now</text>"#,
+            now_y + BAR_HEIGHT / 2.0 + 4.0
+        ),
+        format!(
+            r#"<text x="0" y="{}">after</text>"#,
+            after_y + BAR_HEIGHT / 2.0 + 4.0
+        ),
+    ];
+    for y in [now_y, after_y] {
+        lines.push(format!(
+            r##"<rect x="{SVG_MARGIN}" y="{y}" width="{:.1}" height="{BAR_HEIGHT}" fill="#eee" stroke="#333"/>"##,
+            SVG_WIDTH - SVG_MARGIN - 10.0
+        ));
+    }
+    for (p, on_disk) in report.current() {
+        lines.push(rect(
+            on_disk,
+            now_y,
+            color(p.status),
+            &title(p),
+            &p.view.name,
+        ));
+    }
+    for p in report.after() {
+        lines.push(rect(
+            &p.view.geometry,
+            after_y,
+            color(p.status),
+            &title(p),
+            &p.view.name,
+        ));
+    }
+    lines
+}
+
+fn html(reports: &[DeviceReport]) -> String {
+    let mut lines = vec![
+        "<!DOCTYPE html>".to_string(),
+        "<html>".into(),
+        "<head>".into(),
+        r#"<meta charset="utf-8">"#.into(),
+        "<title>Partition layout report</title>".into(),
+        "<style>body { font-family: sans-serif; } table { border-collapse: collapse; } \
+         td, th { border: 1px solid #999; padding: 2px 6px; text-align: left; }</style>"
+            .into(),
+        "</head>".into(),
+        "<body>".into(),
+    ];
+    for report in reports {
+        lines.push(format!(
+            "<h2>{}</h2>",
+            escape(&report.summary.path.display().to_string())
+        ));
+        lines.push(format!("<p>{}</p>", escape(&report.summary.to_string())));
+        lines.push(format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{SVG_WIDTH}" height="{DEVICE_HEIGHT}" font-family="sans-serif" font-size="12">"#
+        ));
+        lines.extend(svg_device(report));
+        lines.push("</svg>".into());
+
+        lines.push("<table>".into());
+        lines.push(
+            "<tr><th>Partition</th><th>Name</th><th>File system</th><th>Start</th>\
+             <th>Size</th><th>Status</th></tr>"
+                .into(),
+        );
+        for row in report.rows() {
+            let cells = row
+                .cells()
+                .map(|cell| format!("<td>{}</td>", escape(&cell)));
+            lines.push(format!("<tr>{}</tr>", cells.concat()));
+        }
+        lines.push("</table>".into());
+
+        if report.changes.is_empty() {
+            lines.push("<p>No pending changes.</p>".into());
+        } else {
+            lines.push("<h3>Pending changes</h3>".into());
+            lines.push("<ol>".into());
+            lines.extend(
+                report
+                    .changes
+                    .iter()
+                    .map(|c| format!("<li>{}</li>", escape(&c.to_string()))),
+            );
+            lines.push("</ol>".into());
+        }
+    }
+    lines.push("</body>".into());
+    lines.push("</html>".into());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn markdown(reports: &[DeviceReport]) -> String {
+    let mut lines = Vec::new();
+    for report in reports {
+        lines.push(format!("## {}", report.summary.path.display()));
+        lines.push(String::new());
+        lines.push(report.summary.to_string());
+        lines.push(String::new());
+        lines.push("| Partition | Name | File system | Start | Size | Status |".into());
+        lines.push("| --- | --- | --- | --: | --: | --- |".into());
+        for row in report.rows() {
+            let cells = row.cells().map(|cell| escape_markdown(&cell));
+            lines.push(format!("| {} |", cells.join(" | ")));
+        }
+        lines.push(String::new());
+
+        if report.changes.is_empty() {
+            lines.push("No pending changes.".into());
+        } else {
+            lines.push("### Pending changes".into());
+            lines.push(String::new());
+            lines.extend(
+                report
+                    .changes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| format!("{}. {c}", i + 1)),
+            );
+        }
+        lines.push(String::new());
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,209 @@
+//! File system resizing, either with libparted's built-in file system code or with external
+//! tools.
+//!
+//! libparted's file system code is deprecated and only handles FAT, so the external tools are
+//! usually the better choice for anything else.
+
+use crate::{FileSystem, ToolError, ToolOutput, Toolchain, timer::Timer, tool};
+use libparted_sys::{
+    ped_device_get, ped_file_system_close, ped_file_system_open, ped_file_system_resize,
+    ped_geometry_destroy, ped_geometry_new,
+};
+use serde::{Deserialize, Serialize};
+use std::{ffi::CString, ops::RangeInclusive, os::unix::ffi::OsStrExt, path::Path};
+
+/// How a partition's file system is resized along with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResizeStrategy {
+    /// Resize the file system with libparted, which can also move the start of the partition.
+    #[default]
+    Libparted,
+    /// Resize the file system in place with its own tools, such as `resize2fs`. The start of the
+    /// partition must stay the same.
+    External,
+}
+
+impl ResizeStrategy {
+    /// Pick how to resize a partition with the given file system, preferring the file system's
+    /// own tools unless the start of the partition moves, which only libparted can do.
+    pub(crate) fn preferred(fs: Option<FileSystem>, moves_start: bool) -> Self {
+        match fs {
+            Some(fs)
+                if !moves_start
+                    && fs.resize_support(Self::External) != ResizeSupport::default() =>
+            {
+                Self::External
+            }
+            _ => Self::Libparted,
+        }
+    }
+}
+
+/// Which directions a file system can be resized in with a given [`ResizeStrategy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResizeSupport {
+    pub grow: bool,
+    pub shrink: bool,
+}
+
+/// What can be done to a partition with a given file system, as returned by
+/// [`FileSystem::capabilities`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FsCapabilities {
+    /// Whether partner can grow the file system with any [`ResizeStrategy`].
+    pub grow: bool,
+    /// Whether partner can shrink the file system with any [`ResizeStrategy`].
+    pub shrink: bool,
+    /// Whether the file system itself can be grown while mounted. partner only resizes unmounted
+    /// file systems, so this is for frontends to point users at the file system's own tools.
+    pub grow_online: bool,
+    /// Whether the file system itself can be shrunk while mounted.
+    pub shrink_online: bool,
+    /// Whether the partition can be moved. Moves copy the contents as they are, so this holds
+    /// for any file system.
+    pub move_: bool,
+}
+
+impl FileSystem {
+    /// Get which directions this file system can be resized in with the given strategy.
+    pub fn resize_support(self, strategy: ResizeStrategy) -> ResizeSupport {
+        let both = ResizeSupport {
+            grow: true,
+            shrink: true,
+        };
+        match (strategy, self) {
+            (ResizeStrategy::Libparted, Self::Fat16 | Self::Fat32) => both,
+            (ResizeStrategy::External, Self::Ext2 | Self::Ext4 | Self::Ntfs) => both,
+            (ResizeStrategy::External, Self::F2fs) => ResizeSupport {
+                grow: true,
+                shrink: false,
+            },
+            _ => ResizeSupport::default(),
+        }
+    }
+
+    /// Get what can be done to a partition with this file system, so frontends can rule out
+    /// changes that would be rejected, such as shrinking XFS.
+    pub fn capabilities(self) -> FsCapabilities {
+        let (libparted, external) = (
+            self.resize_support(ResizeStrategy::Libparted),
+            self.resize_support(ResizeStrategy::External),
+        );
+        FsCapabilities {
+            grow: libparted.grow || external.grow,
+            shrink: libparted.shrink || external.shrink,
+            grow_online: matches!(self, Self::Btrfs | Self::Ext4 | Self::Jfs | Self::Xfs),
+            shrink_online: matches!(self, Self::Btrfs),
+            move_: true,
+        }
+    }
+}
+
+/// The tools [`resize`] runs for the given file system.
+pub(crate) fn programs(fs: FileSystem) -> &'static [&'static str] {
+    match fs {
+        FileSystem::Ext2 | FileSystem::Ext4 => &["e2fsck", "resize2fs"],
+        FileSystem::Ntfs => &["ntfsresize"],
+        FileSystem::F2fs => &["resize.f2fs"],
+        _ => &[],
+    }
+}
+
+/// Resize the file system on the partition at the given path to `size` bytes with its own tools.
+pub(crate) fn resize(
+    toolchain: &Toolchain,
+    path: &Path,
+    fs: FileSystem,
+    size: u64,
+) -> Result<Vec<ToolOutput>, ToolError> {
+    let mut outputs = Vec::new();
+    match fs {
+        FileSystem::Ext2 | FileSystem::Ext4 => {
+            // resize2fs refuses to resize file systems that haven't just been checked
+            outputs.push(tool::run(
+                toolchain.command("e2fsck")?.args(["-f", "-p"]).arg(path),
+            )?);
+            outputs.push(tool::run(
+                toolchain
+                    .command("resize2fs")?
+                    .arg(path)
+                    .arg(format!("{}K", size / 1024)),
+            )?);
+        }
+        FileSystem::Ntfs => outputs.push(tool::run(
+            toolchain
+                .command("ntfsresize")?
+                .args(["--force", "--size"])
+                .arg(size.to_string())
+                .arg(path),
+        )?),
+        FileSystem::F2fs => outputs.push(tool::run(
+            toolchain
+                .command("resize.f2fs")?
+                // the target is in 512 byte sectors regardless of the device
+                .args(["-t", &(size / 512).to_string()])
+                .arg(path),
+        )?),
+        _ => unreachable!("resize support is checked when staging"),
+    }
+    Ok(outputs)
+}
+
+/// Resize the file system occupying the sectors `from` of the device at the given path to fill
+/// `to` with libparted, which can also move it. `progress` is called with how much of the resize
+/// is done, from 0 to 1.
+///
+/// This calls libparted directly to pass it a [`Timer`].
+pub(crate) fn resize_with_libparted(
+    path: &Path,
+    fs: FileSystem,
+    from: &RangeInclusive<i64>,
+    to: &RangeInclusive<i64>,
+    progress: impl FnMut(f64),
+) -> std::io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let timer = Timer::new(progress)?;
+    // libparted keeps one device per path, so this is the one any open `Disk` uses
+    let device = unsafe { ped_device_get(path.as_ptr()) };
+    if device.is_null() {
+        return Err(std::io::Error::other("libparted couldn't get the device"));
+    }
+    let geometry = |bounds: &RangeInclusive<i64>| {
+        let raw =
+            unsafe { ped_geometry_new(device, *bounds.start(), bounds.end() - bounds.start() + 1) };
+        if raw.is_null() {
+            Err(std::io::Error::other("libparted rejected the geometry"))
+        } else {
+            Ok(raw)
+        }
+    };
+    let from_geometry = geometry(from)?;
+    let to_geometry =
+        geometry(to).inspect_err(|_| unsafe { ped_geometry_destroy(from_geometry) })?;
+
+    let result = unsafe {
+        let raw_fs = ped_file_system_open(from_geometry);
+        if raw_fs.is_null() {
+            Err(std::io::Error::other(format!(
+                "libparted can't open the {fs} file system at sector {} to resize it",
+                from.start()
+            )))
+        } else {
+            let resized = ped_file_system_resize(raw_fs, to_geometry, timer.as_ptr());
+            ped_file_system_close(raw_fs);
+            if resized == 0 {
+                Err(std::io::Error::other(format!(
+                    "libparted failed to resize the {fs} file system"
+                )))
+            } else {
+                Ok(())
+            }
+        }
+    };
+    unsafe {
+        ped_geometry_destroy(from_geometry);
+        ped_geometry_destroy(to_geometry);
+    }
+    result
+}
@@ -0,0 +1,455 @@
+//! Sizes as users write and read them: expressions like `30GiB`, `25%` or `rest-2GiB`, for
+//! frontends and plans to parse the same way and resolve against the space they're for, and
+//! [`format`] for showing sizes the same way everywhere.
+
+use crate::{Device, FreeRegion};
+use byte_unit::Byte;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt::Display, str::FromStr};
+
+/// Binary units that sizes are displayed in when they are a whole number of them, largest first.
+const UNITS: [(&str, u32); 4] = [("TiB", 40), ("GiB", 30), ("MiB", 20), ("KiB", 10)];
+const BINARY_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+const DECIMAL_UNITS: [&str; 7] = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+/// Languages that write a decimal comma rather than a point. This is a best-effort list of the
+/// usual convention for each language, which some of their regions don't follow, e.g. Swiss
+/// German, which uses a point.
+const DECIMAL_COMMA: [&str; 46] = [
+    "af", "az", "be", "bg", "bs", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fo", "fr",
+    "gl", "hr", "hu", "hy", "id", "is", "it", "ka", "kk", "ky", "lt", "lv", "mk", "mn", "nb", "nl",
+    "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sq", "sr", "sv", "tr", "uk", "vi",
+];
+
+/// A size expression, resolved to a size with [`Expr::resolve`].
+///
+/// Parses from:
+/// - a size with an optional unit, e.g. `30GiB`, `500 MB` or `4096`, as [`Byte`] parses it
+/// - a number of sectors, e.g. `2048s`
+/// - a percentage of the [total](Context::total), e.g. `25%`
+/// - `max` or `rest`, for all of the [available](Context::available) space
+/// - `rest-` followed by any of the first three, for the available space less that, e.g.
+///   `rest-2GiB`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Bytes(Byte),
+    Sectors(u64),
+    /// A percentage, from 0 to 100.
+    Percent(u8),
+    Max,
+    /// The available space less the given size, which is never `Max` or `Rest`.
+    Rest(Box<Expr>),
+}
+
+/// What an [`Expr`] is resolved against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Context {
+    /// The sector size in bytes, which sector counts are multiplied by.
+    pub sector_size: u64,
+    /// The size percentages are of, usually the device's, as with `parted`.
+    pub total: Byte,
+    /// The space the size has to fit in, which `max` and `rest` take from.
+    pub available: Byte,
+}
+
+impl Context {
+    /// The context for a partition anywhere on the device, which has all of its
+    /// [usable sectors](Device::usable_sectors) available.
+    pub fn device(device: &Device) -> Self {
+        let usable = device.usable_sectors();
+        let sectors = (usable.end() - usable.start() + 1).max(0) as u64;
+        Self {
+            sector_size: device.sector_size(),
+            total: device.size(),
+            available: Byte::from_u64(sectors * device.sector_size()),
+        }
+    }
+
+    /// The context for a new partition in the given free region of the device.
+    pub fn free_region(device: &Device, region: &FreeRegion) -> Self {
+        Self {
+            available: Byte::from_u64(region.geometry.size_byte),
+            ..Self::device(device)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseExprError {
+    #[error("no size given")]
+    Empty,
+    #[error("invalid size `{0}`")]
+    Invalid(String),
+    #[error("percentages must be from 0 to 100")]
+    PercentOutOfRange,
+}
+
+/// An [`Expr`] that resolves to more than the available space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error(
+    "{} is more than the available {}",
+    format(*.size, FormatOptions::new()),
+    format(*.available, FormatOptions::new())
+)]
+pub struct ResolveError {
+    pub size: Byte,
+    pub available: Byte,
+}
+
+impl Expr {
+    /// Resolve the expression to a size in bytes, which may not be a whole number of sectors.
+    ///
+    /// Fails if the size, or the size taken from the rest, is more than the available space.
+    pub fn resolve(&self, context: &Context) -> Result<Byte, ResolveError> {
+        let available = context.available.as_u64();
+        let size = match self {
+            Self::Bytes(bytes) => bytes.as_u64(),
+            Self::Sectors(sectors) => sectors.saturating_mul(context.sector_size),
+            Self::Percent(percent) => {
+                (context.total.as_u64() as u128 * *percent as u128 / 100) as u64
+            }
+            Self::Max => available,
+            Self::Rest(taken) => {
+                let taken = taken.resolve(context)?;
+                available - taken.as_u64()
+            }
+        };
+        if size > available {
+            return Err(ResolveError {
+                size: Byte::from_u64(size),
+                available: context.available,
+            });
+        }
+        Ok(Byte::from_u64(size))
+    }
+
+    /// Resolve the expression to a number of sectors, rounding down to whole sectors so that the
+    /// result still fits.
+    pub fn resolve_sectors(&self, context: &Context) -> Result<u64, ResolveError> {
+        Ok(self.resolve(context)?.as_u64() / context.sector_size)
+    }
+
+    /// Parse anything but `max` and `rest`, which is all that can follow `rest-`.
+    fn parse_amount(s: &str) -> Result<Self, ParseExprError> {
+        let invalid = || ParseExprError::Invalid(s.into());
+        if let Some(percent) = s.strip_suffix('%') {
+            let percent = percent.trim().parse::<u8>().map_err(|_| invalid())?;
+            return if percent <= 100 {
+                Ok(Self::Percent(percent))
+            } else {
+                Err(ParseExprError::PercentOutOfRange)
+            };
+        }
+        if let Some(sectors) = s.strip_suffix(['s', 'S']).map(str::trim_end)
+            && !sectors.is_empty()
+            && sectors.bytes().all(|b| b.is_ascii_digit())
+        {
+            return sectors.parse().map(Self::Sectors).map_err(|_| invalid());
+        }
+        s.parse::<Byte>().map(Self::Bytes).map_err(|_| invalid())
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ParseExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseExprError::Empty);
+        }
+        if s.eq_ignore_ascii_case("max") || s.eq_ignore_ascii_case("rest") {
+            return Ok(Self::Max);
+        }
+        if let Some(rest) = s.get(..4).filter(|r| r.eq_ignore_ascii_case("rest")) {
+            let Some(taken) = s[rest.len()..].trim_start().strip_prefix('-') else {
+                return Err(ParseExprError::Invalid(s.into()));
+            };
+            return match Self::parse_amount(taken.trim()) {
+                Ok(taken) => Ok(Self::Rest(Box::new(taken))),
+                // the whole expression is invalid, not just what follows `rest-`
+                Err(ParseExprError::Invalid(_)) => Err(ParseExprError::Invalid(s.into())),
+                Err(e) => Err(e),
+            };
+        }
+        Self::parse_amount(s)
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bytes(bytes) => {
+                let bytes = bytes.as_u64();
+                match UNITS
+                    .iter()
+                    .find(|(_, shift)| bytes != 0 && bytes % (1 << shift) == 0)
+                {
+                    Some((unit, shift)) => write!(f, "{}{unit}", bytes >> shift),
+                    None => write!(f, "{bytes}B"),
+                }
+            }
+            Self::Sectors(sectors) => write!(f, "{sectors}s"),
+            Self::Percent(percent) => write!(f, "{percent}%"),
+            Self::Max => write!(f, "max"),
+            Self::Rest(taken) => write!(f, "rest-{taken}"),
+        }
+    }
+}
+
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Which units [`format`] uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Units {
+    /// Powers of 1024, e.g. GiB, as partitioning tools usually use.
+    #[default]
+    Binary,
+    /// Powers of 1000, e.g. GB, as drive vendors label drives.
+    Decimal,
+}
+
+/// Options for [`format`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    units: Units,
+    precision: usize,
+    decimal_separator: char,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            units: Units::Binary,
+            precision: 2,
+            decimal_separator: '.',
+        }
+    }
+}
+
+impl FormatOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Which units to use. Defaults to [`Units::Binary`].
+    pub fn units(mut self, units: Units) -> Self {
+        self.units = units;
+        self
+    }
+
+    /// The most decimal places to show, of which trailing zeros are left out. Defaults to 2.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// The character between the whole and fractional part. Defaults to `.`, which
+    /// [`Expr`] parses back.
+    pub fn decimal_separator(mut self, separator: char) -> Self {
+        self.decimal_separator = separator;
+        self
+    }
+
+    /// Use the decimal separator of the locale in the environment (`LC_ALL`, `LC_NUMERIC` or
+    /// `LANG`), for showing sizes to users rather than writing them where they're parsed.
+    ///
+    /// The separator is guessed from the locale's language, which is right for most locales but
+    /// not all of them.
+    pub fn locale(self) -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+        self.decimal_separator(if DECIMAL_COMMA.contains(&language) {
+            ','
+        } else {
+            '.'
+        })
+    }
+}
+
+/// Format the size in the largest unit it's at least one of, e.g. `1.5 GiB`.
+pub fn format(size: Byte, options: FormatOptions) -> String {
+    let (base, units) = match options.units {
+        Units::Binary => (1024.0, BINARY_UNITS),
+        Units::Decimal => (1000.0, DECIMAL_UNITS),
+    };
+    let mut value = size.as_u64() as f64;
+    let mut unit = 0;
+    let mut number = size.as_u64().to_string();
+    // the unit is chosen after rounding, so that e.g. 1023.999 KiB is shown as 1 MiB
+    while unit < units.len() - 1 && number.parse::<f64>().is_ok_and(|n| n >= base) {
+        value /= base;
+        unit += 1;
+        number = format!("{value:.*}", options.precision);
+    }
+
+    if number.contains('.') {
+        number.truncate(number.trim_end_matches('0').trim_end_matches('.').len());
+    }
+    format!(
+        "{} {}",
+        number.replace('.', &options.decimal_separator.to_string()),
+        units[unit]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GIB: u64 = 1 << 30;
+
+    fn context() -> Context {
+        Context {
+            sector_size: 512,
+            total: Byte::from_u64(100 * GIB),
+            available: Byte::from_u64(10 * GIB),
+        }
+    }
+
+    fn parse(s: &str) -> Expr {
+        s.parse().expect("valid size expression")
+    }
+
+    #[test]
+    fn parses_each_form() {
+        assert_eq!(parse("30GiB"), Expr::Bytes(Byte::from_u64(30 * GIB)));
+        assert_eq!(parse("500 MB"), Expr::Bytes(Byte::from_u64(500_000_000)));
+        assert_eq!(parse("4096"), Expr::Bytes(Byte::from_u64(4096)));
+        assert_eq!(parse("2048s"), Expr::Sectors(2048));
+        assert_eq!(parse("2048 S"), Expr::Sectors(2048));
+        assert_eq!(parse("25%"), Expr::Percent(25));
+        assert_eq!(parse(" max "), Expr::Max);
+        assert_eq!(parse("REST"), Expr::Max);
+        assert_eq!(
+            parse("rest-2GiB"),
+            Expr::Rest(Box::new(Expr::Bytes(Byte::from_u64(2 * GIB))))
+        );
+        assert_eq!(parse("rest - 10%"), Expr::Rest(Box::new(Expr::Percent(10))));
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        assert_eq!("".parse::<Expr>(), Err(ParseExprError::Empty));
+        assert_eq!("  ".parse::<Expr>(), Err(ParseExprError::Empty));
+        assert_eq!(
+            "101%".parse::<Expr>(),
+            Err(ParseExprError::PercentOutOfRange)
+        );
+        for invalid in [
+            "abc",
+            "-1GiB",
+            "rest2GiB",
+            "rest-max",
+            "rest-rest",
+            "s",
+            "1.5%",
+        ] {
+            assert_eq!(
+                invalid.parse::<Expr>(),
+                Err(ParseExprError::Invalid(invalid.into())),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn displays_what_it_parses() {
+        for s in [
+            "30GiB",
+            "1536KiB",
+            "1000B",
+            "2048s",
+            "25%",
+            "max",
+            "rest-2GiB",
+            "rest-5%",
+        ] {
+            assert_eq!(parse(s).to_string(), s);
+            assert_eq!(parse(&parse(s).to_string()), parse(s));
+        }
+    }
+
+    #[test]
+    fn resolves_against_the_context() {
+        let context = context();
+        let resolve = |s: &str| parse(s).resolve(&context).map(|b| b.as_u64());
+        assert_eq!(resolve("1GiB"), Ok(GIB));
+        assert_eq!(resolve("2048s"), Ok(2048 * 512));
+        assert_eq!(resolve("5%"), Ok(5 * GIB));
+        assert_eq!(resolve("max"), Ok(10 * GIB));
+        assert_eq!(resolve("rest-2GiB"), Ok(8 * GIB));
+        assert_eq!(resolve("rest-10GiB"), Ok(0));
+    }
+
+    #[test]
+    fn refuses_more_than_is_available() {
+        let context = context();
+        let too_much = ResolveError {
+            size: Byte::from_u64(11 * GIB),
+            available: Byte::from_u64(10 * GIB),
+        };
+        assert_eq!(parse("11GiB").resolve(&context), Err(too_much));
+        assert_eq!(
+            parse("11%").resolve(&context).map_err(|e| e.size),
+            Err(too_much.size)
+        );
+        assert_eq!(parse("rest-11GiB").resolve(&context), Err(too_much));
+        assert!(parse(&u64::MAX.to_string()).resolve(&context).is_err());
+        assert!(Expr::Sectors(u64::MAX).resolve(&context).is_err());
+    }
+
+    #[test]
+    fn resolves_sectors_rounding_down() {
+        let context = context();
+        assert_eq!(parse("1000B").resolve_sectors(&context), Ok(1));
+        assert_eq!(parse("1024B").resolve_sectors(&context), Ok(2));
+        assert_eq!(parse("max").resolve_sectors(&context), Ok(10 * GIB / 512));
+    }
+
+    #[test]
+    fn formats_in_the_largest_whole_unit() {
+        let format = |bytes, options| super::format(Byte::from_u64(bytes), options);
+        let options = FormatOptions::new();
+        assert_eq!(format(0, options), "0 B");
+        assert_eq!(format(1023, options), "1023 B");
+        assert_eq!(format(1536, options), "1.5 KiB");
+        assert_eq!(format(30 * GIB, options), "30 GiB");
+        assert_eq!(format(1_500_000, options.units(Units::Decimal)), "1.5 MB");
+        assert_eq!(format(1_234_567, options.precision(0)), "1 MiB");
+        assert_eq!(format(u64::MAX, options), "16 EiB");
+    }
+
+    #[test]
+    fn picks_the_unit_after_rounding() {
+        let options = FormatOptions::new();
+        assert_eq!(format(Byte::from_u64((1 << 20) - 1), options), "1 MiB");
+        assert_eq!(
+            format(Byte::from_u64(999_999), options.units(Units::Decimal)),
+            "1 MB"
+        );
+        assert_eq!(format(Byte::from_u64(1023 * 1024 + 1), options), "1023 KiB");
+    }
+
+    #[test]
+    fn uses_the_decimal_separator() {
+        let options = FormatOptions::new().decimal_separator(',');
+        assert_eq!(format(Byte::from_u64(1536), options), "1,5 KiB");
+        assert_eq!(format(Byte::from_u64(1024), options), "1 KiB");
+    }
+}
@@ -0,0 +1,250 @@
+//! A/B update schemes, where a device has two equal root file system slots and boots from one
+//! while the other is updated, as used by embedded and appliance images.
+//!
+//! Which slot to boot is kept in the GPT attribute bits of the slots, following the Chrome OS
+//! convention that boot loaders such as depthcharge and systemd-boot's boot assessment tooling
+//! also understand: a 4-bit priority, a 4-bit count of tries remaining, and a successful-boot
+//! bit.
+
+use crate::{
+    Device, FileSystem, FirstPartitionStart, LayoutSpec, PartitionKind, PartitionSpec,
+    dps::{Arch, Role},
+    gpt,
+};
+use byte_unit::Byte;
+use std::fmt::Display;
+
+const PRIORITY_SHIFT: u64 = 48;
+const TRIES_SHIFT: u64 = 52;
+const SUCCESSFUL_BIT: u64 = 1 << 56;
+const NIBBLE: u64 = 0xF;
+
+/// One of the two root file system slots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Slot {
+    A,
+    B,
+}
+
+impl Slot {
+    /// The other slot.
+    pub fn other(self) -> Self {
+        match self {
+            Self::A => Self::B,
+            Self::B => Self::A,
+        }
+    }
+
+    /// The label [`AbLayout`] gives the slot's partition, e.g. `root-a`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::A => "root-a",
+            Self::B => "root-b",
+        }
+    }
+}
+
+impl Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::A => write!(f, "a"),
+            Self::B => write!(f, "b"),
+        }
+    }
+}
+
+/// A template for an A/B layout: an optional ESP, two root slots of the same size, and a data
+/// partition taking the rest of the device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbLayout {
+    /// The size of the ESP, or `None` for devices that boot without one.
+    pub esp_size: Option<Byte>,
+    pub slot_size: Byte,
+    pub slot_fs: FileSystem,
+    pub data_fs: FileSystem,
+    /// The architecture whose root partition type the slots get.
+    pub arch: Arch,
+}
+
+impl AbLayout {
+    /// Get the layout as a [`LayoutSpec`], e.g. to apply with
+    /// [`oneshot::ensure_layout`](crate::oneshot::ensure_layout) or export for
+    /// `systemd-repart`.
+    pub fn spec(&self) -> LayoutSpec {
+        let fixed = |size: Byte| (Some(size), Some(size));
+        let mut partitions = Vec::new();
+        if let Some(size) = self.esp_size {
+            let (size_min, size_max) = fixed(size);
+            partitions.push(PartitionSpec {
+                label: Some("esp".into()),
+                role: Some(Role::Esp),
+                fs: Some(FileSystem::Fat32),
+                size_min,
+                size_max,
+                weight: 0,
+            });
+        }
+        for slot in [Slot::A, Slot::B] {
+            let (size_min, size_max) = fixed(self.slot_size);
+            partitions.push(PartitionSpec {
+                label: Some(slot.label().into()),
+                role: Some(Role::Root(self.arch)),
+                fs: Some(self.slot_fs),
+                size_min,
+                size_max,
+                weight: 0,
+            });
+        }
+        partitions.push(PartitionSpec {
+            label: Some("data".into()),
+            fs: Some(self.data_fs),
+            ..PartitionSpec::default()
+        });
+        LayoutSpec {
+            partitions,
+            first_start: FirstPartitionStart::OneMib,
+            reserve_end: None,
+        }
+    }
+}
+
+/// The boot state of a slot, kept in its GPT attribute bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SlotAttributes {
+    /// Which slot to try first, from 0 to 15. Slots with priority 0 aren't booted.
+    pub priority: u8,
+    /// How many more times the slot may be tried before it's given up on, from 0 to 15.
+    pub tries_remaining: u8,
+    /// Whether the slot has booted successfully, so it needn't count down its tries.
+    pub successful: bool,
+}
+
+impl SlotAttributes {
+    fn from_bits(bits: u64) -> Self {
+        Self {
+            priority: ((bits >> PRIORITY_SHIFT) & NIBBLE) as u8,
+            tries_remaining: ((bits >> TRIES_SHIFT) & NIBBLE) as u8,
+            successful: bits & SUCCESSFUL_BIT != 0,
+        }
+    }
+
+    /// Replace the slot bits in the given attributes, keeping the others.
+    fn to_bits(self, bits: u64) -> u64 {
+        let cleared = bits & !(NIBBLE << PRIORITY_SHIFT | NIBBLE << TRIES_SHIFT | SUCCESSFUL_BIT);
+        cleared
+            | (self.priority.min(15) as u64) << PRIORITY_SHIFT
+            | (self.tries_remaining.min(15) as u64) << TRIES_SHIFT
+            | if self.successful { SUCCESSFUL_BIT } else { 0 }
+    }
+}
+
+fn unsupported(message: &'static str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Unsupported, message)
+}
+
+/// The GPT entry number of the partition with the slot's label.
+fn slot_number(device: &Device, slot: Slot) -> std::io::Result<u32> {
+    if device.disk_type() != Some("gpt") {
+        return Err(unsupported("A/B slots need a GPT"));
+    }
+    device
+        .partitions
+        .iter()
+        .filter(|p| p.kind == PartitionKind::Real)
+        .find(|p| p.name() == slot.label())
+        .and_then(|p| p.number)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("the device has no {} partition on disk", slot.label()),
+            )
+        })
+}
+
+/// Read the boot state of the given slot from the device.
+pub fn attributes(device: &Device, slot: Slot) -> std::io::Result<SlotAttributes> {
+    let number = slot_number(device, slot)?;
+    let mut file = std::fs::File::open(device.path())?;
+    gpt::Table::read_at(&mut file, device.sector_size(), 1)?
+        .entries()
+        .find(|e| e.number == number)
+        .map(|e| SlotAttributes::from_bits(e.attributes))
+        .ok_or_else(|| unsupported("the slot's GPT entry wasn't found"))
+}
+
+/// Write the boot state of the given slot to the device.
+///
+/// This writes the partition table right away, so it fails if the device has pending changes.
+pub fn set_attributes(
+    device: &Device,
+    slot: Slot,
+    attributes: SlotAttributes,
+) -> std::io::Result<()> {
+    let number = slot_number(device, slot)?;
+    device.edit_gpt(|table| {
+        let bits = table
+            .entries()
+            .find(|e| e.number == number)
+            .map_or(0, |e| e.attributes);
+        table.set_attributes(number, attributes.to_bits(bits))
+    })
+}
+
+/// Make the given slot the one to boot next, e.g. after writing an update to it, giving it the
+/// given number of tries to boot successfully before the boot loader falls back to the other
+/// slot.
+///
+/// The other slot keeps a lower priority if it booted successfully, so it can be fallen back to,
+/// and is disabled otherwise.
+pub fn set_active(device: &Device, slot: Slot, tries: u8) -> std::io::Result<()> {
+    let other = attributes(device, slot.other())?;
+    set_attributes(
+        device,
+        slot.other(),
+        SlotAttributes {
+            priority: u8::from(other.successful),
+            ..other
+        },
+    )?;
+    set_attributes(
+        device,
+        slot,
+        SlotAttributes {
+            priority: 2,
+            tries_remaining: tries,
+            successful: false,
+        },
+    )
+}
+
+/// Record that the given slot booted successfully, so the boot loader keeps booting it without
+/// counting down its tries. Run this once the updated system is known to work.
+pub fn mark_successful(device: &Device, slot: Slot) -> std::io::Result<()> {
+    let current = attributes(device, slot)?;
+    set_attributes(
+        device,
+        slot,
+        SlotAttributes {
+            tries_remaining: 0,
+            successful: true,
+            ..current
+        },
+    )
+}
+
+/// The slot the boot loader picks: the one with the highest priority that booted successfully or
+/// has tries left, preferring A on a tie. `None` if neither can be booted.
+pub fn active(device: &Device) -> std::io::Result<Option<Slot>> {
+    let bootable = |slot: Slot| -> std::io::Result<Option<(u8, Slot)>> {
+        let attributes = attributes(device, slot)?;
+        Ok(
+            (attributes.priority > 0 && (attributes.successful || attributes.tries_remaining > 0))
+                .then_some((attributes.priority, slot)),
+        )
+    };
+    Ok(match (bootable(Slot::A)?, bootable(Slot::B)?) {
+        (Some((a, _)), Some((b, _))) if b > a => Some(Slot::B),
+        (Some(_), _) => Some(Slot::A),
+        (None, b) => b.map(|(_, slot)| slot),
+    })
+}
@@ -0,0 +1,195 @@
+use crate::{Device, FileSystem, Guid, InnerChange, Partition, PartitionKind, commit::overlaps};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// A plain-data copy of a device's layout, as returned by [`Device::snapshot`].
+///
+/// Two snapshots of the same layout are always equal, which makes them suitable for comparing
+/// layouts over time with [`diff`](DeviceSnapshot::diff).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DeviceSnapshot {
+    pub path: PathBuf,
+    /// The partition table type as named by libparted, e.g. `gpt` or `msdos`.
+    pub disk_type: Option<String>,
+    pub sector_size: u64,
+    /// The length of the device in sectors.
+    pub length: u64,
+    /// The partitions, ordered by their start sector.
+    pub partitions: Vec<PartitionSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PartitionSnapshot {
+    pub name: String,
+    /// The first sector of the partition.
+    pub start: i64,
+    /// The last sector of the partition.
+    pub end: i64,
+    pub fs: Option<FileSystem>,
+    pub bootable: bool,
+    pub type_guid: Option<Guid>,
+}
+
+impl From<&Partition> for PartitionSnapshot {
+    fn from(value: &Partition) -> Self {
+        Self {
+            name: value.name().into(),
+            start: *value.bounds().start(),
+            end: *value.bounds().end(),
+            fs: value.fs(),
+            bootable: value.is_bootable(),
+            type_guid: value.type_guid(),
+        }
+    }
+}
+
+impl PartitionSnapshot {
+    /// Snapshot the partition as it is on disk, ignoring pending changes.
+    fn on_disk(value: &Partition) -> Self {
+        Self {
+            name: value.name.0.as_ref().into(),
+            start: *value.bounds.0.start(),
+            end: *value.bounds.0.end(),
+            fs: value.fs.0,
+            bootable: value.bootable.0,
+            type_guid: value.type_guid,
+        }
+    }
+}
+
+/// A difference between two [`DeviceSnapshot`]s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum LayoutDelta {
+    DiskType {
+        before: Option<String>,
+        after: Option<String>,
+    },
+    Added(PartitionSnapshot),
+    Removed(PartitionSnapshot),
+    /// A partition occupies some of the same sectors as before, but differs in some way.
+    Changed {
+        before: PartitionSnapshot,
+        after: PartitionSnapshot,
+    },
+}
+
+impl DeviceSnapshot {
+    pub(crate) fn new(device: &Device) -> Self {
+        let mut partitions = device
+            .partitions()
+            .map(PartitionSnapshot::from)
+            .collect::<Vec<_>>();
+        partitions.sort_by_key(|p| p.start);
+
+        Self {
+            path: device.path().into(),
+            disk_type: device.disk_type.as_deref().map(Into::into),
+            sector_size: device.sector_size(),
+            length: device.raw.length(),
+            partitions,
+        }
+    }
+
+    /// Snapshot the layout of the device as it is on disk, ignoring pending changes.
+    pub(crate) fn on_disk(device: &Device) -> Self {
+        // a new table discards everything, so what came before it is what's on disk
+        let (disk_type, partitions) = device
+            .changes
+            .iter()
+            .find_map(|c| match c {
+                InnerChange::NewTable {
+                    previous_type,
+                    previous_partitions,
+                    ..
+                } => Some((previous_type, previous_partitions)),
+                _ => None,
+            })
+            .unwrap_or((&device.disk_type, &device.partitions));
+
+        let mut partitions = partitions
+            .iter()
+            .filter(|p| p.kind != PartitionKind::Virtual)
+            .map(PartitionSnapshot::on_disk)
+            .collect::<Vec<_>>();
+        partitions.sort_by_key(|p| p.start);
+
+        Self {
+            path: device.path().into(),
+            disk_type: disk_type.as_deref().map(Into::into),
+            sector_size: device.sector_size(),
+            length: device.raw.length(),
+            partitions,
+        }
+    }
+
+    /// Get a hash of the snapshot that is stable across runs, so it can be stored and compared
+    /// later.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        std::hash::Hash::hash(self, &mut hasher);
+        std::hash::Hasher::finish(&hasher)
+    }
+
+    /// Get the changes that turn this snapshot into `other`.
+    ///
+    /// Partitions are matched by start sector, falling back to the first overlapping partition.
+    pub fn diff(&self, other: &Self) -> Vec<LayoutDelta> {
+        let mut deltas = Vec::new();
+        if self.disk_type != other.disk_type {
+            deltas.push(LayoutDelta::DiskType {
+                before: self.disk_type.clone(),
+                after: other.disk_type.clone(),
+            });
+        }
+
+        let mut remaining = other.partitions.iter().collect::<Vec<_>>();
+        for before in &self.partitions {
+            let position = remaining
+                .iter()
+                .position(|p| p.start == before.start)
+                .or_else(|| {
+                    remaining
+                        .iter()
+                        .position(|p| overlaps(&(p.start..=p.end), &(before.start..=before.end)))
+                });
+            let Some(position) = position else {
+                deltas.push(LayoutDelta::Removed(before.clone()));
+                continue;
+            };
+
+            let after = remaining.remove(position);
+            if after != before {
+                deltas.push(LayoutDelta::Changed {
+                    before: before.clone(),
+                    after: after.clone(),
+                });
+            }
+        }
+        deltas.extend(remaining.into_iter().cloned().map(LayoutDelta::Added));
+
+        deltas
+    }
+}
+
+/// The 64-bit FNV-1a hash, used instead of the standard library's hasher because that one may
+/// change between Rust releases.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl std::hash::Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
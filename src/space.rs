@@ -0,0 +1,317 @@
+use crate::PartitionType;
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+
+/// A row of a device's layout as [`layout`] works it out, before it's given the partition's or
+/// region's details.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Slot {
+    /// The partition at the given index.
+    Partition(usize),
+    Free(RangeInclusive<i64>),
+}
+
+/// Lay out the partitions with the given bounds and types, ordered by their start, among the
+/// free regions between them and the ends of the usable sectors.
+///
+/// Free space within an extended partition is separate from the space around it, and each logical
+/// partition is preceded by a sector for its extended boot record.
+///
+/// # Panics
+///
+/// Panics if the partitions overlap, or aren't in order.
+pub(crate) fn layout<'a>(
+    usable: &RangeInclusive<i64>,
+    partitions: impl IntoIterator<Item = (&'a RangeInclusive<i64>, PartitionType)>,
+) -> Vec<Slot> {
+    let mut slots = Vec::new();
+    let mut free_start = *usable.start();
+    let mut previous_end = None;
+    // the end of the extended partition whose logical partitions come next
+    let mut extended_end = None;
+    for (index, (bounds, partition_type)) in partitions.into_iter().enumerate() {
+        if partition_type != PartitionType::Logical
+            && let Some(end) = extended_end.take()
+        {
+            if free_start <= end {
+                slots.push(Slot::Free(free_start..=end));
+            }
+            previous_end = Some(end);
+            free_start = free_start.max(end + 1);
+        }
+        if let Some(end) = previous_end {
+            assert!(*bounds.start() > end, "overlapping partitions");
+        }
+        let free_end = match partition_type {
+            PartitionType::Logical => bounds.start() - 2,
+            _ => bounds.start() - 1,
+        };
+        if free_end >= free_start {
+            slots.push(Slot::Free(free_start..=free_end));
+        }
+        slots.push(Slot::Partition(index));
+        match partition_type {
+            PartitionType::Extended => {
+                extended_end = Some(*bounds.end());
+                previous_end = Some(*bounds.start());
+                free_start = bounds.start() + 1;
+            }
+            PartitionType::Logical => {
+                previous_end = Some(*bounds.end());
+                free_start = bounds.end() + 2;
+            }
+            PartitionType::Primary => {
+                previous_end = Some(*bounds.end());
+                free_start = free_start.max(bounds.end() + 1);
+            }
+        }
+    }
+    if let Some(end) = extended_end {
+        if free_start <= end {
+            slots.push(Slot::Free(free_start..=end));
+        }
+        free_start = free_start.max(end + 1);
+    }
+    if free_start <= *usable.end() {
+        slots.push(Slot::Free(free_start..=*usable.end()));
+    }
+    slots
+}
+
+/// Get the number of free sectors between `bounds` and the partition before them, or the start
+/// of the usable sectors if there's none.
+///
+/// Logical partitions are bounded by the start of their extended partition rather than its end.
+pub(crate) fn gap_before<'a>(
+    usable: &RangeInclusive<i64>,
+    partitions: impl IntoIterator<Item = (&'a RangeInclusive<i64>, PartitionType)>,
+    bounds: &RangeInclusive<i64>,
+) -> u64 {
+    let free_start = partitions
+        .into_iter()
+        .map(|(other, partition_type)| match partition_type {
+            PartitionType::Extended if other != bounds && other.contains(bounds.start()) => {
+                *other.start()
+            }
+            _ => *other.end(),
+        })
+        .filter(|end| end < bounds.start())
+        .max()
+        .map_or(*usable.start(), |end| end + 1);
+    (bounds.start() - free_start).max(0) as u64
+}
+
+/// Get the number of free sectors between `bounds` and the partition after them, or the end of
+/// the usable sectors if there's none.
+///
+/// Logical partitions are bounded by the end of their extended partition.
+pub(crate) fn gap_after<'a>(
+    usable: &RangeInclusive<i64>,
+    partitions: impl IntoIterator<Item = (&'a RangeInclusive<i64>, PartitionType)>,
+    bounds: &RangeInclusive<i64>,
+) -> u64 {
+    let free_end = partitions
+        .into_iter()
+        .map(|(other, partition_type)| match partition_type {
+            PartitionType::Extended if other != bounds && other.contains(bounds.end()) => {
+                other.end() + 1
+            }
+            _ => *other.start(),
+        })
+        .filter(|start| start > bounds.end())
+        .min()
+        .map_or(*usable.end(), |start| start - 1);
+    (free_end - bounds.end()).max(0) as u64
+}
+
+/// Work out the new bounds of a partition with the bounds `from` being resized to `new_bounds`.
+///
+/// Unbounded ends reach the ends of the usable sectors, except that a partition starting before
+/// them, e.g. at sector 63 from an old tool, keeps its start. Returns `None` if the bounds are
+/// empty or reach past the usable sectors.
+pub(crate) fn resize_bounds(
+    usable: &RangeInclusive<i64>,
+    from: &RangeInclusive<i64>,
+    new_bounds: impl RangeBounds<i64>,
+) -> Option<RangeInclusive<i64>> {
+    let first = *usable.start().min(from.start());
+    let start = match new_bounds.start_bound() {
+        Bound::Included(b) => *b,
+        Bound::Excluded(b) => b + 1,
+        Bound::Unbounded => first,
+    };
+    let end = match new_bounds.end_bound() {
+        Bound::Included(b) => *b,
+        Bound::Excluded(b) => b - 1,
+        Bound::Unbounded => *usable.end(),
+    };
+    (start >= first && end <= *usable.end() && start <= end).then_some(start..=end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USABLE: RangeInclusive<i64> = 34..=1000;
+
+    fn primary(partitions: &[RangeInclusive<i64>]) -> Vec<(RangeInclusive<i64>, PartitionType)> {
+        partitions
+            .iter()
+            .map(|bounds| (bounds.clone(), PartitionType::Primary))
+            .collect()
+    }
+
+    fn gaps(
+        partitions: &[(RangeInclusive<i64>, PartitionType)],
+        bounds: &RangeInclusive<i64>,
+    ) -> (u64, u64) {
+        let partitions = || partitions.iter().map(|(b, t)| (b, *t));
+        (
+            gap_before(&USABLE, partitions(), bounds),
+            gap_after(&USABLE, partitions(), bounds),
+        )
+    }
+
+    fn slots(partitions: &[(RangeInclusive<i64>, PartitionType)]) -> Vec<Slot> {
+        layout(&USABLE, partitions.iter().map(|(b, t)| (b, *t)))
+    }
+
+    #[test]
+    fn an_empty_disk_is_one_free_region() {
+        assert_eq!(slots(&[]), [Slot::Free(USABLE)]);
+    }
+
+    #[test]
+    fn free_regions_span_the_usable_sectors_around_partitions() {
+        assert_eq!(
+            slots(&primary(&[100..=199, 500..=599])),
+            [
+                Slot::Free(34..=99),
+                Slot::Partition(0),
+                Slot::Free(200..=499),
+                Slot::Partition(1),
+                Slot::Free(600..=1000),
+            ]
+        );
+    }
+
+    #[test]
+    fn partitions_at_the_usable_ends_leave_no_free_region_there() {
+        assert_eq!(
+            slots(&primary(&[34..=99, 900..=1000])),
+            [
+                Slot::Partition(0),
+                Slot::Free(100..=899),
+                Slot::Partition(1)
+            ]
+        );
+    }
+
+    #[test]
+    fn adjacent_partitions_have_no_free_region_between_them() {
+        assert_eq!(
+            slots(&primary(&[34..=499, 500..=1000])),
+            [Slot::Partition(0), Slot::Partition(1)]
+        );
+    }
+
+    #[test]
+    fn free_space_in_an_extended_partition_is_separate() {
+        let partitions = [
+            (100..=599, PartitionType::Extended),
+            (102..=199, PartitionType::Logical),
+            (201..=299, PartitionType::Logical),
+            (700..=799, PartitionType::Primary),
+        ];
+        assert_eq!(
+            slots(&partitions),
+            [
+                Slot::Free(34..=99),
+                Slot::Partition(0),
+                Slot::Partition(1),
+                Slot::Partition(2),
+                // past the sector for the extended boot record of the next logical partition
+                Slot::Free(301..=599),
+                Slot::Free(600..=699),
+                Slot::Partition(3),
+                Slot::Free(800..=1000),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "overlapping partitions")]
+    fn overlapping_partitions_panic() {
+        slots(&primary(&[100..=199, 150..=249]));
+    }
+
+    #[test]
+    fn gaps_of_a_lone_partition_reach_the_usable_ends() {
+        let partitions = primary(&[100..=199]);
+        assert_eq!(gaps(&partitions, &(100..=199)), (66, 801));
+    }
+
+    #[test]
+    fn gaps_of_partitions_at_the_usable_ends_are_empty() {
+        let partitions = primary(&[34..=99, 900..=1000]);
+        assert_eq!(gaps(&partitions, &(34..=99)), (0, 800));
+        assert_eq!(gaps(&partitions, &(900..=1000)), (800, 0));
+    }
+
+    #[test]
+    fn adjacent_partitions_have_no_gap_between_them() {
+        let partitions = primary(&[100..=199, 200..=299, 350..=399]);
+        assert_eq!(gaps(&partitions, &(100..=199)), (66, 0));
+        assert_eq!(gaps(&partitions, &(200..=299)), (0, 50));
+        assert_eq!(gaps(&partitions, &(350..=399)), (50, 601));
+    }
+
+    #[test]
+    fn gaps_of_new_bounds_are_between_their_neighbours() {
+        let partitions = primary(&[100..=199, 500..=599]);
+        assert_eq!(gaps(&partitions, &(300..=399)), (100, 100));
+        assert_eq!(gaps(&[], &(300..=399)), (266, 601));
+    }
+
+    #[test]
+    fn gaps_of_logical_partitions_stay_within_the_extended_one() {
+        let partitions = [
+            (34..=99, PartitionType::Primary),
+            (100..=599, PartitionType::Extended),
+            (110..=199, PartitionType::Logical),
+            (700..=799, PartitionType::Primary),
+        ];
+        assert_eq!(gaps(&partitions, &(110..=199)), (9, 400));
+        assert_eq!(gaps(&partitions, &(100..=599)), (0, 100));
+    }
+
+    #[test]
+    fn unbounded_resizes_reach_the_usable_ends() {
+        assert_eq!(resize_bounds(&USABLE, &(100..=199), ..), Some(USABLE));
+        assert_eq!(
+            resize_bounds(&USABLE, &(100..=199), 100..),
+            Some(100..=1000)
+        );
+        assert_eq!(resize_bounds(&USABLE, &(100..=199), ..=199), Some(34..=199));
+    }
+
+    #[test]
+    fn resizes_past_the_usable_sectors_are_rejected() {
+        assert_eq!(resize_bounds(&USABLE, &(100..=199), 100..=1001), None);
+        assert_eq!(resize_bounds(&USABLE, &(100..=199), 33..=199), None);
+        assert_eq!(resize_bounds(&USABLE, &(100..=199), 100..100), None);
+        assert_eq!(
+            resize_bounds(&USABLE, &(100..=199), 100..1001),
+            Some(100..=1000)
+        );
+    }
+
+    #[test]
+    fn partitions_starting_before_the_usable_sectors_keep_their_start() {
+        assert_eq!(resize_bounds(&USABLE, &(20..=199), ..), Some(20..=1000));
+        assert_eq!(
+            resize_bounds(&USABLE, &(20..=199), 20..=299),
+            Some(20..=299)
+        );
+        assert_eq!(resize_bounds(&USABLE, &(20..=199), 19..=299), None);
+    }
+}
@@ -0,0 +1,59 @@
+use std::{path::Path, time::Duration};
+
+/// IO counters of a block device since boot, from `/proc/diskstats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IoStats {
+    pub reads_completed: u64,
+    pub sectors_read: u64,
+    pub writes_completed: u64,
+    pub sectors_written: u64,
+    /// The number of requests currently being processed.
+    pub in_flight: u64,
+    /// The time spent with at least one request in flight.
+    pub busy: Duration,
+}
+
+impl IoStats {
+    /// The fraction of the time between two samples that the device was busy, from 0 to 1.
+    ///
+    /// A device close to 1 is saturated, so anything else using it will be slow.
+    pub fn utilization(&self, later: &Self, elapsed: Duration) -> f64 {
+        if elapsed.is_zero() {
+            return 0.0;
+        }
+        let busy = later.busy.saturating_sub(self.busy);
+        (busy.as_secs_f64() / elapsed.as_secs_f64()).min(1.0)
+    }
+}
+
+/// Read the stats of the block device with the given path, e.g. `/dev/sda`.
+pub(crate) fn read(path: &Path) -> std::io::Result<IoStats> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| std::io::Error::other("device path has no name"))?;
+    let diskstats = std::fs::read_to_string("/proc/diskstats")?;
+
+    diskstats
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        // major, minor, name, then at least 11 counters
+        .find(|fields| fields.len() >= 14 && fields[2] == name)
+        .map(|fields| {
+            let field = |i: usize| fields[i].parse::<u64>().unwrap_or_default();
+            IoStats {
+                reads_completed: field(3),
+                sectors_read: field(5),
+                writes_completed: field(7),
+                sectors_written: field(9),
+                in_flight: field(11),
+                busy: Duration::from_millis(field(12)),
+            }
+        })
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{name} isn't listed in /proc/diskstats"),
+            )
+        })
+}
@@ -0,0 +1,123 @@
+//! Pending changes as the steps that staged them, so that they can be saved and staged again
+//! later or on another device with the same layout.
+
+use crate::{
+    DeviceSnapshot, FileSystem, Guid, HybridEntry, PartitionFlag, PartitionTable, PartitionType,
+    ResizeStrategy,
+};
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// The pending changes of a device, as returned by
+/// [`Device::export_plan`](crate::Device::export_plan).
+///
+/// Unlike a [`ChangePlan`](crate::ChangePlan), which records the layout to end up with, this
+/// records each change in the order it was staged. Partitions are referred to by the sector they
+/// started at when the step was taken, which stays the same across reboots and identical devices.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    /// The on-disk layout of the device the plan was made on.
+    pub source: DeviceSnapshot,
+    pub steps: Vec<PlanStep>,
+}
+
+/// A change in a [`Plan`]. Bounds are **in sectors** of the plan's
+/// [source](DeviceSnapshot::sector_size).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "kebab-case")]
+pub enum PlanStep {
+    Rename {
+        partition: i64,
+        name: String,
+    },
+    NewPartition {
+        name: String,
+        fs: Option<FileSystem>,
+        start: i64,
+        end: i64,
+        /// The GPT partition number to give the partition, rather than the first free one.
+        number: Option<u32>,
+        /// Whether the bounds were aligned by partner, so libparted mustn't align them again.
+        exact: bool,
+        partition_type: PartitionType,
+    },
+    RemovePartition {
+        partition: i64,
+    },
+    ResizePartition {
+        partition: i64,
+        start: i64,
+        end: i64,
+        strategy: ResizeStrategy,
+    },
+    /// Move a partition to start at the given sector, as
+    /// [`Device::realign_partition`](crate::Device::realign_partition) does.
+    MovePartition {
+        partition: i64,
+        to: i64,
+    },
+    SetBootable {
+        partition: i64,
+    },
+    SetFlag {
+        partition: i64,
+        flag: PartitionFlag,
+        state: bool,
+    },
+    SetUuid {
+        partition: i64,
+        uuid: Guid,
+    },
+    HybridMbr {
+        entries: Vec<HybridEntry>,
+    },
+    GptEntryCapacity {
+        capacity: u32,
+    },
+    NewTable {
+        table: PartitionTable,
+    },
+    /// Give a superfloppy a partition table, keeping its file system, as
+    /// [`Device::convert_superfloppy`](crate::Device::convert_superfloppy) does.
+    ConvertSuperfloppy {
+        table: PartitionTable,
+    },
+    Format {
+        start: i64,
+        end: i64,
+        fs: FileSystem,
+        label: String,
+    },
+}
+
+/// How a device differs from what a [`Plan`] expects, as found by
+/// [`Device::import_plan`](crate::Device::import_plan).
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PlanMismatch {
+    #[error("the plan is for {plan} byte sectors, but the device has {device} byte sectors")]
+    SectorSize { plan: u64, device: u64 },
+    #[error("the plan is for a device of {plan} sectors, but the device has {device}")]
+    Length { plan: u64, device: u64 },
+    #[error("the plan is for a {plan:?} partition table, but the device has {device:?}")]
+    DiskType {
+        plan: Option<String>,
+        device: Option<String>,
+    },
+    /// The plan was made against a partition with these bounds, which the device doesn't have.
+    #[error("the device has no partition at sectors {}-{}", .0.start(), .0.end())]
+    MissingPartition(RangeInclusive<i64>),
+    /// The device has a partition with these bounds, which the plan wasn't made against.
+    #[error("the device has a partition at sectors {}-{} the plan doesn't know of", .0.start(), .0.end())]
+    UnexpectedPartition(RangeInclusive<i64>),
+    #[error("step {step} of the plan refers to a partition at sector {start}, but there is none")]
+    NoPartition { step: usize, start: i64 },
+    /// Realigning a partition on the device would move it elsewhere than the plan did.
+    #[error(
+        "step {step} of the plan moves a partition to sector {expected}, but it would go to {actual}"
+    )]
+    MoveTarget {
+        step: usize,
+        expected: i64,
+        actual: i64,
+    },
+}
@@ -0,0 +1,99 @@
+//! Overviews of devices, for inventories and listings.
+
+use crate::{
+    Geometry,
+    size::{self, FormatOptions},
+};
+use byte_unit::Byte;
+use serde::Serialize;
+use std::{fmt::Display, path::PathBuf};
+
+/// An overview of a device, as returned by [`Device::summary`](crate::Device::summary).
+///
+/// Displays as a single line, e.g.
+/// `/dev/sda  Samsung SSD 870  465.76 GiB  GPT  4 partitions  81.2% allocated  ok`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceSummary {
+    pub path: PathBuf,
+    pub model: String,
+    /// The size of the device in bytes.
+    pub size: u64,
+    /// The partition table type as named by libparted, e.g. `gpt` or `msdos`.
+    pub disk_type: Option<String>,
+    pub partitions: usize,
+    /// The bytes taken by partitions.
+    pub allocated: u64,
+    /// The share of the sectors partitions can occupy that they do, from 0 to 1.
+    pub allocated_fraction: f64,
+    /// The largest region new partitions can be created in, if there's any.
+    pub largest_free: Option<Geometry>,
+    pub health: DeviceHealth,
+}
+
+/// Problems partner knows of with a device, as part of a [`DeviceSummary`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct DeviceHealth {
+    /// Whether the primary GPT is corrupt. See [`Device::is_degraded`](crate::Device::is_degraded).
+    pub degraded: bool,
+    /// Whether the kernel has the device read-only. See
+    /// [`Device::is_read_only`](crate::Device::is_read_only).
+    pub read_only: bool,
+    /// The number of partitions that aren't aligned to the device's physical sectors. See
+    /// [`Device::misaligned_partitions`](crate::Device::misaligned_partitions).
+    pub misaligned: usize,
+}
+
+impl DeviceHealth {
+    pub fn is_ok(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Display for DeviceHealth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut problems = Vec::new();
+        if self.degraded {
+            problems.push("degraded GPT".to_string());
+        }
+        if self.read_only {
+            problems.push("read-only".to_string());
+        }
+        if self.misaligned > 0 {
+            problems.push(format!("{} misaligned", self.misaligned));
+        }
+        if problems.is_empty() {
+            write!(f, "ok")
+        } else {
+            write!(f, "{}", problems.join(", "))
+        }
+    }
+}
+
+impl Display for DeviceSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}  {}  {}  {}  {} partition{}  {:.1}% allocated",
+            self.path.display(),
+            self.model,
+            size::format(Byte::from_u64(self.size), FormatOptions::new()),
+            match self.disk_type.as_deref() {
+                Some("gpt") => "GPT",
+                Some("msdos") => "MBR",
+                Some(other) => other,
+                None => "no table",
+            },
+            self.partitions,
+            if self.partitions == 1 { "" } else { "s" },
+            self.allocated_fraction * 100.0,
+        )?;
+        if let Some(free) = &self.largest_free {
+            write!(
+                f,
+                "  {} free",
+                size::format(Byte::from_u64(free.size_byte), FormatOptions::new())
+            )?;
+        }
+        write!(f, "  {}", self.health)
+    }
+}
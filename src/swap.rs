@@ -0,0 +1,136 @@
+//! Detection of how the system swaps, so that frontends don't suggest swap partitions it doesn't
+//! need.
+
+use crate::{LayoutSpec, SwapStrategy, dependents, dps::Role};
+use byte_unit::Byte;
+use std::path::{Path, PathBuf};
+
+/// Where zram-generator looks for its config. Any of them existing means zram swap is set up.
+const ZRAM_GENERATOR_CONFIGS: &[&str] = &[
+    "/etc/systemd/zram-generator.conf",
+    "/run/systemd/zram-generator.conf",
+    "/usr/lib/systemd/zram-generator.conf",
+    "/usr/local/lib/systemd/zram-generator.conf",
+];
+
+/// Something the system swaps to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SwapTarget {
+    Partition(PathBuf),
+    File(PathBuf),
+    /// A compressed swap device in memory.
+    Zram(PathBuf),
+}
+
+/// The swap the system uses now or is configured to use on boot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SwapSetup {
+    /// Swap in use, from `/proc/swaps`.
+    pub active: Vec<SwapTarget>,
+    /// Swap files set up in `/etc/fstab`, whether or not they're in use.
+    pub configured_files: Vec<PathBuf>,
+    /// Whether zram-generator is configured to set up zram swap on boot.
+    pub zram_generator: bool,
+}
+
+impl SwapSetup {
+    /// Detect the running system's swap setup.
+    pub fn detect() -> Self {
+        let active = std::fs::read_to_string("/proc/swaps")
+            .map(|swaps| {
+                swaps
+                    .lines()
+                    .skip(1)
+                    .filter_map(|line| {
+                        let mut fields = line.split_whitespace();
+                        let path = PathBuf::from(fields.next()?);
+                        Some(if path.starts_with("/dev") && is_zram(&path) {
+                            SwapTarget::Zram(path)
+                        } else if fields.next() == Some("file") {
+                            SwapTarget::File(path)
+                        } else {
+                            SwapTarget::Partition(path)
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // fstab lines with the file system type as the target
+        let configured_files = dependents::read_config(Path::new("/etc/fstab"), 0, 2)
+            .into_iter()
+            .filter(|entry| entry.target == "swap" && entry.spec.starts_with('/'))
+            .map(|entry| PathBuf::from(entry.spec))
+            .filter(|path| !path.starts_with("/dev"))
+            .collect();
+
+        Self {
+            active,
+            configured_files,
+            zram_generator: ZRAM_GENERATOR_CONFIGS
+                .iter()
+                .any(|path| Path::new(path).exists()),
+        }
+    }
+
+    /// Whether the system swaps to zram or a swap file, or is set up to, which makes a swap
+    /// partition redundant.
+    pub fn has_alternative(&self) -> bool {
+        self.zram_generator
+            || !self.configured_files.is_empty()
+            || self
+                .active
+                .iter()
+                .any(|t| matches!(t, SwapTarget::File(_) | SwapTarget::Zram(_)))
+    }
+
+    /// Whether the system has any swap at all, now or after booting.
+    pub fn has_swap(&self) -> bool {
+        !self.active.is_empty() || self.has_alternative()
+    }
+
+    /// Get the swap strategy to suggest for a new layout: a swap partition of the given size,
+    /// unless the system already has an alternative.
+    pub fn suggested_strategy(&self, size: Byte) -> SwapStrategy {
+        if self.has_alternative() {
+            SwapStrategy::None
+        } else {
+            SwapStrategy::Partition(size)
+        }
+    }
+
+    /// Check the swap partitions in the given layout against this setup.
+    pub fn advise(&self, spec: &LayoutSpec) -> Option<SwapAdvisory> {
+        let swap_partitions = spec
+            .partitions
+            .iter()
+            .filter(|p| p.role == Some(Role::Swap))
+            .count();
+        if swap_partitions > 0 && self.has_alternative() {
+            Some(SwapAdvisory::Redundant)
+        } else if swap_partitions == 0 && !self.has_swap() {
+            Some(SwapAdvisory::NoSwap)
+        } else {
+            None
+        }
+    }
+}
+
+/// Advice about the swap in a layout, from [`SwapSetup::advise`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum SwapAdvisory {
+    /// The layout has no swap partition and the system has no other swap, so it can't hibernate
+    /// and has nothing to fall back on under memory pressure. A swap file or zram would do
+    /// without repartitioning.
+    #[strum(serialize = "the system has no swap; consider a swap file or zram")]
+    NoSwap,
+    /// The layout has a swap partition, but the system already swaps to zram or a swap file.
+    #[strum(serialize = "the system already swaps to zram or a swap file")]
+    Redundant,
+}
+
+fn is_zram(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("zram"))
+}
@@ -0,0 +1,47 @@
+//! Rust callbacks for libparted's timers, which it updates to report the progress of slow
+//! operations such as resizing file systems.
+
+use libparted_sys::{PedTimer, ped_timer_destroy, ped_timer_new};
+use std::{ffi::c_void, panic::AssertUnwindSafe};
+
+type Handler<'f> = Box<dyn FnMut(f64) + 'f>;
+
+/// A libparted timer that calls a closure with how much of the operation it's passed to is done,
+/// from 0 to 1.
+pub(crate) struct Timer<'f> {
+    raw: *mut PedTimer,
+    // boxed again so that the address libparted holds on to stays the same when the timer moves
+    _handler: Box<Handler<'f>>,
+}
+
+impl<'f> Timer<'f> {
+    pub fn new(handler: impl FnMut(f64) + 'f) -> std::io::Result<Self> {
+        let mut handler: Box<Handler<'f>> = Box::new(Box::new(handler));
+        let context = std::ptr::addr_of_mut!(*handler).cast::<c_void>();
+        let raw = unsafe { ped_timer_new(Some(dispatch), context) };
+        if raw.is_null() {
+            return Err(std::io::Error::other("libparted couldn't create a timer"));
+        }
+        Ok(Self {
+            raw,
+            _handler: handler,
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut PedTimer {
+        self.raw
+    }
+}
+
+impl Drop for Timer<'_> {
+    fn drop(&mut self) {
+        unsafe { ped_timer_destroy(self.raw) };
+    }
+}
+
+unsafe extern "C" fn dispatch(timer: *mut PedTimer, context: *mut c_void) {
+    let handler = unsafe { &mut *context.cast::<Handler>() };
+    let fraction = f64::from(unsafe { (*timer).frac });
+    // unwinding into C is undefined behavior
+    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| handler(fraction.clamp(0.0, 1.0))));
+}
@@ -0,0 +1,159 @@
+//! Locating and running external tools, keeping their output so it can be shown when they fail.
+
+use std::{
+    fmt::Display,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Directories searched after `$PATH`, since file system tools usually live in `sbin`
+/// directories that aren't in unprivileged users' `$PATH`.
+const SBIN_PATHS: &[&str] = &["/usr/local/sbin", "/usr/sbin", "/sbin"];
+
+/// Where to find the external tools partner runs, such as `mkfs.ext4`.
+#[derive(Debug, Clone, Default)]
+pub struct Toolchain {
+    search_paths: Option<Vec<PathBuf>>,
+    strict: bool,
+}
+
+impl Toolchain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The directories to search for tools, in order. Defaults to `$PATH` followed by the usual
+    /// `sbin` directories.
+    pub fn search_paths(mut self, paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.search_paths = Some(paths.into_iter().collect());
+        self
+    }
+
+    /// Whether to refuse running any external tools, for minimal environments that don't have
+    /// them. Operations that need one then fail before changing anything. Defaults to `false`.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Find the executable with the given name, or `None` if it's missing or in
+    /// [strict](Toolchain::strict) mode.
+    pub fn locate(&self, name: &str) -> Option<PathBuf> {
+        if self.strict {
+            return None;
+        }
+        let is_executable = |path: &Path| {
+            path.metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        };
+        match &self.search_paths {
+            Some(paths) => paths
+                .iter()
+                .map(|dir| dir.join(name))
+                .find(|p| is_executable(p)),
+            None => std::env::var_os("PATH")
+                .iter()
+                .flat_map(std::env::split_paths)
+                .chain(SBIN_PATHS.iter().map(PathBuf::from))
+                .map(|dir| dir.join(name))
+                .find(|p| is_executable(p)),
+        }
+    }
+
+    /// Check that all of the given tools can be found, e.g. those from
+    /// [`Device::required_tools`](crate::Device::required_tools).
+    pub fn check(&self, tools: &[&str]) -> Result<(), ToolError> {
+        let missing = tools
+            .iter()
+            .filter(|t| self.locate(t).is_none())
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(ToolError::Missing(missing))
+        }
+    }
+
+    /// Get a command running the tool with the given name.
+    pub(crate) fn command(&self, name: &str) -> Result<Command, ToolError> {
+        self.locate(name)
+            .map(Command::new)
+            .ok_or_else(|| ToolError::Missing(vec![name.into()]))
+    }
+}
+
+/// The captured output of an external tool, such as `mkfs.ext4`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolOutput {
+    /// The command line that was run.
+    pub command: Vec<String>,
+    /// The exit code, or `None` if the tool was killed by a signal.
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Display for ToolOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` ", self.command.join(" "))?;
+        match self.status {
+            Some(status) => write!(f, "exited with status {status}"),
+            None => write!(f, "was killed"),
+        }
+    }
+}
+
+impl ToolOutput {
+    pub fn success(&self) -> bool {
+        self.status == Some(0)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ToolError {
+    #[error("failed to run `{program}`: {error}")]
+    Spawn {
+        program: String,
+        error: std::io::Error,
+    },
+    #[error("{}: {}", .0, .0.stderr.trim())]
+    Failed(Box<ToolOutput>),
+    #[error("missing external tools: {}", .0.join(", "))]
+    Missing(Vec<String>),
+}
+
+impl From<ToolError> for std::io::Error {
+    fn from(value: ToolError) -> Self {
+        let kind = match &value {
+            ToolError::Spawn { error, .. } => error.kind(),
+            ToolError::Failed(_) => std::io::ErrorKind::Other,
+            ToolError::Missing(_) => std::io::ErrorKind::NotFound,
+        };
+        std::io::Error::new(kind, value)
+    }
+}
+
+/// Run the given command to completion, capturing its output.
+pub(crate) fn run(command: &mut Command) -> Result<ToolOutput, ToolError> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command.output().map_err(|error| ToolError::Spawn {
+        program: program.clone(),
+        error,
+    })?;
+
+    let output = ToolOutput {
+        command: std::iter::once(program)
+            .chain(command.get_args().map(|a| a.to_string_lossy().into_owned()))
+            .collect(),
+        status: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    };
+    if output.success() {
+        Ok(output)
+    } else {
+        Err(ToolError::Failed(Box::new(output)))
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{
+    CommitError, CommitOptions, CommitProgress, DestructionAck, Device, VerificationReport,
+};
+
+/// A commit spanning several devices, e.g. both halves of a mirrored pair.
+///
+/// The devices are committed one after another, stopping at the first failure, since there's no
+/// way to commit them atomically.
+pub struct Transaction<'d, 'a> {
+    devices: Vec<&'d mut Device<'a>>,
+}
+
+/// What happened to a device in a [`Transaction`].
+#[derive(Debug)]
+pub enum DeviceOutcome {
+    /// The device was committed, with the verification report if verification was enabled.
+    Committed(Option<VerificationReport>),
+    Failed(CommitError),
+    /// The device wasn't committed because another one failed, or exceeded the
+    /// [destroy limit](CommitOptions::destroy_limit).
+    Skipped,
+}
+
+impl DeviceOutcome {
+    pub fn is_committed(&self) -> bool {
+        matches!(self, Self::Committed(_))
+    }
+}
+
+impl<'d, 'a> Transaction<'d, 'a> {
+    pub fn new(devices: impl IntoIterator<Item = &'d mut Device<'a>>) -> Self {
+        Self {
+            devices: devices.into_iter().collect(),
+        }
+    }
+
+    pub fn devices(&self) -> impl Iterator<Item = &Device<'a>> {
+        self.devices.iter().map(|d| &**d)
+    }
+
+    /// Get a token acknowledging the data the pending changes destroy on all the devices, like
+    /// [`Device::acknowledge_destruction`].
+    pub fn acknowledge_destruction(&self) -> DestructionAck {
+        DestructionAck(
+            self.devices
+                .iter()
+                .map(|d| d.pending_bytes_destroyed().as_u64())
+                .sum(),
+        )
+    }
+
+    pub fn commit(self, options: &CommitOptions) -> Vec<DeviceOutcome> {
+        self.commit_with_progress(options, |_, _| {})
+    }
+
+    /// Commit every device in order, reporting each device's progress along with its index.
+    ///
+    /// Returns the outcome for each device, in the same order. If any device exceeds the
+    /// [destroy limit](CommitOptions::destroy_limit), none of them are committed.
+    pub fn commit_with_progress(
+        self,
+        options: &CommitOptions,
+        mut progress: impl FnMut(usize, CommitProgress),
+    ) -> Vec<DeviceOutcome> {
+        let checks = self
+            .devices
+            .iter()
+            .map(|d| d.check_destruction(options))
+            .collect::<Vec<_>>();
+        if checks.iter().any(Result::is_err) {
+            return checks
+                .into_iter()
+                .map(|check| match check {
+                    Err(e) => DeviceOutcome::Failed(e),
+                    Ok(()) => DeviceOutcome::Skipped,
+                })
+                .collect();
+        }
+
+        let mut failed = false;
+        self.devices
+            .into_iter()
+            .enumerate()
+            .map(|(i, device)| {
+                if failed {
+                    return DeviceOutcome::Skipped;
+                }
+                match device.commit_with_progress(options, |p| progress(i, p)) {
+                    Ok(report) => DeviceOutcome::Committed(report),
+                    Err(e) => {
+                        failed = true;
+                        DeviceOutcome::Failed(e)
+                    }
+                }
+            })
+            .collect()
+    }
+}
@@ -7,9 +7,20 @@ pub struct Cli {
     #[arg()]
     /// The path to the device to use
     pub device: Option<PathBuf>,
+    #[arg(long, short = 'l')]
+    /// Print a summary of each device, or just the given one, and exit
+    pub list: bool,
     #[arg(long, short = 'D')]
     /// Path to log file
     pub debug: bool,
+    #[arg(long, hide = true, requires = "device")]
+    /// Feed the key presses in the given JSON file to the UI instead of reading the terminal,
+    /// then print the final screen. The device must be a disk image
+    pub replay: Option<PathBuf>,
+    #[arg(long, hide = true, requires = "replay")]
+    /// Compare the final screen of a replay with the given snapshot file, which is created if it
+    /// doesn't exist
+    pub snapshot: Option<PathBuf>,
 }
 
 pub fn parse() -> Cli {
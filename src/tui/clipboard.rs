@@ -0,0 +1,65 @@
+//! Copying to the system clipboard, through the terminal with OSC 52 and through `wl-copy` or
+//! `xclip` where a display server is reachable.
+
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn copy(text: &str) -> std::io::Result<()> {
+    // terminals that don't support OSC 52 ignore it, so it's always worth a try
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+
+    let tool: &[&str] = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        &["wl-copy"]
+    } else if std::env::var_os("DISPLAY").is_some() {
+        &["xclip", "-selection", "clipboard"]
+    } else {
+        return Ok(());
+    };
+    let mut child = Command::new(tool[0])
+        .args(&tool[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Find the name of the udev symlink in the given `/dev/disk` directory that points to the
+/// partition at the given path, e.g. its file system UUID in `/dev/disk/by-uuid`.
+pub fn udev_name(dir: &str, path: &Path) -> Option<String> {
+    let path = path.canonicalize().ok()?;
+    std::fs::read_dir(Path::new("/dev/disk").join(dir))
+        .ok()?
+        .flatten()
+        .find(|entry| entry.path().canonicalize().is_ok_and(|p| p == path))
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | ((*b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,53 @@
+//! Widgets shared between screens.
+
+use byte_unit::Byte;
+use itertools::intersperse_with;
+use partner::{
+    DeviceSnapshot, PartitionSnapshot,
+    size::{self, FormatOptions},
+};
+use ratatui::{
+    style::{Style, Stylize},
+    text::{Line, Span, Text},
+};
+
+pub fn legend<'a>(spans: impl IntoIterator<Item = impl Into<Span<'a>>>) -> Text<'a> {
+    Line::from_iter(intersperse_with(spans.into_iter().map(Into::into), || {
+        Span::raw(" | ")
+    }))
+    .into()
+}
+
+/// Format a size for display, with the locale's decimal separator.
+pub fn size_label(bytes: Byte) -> String {
+    size::format(bytes, FormatOptions::new().precision(10).locale())
+}
+
+/// Format a size to start an input with, which [`size::Expr`] parses back whatever the locale.
+pub fn size_input(bytes: Byte) -> String {
+    size::format(bytes, FormatOptions::new().precision(10))
+}
+
+/// Draw a layout as a labelled bar of the given width, with a cell per equal share of sectors.
+pub fn layout_bar<'a>(
+    label: &'a str,
+    snapshot: &DeviceSnapshot,
+    width: usize,
+    style: impl Fn(&PartitionSnapshot) -> Style,
+) -> Line<'a> {
+    let mut line = Line::from(format!("{label:<8} "));
+    for cell in 0..width {
+        let sector = (cell as u64 * snapshot.length / width as u64) as i64;
+        line.push_span(
+            match snapshot
+                .partitions
+                .iter()
+                .find(|p| (p.start..=p.end).contains(&sector))
+            {
+                Some(p) => Span::styled("█", style(p)),
+                None => Span::styled("░", Style::new().dim()),
+            },
+        );
+    }
+    line
+}
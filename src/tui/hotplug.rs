@@ -0,0 +1,35 @@
+//! Noticing when block devices are added or removed, by polling `/sys/block`.
+
+use super::Message;
+use std::{ffi::OsString, time::Duration};
+use tokio_stream::{Stream, wrappers::UnboundedReceiverStream};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Get a stream that yields [`Message::Hotplug`] whenever the set of block devices changes.
+pub fn watch() -> impl Stream<Item = Message> + Send + 'static {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let mut last = block_devices();
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let current = block_devices();
+            if current != last {
+                last = current;
+                // the receiver is gone once the app quits
+                if tx.send(Message::Hotplug).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    UnboundedReceiverStream::new(rx)
+}
+
+fn block_devices() -> Vec<OsString> {
+    let mut names = std::fs::read_dir("/sys/block")
+        .map(|entries| entries.flatten().map(|e| e.file_name()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
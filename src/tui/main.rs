@@ -1,16 +1,19 @@
 mod cli;
-mod logic;
-mod ui;
+mod clipboard;
+mod components;
+mod hotplug;
+mod replay;
+mod screens;
 
-use byte_unit::Byte;
 use color_eyre::{
     Result,
     eyre::{Context, eyre},
 };
 use either::Either;
-use partner::{Device, FileSystem};
+use partner::{Device, FileSystem, Resolution};
 use ratatui::widgets::TableState;
 use ratatui_elm::App;
+use screens::CommitScreen;
 use std::ops::RangeInclusive;
 use tracing_subscriber::EnvFilter;
 use tui_input::Input;
@@ -27,7 +30,8 @@ fn main() -> Result<()> {
 
     let cli = cli::parse();
 
-    if !nix::unistd::Uid::effective().is_root() {
+    // replays are run against image files, which don't need root
+    if cli.replay.is_none() && !nix::unistd::Uid::effective().is_root() {
         return Err(eyre!("partner must be run as root"));
     }
 
@@ -39,16 +43,62 @@ fn main() -> Result<()> {
             .with_env_filter(EnvFilter::from_default_env())
             .init();
     }
+    // printing to stderr would garble the UI
+    Device::set_exception_handler(|e| {
+        tracing::warn!(kind = %e.kind, "libparted: {}", e.message);
+        Resolution::Unhandled
+    });
+    if cli.list {
+        let devices = match &cli.device {
+            Some(device) => vec![Device::open(device).context("failed to open device")?],
+            None => Device::get_all().context("failed to get devices")?,
+        };
+        for device in devices {
+            println!("{}", device.summary());
+        }
+        return Ok(());
+    }
+
+    // replays run unattended, so they're kept away from real devices
+    let devices = if cli.replay.is_some() {
+        let image = cli
+            .device
+            .as_ref()
+            .ok_or_else(|| eyre!("replays need a disk image"))?;
+        if !std::fs::metadata(image)
+            .context("failed to read disk image")?
+            .is_file()
+        {
+            return Err(eyre!("replays can only be run against disk images"));
+        }
+        Vec::new()
+    } else {
+        Device::get_all().context("failed to get devices")?
+    };
+
     let mut state = State {
-        devices: Device::get_all().context("failed to get devices")?,
+        devices,
         selected_device: None,
         selected_partition: None,
         table: TableState::new().with_selected(Some(0)),
         input: None,
+        queue: None,
+        diff: false,
+        commit: None,
+        devices_stale: false,
+        armed: None,
+        replay: cli.replay.is_some(),
     };
 
     if let Some(device) = cli.device {
-        if let Some(index) = state.devices.iter().position(|d| d.path() == device) {
+        let canonical = device
+            .canonicalize()
+            .context("failed to resolve device path")?;
+        if let Some(index) = state.devices.iter().position(|d| d.path() == canonical) {
+            // reopen under the given name so that it's displayed
+            if canonical != device {
+                state.devices[index] = Device::open(device).context("failed to open device")?;
+            }
             state.selected_device = Some(index);
         } else {
             state
@@ -59,7 +109,13 @@ fn main() -> Result<()> {
         }
     }
 
-    App::new_with(state, logic::update, ui::view).run()?;
+    if let Some(events) = cli.replay {
+        return replay::run(state, &events, cli.snapshot.as_deref());
+    }
+
+    App::new_with(state, screens::update, screens::view)
+        .subscription(hotplug::watch())
+        .run()?;
 
     Ok(())
 }
@@ -76,44 +132,35 @@ struct State<'a> {
     selected_device: Option<usize>,
     selected_partition: Option<(Either<usize, NewPartition>, TableState)>,
     input: Option<Input>,
+    /// The pending changes of the selected device, when they're being shown.
+    queue: Option<TableState>,
+    /// Whether the on-disk and planned layouts of the selected device are shown.
+    diff: bool,
+    commit: Option<CommitScreen>,
+    /// Whether devices were added or removed since the device list was last refreshed.
+    devices_stale: bool,
+    /// A key for an action that wipes the whole device, which was pressed once and takes effect
+    /// if it's pressed again right away.
+    armed: Option<char>,
+    /// Whether key presses are replayed from a file, in which case nothing is committed or done
+    /// to the devices right away.
+    replay: bool,
 }
 
-impl State<'_> {
-    pub fn real_partition_index(&self, device: usize, partition: usize) -> usize {
-        partition
-            - self.devices[device]
-                .partitions_with_empty()
-                .iter()
-                .take(partition)
-                .filter(|p| p.is_right())
-                .count()
-    }
-}
-
-fn as_left<T, U>(either: &Either<T, U>) -> Option<&T> {
-    match either {
-        Either::Left(l) => Some(l),
-        Either::Right(_) => None,
-    }
+/// Results of background work, fed to the update function.
+enum Message {
+    /// Block devices were added or removed.
+    Hotplug,
 }
 
-fn get_preceding(dev: &Device, bounds: &RangeInclusive<i64>) -> Byte {
-    let prev_index = {
-        let next_index = dev
-            .partitions()
-            .position(|p| p.bounds().end() > bounds.start())
-            .unwrap_or_else(|| dev.partitions().count() - 1);
-        next_index as i64 - 1
-    };
-    if prev_index < 0 {
-        Byte::from_u64(0)
-    } else {
-        let prev_end = dev
-            .partitions()
-            .nth(prev_index as usize)
-            .unwrap()
-            .bounds()
-            .end();
-        Byte::from_u64((bounds.start() - prev_end - 1) as u64 * dev.sector_size())
+impl State<'_> {
+    /// Get the index of the partition in the given row of a device's table, or `None` if the row
+    /// is free space.
+    pub fn partition_index(&self, device: usize, row: usize) -> Option<usize> {
+        self.devices[device]
+            .partitions_with_empty()
+            .get(row)?
+            .as_partition()
+            .map(|p| p.index)
     }
 }
@@ -0,0 +1,243 @@
+//! Replaying key presses from a file without a terminal, for automated end-to-end tests and
+//! reproducible bug reports.
+//!
+//! The file holds a JSON array of keys such as `"Down"`, `"Enter"`, `"a"`, or `"Ctrl+z"`. Replays
+//! only ever open the disk image they're given, so they need neither root nor real hardware, and
+//! keys that commit, eject, or wipe do nothing.
+//!
+//! Given a snapshot file, the final screen is compared with it instead of printed, which makes
+//! replays usable as regression tests for the UI. Missing snapshots are recorded.
+
+use super::{State, screens};
+use color_eyre::{
+    Result,
+    eyre::{Context, eyre},
+};
+use ratatui::{
+    Terminal,
+    backend::TestBackend,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
+};
+use ratatui_elm::{Task, Update};
+use std::path::Path;
+
+const WIDTH: u16 = 120;
+const HEIGHT: u16 = 40;
+
+pub fn run(state: State, events: &Path, snapshot: Option<&Path>) -> Result<()> {
+    let keys: Vec<String> = serde_json::from_str(
+        &std::fs::read_to_string(events).context("failed to read replay file")?,
+    )
+    .context("failed to parse replay file")?;
+
+    let screen = replay(state, &keys)?;
+    match snapshot {
+        Some(snapshot) => compare_snapshot(&screen, snapshot),
+        None => {
+            println!("{screen}");
+            Ok(())
+        }
+    }
+}
+
+/// Press the given keys, rendering after each one, and get the final screen.
+fn replay(mut state: State, keys: &[String]) -> Result<String> {
+    let mut terminal = Terminal::new(TestBackend::new(WIDTH, HEIGHT))?;
+    terminal.draw(|frame| screens::view(&mut state, frame))?;
+    for key in keys {
+        let event = Event::Key(parse_key(key)?);
+        let (task, _) = screens::update(&mut state, Update::Terminal(event));
+        if matches!(task, Task::Quit) {
+            break;
+        }
+        // rendering corrects selections, so it has to happen between events like it does live
+        terminal.draw(|frame| screens::view(&mut state, frame))?;
+    }
+    Ok(terminal.backend().to_string())
+}
+
+/// Compare a screen with a snapshot file, or record it if the file is missing.
+fn compare_snapshot(screen: &str, snapshot: &Path) -> Result<()> {
+    match std::fs::read_to_string(snapshot) {
+        Ok(expected) if expected == screen => Ok(()),
+        Ok(expected) => Err(eyre!(
+            "screen differs from {}\nexpected:\n{expected}\nactual:\n{screen}",
+            snapshot.display()
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            std::fs::write(snapshot, screen).context("failed to record snapshot")
+        }
+        Err(e) => Err(e).context("failed to read snapshot"),
+    }
+}
+
+/// Parse a key like `Shift+Up` or `q`.
+fn parse_key(key: &str) -> Result<KeyEvent> {
+    let (modifiers, code) = match key.strip_suffix("++") {
+        // a plus sign after modifiers
+        Some(modifiers) => (modifiers, "+"),
+        None => match key.rsplit_once('+').filter(|(_, code)| !code.is_empty()) {
+            Some((modifiers, code)) => (modifiers, code),
+            None => ("", key),
+        },
+    };
+
+    let modifiers = modifiers
+        .split('+')
+        .filter(|m| !m.is_empty())
+        .map(|m| match m {
+            "Ctrl" => Ok(KeyModifiers::CONTROL),
+            "Alt" => Ok(KeyModifiers::ALT),
+            "Shift" => Ok(KeyModifiers::SHIFT),
+            _ => Err(eyre!("unknown modifier `{m}` in key `{key}`")),
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .fold(KeyModifiers::NONE, |a, b| a | b);
+
+    let code = match code {
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Enter" => KeyCode::Enter,
+        "Esc" => KeyCode::Esc,
+        "Delete" => KeyCode::Delete,
+        "Backspace" => KeyCode::Backspace,
+        "Tab" => KeyCode::Tab,
+        _ => {
+            let mut chars = code.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return Err(eyre!("unknown key `{key}`")),
+            }
+        }
+    };
+
+    Ok(KeyEvent::new(code, modifiers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::widgets::TableState;
+    use std::path::PathBuf;
+
+    /// The state the TUI starts in, without any devices.
+    fn state() -> State<'static> {
+        State {
+            devices: Vec::new(),
+            selected_device: None,
+            selected_partition: None,
+            table: TableState::new().with_selected(Some(0)),
+            input: None,
+            queue: None,
+            diff: false,
+            commit: None,
+            devices_stale: false,
+            armed: None,
+            replay: true,
+        }
+    }
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    /// A path in the temporary directory, removed when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("partner-snapshot-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn keys_are_parsed_with_their_modifiers() {
+        let parsed = |key| parse_key(key).expect("failed to parse key");
+        assert_eq!(
+            parsed("q"),
+            KeyEvent::new(KeyCode::Char('q'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parsed("Enter"),
+            KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parsed("Ctrl+z"),
+            KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            parsed("Ctrl+Shift+Up"),
+            KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+        );
+        // a plus sign on its own, or as the key after modifiers
+        assert_eq!(
+            parsed("+"),
+            KeyEvent::new(KeyCode::Char('+'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parsed("Alt++"),
+            KeyEvent::new(KeyCode::Char('+'), KeyModifiers::ALT)
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        for key in ["", "Home", "Meta+a", "Ctrl+"] {
+            assert!(parse_key(key).is_err(), "`{key}` was parsed");
+        }
+    }
+
+    #[test]
+    fn missing_snapshots_are_recorded() {
+        let snapshot = TempPath::new("recorded");
+        compare_snapshot("screen", &snapshot.0).expect("failed to record the snapshot");
+        assert_eq!(
+            std::fs::read_to_string(&snapshot.0).expect("snapshot wasn't recorded"),
+            "screen"
+        );
+        compare_snapshot("screen", &snapshot.0).expect("the recorded snapshot didn't match");
+    }
+
+    #[test]
+    fn differing_screens_fail_with_both_screens() {
+        let snapshot = TempPath::new("differing");
+        std::fs::write(&snapshot.0, "before").expect("failed to write the snapshot");
+        let error = compare_snapshot("after", &snapshot.0)
+            .expect_err("a differing screen matched")
+            .to_string();
+        assert!(
+            error.contains("expected:\nbefore\nactual:\nafter"),
+            "{error}"
+        );
+        // the snapshot is left for the tester to update
+        assert_eq!(
+            std::fs::read_to_string(&snapshot.0).expect("snapshot was removed"),
+            "before"
+        );
+    }
+
+    #[test]
+    fn the_device_list_matches_its_snapshot() {
+        let screen = replay(state(), &keys(&["Down", "Up"])).expect("failed to replay");
+        let snapshot = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/tui/snapshots/devices.txt");
+        compare_snapshot(&screen, &snapshot).expect("the screen changed");
+    }
+
+    #[test]
+    fn replays_stop_at_quit() {
+        let screen = replay(state(), &keys(&["q", "not a key"])).expect("failed to replay");
+        assert_eq!(screen, replay(state(), &[]).expect("failed to replay"));
+    }
+}
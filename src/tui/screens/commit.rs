@@ -0,0 +1,121 @@
+use crate::{Message, State, components::legend};
+use partner::{CommitOptions, Device, DeviceOutcome, Transaction};
+use ratatui::{
+    Frame,
+    crossterm::event::{Event, KeyCode, KeyEvent},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    widgets::{Block, Gauge},
+};
+use ratatui_elm::{Task, Update};
+use tracing::warn;
+
+/// The results of committing every device with pending changes.
+pub struct CommitScreen {
+    rows: Vec<CommitRow>,
+}
+
+struct CommitRow {
+    path: String,
+    /// How much of the device's commit was done when it last reported progress.
+    fraction: f64,
+    /// `None` if the device was skipped because an earlier one failed.
+    status: Option<Result<(), String>>,
+}
+
+pub fn update(state: &mut State, update: Update<Message>) -> (Task<Message>, bool) {
+    let Update::Terminal(Event::Key(KeyEvent { code, .. })) = update else {
+        return (Task::None, false);
+    };
+
+    match code {
+        KeyCode::Esc | KeyCode::Enter => {
+            state.commit = None;
+            (Task::None, true)
+        }
+        _ => (Task::None, false),
+    }
+}
+
+/// Commit every device with pending changes as one transaction.
+pub fn commit_all(state: &mut State) {
+    let committed = state
+        .devices
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.n_changes() > 0)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut rows = committed
+        .iter()
+        .map(|i| CommitRow {
+            path: state.devices[*i].display_path().display().to_string(),
+            fraction: 0.0,
+            status: None,
+        })
+        .collect::<Vec<_>>();
+
+    let outcomes = Transaction::new(state.devices.iter_mut().filter(|d| d.n_changes() > 0))
+        .commit_with_progress(&CommitOptions::new(), |i, progress| {
+            rows[i].fraction = progress.fraction;
+        });
+
+    for ((row, outcome), index) in rows.iter_mut().zip(outcomes).zip(committed) {
+        row.status = match outcome {
+            DeviceOutcome::Committed(_) => {
+                row.fraction = 1.0;
+                // re-read the layout libparted actually wrote
+                match Device::open(state.devices[index].display_path()) {
+                    Ok(device) => state.devices[index] = device,
+                    Err(e) => warn!(?e, path = row.path, "Failed to reopen device"),
+                }
+                Some(Ok(()))
+            }
+            DeviceOutcome::Failed(e) => {
+                warn!(?e, path = row.path, "Failed to commit device");
+                Some(Err(e.to_string()))
+            }
+            DeviceOutcome::Skipped => None,
+        };
+    }
+
+    state.commit = Some(CommitScreen { rows });
+}
+
+pub fn view(commit: &CommitScreen, frame: &mut Frame) {
+    let [top, bottom] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+
+    let block = Block::bordered()
+        .title("Commit")
+        .title_style(Style::new().bold());
+    let inner = block.inner(top);
+    frame.render_widget(block, top);
+
+    // one row per device, a blank line, and the overall progress
+    let areas = Layout::vertical(vec![Constraint::Length(1); commit.rows.len() + 2]).split(inner);
+    for (row, area) in commit.rows.iter().zip(areas.iter()) {
+        let (status, style) = match &row.status {
+            Some(Ok(())) => ("done".to_string(), Style::new().green()),
+            Some(Err(e)) => (format!("failed: {e}"), Style::new().red()),
+            None => ("skipped".to_string(), Style::new().dim()),
+        };
+        frame.render_widget(
+            Gauge::default()
+                .ratio(row.fraction.clamp(0.0, 1.0))
+                .label(format!("{}: {status}", row.path))
+                .gauge_style(style),
+            *area,
+        );
+    }
+    let overall =
+        commit.rows.iter().map(|r| r.fraction).sum::<f64>() / commit.rows.len().max(1) as f64;
+    frame.render_widget(
+        Gauge::default()
+            .ratio(overall.clamp(0.0, 1.0))
+            .label(format!("Overall: {:.0}%", overall * 100.0)),
+        areas[commit.rows.len() + 1],
+    );
+
+    frame.render_widget(legend(["Esc/Enter: Back"]), bottom);
+}
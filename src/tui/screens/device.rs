@@ -0,0 +1,439 @@
+use crate::{
+    Message, NewPartition, State, clipboard,
+    components::{layout_bar, legend, size_label},
+    consts::*,
+};
+use byte_unit::Byte;
+use either::Either;
+use partner::{Device, DiskLabel, FileSystem, LayoutDelta, LayoutRow};
+use ratatui::{
+    Frame,
+    crossterm::event::{Event, KeyCode, KeyEvent},
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Row, Table, TableState},
+};
+use ratatui_elm::{Task, Update};
+use tracing::warn;
+
+/// `armed` is the key of a wipe pressed right before this one, which this key confirms if it's the
+/// same.
+pub fn update(
+    state: &mut State,
+    update: Update<Message>,
+    device: usize,
+    armed: Option<char>,
+) -> (Task<Message>, bool) {
+    let Update::Terminal(Event::Key(KeyEvent { code, .. })) = update else {
+        return (Task::None, false);
+    };
+
+    let partitions = state.devices[device].partitions_with_empty();
+    let selected_partition = &partitions[state.table.selected().unwrap()];
+
+    match code {
+        KeyCode::Esc => {
+            state.table.select(Some(device));
+
+            state.selected_device = None;
+            (Task::None, true)
+        }
+        KeyCode::Enter
+            if selected_partition
+                .as_partition()
+                .is_some_and(|p| !p.mounted()) =>
+        {
+            state.selected_partition = state.table.selected().map(|s| {
+                (
+                    Either::Left(s),
+                    TableState::new().with_selected_cell(Some((0, 0))),
+                )
+            });
+            (Task::None, true)
+        }
+        KeyCode::Enter => {
+            let Some(free) = selected_partition.as_free() else {
+                return (Task::None, false);
+            };
+            state.selected_partition = Some((
+                Either::Right(NewPartition {
+                    name: "".into(),
+                    fs: FileSystem::Ext4,
+                    bounds: free.bounds(),
+                }),
+                TableState::new().with_selected_cell(Some((0, 0))),
+            ));
+            (Task::None, true)
+        }
+        KeyCode::Delete
+            if selected_partition
+                .as_partition()
+                .is_some_and(|p| !p.mounted()) =>
+        {
+            let index = selected_partition.as_partition().unwrap().index;
+            if let Err(e) = state.devices[device].remove_partition(index) {
+                warn!("failed to remove partition: {e}");
+            }
+            (Task::None, true)
+        }
+        KeyCode::Char('a')
+            if selected_partition
+                .as_partition()
+                .is_some_and(|p| !p.mounted()) =>
+        {
+            let index = selected_partition.as_partition().unwrap().index;
+            if state.devices[device]
+                .misaligned_partitions()
+                .contains(&index)
+                && let Err(e) = state.devices[device].realign_partition(index)
+            {
+                warn!("failed to realign partition: {e}");
+            }
+            (Task::None, true)
+        }
+        KeyCode::Char(key @ ('y' | 'u' | 'U'))
+            if selected_partition
+                .as_partition()
+                .is_some_and(|p| p.path.is_some()) =>
+        {
+            let path = selected_partition
+                .as_partition()
+                .unwrap()
+                .path
+                .clone()
+                .unwrap();
+            let text = match key {
+                'y' => Some(path.display().to_string()),
+                'u' => clipboard::udev_name("by-uuid", &path),
+                _ => clipboard::udev_name("by-partuuid", &path),
+            };
+            match text {
+                Some(text) => {
+                    if let Err(e) = clipboard::copy(&text) {
+                        warn!(?e, "Failed to copy to clipboard");
+                    }
+                }
+                None => warn!(?path, "Partition has no such identifier"),
+            }
+            (Task::None, true)
+        }
+        KeyCode::Char('d') => {
+            state.diff = !state.diff;
+            (Task::None, true)
+        }
+        KeyCode::Char('p') if state.devices[device].n_changes() > 0 => {
+            state.queue = Some(TableState::new().with_selected(Some(0)));
+            (Task::None, true)
+        }
+        KeyCode::Char('f' | 'g') if state.replay => (Task::None, false),
+        KeyCode::Char(key @ ('f' | 'g')) if armed != Some(key) => {
+            state.armed = Some(key);
+            (Task::None, true)
+        }
+        KeyCode::Char('f') => {
+            if let Err(e) = state.devices[device].quick_format(FileSystem::Fat32, "") {
+                warn!("failed to stage format: {e}");
+            }
+            state.table.select(Some(0));
+            (Task::None, true)
+        }
+        KeyCode::Char('g') => {
+            if let Err(e) = state.devices[device].create_label(DiskLabel::Gpt) {
+                warn!("failed to stage partition table: {e}");
+            }
+            state.table.select(Some(0));
+            (Task::None, true)
+        }
+        _ => (Task::None, false),
+    }
+}
+
+pub fn view(state: &mut State, frame: &mut Frame, device: usize) {
+    const COLUMNS: usize = 5;
+
+    let dev = &state.devices[device];
+
+    let mut constraints = if state.selected_partition.is_some() || state.queue.is_some() {
+        vec![Constraint::Ratio(1, 2); 2]
+    } else if state.diff {
+        // a border around a line for each layout
+        vec![Constraint::Min(0), Constraint::Length(4)]
+    } else {
+        vec![Constraint::Min(0)]
+    };
+    constraints.push(Constraint::Length(1));
+    let layout = Layout::vertical(constraints).split(frame.area());
+
+    let n_changes_contents = format!(
+        "{} pending change{}",
+        dev.n_changes(),
+        if dev.n_changes() > 1 { "s" } else { "" }
+    );
+
+    let top = layout[0];
+    let [legend_area, n_changes] = Layout::horizontal([
+        Constraint::Min(0),
+        Constraint::Length(n_changes_contents.chars().count() as u16),
+    ])
+    .areas(*layout.last().unwrap());
+
+    let block = Block::bordered().title(format!(
+        "Partitions of {}{}",
+        dev.display_path().display(),
+        if dev.hybrid_mbr().is_empty() {
+            ""
+        } else {
+            " (hybrid MBR)"
+        }
+    ));
+
+    let block = if state.selected_partition.is_none() && state.queue.is_none() {
+        block.title_style(Style::new().bold())
+    } else {
+        block
+    };
+
+    let partitions = dev.partitions_with_empty();
+    let misaligned = dev
+        .misaligned_partitions()
+        .into_iter()
+        .filter_map(|i| dev.partitions().nth(i))
+        .map(|p| *p.bounds().start())
+        .collect::<Vec<_>>();
+
+    let table = Table::new(
+        partitions.iter().map(|p| {
+            let p = match p {
+                LayoutRow::Partition(p) => p,
+                LayoutRow::Free(free) => {
+                    return Row::new::<[String; COLUMNS]>([
+                        "unused".into(),
+                        "".into(),
+                        size_label(Byte::from_u64(free.geometry.size_byte)),
+                        "".into(),
+                        "".into(),
+                    ]);
+                }
+            };
+            let path_line = {
+                let path_span = Span::raw(
+                    p.path
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_else(|| "N/A".into()),
+                );
+                let mut line = Line::from(path_span);
+                if let Some(label) = &p.fs_label {
+                    line.push_span(Span::styled(format!(" [{label}]"), Style::new().italic()));
+                }
+                if p.mounted() {
+                    line.push_span(Span::styled(" (mounted)", Style::new().bold()));
+                }
+                if p.booted_esp {
+                    line.push_span(Span::styled(" (booted ESP)", Style::new().bold()));
+                }
+                if misaligned.contains(&p.geometry.start_sector) {
+                    line.push_span(Span::styled(" (misaligned)", Style::new().yellow()));
+                }
+                line
+            };
+            Row::new::<[Line; COLUMNS]>([
+                path_line,
+                Line::raw(
+                    p.fs.map(|f| f.to_string())
+                        .or_else(|| p.content.map(|c| format!("({c})")))
+                        .unwrap_or_default(),
+                ),
+                Line::raw(size_label(Byte::from_u64(p.geometry.size_byte))),
+                {
+                    let mut line = Line::raw(p.name.as_str());
+                    if let Some(note) = &p.note {
+                        line.push_span(Span::styled(format!(" ({note})"), Style::new().dim()));
+                    }
+                    line
+                },
+                Line::raw(
+                    p.mount_point
+                        .as_ref()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default(),
+                ),
+            ])
+        }),
+        [Constraint::Ratio(1, COLUMNS as u32); COLUMNS],
+    )
+    .header(
+        Row::new::<[&'static str; COLUMNS]>(["Path", "File System", "Size", "Name", "Mount"])
+            .style(Style::new().bold()),
+    )
+    .row_highlight_style(Style::new().reversed())
+    .block(block);
+
+    // the table has to be rendered first so out-of-bounds selections get corrected
+    frame.render_stateful_widget(table, top, &mut state.table);
+
+    let mut actions = if state.input.is_none() {
+        vec!["q: Quit"]
+    } else {
+        Vec::new()
+    };
+    if state.queue.is_some() {
+        actions.extend([
+            "Esc: Back",
+            "Up/Down: Change selection",
+            "Shift+Up/Down: Reorder",
+            "Ctrl+z: Undo",
+        ]);
+        frame.render_widget(legend(actions), legend_area);
+        super::queue::view(state, frame, layout[1], device);
+        return;
+    }
+    if matches!(state.selected_partition, Some((Either::Right(_), _))) {
+        actions.push("Esc: Abort");
+    } else {
+        actions.push("Esc: Back");
+    }
+    let partition = &partitions[state.table.selected().unwrap()];
+    if state.selected_partition.is_none() {
+        actions.push("Up/Down: Change selection");
+    }
+    if state.input.is_none() && dev.n_changes() > 0 {
+        actions.push("Ctrl+z: Undo");
+    }
+    if state.input.is_none() && dev.n_redoable() > 0 {
+        actions.push("Ctrl+y: Redo");
+    }
+    if state.selected_partition.is_none() && partition.is_free() {
+        actions.push("Enter: Create");
+    }
+    if state.selected_partition.is_none()
+        && let LayoutRow::Partition(partition) = partition
+        && !partition.mounted()
+    {
+        actions.push("Enter: Edit");
+    }
+    if state.selected_partition.is_some() && state.input.is_none() {
+        actions.push("Enter: Select");
+    }
+    if state.selected_partition.is_none()
+        && let LayoutRow::Partition(partition) = partition
+        && !partition.mounted()
+    {
+        actions.push("Delete: Remove");
+    }
+    if state.selected_partition.is_none()
+        && let LayoutRow::Partition(partition) = partition
+        && !partition.mounted()
+        && misaligned.contains(&partition.geometry.start_sector)
+    {
+        actions.push("a: Realign");
+    }
+    if state.selected_partition.is_none()
+        && partition.as_partition().is_some_and(|p| p.path.is_some())
+    {
+        actions.extend(["y: Copy path", "u: Copy UUID", "U: Copy PARTUUID"]);
+    }
+    if state.selected_partition.is_none() && dev.n_changes() > 0 {
+        actions.push("p: Pending changes");
+    }
+    if state.selected_partition.is_none() {
+        actions.push(if state.diff {
+            "d: Hide diff"
+        } else {
+            "d: Show diff"
+        });
+    }
+    if !state.replay && state.selected_partition.is_none() && !dev.partitions().any(|p| p.mounted())
+    {
+        actions.push(match state.armed {
+            Some('f') => "f: Confirm wiping the device to format it",
+            _ => "f: Format as FAT32",
+        });
+        actions.push(match state.armed {
+            Some('g') => "g: Confirm wiping the device for a new GPT",
+            _ => "g: New GPT",
+        });
+    }
+    if state.input.is_some() {
+        actions.extend(["Esc: Abort", "Enter: Apply"]);
+    }
+    if state.input.is_some()
+        && let Some((Either::Left(_), table)) = &state.selected_partition
+        && table.selected_cell() == Some(SIZE_CELL)
+        && partition.as_partition().is_some_and(|p| p.used.is_some())
+    {
+        actions.push("Alt+Enter: Apply anyway");
+    }
+
+    frame.render_widget(legend(actions), legend_area);
+    if dev.n_changes() > 0 {
+        frame.render_widget(
+            Text::raw(n_changes_contents).alignment(ratatui::layout::Alignment::Right),
+            n_changes,
+        );
+    }
+
+    if let Some(partition) = state.selected_partition.take() {
+        super::partition::view(state, frame, layout[1], device, partition);
+    } else if state.diff {
+        view_diff(dev, frame, layout[1]);
+    }
+}
+
+fn view_diff(dev: &Device, frame: &mut Frame, area: Rect) {
+    const LABEL_WIDTH: usize = 9;
+
+    let before = dev.on_disk_snapshot();
+    let after = dev.snapshot();
+    let deltas = before.diff(&after);
+    let table_replaced = deltas
+        .iter()
+        .any(|d| matches!(d, LayoutDelta::DiskType { .. }));
+
+    // partitions are identified by their start sector within each snapshot
+    let (mut removed, mut added, mut changed_before, mut changed_after) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for delta in &deltas {
+        match delta {
+            LayoutDelta::Removed(p) => removed.push(p.start),
+            LayoutDelta::Added(p) => added.push(p.start),
+            LayoutDelta::Changed { before, after } => {
+                changed_before.push(before.start);
+                changed_after.push(after.start);
+            }
+            LayoutDelta::DiskType { .. } => {}
+        }
+    }
+
+    let block = Block::bordered().title("On disk vs. planned");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let width = (inner.width as usize).saturating_sub(LABEL_WIDTH);
+    let [before_area, after_area] = Layout::vertical([Constraint::Length(1); 2]).areas(inner);
+    frame.render_widget(
+        layout_bar("On disk", &before, width, |p| {
+            if table_replaced || removed.contains(&p.start) {
+                Style::new().red()
+            } else if changed_before.contains(&p.start) {
+                Style::new().yellow()
+            } else {
+                Style::new().blue()
+            }
+        }),
+        before_area,
+    );
+    frame.render_widget(
+        layout_bar("Planned", &after, width, |p| {
+            if table_replaced || added.contains(&p.start) {
+                Style::new().green()
+            } else if changed_after.contains(&p.start) {
+                Style::new().yellow()
+            } else {
+                Style::new().blue()
+            }
+        }),
+        after_area,
+    );
+}
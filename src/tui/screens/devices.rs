@@ -0,0 +1,107 @@
+use crate::{
+    Message, State,
+    components::{legend, size_label},
+};
+use ratatui::{
+    Frame,
+    crossterm::event::{Event, KeyCode, KeyEvent},
+    layout::{Constraint, Layout},
+    style::{Style, Stylize},
+    widgets::{Block, Row, Table},
+};
+use ratatui_elm::{Task, Update};
+use tracing::warn;
+
+pub fn update(state: &mut State, update: Update<Message>) -> (Task<Message>, bool) {
+    let Update::Terminal(Event::Key(KeyEvent { code, .. })) = update else {
+        return (Task::None, false);
+    };
+
+    match code {
+        KeyCode::Esc => (Task::Quit, false),
+        KeyCode::Char('c' | 'e') if state.replay => (Task::None, false),
+        KeyCode::Char('c') if state.devices.iter().any(|d| d.n_changes() > 0) => {
+            super::commit::commit_all(state);
+            (Task::None, true)
+        }
+        KeyCode::Enter => {
+            state.selected_device = state.table.selected();
+            state.table.select(Some(0));
+            (Task::None, true)
+        }
+        KeyCode::Char('e')
+            if state
+                .table
+                .selected()
+                .and_then(|i| state.devices.get(i))
+                .is_some_and(|d| d.is_removable()) =>
+        {
+            let index = state.table.selected().unwrap();
+            let device = state.devices.remove(index);
+            let path = device.display_path().to_owned();
+            if let Err(e) = device.eject() {
+                warn!("failed to eject {}: {e}", path.display());
+            }
+            state
+                .table
+                .select((!state.devices.is_empty()).then(|| index.min(state.devices.len() - 1)));
+            (Task::None, true)
+        }
+        _ => (Task::None, false),
+    }
+}
+
+pub fn view(state: &mut State, frame: &mut Frame) {
+    const COLUMNS: usize = 3;
+
+    let [top, bottom] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+
+    let table = Table::new(
+        state.devices.iter().map(|d| {
+            Row::new::<[String; COLUMNS]>([
+                d.display_path().display().to_string(),
+                if d.is_read_only() {
+                    format!("{} (read-only)", d.model())
+                } else if d.is_superfloppy() {
+                    format!("{} (no partition table)", d.model())
+                } else if d.label().is_none() {
+                    format!("{} (blank)", d.model())
+                } else if let Some(label) = d.disk_type()
+                    && !d.is_editable()
+                {
+                    format!("{} ({label} label, not editable)", d.model())
+                } else {
+                    d.model().to_string()
+                },
+                size_label(d.size()),
+            ])
+        }),
+        [Constraint::Ratio(1, COLUMNS as u32); COLUMNS],
+    )
+    .header(
+        Row::new::<[&'static str; COLUMNS]>(["Path", "Model", "Size"]).style(Style::new().bold()),
+    )
+    .row_highlight_style(Style::new().reversed())
+    .block(
+        Block::bordered()
+            .title("Devices")
+            .title_style(Style::new().bold()),
+    );
+
+    frame.render_stateful_widget(table, top, &mut state.table);
+    let mut actions = vec!["Esc/q: Quit", "Up/Down: Change selection", "Enter: Select"];
+    if !state.replay
+        && state
+            .table
+            .selected()
+            .and_then(|i| state.devices.get(i))
+            .is_some_and(|d| d.is_removable())
+    {
+        actions.push("e: Eject");
+    }
+    if !state.replay && state.devices.iter().any(|d| d.n_changes() > 0) {
+        actions.push("c: Commit all");
+    }
+    frame.render_widget(legend(actions), bottom);
+}
@@ -0,0 +1,156 @@
+//! The screens of the UI. Each has an `update` and a `view` function, which the functions here
+//! dispatch to depending on what is open.
+
+mod commit;
+mod device;
+mod devices;
+mod partition;
+mod queue;
+
+pub use commit::CommitScreen;
+
+use crate::{Message, State};
+use either::Either;
+use partner::{Change, Device};
+use ratatui::{
+    Frame,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
+};
+use ratatui_elm::{Task, Update};
+use tracing::warn;
+
+pub fn update(state: &mut State, update: Update<Message>) -> (Task<Message>, bool) {
+    if let Update::Message(Message::Hotplug) = update {
+        state.devices_stale = true;
+    }
+
+    let (task, render) = route(state, update);
+
+    // only refresh on the device list, so indices don't shift under the other screens
+    if state.devices_stale && state.selected_device.is_none() && state.commit.is_none() {
+        refresh_devices(state);
+        return (task, true);
+    }
+    (task, render)
+}
+
+/// Reread the list of devices, keeping those with pending changes or opened under an alias.
+fn refresh_devices(state: &mut State) {
+    let devices = match Device::get_all() {
+        Ok(devices) => devices,
+        Err(e) => {
+            warn!(?e, "Failed to refresh devices");
+            return;
+        }
+    };
+    state.devices_stale = false;
+
+    let mut kept = std::mem::take(&mut state.devices)
+        .into_iter()
+        .filter(|d| d.n_changes() > 0 || d.alias().is_some())
+        .collect::<Vec<_>>();
+    for device in devices {
+        match kept.iter().position(|d| d.path() == device.path()) {
+            Some(i) => state.devices.push(kept.remove(i)),
+            None => state.devices.push(device),
+        }
+    }
+}
+
+fn route(state: &mut State, update: Update<Message>) -> (Task<Message>, bool) {
+    // a wipe is only confirmed by the very next key
+    let armed = if let Update::Terminal(Event::Key(_)) = &update {
+        state.armed.take()
+    } else {
+        state.armed
+    };
+
+    if let Update::Terminal(Event::Key(KeyEvent {
+        code, modifiers, ..
+    })) = &update
+    {
+        match code {
+            // Shift moves the selected change instead
+            KeyCode::Up if !(state.queue.is_some() && modifiers.contains(KeyModifiers::SHIFT)) => {
+                if let Some(queue) = &mut state.queue {
+                    queue.scroll_up_by(1);
+                } else if let Some((_, table)) = &mut state.selected_partition {
+                    table.scroll_up_by(1);
+                } else {
+                    state.table.scroll_up_by(1);
+                }
+                return (Task::None, true);
+            }
+            KeyCode::Down
+                if !(state.queue.is_some() && modifiers.contains(KeyModifiers::SHIFT)) =>
+            {
+                if let Some(queue) = &mut state.queue {
+                    queue.scroll_down_by(1);
+                } else if let Some((_, table)) = &mut state.selected_partition {
+                    table.scroll_down_by(1);
+                } else {
+                    state.table.scroll_down_by(1);
+                }
+                return (Task::None, true);
+            }
+            KeyCode::Char('q') if state.input.is_none() => return (Task::Quit, false),
+            KeyCode::Char('z') if modifiers.contains(KeyModifiers::CONTROL) => {
+                if state.input.is_none()
+                    && let Some(device) = state.selected_device
+                    && let Some(Change::ResizePartition { index, bounds, .. }) =
+                        state.devices[device].undo_change()
+                    && bounds.start()
+                        > state.devices[device]
+                            .partitions()
+                            .nth(index)
+                            .unwrap()
+                            .bounds()
+                            .start()
+                    && state
+                        .table
+                        .selected()
+                        .and_then(|i| state.partition_index(device, i))
+                        == Some(index + 1)
+                    && let Some((Either::Left(partition), _)) = &mut state.selected_partition
+                {
+                    state.table.scroll_up_by(1);
+                    *partition -= 1;
+                }
+                return (Task::None, true);
+            }
+            KeyCode::Char('y') if modifiers.contains(KeyModifiers::CONTROL) => {
+                if state.input.is_none()
+                    && let Some(device) = state.selected_device
+                {
+                    state.devices[device].redo_change();
+                }
+                return (Task::None, true);
+            }
+            _ => {}
+        }
+    }
+
+    if state.commit.is_some() {
+        commit::update(state, update)
+    } else if let Some(partition) = state.selected_partition.take() {
+        partition::update(state, update, partition)
+    } else if let Some(device) = state.selected_device
+        && state.queue.is_some()
+    {
+        queue::update(state, update, device)
+    } else if let Some(device) = state.selected_device {
+        device::update(state, update, device, armed)
+    } else {
+        devices::update(state, update)
+    }
+}
+
+pub fn view(state: &mut State, frame: &mut Frame) {
+    if let Some(commit) = &state.commit {
+        commit::view(commit, frame);
+    } else if let Some(device) = state.selected_device {
+        device::view(state, frame, device);
+    } else {
+        devices::view(state, frame);
+    }
+}
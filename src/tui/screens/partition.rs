@@ -0,0 +1,415 @@
+use crate::{
+    Message, NewPartition, State,
+    components::{size_input, size_label},
+    consts::*,
+};
+use byte_unit::Byte;
+use either::Either;
+use partner::{Geometry, size};
+use ratatui::{
+    Frame,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
+    layout::{Constraint, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Row, Table, TableState},
+};
+use ratatui_elm::{Task, Update};
+use tracing::warn;
+use tui_input::{Input, backend::crossterm::EventHandler};
+
+/// The smallest size a partition whose file system uses the given number of bytes can be shrunk
+/// to without forcing it.
+fn min_size(used: u64) -> u64 {
+    used + used / 20
+}
+
+/// What sizes typed for the given partition are resolved against: an existing partition can
+/// grow into the free space after it.
+fn size_context(
+    state: &State,
+    device: usize,
+    partition: &Either<usize, NewPartition>,
+) -> size::Context {
+    let dev = &state.devices[device];
+    let context = size::Context::device(dev);
+    match partition {
+        Either::Left(row) => {
+            let index = state.partition_index(device, *row).unwrap();
+            let current = dev.partitions().nth(index).unwrap();
+            size::Context {
+                available: Byte::from_u64(current.size().as_u64() + dev.gap_after(index).as_u64()),
+                ..context
+            }
+        }
+        Either::Right(_) => context,
+    }
+}
+
+pub fn update(
+    state: &mut State,
+    update: Update<Message>,
+    (mut partition, table): (Either<usize, NewPartition>, TableState),
+) -> (Task<Message>, bool) {
+    let Update::Terminal(event) = update else {
+        return (Task::None, false);
+    };
+    let Event::Key(KeyEvent {
+        code, modifiers, ..
+    }) = event
+    else {
+        return (Task::None, false);
+    };
+
+    let out = match code {
+        KeyCode::Esc => {
+            if state.input.is_some() {
+                state.input = None;
+                state.selected_partition = Some((partition, table));
+                return (Task::None, true);
+            }
+
+            if let Either::Left(partition) = partition {
+                state.table.select(Some(partition));
+            }
+
+            state.selected_partition = None;
+            return (Task::None, true);
+        }
+        KeyCode::Enter => {
+            if let Some(input) = &state.input {
+                match table.selected_cell() {
+                    Some(NAME_CELL) => {
+                        let result = match &mut partition {
+                            Either::Left(partition) => {
+                                let device = state.selected_device.unwrap();
+                                let real_partition =
+                                    state.partition_index(device, *partition).unwrap();
+                                state.devices[device]
+                                    .change_partition_name(real_partition, input.value().into())
+                            }
+                            Either::Right(partition) => partner::validate_name(input.value())
+                                .map(|()| partition.name = input.value().into())
+                                .map_err(Into::into),
+                        };
+                        if let Err(e) = result {
+                            warn!(?e, "Invalid name input");
+                            state.selected_partition = Some((partition, table));
+                            return (Task::None, false);
+                        }
+                    }
+                    Some(PRECEDING_CELL) => {
+                        let new_preceding = match input.value().parse::<Byte>() {
+                            Ok(new_preceding) => new_preceding,
+                            Err(e) => {
+                                warn!(?e, "Invalid byte input");
+                                state.selected_partition = Some((partition, table));
+                                return (Task::None, false);
+                            }
+                        };
+                        let result = match &mut partition {
+                            Either::Left(partition) => {
+                                let selected_device = state.selected_device.unwrap();
+                                let selected_partition_index =
+                                    state.partition_index(selected_device, *partition).unwrap();
+                                let prev_bounds = state.devices[selected_device]
+                                    .partitions()
+                                    .nth(selected_partition_index)
+                                    .unwrap()
+                                    .bounds();
+                                let end = *prev_bounds.end();
+                                let new_start = prev_bounds.start()
+                                    + (new_preceding.as_u64()
+                                        / state.devices[selected_device].sector_size())
+                                        as i64;
+                                if new_start == *prev_bounds.start() {
+                                    Ok(())
+                                } else {
+                                    state.devices[selected_device]
+                                        .resize_partition(selected_partition_index, new_start..=end)
+                                        .map(|()| {
+                                            *partition += 1;
+                                            state.table.scroll_down_by(1);
+                                        })
+                                }
+                            }
+                            Either::Right(partition) => {
+                                let new_start = partition.bounds.start()
+                                    + (new_preceding.as_u64()
+                                        / state.devices[state.selected_device.unwrap()]
+                                            .sector_size())
+                                        as i64;
+                                partition.bounds = new_start..=*partition.bounds.end();
+                                Ok(())
+                            }
+                        };
+                        if let Err(e) = result {
+                            warn!(?e, "Invalid resize");
+                            state.selected_partition = Some((partition, table));
+                            return (Task::None, false);
+                        }
+                    }
+                    Some(SIZE_CELL) => {
+                        let selected_device = state.selected_device.unwrap();
+                        let context = size_context(state, selected_device, &partition);
+                        let new_size = match input
+                            .value()
+                            .parse::<size::Expr>()
+                            .map_err(|e| e.to_string())
+                            .and_then(|e| e.resolve_sectors(&context).map_err(|e| e.to_string()))
+                        {
+                            Ok(new_size) => new_size as i64,
+                            Err(e) => {
+                                warn!(%e, "Invalid size input");
+                                state.selected_partition = Some((partition, table));
+                                return (Task::None, false);
+                            }
+                        };
+                        let applied = match &mut partition {
+                            Either::Left(partition) => {
+                                let selected_device = state.selected_device.unwrap();
+                                let selected_partition =
+                                    state.partition_index(selected_device, *partition).unwrap();
+                                let current = state.devices[selected_device]
+                                    .partitions()
+                                    .nth(selected_partition)
+                                    .unwrap();
+                                let start = *current.bounds().start();
+                                let used = current.used_space();
+                                // Alt+Enter forces sizes that leave the file system too little room
+                                if !modifiers.contains(KeyModifiers::ALT)
+                                    && let Some(used) = used
+                                    && (new_size as u64
+                                        * state.devices[selected_device].sector_size())
+                                        < min_size(used.as_u64())
+                                {
+                                    warn!(%used, "Refusing to shrink below used space");
+                                    false
+                                } else if let Err(e) = state.devices[selected_device]
+                                    .resize_partition(selected_partition, start..=start + new_size)
+                                {
+                                    warn!(?e, "Invalid resize");
+                                    false
+                                } else {
+                                    true
+                                }
+                            }
+                            Either::Right(partition) => {
+                                partition.bounds = new_size..=*partition.bounds.end();
+                                true
+                            }
+                        };
+                        if !applied {
+                            state.selected_partition = Some((partition, table));
+                            return (Task::None, false);
+                        }
+                    }
+                    _ => {}
+                }
+                state.input = None;
+            } else {
+                match table.selected_cell() {
+                    Some(NAME_CELL) => {
+                        let starting_name = match &partition {
+                            Either::Left(partition) => {
+                                let device = state.selected_device.unwrap();
+                                state.devices[device]
+                                    .partitions()
+                                    .nth(state.partition_index(device, *partition).unwrap())
+                                    .unwrap()
+                                    .name()
+                                    .to_string()
+                            }
+                            Either::Right(partition) => partition.name.clone(),
+                        };
+                        state.input = Some(Input::new(starting_name));
+                    }
+                    Some(PRECEDING_CELL) => {
+                        let selected_device = state.selected_device.unwrap();
+                        let dev = &state.devices[selected_device];
+                        let starting_preceding = match &partition {
+                            Either::Left(partition) => dev.gap_before(
+                                state.partition_index(selected_device, *partition).unwrap(),
+                            ),
+                            Either::Right(partition) => dev.gap_before_bounds(&partition.bounds),
+                        };
+                        state.input = Some(Input::new(size_input(starting_preceding)));
+                    }
+                    Some(SIZE_CELL) => {
+                        let selected_device = state.selected_device.unwrap();
+                        let dev = &state.devices[selected_device];
+                        let starting_size = match &partition {
+                            Either::Left(partition) => dev
+                                .partitions()
+                                .nth(state.partition_index(selected_device, *partition).unwrap())
+                                .unwrap()
+                                .size(),
+                            Either::Right(partition) => Byte::from_u64(
+                                Geometry::new(&partition.bounds, dev.sector_size()).size_byte,
+                            ),
+                        };
+                        state.input = Some(Input::new(size_input(starting_size)));
+                    }
+                    Some(SUBMIT_CELL) => {
+                        if let Either::Right(new) = &partition {
+                            if let Err(e) = state.devices[state.selected_device.unwrap()]
+                                .new_partition(
+                                    new.name.as_str().into(),
+                                    Some(new.fs),
+                                    new.bounds.clone(),
+                                )
+                            {
+                                warn!(?e, "Invalid partition");
+                                state.selected_partition = Some((partition, table));
+                                return (Task::None, false);
+                            }
+                            return (Task::None, true);
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            (Task::None, true)
+        }
+        _ => {
+            if let Some(input) = &mut state.input {
+                (Task::None, input.handle_event(&event).is_some())
+            } else {
+                (Task::None, false)
+            }
+        }
+    };
+    state.selected_partition = Some((partition, table));
+    out
+}
+
+pub fn view(
+    state: &mut State,
+    frame: &mut Frame,
+    area: Rect,
+    device: usize,
+    (partition, mut table_state): (Either<usize, NewPartition>, TableState),
+) {
+    let dev = &state.devices[device];
+    let partitions = dev.partitions_with_empty();
+    let title = if let Either::Left(partition) = &partition {
+        format!(
+            "Partition {}",
+            partitions[*partition]
+                .as_partition()
+                .unwrap()
+                .path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| { partition.to_string() })
+        )
+    } else {
+        "New Partition".to_string()
+    };
+    let block = Block::bordered()
+        .title(title)
+        .title_style(Style::new().bold());
+
+    let selected_cell = table_state.selected_cell().unwrap();
+
+    let name = match &partition {
+        Either::Left(partition) => partitions[*partition].as_partition().unwrap().name.as_str(),
+        Either::Right(partition) => partition.name.as_str(),
+    };
+    let name = if selected_cell.0 == 0 {
+        state.input.as_ref().map(|i| i.value()).unwrap_or(name)
+    } else {
+        name
+    };
+    let bounds = match &partition {
+        Either::Left(partition) => partitions[*partition].as_partition().unwrap().bounds(),
+        Either::Right(partition) => partition.bounds.clone(),
+    };
+    let size = Byte::from_u64(Geometry::new(&bounds, dev.sector_size()).size_byte);
+    let size = if selected_cell.0 == 2 {
+        state
+            .input
+            .as_ref()
+            .map(|i| i.value().to_string())
+            .unwrap_or_else(|| size_input(size))
+    } else {
+        size_label(size)
+    };
+
+    let preceding = if selected_cell.0 == 1 {
+        state
+            .input
+            .as_ref()
+            .map(|i| i.value().to_string())
+            .unwrap_or_else(|| size_input(dev.gap_before_bounds(&bounds)))
+    } else {
+        size_label(dev.gap_before_bounds(&bounds))
+    };
+
+    let used = match &partition {
+        Either::Left(partition) => partitions[*partition].as_partition().unwrap().used,
+        Either::Right(_) => None,
+    };
+    let block = if let Some(used) = used {
+        // forecast with the size being typed, if it parses
+        let context = size_context(state, device, &partition);
+        let new_size = state
+            .input
+            .as_ref()
+            .filter(|_| selected_cell.0 == 2)
+            .and_then(|i| i.value().parse::<size::Expr>().ok())
+            .and_then(|e| e.resolve(&context).ok())
+            .map(|b| b.as_u64())
+            .unwrap_or(Geometry::new(&bounds, dev.sector_size()).size_byte);
+        let style = if new_size < min_size(used) {
+            Style::new().red()
+        } else {
+            Style::new()
+        };
+        block.title_bottom(Line::styled(
+            format!(
+                " used {} / new size {} ({:.0}% full) ",
+                size_label(Byte::from_u64(used)),
+                size_label(Byte::from_u64(new_size)),
+                used as f64 / new_size.max(1) as f64 * 100.0
+            ),
+            style,
+        ))
+    } else {
+        block
+    };
+
+    let mut rows = vec![
+        Row::from_iter([format!("Name: {name}")]),
+        Row::from_iter([format!("Preceding: {preceding}")]),
+        Row::from_iter([format!("Size: {size}")]),
+    ];
+    if matches!(partition, Either::Right(_)) {
+        rows.push(Row::from_iter(["Submit"]));
+    }
+    let mut table = Table::new(rows, [Constraint::Min(0)]).block(block);
+    if state.input.is_none() {
+        table = table.cell_highlight_style(Style::new().reversed());
+    }
+
+    frame.render_stateful_widget(table, area, &mut table_state);
+
+    if let Some(input) = &state.input {
+        let selected_cell = table_state.selected_cell().unwrap();
+        let x_offset = match selected_cell {
+            NAME_CELL => "Name: ".len(),
+            PRECEDING_CELL => "Preceding: ".len(),
+            SIZE_CELL => "Size: ".len(),
+            SUBMIT_CELL => 0,
+            _ => unreachable!(),
+        } as u16
+            + 1;
+        let x = input.visual_cursor();
+        frame.set_cursor_position((
+            area.x + x as u16 + x_offset,
+            area.y + 1 + selected_cell.0 as u16,
+        ));
+    }
+
+    state.selected_partition = Some((partition, table_state));
+}
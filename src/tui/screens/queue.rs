@@ -0,0 +1,74 @@
+use crate::{Message, State};
+use ratatui::{
+    Frame,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
+    layout::{Constraint, Rect},
+    style::{Style, Stylize},
+    widgets::{Block, Row, Table, TableState},
+};
+use ratatui_elm::{Task, Update};
+use tracing::warn;
+
+pub fn update(state: &mut State, update: Update<Message>, device: usize) -> (Task<Message>, bool) {
+    let Update::Terminal(Event::Key(KeyEvent {
+        code, modifiers, ..
+    })) = update
+    else {
+        return (Task::None, false);
+    };
+
+    match code {
+        KeyCode::Esc => {
+            state.queue = None;
+            (Task::None, true)
+        }
+        KeyCode::Up | KeyCode::Down if modifiers.contains(KeyModifiers::SHIFT) => {
+            let dev = &mut state.devices[device];
+            let Some(from) = state
+                .queue
+                .as_ref()
+                .and_then(|q| q.selected())
+                .filter(|i| *i < dev.n_changes())
+            else {
+                return (Task::None, false);
+            };
+            let to = if code == KeyCode::Up {
+                from.checked_sub(1)
+            } else {
+                Some(from + 1).filter(|i| *i < dev.n_changes())
+            };
+            let Some(to) = to else {
+                return (Task::None, false);
+            };
+
+            match dev.move_change(from, to) {
+                Ok(()) => state.queue = Some(TableState::new().with_selected(Some(to))),
+                Err(e) => warn!(?e, "Failed to reorder change"),
+            }
+            (Task::None, true)
+        }
+        _ => (Task::None, false),
+    }
+}
+
+pub fn view(state: &mut State, frame: &mut Frame, area: Rect, device: usize) {
+    let dev = &state.devices[device];
+    let Some(queue) = &mut state.queue else {
+        return;
+    };
+
+    let table = Table::new(
+        dev.changes()
+            .iter()
+            .enumerate()
+            .map(|(i, c)| Row::new([format!("{}.", i + 1), c.to_string()])),
+        [Constraint::Length(4), Constraint::Min(0)],
+    )
+    .row_highlight_style(Style::new().reversed())
+    .block(
+        Block::bordered()
+            .title("Pending changes")
+            .title_style(Style::new().bold()),
+    );
+    frame.render_stateful_widget(table, area, queue);
+}
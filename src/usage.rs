@@ -0,0 +1,55 @@
+//! How much of a partition its file system uses, so frontends can tell how far it can be shrunk.
+
+use nix::sys::statvfs::statvfs;
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const EXT_SUPERBLOCK_OFFSET: u64 = 1024;
+const EXT_MAGIC: u16 = 0xEF53;
+/// The `INCOMPAT_64BIT` feature, which adds high halves to the block counts.
+const EXT_64BIT: u32 = 0x80;
+
+/// Get the number of bytes used by the file system on the partition at the given path.
+///
+/// Mounted file systems are asked through `statvfs`. Of unmounted ones, only ext2/3/4 are
+/// understood, by reading their superblock.
+pub(crate) fn used_bytes(path: &Path, mount_point: Option<&Path>) -> Option<u64> {
+    if let Some(mount_point) = mount_point {
+        let stat = statvfs(mount_point).ok()?;
+        let blocks = stat.blocks().saturating_sub(stat.blocks_free());
+        return Some(blocks as u64 * stat.fragment_size() as u64);
+    }
+
+    let mut superblock = [0u8; 1024];
+    let mut file = File::open(path).ok()?;
+    file.seek(SeekFrom::Start(EXT_SUPERBLOCK_OFFSET)).ok()?;
+    file.read_exact(&mut superblock).ok()?;
+    ext_used_bytes(&superblock)
+}
+
+fn ext_used_bytes(superblock: &[u8; 1024]) -> Option<u64> {
+    let u16_at = |offset: usize| u16::from_le_bytes([superblock[offset], superblock[offset + 1]]);
+    let u32_at = |offset: usize| {
+        u32::from_le_bytes([
+            superblock[offset],
+            superblock[offset + 1],
+            superblock[offset + 2],
+            superblock[offset + 3],
+        ])
+    };
+
+    if u16_at(0x38) != EXT_MAGIC {
+        return None;
+    }
+    let (mut blocks, mut free) = (u32_at(0x4) as u64, u32_at(0xC) as u64);
+    if u32_at(0x60) & EXT_64BIT != 0 {
+        blocks |= (u32_at(0x150) as u64) << 32;
+        free |= (u32_at(0x158) as u64) << 32;
+    }
+    let block_size = 1024u64.checked_shl(u32_at(0x18))?;
+
+    Some(blocks.saturating_sub(free) * block_size)
+}
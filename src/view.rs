@@ -0,0 +1,171 @@
+use crate::{
+    ContentHint, FileSystem, Geometry, Guid, Partition, PartitionFlag, PartitionType,
+    RegionAnalysis, content,
+};
+use serde::Serialize;
+use std::{
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A row of a device's layout, as returned by
+/// [`Device::partitions_with_empty`](crate::Device::partitions_with_empty).
+///
+/// Rows are owned, so they can be kept around without borrowing the device.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum LayoutRow {
+    Partition(Box<PartitionView>),
+    Free(FreeRegion),
+}
+
+impl LayoutRow {
+    pub fn as_partition(&self) -> Option<&PartitionView> {
+        match self {
+            Self::Partition(p) => Some(p),
+            Self::Free(_) => None,
+        }
+    }
+
+    pub fn as_free(&self) -> Option<&FreeRegion> {
+        match self {
+            Self::Partition(_) => None,
+            Self::Free(f) => Some(f),
+        }
+    }
+
+    pub fn is_free(&self) -> bool {
+        matches!(self, Self::Free(_))
+    }
+
+    pub fn geometry(&self) -> &Geometry {
+        match self {
+            Self::Partition(p) => &p.geometry,
+            Self::Free(f) => &f.geometry,
+        }
+    }
+}
+
+/// A plain-data copy of a [`Partition`] with its pending changes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PartitionView {
+    /// The index of the partition, as taken by [`Device`](crate::Device)'s methods.
+    pub index: usize,
+    pub path: Option<PathBuf>,
+    pub mount_point: Option<PathBuf>,
+    pub name: String,
+    pub fs: Option<FileSystem>,
+    pub fs_label: Option<String>,
+    pub note: Option<String>,
+    pub content: Option<ContentHint>,
+    pub bootable: bool,
+    pub flags: Vec<PartitionFlag>,
+    pub type_guid: Option<Guid>,
+    /// The number of the partition on disk. See [`Partition::number`].
+    pub number: Option<u32>,
+    /// The PARTUUID. See [`Partition::uuid`].
+    pub uuid: Option<Guid>,
+    /// Whether this is a primary, extended or logical partition. See
+    /// [`Partition::partition_type`].
+    pub partition_type: PartitionType,
+    pub geometry: Geometry,
+    /// The bytes used by the file system, if known. See [`Partition::used_space`].
+    pub used: Option<u64>,
+    /// Whether this is the EFI system partition the machine booted from. See
+    /// [`Partition::is_booted_esp`].
+    pub booted_esp: bool,
+}
+
+impl PartitionView {
+    pub(crate) fn new(index: usize, partition: &Partition) -> Self {
+        Self {
+            index,
+            path: partition.path.as_deref().map(Into::into),
+            mount_point: partition.mount_point.as_deref().map(Into::into),
+            name: partition.name().into(),
+            fs: partition.fs(),
+            fs_label: partition.fs_label().map(Into::into),
+            note: partition.note().map(Into::into),
+            content: partition.content_hint(),
+            bootable: partition.is_bootable(),
+            flags: partition.flags(),
+            type_guid: partition.type_guid(),
+            number: partition.number(),
+            uuid: partition.uuid(),
+            partition_type: partition.partition_type(),
+            geometry: partition.geometry(),
+            used: partition.used_space().map(|b| b.as_u64()),
+            booted_esp: partition.is_booted_esp(),
+        }
+    }
+
+    pub fn mounted(&self) -> bool {
+        self.mount_point.is_some()
+    }
+
+    /// The bounds of the partition **in sectors**.
+    pub fn bounds(&self) -> RangeInclusive<i64> {
+        self.geometry.start_sector..=self.geometry.end_sector
+    }
+}
+
+/// A partition as it will be after committing, or one that committing removes, as returned by
+/// [`Device::preview`](crate::Device::preview).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectedPartition {
+    /// The partition with its pending changes.
+    ///
+    /// A removed partition has the index it would have if the removal were undone, unless a new
+    /// partition table removes it, in which case its index is its position in the table being
+    /// replaced.
+    pub view: PartitionView,
+    pub status: ProjectedStatus,
+    /// The position and size of the partition on disk, unless it's new.
+    pub on_disk: Option<Geometry>,
+}
+
+/// What committing does to a [`ProjectedPartition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectedStatus {
+    Unchanged,
+    /// The partition is created.
+    New,
+    /// The partition's name, bounds, file system or flags change.
+    Modified,
+    Removed,
+}
+
+/// Unpartitioned space on a device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FreeRegion {
+    pub geometry: Geometry,
+    #[serde(skip)]
+    device: Arc<Path>,
+}
+
+impl FreeRegion {
+    pub(crate) fn new(device: Arc<Path>, bounds: &RangeInclusive<i64>, sector_size: u64) -> Self {
+        Self {
+            geometry: Geometry::new(bounds, sector_size),
+            device,
+        }
+    }
+
+    /// Read samples of the region to find out whether it's really unused, or holds data such as
+    /// what's left of a deleted partition.
+    ///
+    /// This reads what's on disk now, regardless of any pending changes.
+    pub fn analyze(&self) -> std::io::Result<RegionAnalysis> {
+        content::analyze(
+            &self.device,
+            self.geometry.start_byte,
+            self.geometry.size_byte,
+        )
+    }
+
+    /// The bounds of the region **in sectors**.
+    pub fn bounds(&self) -> RangeInclusive<i64> {
+        self.geometry.start_sector..=self.geometry.end_sector
+    }
+}
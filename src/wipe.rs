@@ -0,0 +1,74 @@
+//! Erasing the space between partitions, where deleted partitions leave their data behind.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::Read,
+    os::unix::fs::FileExt,
+    path::Path,
+};
+
+const CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// How [`Device::wipe_free_space`](crate::Device::wipe_free_space) erases free space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WipeMethod {
+    /// Overwrite it with zeros.
+    #[default]
+    Zeros,
+    /// Overwrite it with random data, so that it can't be told apart from encrypted data, at the
+    /// cost of speed.
+    Random,
+}
+
+/// Progress of a running [`Device::wipe_free_space`](crate::Device::wipe_free_space).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WipeProgress {
+    /// The number of bytes wiped so far, across all free regions.
+    pub wiped: u64,
+    pub total: u64,
+}
+
+impl WipeProgress {
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.wiped as f64 / self.total as f64
+        }
+    }
+}
+
+/// Overwrite the given regions of the device, as `(offset, length)` pairs **in bytes**.
+pub(crate) fn wipe(
+    path: &Path,
+    regions: &[(u64, u64)],
+    method: WipeMethod,
+    mut progress: impl FnMut(WipeProgress),
+) -> std::io::Result<()> {
+    let device = OpenOptions::new().write(true).open(path)?;
+    let mut random = match method {
+        WipeMethod::Zeros => None,
+        WipeMethod::Random => Some(File::open("/dev/urandom")?),
+    };
+    let mut buf = vec![0; CHUNK_SIZE as usize];
+
+    let mut report = WipeProgress {
+        wiped: 0,
+        total: regions.iter().map(|(_, length)| length).sum(),
+    };
+    progress(report);
+    for (offset, length) in regions {
+        let mut done = 0;
+        while done < *length {
+            let chunk = &mut buf[..CHUNK_SIZE.min(length - done) as usize];
+            if let Some(random) = &mut random {
+                random.read_exact(chunk)?;
+            }
+            device.write_all_at(chunk, offset + done)?;
+            done += chunk.len() as u64;
+            report.wiped += chunk.len() as u64;
+            progress(report);
+        }
+    }
+    device.sync_all()
+}